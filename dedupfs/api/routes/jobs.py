@@ -32,7 +32,13 @@ def get_job_service() -> JobService:
 @router.post("", response_model=JobResponse, status_code=status.HTTP_201_CREATED)
 def create_job(request: CreateJobRequest, service: JobService = Depends(get_job_service)) -> JobResponse:
     try:
-        job = service.create_job(kind=request.kind, payload=request.payload, dry_run=request.dry_run)
+        job = service.create_job(
+            kind=request.kind,
+            payload=request.payload,
+            dry_run=request.dry_run,
+            priority=request.priority,
+            max_retries=request.max_retries,
+        )
     except (JobPolicyError, JobConflictError) as exc:
         raise HTTPException(status_code=status.HTTP_409_CONFLICT, detail=str(exc)) from exc
     return JobResponse.model_validate(snapshot_to_dict(job))
@@ -129,6 +135,22 @@ def reset_retryable_job(job_id: str, service: JobService = Depends(get_job_servi
     return JobResponse.model_validate(snapshot_to_dict(job))
 
 
+@router.get("/dead-lettered", response_model=list[JobResponse])
+def list_dead_lettered_jobs(service: JobService = Depends(get_job_service)) -> list[JobResponse]:
+    return [JobResponse.model_validate(snapshot_to_dict(job)) for job in service.list_dead_lettered_jobs()]
+
+
+@router.post("/{job_id}/restore", response_model=JobResponse)
+def restore_dead_lettered_job(job_id: str, service: JobService = Depends(get_job_service)) -> JobResponse:
+    try:
+        job = service.restore_dead_lettered_job(job_id)
+    except JobNotFoundError as exc:
+        raise HTTPException(status_code=status.HTTP_404_NOT_FOUND, detail=str(exc)) from exc
+    except (InvalidJobStateError, JobConflictError) as exc:
+        raise HTTPException(status_code=status.HTTP_409_CONFLICT, detail=str(exc)) from exc
+    return JobResponse.model_validate(snapshot_to_dict(job))
+
+
 @router.post("/recover-stale")
 def recover_stale_jobs(service: JobService = Depends(get_job_service)) -> dict[str, int]:
     recovered = service.recover_stale_jobs()
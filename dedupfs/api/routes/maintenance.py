@@ -3,19 +3,28 @@ from __future__ import annotations
 from fastapi import APIRouter, Depends, HTTPException, status
 
 from dedupfs.api.schemas.maintenance import (
+    DbMaintenanceRequest,
+    DbMaintenanceResponse,
     WalCheckpointRequest,
     WalMaintenanceMetricsResponse,
     WalMaintenanceResponse,
+    WorkerControlResponse,
+    WorkerControlUpdateRequest,
 )
 from dedupfs.core.config import get_settings
 from dedupfs.db.session import get_session_factory
 from dedupfs.maintenance.service import (
+    DbMaintenanceNotFoundError,
+    DbMaintenanceService,
     WalMaintenanceConflictError,
     WalMaintenanceNotFoundError,
     WalMaintenancePolicyError,
     WalMaintenanceService,
+    WorkerControlService,
+    db_maintenance_snapshot_to_dict,
     wal_maintenance_metrics_to_dict,
     wal_maintenance_snapshot_to_dict,
+    worker_control_snapshot_to_dict,
 )
 
 router = APIRouter(prefix="/maintenance", tags=["maintenance"])
@@ -25,6 +34,14 @@ def get_wal_maintenance_service() -> WalMaintenanceService:
     return WalMaintenanceService(settings=get_settings(), session_factory=get_session_factory())
 
 
+def get_db_maintenance_service() -> DbMaintenanceService:
+    return DbMaintenanceService(session_factory=get_session_factory())
+
+
+def get_worker_control_service() -> WorkerControlService:
+    return WorkerControlService(session_factory=get_session_factory())
+
+
 @router.post("/wal/checkpoint", response_model=WalMaintenanceResponse, status_code=status.HTTP_202_ACCEPTED)
 def request_wal_checkpoint(
     request: WalCheckpointRequest,
@@ -57,3 +74,43 @@ def get_latest_wal_checkpoint(
 def get_wal_metrics(service: WalMaintenanceService = Depends(get_wal_maintenance_service)) -> WalMaintenanceMetricsResponse:
     metrics = service.get_metrics()
     return WalMaintenanceMetricsResponse.model_validate(wal_maintenance_metrics_to_dict(metrics))
+
+
+@router.post("/db", response_model=DbMaintenanceResponse, status_code=status.HTTP_202_ACCEPTED)
+def request_db_maintenance(
+    request: DbMaintenanceRequest,
+    service: DbMaintenanceService = Depends(get_db_maintenance_service),
+) -> DbMaintenanceResponse:
+    try:
+        snapshot = service.request_operation(operation=request.operation, reason=request.reason)
+    except ValueError as exc:
+        raise HTTPException(status_code=status.HTTP_422_UNPROCESSABLE_ENTITY, detail=str(exc)) from exc
+    return DbMaintenanceResponse.model_validate(db_maintenance_snapshot_to_dict(snapshot))
+
+
+@router.get("/db/latest", response_model=DbMaintenanceResponse)
+def get_latest_db_maintenance(
+    service: DbMaintenanceService = Depends(get_db_maintenance_service),
+) -> DbMaintenanceResponse:
+    try:
+        snapshot = service.get_latest()
+    except DbMaintenanceNotFoundError as exc:
+        raise HTTPException(status_code=status.HTTP_404_NOT_FOUND, detail=str(exc)) from exc
+    return DbMaintenanceResponse.model_validate(db_maintenance_snapshot_to_dict(snapshot))
+
+
+@router.get("/worker-control", response_model=WorkerControlResponse)
+def get_worker_control(
+    service: WorkerControlService = Depends(get_worker_control_service),
+) -> WorkerControlResponse:
+    snapshot = service.get()
+    return WorkerControlResponse.model_validate(worker_control_snapshot_to_dict(snapshot))
+
+
+@router.patch("/worker-control", response_model=WorkerControlResponse)
+def update_worker_control(
+    request: WorkerControlUpdateRequest,
+    service: WorkerControlService = Depends(get_worker_control_service),
+) -> WorkerControlResponse:
+    snapshot = service.update(**request.model_dump())
+    return WorkerControlResponse.model_validate(worker_control_snapshot_to_dict(snapshot))
@@ -1,11 +1,12 @@
 from __future__ import annotations
 
-from fastapi import APIRouter, Depends, HTTPException, status
+from fastapi import APIRouter, Depends, HTTPException, Query, status
 from fastapi.responses import FileResponse
 
 from dedupfs.api.schemas.thumbs import (
     RequestThumbnailRequest,
     ScheduleGroupCleanupRequest,
+    ScheduleOrphanSweepRequest,
     ThumbnailCleanupResponse,
     ThumbnailMetricsResponse,
     ThumbnailResponse,
@@ -40,6 +41,8 @@ def request_thumbnail(
             file_id=request.file_id,
             max_dimension=request.max_dimension,
             output_format=request.output_format,
+            seek_seconds=request.seek_seconds,
+            sizes=request.sizes,
         )
     except ThumbnailNotFoundError as exc:
         raise HTTPException(status_code=status.HTTP_404_NOT_FOUND, detail=str(exc)) from exc
@@ -76,7 +79,11 @@ def get_thumbnail(thumb_key: str, service: ThumbnailService = Depends(get_thumbn
 
 
 @router.get("/{thumb_key}/content")
-def get_thumbnail_content(thumb_key: str, service: ThumbnailService = Depends(get_thumbnail_service)) -> FileResponse:
+def get_thumbnail_content(
+    thumb_key: str,
+    size: int | None = Query(default=None, ge=1),
+    service: ThumbnailService = Depends(get_thumbnail_service),
+) -> FileResponse:
     try:
         snapshot = service.get_thumbnail(thumb_key)
     except ThumbnailNotFoundError as exc:
@@ -86,14 +93,14 @@ def get_thumbnail_content(thumb_key: str, service: ThumbnailService = Depends(ge
         raise HTTPException(status_code=status.HTTP_409_CONFLICT, detail="Thumbnail is not ready")
 
     try:
-        output_path = service.resolve_thumbnail_output_path(snapshot)
+        output_path = service.resolve_thumbnail_output_path(snapshot, size=size)
     except ThumbnailPolicyError as exc:
         raise HTTPException(status_code=status.HTTP_409_CONFLICT, detail=str(exc)) from exc
 
     if not output_path.is_file():
         raise HTTPException(status_code=status.HTTP_404_NOT_FOUND, detail="Thumbnail file missing")
 
-    media_type = "image/jpeg" if snapshot.format.value == "jpeg" else "image/webp"
+    media_type = {"jpeg": "image/jpeg", "webp": "image/webp", "png": "image/png"}[snapshot.format.value]
     return FileResponse(path=output_path, media_type=media_type)
 
 
@@ -111,3 +118,16 @@ def schedule_group_cleanup(
         raise HTTPException(status_code=status.HTTP_409_CONFLICT, detail=str(exc)) from exc
 
     return ThumbnailCleanupResponse.model_validate(thumbnail_cleanup_snapshot_to_dict(snapshot))
+
+
+@router.post("/cleanup/orphans", response_model=ThumbnailCleanupResponse)
+def schedule_orphan_sweep(
+    request: ScheduleOrphanSweepRequest,
+    service: ThumbnailService = Depends(get_thumbnail_service),
+) -> ThumbnailCleanupResponse:
+    try:
+        snapshot = service.schedule_orphan_sweep(delay_seconds=request.delay_seconds)
+    except ThumbnailCleanupError as exc:
+        raise HTTPException(status_code=status.HTTP_409_CONFLICT, detail=str(exc)) from exc
+
+    return ThumbnailCleanupResponse.model_validate(thumbnail_cleanup_snapshot_to_dict(snapshot))
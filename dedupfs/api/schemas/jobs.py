@@ -14,6 +14,8 @@ class CreateJobRequest(BaseModel):
     kind: JobKind
     payload: dict[str, Any] = Field(default_factory=dict)
     dry_run: bool | None = None
+    priority: int = Field(default=0, ge=-1000, le=1000)
+    max_retries: int | None = Field(default=None, ge=1)
 
 
 class ClaimJobRequest(BaseModel):
@@ -54,6 +56,9 @@ class JobResponse(BaseModel):
     kind: str
     status: str
     dry_run: bool
+    priority: int
+    retry_count: int
+    max_retries: int | None
     worker_id: str | None
     worker_heartbeat_at: datetime | None
     lease_expires_at: datetime | None
@@ -44,3 +44,52 @@ class WalMaintenanceMetricsResponse(BaseModel):
     failed: int
     completed: int
     latest_completed_at: datetime | None
+
+
+class DbMaintenanceRequest(BaseModel):
+    model_config = ConfigDict(extra="forbid")
+
+    operation: str = Field(max_length=32)
+    reason: str | None = Field(default=None, max_length=2048)
+
+
+class DbMaintenanceResponse(BaseModel):
+    id: int
+    operation: str
+    status: str
+    requested_by: str | None
+    reason: str | None
+    execute_after: datetime
+    worker_id: str | None
+    worker_heartbeat_at: datetime | None
+    lease_expires_at: datetime | None
+    page_count_before: int | None
+    page_count_after: int | None
+    freelist_count_before: int | None
+    freelist_count_after: int | None
+    integrity_result: str | None
+    error_code: str | None
+    error_message: str | None
+    created_at: datetime
+    updated_at: datetime
+    started_at: datetime | None
+    finished_at: datetime | None
+
+
+class WorkerControlUpdateRequest(BaseModel):
+    model_config = ConfigDict(extra="forbid")
+
+    paused: bool | None = None
+    pause_scan: bool | None = None
+    pause_hash: bool | None = None
+    pause_thumbnail: bool | None = None
+    pause_wal: bool | None = None
+
+
+class WorkerControlResponse(BaseModel):
+    paused: bool
+    pause_scan: bool
+    pause_hash: bool
+    pause_thumbnail: bool
+    pause_wal: bool
+    updated_at: datetime | None
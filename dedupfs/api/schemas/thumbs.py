@@ -11,6 +11,8 @@ class RequestThumbnailRequest(BaseModel):
     file_id: int = Field(ge=1)
     max_dimension: int | None = Field(default=None, ge=1, le=4096)
     output_format: str | None = None
+    seek_seconds: float | None = Field(default=None, ge=0.0)
+    sizes: list[int] | None = Field(default=None, min_length=1, max_length=8)
 
 
 class ScheduleGroupCleanupRequest(BaseModel):
@@ -20,6 +22,12 @@ class ScheduleGroupCleanupRequest(BaseModel):
     delay_seconds: int | None = Field(default=None, ge=0, le=86400)
 
 
+class ScheduleOrphanSweepRequest(BaseModel):
+    model_config = ConfigDict(extra="forbid")
+
+    delay_seconds: int | None = Field(default=None, ge=0, le=86400)
+
+
 class ThumbnailResponse(BaseModel):
     id: int
     thumb_key: str
@@ -29,6 +37,7 @@ class ThumbnailResponse(BaseModel):
     media_type: str
     format: str
     max_dimension: int
+    sizes: list[int]
     version: int
     source_size_bytes: int
     source_mtime_ns: int
@@ -53,6 +62,7 @@ class ThumbnailResponse(BaseModel):
 class ThumbnailCleanupResponse(BaseModel):
     id: int
     group_key: str
+    cleanup_kind: str
     status: str
     execute_after: datetime
     worker_id: str | None
@@ -32,6 +32,7 @@ class Settings(BaseSettings):
 
     job_lock_ttl_seconds: PositiveInt = 300
     job_lock_heartbeat_seconds: PositiveInt = 30
+    job_max_retries: PositiveInt | None = None
 
     scan_write_batch_size: PositiveInt = 2000
     hash_fetch_batch_size: PositiveInt = 512
@@ -84,8 +85,8 @@ class Settings(BaseSettings):
         self.state_root = self.state_root.resolve(strict=False)
         self.thumbs_root = self.thumbs_root.resolve(strict=False)
 
-        if self.libraries_root.as_posix() != "/libraries":
-            raise ValueError("libraries_root must resolve to /libraries")
+        if self.libraries_root.parent == self.libraries_root:
+            raise ValueError("libraries_root cannot be the filesystem root")
 
         self.state_root.mkdir(parents=True, exist_ok=True)
         if self.thumbs_root.as_posix() == "/state/thumbs" and self.state_root.as_posix() != "/state":
@@ -109,16 +110,16 @@ class Settings(BaseSettings):
             raise ValueError("hash_retry_max_seconds must be greater than or equal to hash_retry_base_seconds")
 
         normalized_thumb_format = self.thumbnail_default_format.lower().strip()
-        if normalized_thumb_format not in {"jpeg", "webp"}:
-            raise ValueError("thumbnail_default_format must be one of ['jpeg', 'webp']")
+        if normalized_thumb_format not in {"jpeg", "webp", "png"}:
+            raise ValueError("thumbnail_default_format must be one of ['jpeg', 'webp', 'png']")
         self.thumbnail_default_format = normalized_thumb_format
 
         if self.thumbnail_retry_max_seconds < self.thumbnail_retry_base_seconds:
             raise ValueError("thumbnail_retry_max_seconds must be >= thumbnail_retry_base_seconds")
 
         normalized_wal_mode = self.wal_checkpoint_default_mode.lower().strip()
-        if normalized_wal_mode not in {"passive", "restart", "truncate"}:
-            raise ValueError("wal_checkpoint_default_mode must be one of ['passive', 'restart', 'truncate']")
+        if normalized_wal_mode not in {"passive", "full", "restart", "truncate"}:
+            raise ValueError("wal_checkpoint_default_mode must be one of ['passive', 'full', 'restart', 'truncate']")
         self.wal_checkpoint_default_mode = normalized_wal_mode
 
         return self
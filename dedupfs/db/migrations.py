@@ -294,10 +294,12 @@ def _resolve_duplicate_running_scan_hash_jobs(conn: Connection) -> None:
     )
 
 
-def _resolve_duplicate_pending_or_running_scan_hash_jobs(conn: Connection) -> None:
+def _resolve_duplicate_pending_or_running_scan_hash_jobs(
+    conn: Connection, kinds: tuple[str, ...] = ("scan", "hash")
+) -> None:
     conn.execute(
         text(
-            """
+            f"""
             WITH ranked AS (
                 SELECT
                     id,
@@ -312,7 +314,7 @@ def _resolve_duplicate_pending_or_running_scan_hash_jobs(conn: Connection) -> No
                             id ASC
                     ) AS row_num
                 FROM jobs
-                WHERE lower(kind) IN ('scan', 'hash')
+                WHERE lower(kind) IN ({_sql_string_list(kinds)})
                   AND lower(status) IN ('pending', 'running')
             )
             UPDATE jobs
@@ -346,16 +348,21 @@ def _drop_single_active_scan_hash_index(conn: Connection) -> None:
     conn.execute(text("DROP INDEX IF EXISTS ix_jobs_single_active_scan_hash"))
 
 
-def _rebuild_single_active_scan_hash_index(conn: Connection) -> None:
+def _rebuild_single_active_scan_hash_index(conn: Connection, kinds: tuple[str, ...] = ("scan", "hash")) -> None:
     _drop_single_active_scan_hash_index(conn)
     conn.execute(
         text(
             "CREATE UNIQUE INDEX ix_jobs_single_active_scan_hash "
-            "ON jobs((1)) WHERE lower(status) IN ('pending', 'running') AND lower(kind) IN ('scan', 'hash')"
+            "ON jobs((1)) WHERE lower(status) IN ('pending', 'running') "
+            f"AND lower(kind) IN ({_sql_string_list(kinds)})"
         )
     )
 
 
+def _sql_string_list(values: tuple[str, ...]) -> str:
+    return ", ".join(f"'{value}'" for value in values)
+
+
 def _migration_0006_jobs_lease_protocol(conn: Connection) -> None:
     if not _table_exists(conn, "jobs"):
         return
@@ -703,6 +710,425 @@ def _migration_0012_wal_maintenance_jobs(conn: Connection) -> None:
         )
 
 
+def _migration_0013_hash_verifications(conn: Connection) -> None:
+    if not _table_exists(conn, "hash_verifications"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE hash_verifications (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    file_id INTEGER NOT NULL REFERENCES library_files(id) ON DELETE CASCADE,
+                    verified_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    matched BOOLEAN NOT NULL,
+                    stored_hash BLOB,
+                    computed_hash BLOB
+                )
+                """
+            )
+        )
+    if not _index_exists(conn, "hash_verifications", "ix_hash_verifications_file_id"):
+        conn.execute(text("CREATE INDEX ix_hash_verifications_file_id ON hash_verifications(file_id)"))
+
+
+def _migration_0014_library_files_quick_hash(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "quick_hash"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN quick_hash BLOB"))
+    if not _index_exists(conn, "library_files", "ix_library_files_quick_hash"):
+        conn.execute(text("CREATE INDEX ix_library_files_quick_hash ON library_files(size_bytes, quick_hash)"))
+
+
+def _migration_0015_scan_sessions_depth_skipped(conn: Connection) -> None:
+    if not _table_exists(conn, "scan_sessions"):
+        return
+    if not _column_exists(conn, "scan_sessions", "depth_skipped"):
+        conn.execute(text("ALTER TABLE scan_sessions ADD COLUMN depth_skipped INTEGER"))
+
+
+def _migration_0016_library_roots_link_path(conn: Connection) -> None:
+    if not _table_exists(conn, "library_roots"):
+        return
+    if not _column_exists(conn, "library_roots", "link_path"):
+        conn.execute(text("ALTER TABLE library_roots ADD COLUMN link_path VARCHAR(2048)"))
+
+
+def _migration_0017_hardlink_aliases(conn: Connection) -> None:
+    if not _table_exists(conn, "hardlink_aliases"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE hardlink_aliases (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    primary_file_id INTEGER NOT NULL REFERENCES library_files(id) ON DELETE CASCADE,
+                    alias_library_id INTEGER NOT NULL REFERENCES library_roots(id) ON DELETE CASCADE,
+                    alias_relative_path VARCHAR(4096) NOT NULL,
+                    scan_session_id INTEGER REFERENCES scan_sessions(id) ON DELETE SET NULL,
+                    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    CONSTRAINT uq_hardlink_aliases_alias UNIQUE (alias_library_id, alias_relative_path)
+                )
+                """
+            )
+        )
+    if not _index_exists(conn, "hardlink_aliases", "ix_hardlink_aliases_primary"):
+        conn.execute(text("CREATE INDEX ix_hardlink_aliases_primary ON hardlink_aliases(primary_file_id)"))
+
+
+def _migration_0018_library_files_relative_path_hash(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "relative_path_hash"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN relative_path_hash BIGINT"))
+    if not _index_exists(conn, "library_files", "uq_library_files_library_path_hash"):
+        conn.execute(
+            text(
+                "CREATE UNIQUE INDEX uq_library_files_library_path_hash "
+                "ON library_files(library_id, relative_path_hash) "
+                "WHERE relative_path_hash IS NOT NULL"
+            )
+        )
+
+
+def _migration_0019_library_files_prefix_hash(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "prefix_hash"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN prefix_hash BLOB"))
+    if not _index_exists(conn, "library_files", "ix_library_files_prefix_hash"):
+        conn.execute(text("CREATE INDEX ix_library_files_prefix_hash ON library_files(size_bytes, prefix_hash)"))
+
+
+def _migration_0020_library_files_permissions(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    for column in ("file_mode", "owner_uid", "owner_gid"):
+        if not _column_exists(conn, "library_files", column):
+            conn.execute(text(f"ALTER TABLE library_files ADD COLUMN {column} INTEGER"))
+
+
+def _migration_0021_dedup_groups(conn: Connection) -> None:
+    if not _table_exists(conn, "dedup_groups"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE dedup_groups (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    group_key VARCHAR(256) NOT NULL UNIQUE,
+                    hash_algorithm VARCHAR(16) NOT NULL,
+                    content_hash BLOB NOT NULL,
+                    member_count INTEGER NOT NULL,
+                    total_bytes BIGINT NOT NULL,
+                    reclaimable_bytes BIGINT NOT NULL,
+                    job_id VARCHAR(36),
+                    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                )
+                """
+            )
+        )
+    if not _index_exists(conn, "dedup_groups", "ix_dedup_groups_reclaimable"):
+        conn.execute(text("CREATE INDEX ix_dedup_groups_reclaimable ON dedup_groups(reclaimable_bytes)"))
+
+
+def _migration_0022_library_roots_last_scan_timed_out_at(conn: Connection) -> None:
+    if not _table_exists(conn, "library_roots"):
+        return
+    if not _column_exists(conn, "library_roots", "last_scan_timed_out_at"):
+        conn.execute(text("ALTER TABLE library_roots ADD COLUMN last_scan_timed_out_at DATETIME"))
+
+
+def _migration_0023_thumbnails_seek_seconds(conn: Connection) -> None:
+    if not _table_exists(conn, "thumbnails"):
+        return
+    if not _column_exists(conn, "thumbnails", "seek_seconds"):
+        conn.execute(text("ALTER TABLE thumbnails ADD COLUMN seek_seconds FLOAT"))
+
+
+def _migration_0024_library_files_verify_status(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "verify_status"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN verify_status VARCHAR(16)"))
+    if not _column_exists(conn, "library_files", "verified_at"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN verified_at DATETIME"))
+    if not _index_exists(conn, "library_files", "ix_library_files_verify_status"):
+        conn.execute(text("CREATE INDEX ix_library_files_verify_status ON library_files(verify_status)"))
+
+
+def _migration_0025_worker_control(conn: Connection) -> None:
+    if not _table_exists(conn, "worker_control"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE worker_control (
+                    id INTEGER PRIMARY KEY CHECK (id = 1),
+                    paused BOOLEAN NOT NULL DEFAULT 0,
+                    pause_scan BOOLEAN NOT NULL DEFAULT 0,
+                    pause_hash BOOLEAN NOT NULL DEFAULT 0,
+                    pause_thumbnail BOOLEAN NOT NULL DEFAULT 0,
+                    pause_wal BOOLEAN NOT NULL DEFAULT 0,
+                    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                )
+                """
+            )
+        )
+    conn.execute(text("INSERT OR IGNORE INTO worker_control(id) VALUES (1)"))
+
+
+def _migration_0026_thumbnails_task_group_id(conn: Connection) -> None:
+    if not _table_exists(conn, "thumbnails"):
+        return
+    if not _column_exists(conn, "thumbnails", "task_group_id"):
+        conn.execute(text("ALTER TABLE thumbnails ADD COLUMN task_group_id INTEGER"))
+    if not _index_exists(conn, "thumbnails", "ix_thumbnails_task_group_id"):
+        conn.execute(text("CREATE INDEX ix_thumbnails_task_group_id ON thumbnails(task_group_id)"))
+
+
+def _migration_0027_jobs_priority(conn: Connection) -> None:
+    if not _table_exists(conn, "jobs"):
+        return
+    if not _column_exists(conn, "jobs", "priority"):
+        conn.execute(text("ALTER TABLE jobs ADD COLUMN priority INTEGER NOT NULL DEFAULT 0"))
+    if not _index_exists(conn, "jobs", "ix_jobs_status_priority_created"):
+        conn.execute(
+            text("CREATE INDEX ix_jobs_status_priority_created ON jobs(status, priority DESC, created_at)")
+        )
+
+
+def _migration_0028_jobs_dead_letter(conn: Connection) -> None:
+    if not _table_exists(conn, "jobs"):
+        return
+    if not _column_exists(conn, "jobs", "retry_count"):
+        conn.execute(text("ALTER TABLE jobs ADD COLUMN retry_count INTEGER NOT NULL DEFAULT 0"))
+    if not _column_exists(conn, "jobs", "max_retries"):
+        conn.execute(text("ALTER TABLE jobs ADD COLUMN max_retries INTEGER"))
+
+
+def _migration_0029_db_maintenance_jobs(conn: Connection) -> None:
+    if not _table_exists(conn, "db_maintenance_jobs"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE db_maintenance_jobs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    operation VARCHAR(32) NOT NULL,
+                    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+                    requested_by VARCHAR(64),
+                    reason TEXT,
+                    execute_after DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    worker_id VARCHAR(128),
+                    worker_heartbeat_at DATETIME,
+                    lease_expires_at DATETIME,
+                    page_count_before INTEGER,
+                    page_count_after INTEGER,
+                    freelist_count_before INTEGER,
+                    freelist_count_after INTEGER,
+                    integrity_result TEXT,
+                    error_code VARCHAR(64),
+                    error_message TEXT,
+                    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    started_at DATETIME,
+                    finished_at DATETIME
+                )
+                """
+            )
+        )
+    conn.execute(
+        text(
+            "CREATE INDEX IF NOT EXISTS ix_db_jobs_status_execute "
+            "ON db_maintenance_jobs (status, execute_after, id)"
+        )
+    )
+    conn.execute(
+        text(
+            "CREATE INDEX IF NOT EXISTS ix_db_jobs_running_lease "
+            "ON db_maintenance_jobs (status, lease_expires_at)"
+        )
+    )
+    conn.execute(
+        text(
+            "CREATE INDEX IF NOT EXISTS ix_db_jobs_created_at "
+            "ON db_maintenance_jobs (created_at)"
+        )
+    )
+
+
+def _migration_0030_db_maintenance_integrity_result(conn: Connection) -> None:
+    if not _table_exists(conn, "db_maintenance_jobs"):
+        return
+    if not _column_exists(conn, "db_maintenance_jobs", "integrity_result"):
+        conn.execute(text("ALTER TABLE db_maintenance_jobs ADD COLUMN integrity_result TEXT"))
+
+
+def _migration_0031_job_log_entries(conn: Connection) -> None:
+    if not _table_exists(conn, "job_log_entries"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE job_log_entries (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    job_id VARCHAR(36) NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+                    logged_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    level VARCHAR(8) NOT NULL,
+                    message TEXT NOT NULL
+                )
+                """
+            )
+        )
+    conn.execute(
+        text(
+            "CREATE INDEX IF NOT EXISTS ix_job_log_entries_job_id "
+            "ON job_log_entries (job_id, id)"
+        )
+    )
+
+
+def _migration_0032_scan_throughput_samples(conn: Connection) -> None:
+    if not _table_exists(conn, "scan_throughput_samples"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE scan_throughput_samples (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    scan_session_id INTEGER NOT NULL REFERENCES scan_sessions(id) ON DELETE CASCADE,
+                    library_id INTEGER NOT NULL,
+                    sampled_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    interval_ms INTEGER NOT NULL,
+                    files_delta BIGINT NOT NULL,
+                    directories_delta BIGINT NOT NULL,
+                    bytes_delta BIGINT NOT NULL,
+                    files_per_sec FLOAT NOT NULL,
+                    directories_per_sec FLOAT NOT NULL,
+                    bytes_per_sec FLOAT NOT NULL,
+                    current_dir TEXT
+                )
+                """
+            )
+        )
+    conn.execute(
+        text(
+            "CREATE INDEX IF NOT EXISTS ix_scan_throughput_samples_session "
+            "ON scan_throughput_samples (scan_session_id, id)"
+        )
+    )
+
+
+def _migration_0033_scheduled_jobs(conn: Connection) -> None:
+    if not _table_exists(conn, "scheduled_jobs"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE scheduled_jobs (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    kind VARCHAR(16) NOT NULL UNIQUE,
+                    last_enqueued_at DATETIME,
+                    interval_seconds INTEGER NOT NULL
+                )
+                """
+            )
+        )
+
+
+def _migration_0034_thumbnail_orphan_sweep(conn: Connection) -> None:
+    if not _column_exists(conn, "thumbnail_cleanup_jobs", "cleanup_kind"):
+        conn.execute(
+            text("ALTER TABLE thumbnail_cleanup_jobs ADD COLUMN cleanup_kind VARCHAR(16) NOT NULL DEFAULT 'group'")
+        )
+    conn.execute(text("CREATE INDEX IF NOT EXISTS ix_thumbnails_output_relpath ON thumbnails (output_relpath)"))
+
+
+def _migration_0035_library_files_pinned(conn: Connection) -> None:
+    if not _column_exists(conn, "library_files", "pinned"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0"))
+    conn.execute(
+        text("CREATE INDEX IF NOT EXISTS ix_library_files_pinned ON library_files (pinned) WHERE pinned = 1")
+    )
+
+
+def _migration_0036_thumbnails_sizes(conn: Connection) -> None:
+    if not _table_exists(conn, "thumbnails"):
+        return
+    if not _column_exists(conn, "thumbnails", "sizes"):
+        conn.execute(text("ALTER TABLE thumbnails ADD COLUMN sizes VARCHAR(64)"))
+
+
+def _migration_0037_library_files_media_type(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "media_type"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN media_type VARCHAR(16)"))
+
+
+def _migration_0038_thumbnails_permanent_failure(conn: Connection) -> None:
+    if not _table_exists(conn, "thumbnails"):
+        return
+    if not _column_exists(conn, "thumbnails", "permanent_failure"):
+        conn.execute(text("ALTER TABLE thumbnails ADD COLUMN permanent_failure BOOLEAN NOT NULL DEFAULT 0"))
+
+
+def _migration_0039_library_files_file_types(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "extension"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN extension VARCHAR(32)"))
+    if not _column_exists(conn, "library_files", "mime_type"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN mime_type VARCHAR(128)"))
+
+
+def _migration_0040_library_circuit_breaker(conn: Connection) -> None:
+    if not _table_exists(conn, "library_circuit_breaker"):
+        conn.execute(
+            text(
+                """
+                CREATE TABLE library_circuit_breaker (
+                    library_id INTEGER PRIMARY KEY REFERENCES library_roots(id) ON DELETE CASCADE,
+                    tripped_at DATETIME NOT NULL,
+                    reset_after DATETIME NOT NULL,
+                    failure_count INTEGER NOT NULL
+                )
+                """
+            )
+        )
+
+
+def _migration_0041_library_files_image_dimensions(conn: Connection) -> None:
+    if not _table_exists(conn, "library_files"):
+        return
+    if not _column_exists(conn, "library_files", "image_width"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN image_width INTEGER"))
+    if not _column_exists(conn, "library_files", "image_height"):
+        conn.execute(text("ALTER TABLE library_files ADD COLUMN image_height INTEGER"))
+
+
+def _migration_0042_library_roots_io_rate_limit(conn: Connection) -> None:
+    if not _table_exists(conn, "library_roots"):
+        return
+    if not _column_exists(conn, "library_roots", "io_rate_limit_mib_per_sec"):
+        conn.execute(text("ALTER TABLE library_roots ADD COLUMN io_rate_limit_mib_per_sec REAL"))
+
+
+def _migration_0043_library_files_inode_lookup(conn: Connection) -> None:
+    conn.execute(
+        text(
+            "CREATE INDEX IF NOT EXISTS ix_library_files_library_inode "
+            "ON library_files (library_id, inode, device)"
+        )
+    )
+
+
+def _migration_0044_jobs_single_active_claim_kinds(conn: Connection) -> None:
+    # verify, dedup and migrate share the scan/hash claim path and admission mutex.
+    if not _table_exists(conn, "jobs"):
+        return
+
+    kinds = ("scan", "hash", "verify", "dedup", "migrate")
+    _drop_single_active_scan_hash_index(conn)
+    _resolve_duplicate_pending_or_running_scan_hash_jobs(conn, kinds)
+    _rebuild_single_active_scan_hash_index(conn, kinds)
+
+
 MIGRATIONS: tuple[MigrationStep, ...] = (
     MigrationStep(version=1, name="baseline", apply=_migration_0001_baseline),
     MigrationStep(version=2, name="scan_sessions_error_count", apply=_migration_0002_scan_session_error_count),
@@ -744,6 +1170,166 @@ MIGRATIONS: tuple[MigrationStep, ...] = (
         name="wal_maintenance_jobs",
         apply=_migration_0012_wal_maintenance_jobs,
     ),
+    MigrationStep(
+        version=13,
+        name="hash_verifications",
+        apply=_migration_0013_hash_verifications,
+    ),
+    MigrationStep(
+        version=14,
+        name="library_files_quick_hash",
+        apply=_migration_0014_library_files_quick_hash,
+    ),
+    MigrationStep(
+        version=15,
+        name="scan_sessions_depth_skipped",
+        apply=_migration_0015_scan_sessions_depth_skipped,
+    ),
+    MigrationStep(
+        version=16,
+        name="library_roots_link_path",
+        apply=_migration_0016_library_roots_link_path,
+    ),
+    MigrationStep(
+        version=17,
+        name="hardlink_aliases",
+        apply=_migration_0017_hardlink_aliases,
+    ),
+    MigrationStep(
+        version=18,
+        name="library_files_relative_path_hash",
+        apply=_migration_0018_library_files_relative_path_hash,
+    ),
+    MigrationStep(
+        version=19,
+        name="library_files_prefix_hash",
+        apply=_migration_0019_library_files_prefix_hash,
+    ),
+    MigrationStep(
+        version=20,
+        name="library_files_permissions",
+        apply=_migration_0020_library_files_permissions,
+    ),
+    MigrationStep(
+        version=21,
+        name="dedup_groups",
+        apply=_migration_0021_dedup_groups,
+    ),
+    MigrationStep(
+        version=22,
+        name="library_roots_last_scan_timed_out_at",
+        apply=_migration_0022_library_roots_last_scan_timed_out_at,
+    ),
+    MigrationStep(
+        version=23,
+        name="thumbnails_seek_seconds",
+        apply=_migration_0023_thumbnails_seek_seconds,
+    ),
+    MigrationStep(
+        version=24,
+        name="library_files_verify_status",
+        apply=_migration_0024_library_files_verify_status,
+    ),
+    MigrationStep(
+        version=25,
+        name="worker_control",
+        apply=_migration_0025_worker_control,
+    ),
+    MigrationStep(
+        version=26,
+        name="thumbnails_task_group_id",
+        apply=_migration_0026_thumbnails_task_group_id,
+    ),
+    MigrationStep(
+        version=27,
+        name="jobs_priority",
+        apply=_migration_0027_jobs_priority,
+    ),
+    MigrationStep(
+        version=28,
+        name="jobs_dead_letter",
+        apply=_migration_0028_jobs_dead_letter,
+    ),
+    MigrationStep(
+        version=29,
+        name="db_maintenance_jobs",
+        apply=_migration_0029_db_maintenance_jobs,
+    ),
+    MigrationStep(
+        version=30,
+        name="db_maintenance_integrity_result",
+        apply=_migration_0030_db_maintenance_integrity_result,
+    ),
+    MigrationStep(
+        version=31,
+        name="job_log_entries",
+        apply=_migration_0031_job_log_entries,
+    ),
+    MigrationStep(
+        version=32,
+        name="scan_throughput_samples",
+        apply=_migration_0032_scan_throughput_samples,
+    ),
+    MigrationStep(
+        version=33,
+        name="scheduled_jobs",
+        apply=_migration_0033_scheduled_jobs,
+    ),
+    MigrationStep(
+        version=34,
+        name="thumbnail_orphan_sweep",
+        apply=_migration_0034_thumbnail_orphan_sweep,
+    ),
+    MigrationStep(
+        version=35,
+        name="library_files_pinned",
+        apply=_migration_0035_library_files_pinned,
+    ),
+    MigrationStep(
+        version=36,
+        name="thumbnails_sizes",
+        apply=_migration_0036_thumbnails_sizes,
+    ),
+    MigrationStep(
+        version=37,
+        name="library_files_media_type",
+        apply=_migration_0037_library_files_media_type,
+    ),
+    MigrationStep(
+        version=38,
+        name="thumbnails_permanent_failure",
+        apply=_migration_0038_thumbnails_permanent_failure,
+    ),
+    MigrationStep(
+        version=39,
+        name="library_files_file_types",
+        apply=_migration_0039_library_files_file_types,
+    ),
+    MigrationStep(
+        version=40,
+        name="library_circuit_breaker",
+        apply=_migration_0040_library_circuit_breaker,
+    ),
+    MigrationStep(
+        version=41,
+        name="library_files_image_dimensions",
+        apply=_migration_0041_library_files_image_dimensions,
+    ),
+    MigrationStep(
+        version=42,
+        name="library_roots_io_rate_limit",
+        apply=_migration_0042_library_roots_io_rate_limit,
+    ),
+    MigrationStep(
+        version=43,
+        name="library_files_inode_lookup",
+        apply=_migration_0043_library_files_inode_lookup,
+    ),
+    MigrationStep(
+        version=44,
+        name="jobs_single_active_claim_kinds",
+        apply=_migration_0044_jobs_single_active_claim_kinds,
+    ),
 )
 
 
@@ -19,6 +19,7 @@ from sqlalchemy import (
     Text,
     UniqueConstraint,
     func,
+    text,
 )
 from sqlalchemy.orm import DeclarativeBase, Mapped, mapped_column
 
@@ -36,6 +37,9 @@ class JobKind(str, Enum):
     HASH = "hash"
     DELETE = "delete"
     THUMBNAIL = "thumbnail"
+    VERIFY = "verify"
+    DEDUP = "dedup"
+    MIGRATE = "migrate"
 
 
 class JobStatus(str, Enum):
@@ -45,6 +49,7 @@ class JobStatus(str, Enum):
     FAILED = "failed"
     CANCELLED = "cancelled"
     RETRYABLE = "retryable"
+    DEAD_LETTERED = "dead_lettered"
 
 
 class HashAlgorithm(str, Enum):
@@ -52,11 +57,17 @@ class HashAlgorithm(str, Enum):
     SHA256 = "sha256"
 
 
+class VerifyStatus(str, Enum):
+    OK = "ok"
+    MISMATCH = "mismatch"
+
+
 class ThumbnailStatus(str, Enum):
     PENDING = "pending"
     RUNNING = "running"
     READY = "ready"
     FAILED = "failed"
+    DEAD = "dead"
 
 
 class ThumbnailMediaType(str, Enum):
@@ -67,6 +78,7 @@ class ThumbnailMediaType(str, Enum):
 class ThumbnailFormat(str, Enum):
     JPEG = "jpeg"
     WEBP = "webp"
+    PNG = "png"
 
 
 class ThumbnailCleanupStatus(str, Enum):
@@ -76,6 +88,11 @@ class ThumbnailCleanupStatus(str, Enum):
     FAILED = "failed"
 
 
+class ThumbnailCleanupKind(str, Enum):
+    GROUP = "group"
+    ORPHAN_SWEEP = "orphan_sweep"
+
+
 class ScanSessionStatus(str, Enum):
     RUNNING = "running"
     SUCCEEDED = "succeeded"
@@ -84,6 +101,7 @@ class ScanSessionStatus(str, Enum):
 
 class WalCheckpointMode(str, Enum):
     PASSIVE = "passive"
+    FULL = "full"
     RESTART = "restart"
     TRUNCATE = "truncate"
 
@@ -96,6 +114,20 @@ class WalMaintenanceStatus(str, Enum):
     RETRYABLE = "retryable"
 
 
+class DbMaintenanceOperation(str, Enum):
+    VACUUM = "vacuum"
+    ANALYZE = "analyze"
+    INCREMENTAL_VACUUM = "incremental_vacuum"
+    INTEGRITY_CHECK = "integrity_check"
+
+
+class DbMaintenanceStatus(str, Enum):
+    PENDING = "pending"
+    RUNNING = "running"
+    COMPLETED = "completed"
+    FAILED = "failed"
+
+
 class Job(Base):
     __tablename__ = "jobs"
 
@@ -110,6 +142,9 @@ class Job(Base):
         default=JobStatus.PENDING,
     )
     dry_run: Mapped[bool] = mapped_column(Boolean, nullable=False, default=True)
+    priority: Mapped[int] = mapped_column(Integer, nullable=False, default=0, server_default=text("0"))
+    retry_count: Mapped[int] = mapped_column(Integer, nullable=False, default=0, server_default=text("0"))
+    max_retries: Mapped[int | None] = mapped_column(Integer, nullable=True)
     worker_id: Mapped[str | None] = mapped_column(String(128), nullable=True)
     worker_heartbeat_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
     lease_expires_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
@@ -135,6 +170,7 @@ class Job(Base):
         Index("ix_jobs_created_at", "created_at"),
         Index("ix_jobs_created_id", "created_at", "id"),
         Index("ix_jobs_status_updated", "status", "updated_at"),
+        Index("ix_jobs_status_priority_created", "status", text("priority DESC"), "created_at"),
     )
 
 
@@ -153,17 +189,32 @@ class JobLock(Base):
     )
 
 
+class JobLogEntry(Base):
+    __tablename__ = "job_log_entries"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    job_id: Mapped[str] = mapped_column(String(36), ForeignKey("jobs.id", ondelete="CASCADE"), nullable=False)
+    logged_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
+    level: Mapped[str] = mapped_column(String(8), nullable=False)
+    message: Mapped[str] = mapped_column(Text, nullable=False)
+
+    __table_args__ = (Index("ix_job_log_entries_job_id", "job_id", "id"),)
+
+
 class LibraryRoot(Base):
     __tablename__ = "library_roots"
 
     id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
     name: Mapped[str] = mapped_column(String(255), nullable=False, unique=True)
     root_path: Mapped[str] = mapped_column(String(2048), nullable=False, unique=True)
+    link_path: Mapped[str | None] = mapped_column(String(2048), nullable=True)
     created_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
     updated_at: Mapped[datetime] = mapped_column(
         DateTime(timezone=True), nullable=False, server_default=func.now(), onupdate=func.now()
     )
     last_scanned_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    last_scan_timed_out_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    io_rate_limit_mib_per_sec: Mapped[float | None] = mapped_column(Float, nullable=True)
 
     __table_args__ = (Index("ix_library_roots_last_scanned_at", "last_scanned_at"),)
 
@@ -184,6 +235,7 @@ class ScanSession(Base):
     files_seen: Mapped[int] = mapped_column(BigInteger, nullable=False, default=0)
     directories_seen: Mapped[int] = mapped_column(BigInteger, nullable=False, default=0)
     bytes_seen: Mapped[int] = mapped_column(BigInteger, nullable=False, default=0)
+    depth_skipped: Mapped[int | None] = mapped_column(Integer, nullable=True)
     error_count: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
 
     __table_args__ = (
@@ -192,20 +244,71 @@ class ScanSession(Base):
     )
 
 
+class ScanThroughputSample(Base):
+    __tablename__ = "scan_throughput_samples"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    scan_session_id: Mapped[int] = mapped_column(
+        Integer, ForeignKey("scan_sessions.id", ondelete="CASCADE"), nullable=False
+    )
+    library_id: Mapped[int] = mapped_column(Integer, nullable=False)
+    sampled_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
+    interval_ms: Mapped[int] = mapped_column(Integer, nullable=False)
+    files_delta: Mapped[int] = mapped_column(BigInteger, nullable=False)
+    directories_delta: Mapped[int] = mapped_column(BigInteger, nullable=False)
+    bytes_delta: Mapped[int] = mapped_column(BigInteger, nullable=False)
+    files_per_sec: Mapped[float] = mapped_column(Float, nullable=False)
+    directories_per_sec: Mapped[float] = mapped_column(Float, nullable=False)
+    bytes_per_sec: Mapped[float] = mapped_column(Float, nullable=False)
+    current_dir: Mapped[str | None] = mapped_column(Text, nullable=True)
+
+    __table_args__ = (Index("ix_scan_throughput_samples_session", "scan_session_id", "id"),)
+
+
+class ScheduledJob(Base):
+    __tablename__ = "scheduled_jobs"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    kind: Mapped[str] = mapped_column(String(16), nullable=False, unique=True)
+    last_enqueued_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    interval_seconds: Mapped[int] = mapped_column(Integer, nullable=False)
+
+
+class LibraryCircuitBreaker(Base):
+    __tablename__ = "library_circuit_breaker"
+
+    library_id: Mapped[int] = mapped_column(
+        Integer, ForeignKey("library_roots.id", ondelete="CASCADE"), primary_key=True
+    )
+    tripped_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False)
+    reset_after: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False)
+    failure_count: Mapped[int] = mapped_column(Integer, nullable=False)
+
+
 class LibraryFile(Base):
     __tablename__ = "library_files"
 
     id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
     library_id: Mapped[int] = mapped_column(Integer, ForeignKey("library_roots.id", ondelete="CASCADE"), nullable=False)
     relative_path: Mapped[str] = mapped_column(String(4096), nullable=False)
+    relative_path_hash: Mapped[int | None] = mapped_column(BigInteger, nullable=True)
 
     size_bytes: Mapped[int] = mapped_column(BigInteger, nullable=False)
     mtime_ns: Mapped[int] = mapped_column(BigInteger, nullable=False)
     inode: Mapped[int | None] = mapped_column(BigInteger, nullable=True)
     device: Mapped[int | None] = mapped_column(BigInteger, nullable=True)
+    file_mode: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    owner_uid: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    owner_gid: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    media_type: Mapped[str | None] = mapped_column(String(16), nullable=True)
+    extension: Mapped[str | None] = mapped_column(String(32), nullable=True)
+    mime_type: Mapped[str | None] = mapped_column(String(128), nullable=True)
+    image_width: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    image_height: Mapped[int | None] = mapped_column(Integer, nullable=True)
 
     is_missing: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
     needs_hash: Mapped[bool] = mapped_column(Boolean, nullable=False, default=True)
+    pinned: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
     last_seen_scan_id: Mapped[int | None] = mapped_column(
         Integer,
         ForeignKey("scan_sessions.id", ondelete="SET NULL"),
@@ -217,9 +320,16 @@ class LibraryFile(Base):
         nullable=True,
     )
     content_hash: Mapped[bytes | None] = mapped_column(LargeBinary, nullable=True)
+    quick_hash: Mapped[bytes | None] = mapped_column(LargeBinary, nullable=True)
+    prefix_hash: Mapped[bytes | None] = mapped_column(LargeBinary, nullable=True)
     hashed_size_bytes: Mapped[int | None] = mapped_column(BigInteger, nullable=True)
     hashed_mtime_ns: Mapped[int | None] = mapped_column(BigInteger, nullable=True)
     hashed_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    verify_status: Mapped[VerifyStatus | None] = mapped_column(
+        SAEnum(VerifyStatus, native_enum=False, values_callable=_enum_values),
+        nullable=True,
+    )
+    verified_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
     hash_error_count: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
     hash_last_error: Mapped[str | None] = mapped_column(Text, nullable=True)
     hash_last_error_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
@@ -239,12 +349,71 @@ class LibraryFile(Base):
         Index("ix_library_files_hash_lookup", "hash_algorithm", "content_hash", "size_bytes", "is_missing"),
         Index("ix_library_files_dedup_group", "is_missing", "needs_hash", "hash_algorithm", "content_hash", "id"),
         Index("ix_library_files_library_path", "library_id", "relative_path"),
+        Index(
+            "uq_library_files_library_path_hash",
+            "library_id",
+            "relative_path_hash",
+            unique=True,
+            sqlite_where=text("relative_path_hash IS NOT NULL"),
+        ),
         Index("ix_library_files_library_mtime_size", "library_id", "mtime_ns", "size_bytes"),
+        Index("ix_library_files_library_inode", "library_id", "inode", "device"),
         Index("ix_library_files_hash_retry", "needs_hash", "is_missing", "hash_retry_after", "id"),
         Index("ix_library_files_hash_claimed", "hash_claim_token", "hash_claimed_at"),
+        Index("ix_library_files_verify_status", "verify_status"),
+        Index("ix_library_files_pinned", "pinned", sqlite_where=text("pinned = 1")),
+    )
+
+
+class HardlinkAlias(Base):
+    __tablename__ = "hardlink_aliases"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    primary_file_id: Mapped[int] = mapped_column(
+        Integer, ForeignKey("library_files.id", ondelete="CASCADE"), nullable=False
+    )
+    alias_library_id: Mapped[int] = mapped_column(
+        Integer, ForeignKey("library_roots.id", ondelete="CASCADE"), nullable=False
+    )
+    alias_relative_path: Mapped[str] = mapped_column(String(4096), nullable=False)
+    scan_session_id: Mapped[int | None] = mapped_column(
+        Integer,
+        ForeignKey("scan_sessions.id", ondelete="SET NULL"),
+        nullable=True,
+    )
+    created_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
+    updated_at: Mapped[datetime] = mapped_column(
+        DateTime(timezone=True), nullable=False, server_default=func.now(), onupdate=func.now()
+    )
+
+    __table_args__ = (
+        UniqueConstraint("alias_library_id", "alias_relative_path", name="uq_hardlink_aliases_alias"),
+        Index("ix_hardlink_aliases_primary", "primary_file_id"),
     )
 
 
+class DedupGroup(Base):
+    __tablename__ = "dedup_groups"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    group_key: Mapped[str] = mapped_column(String(256), nullable=False, unique=True)
+    hash_algorithm: Mapped[HashAlgorithm] = mapped_column(
+        SAEnum(HashAlgorithm, native_enum=False, values_callable=_enum_values),
+        nullable=False,
+    )
+    content_hash: Mapped[bytes] = mapped_column(LargeBinary, nullable=False)
+    member_count: Mapped[int] = mapped_column(Integer, nullable=False)
+    total_bytes: Mapped[int] = mapped_column(BigInteger, nullable=False)
+    reclaimable_bytes: Mapped[int] = mapped_column(BigInteger, nullable=False)
+    job_id: Mapped[str | None] = mapped_column(String(36), nullable=True)
+    created_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
+    updated_at: Mapped[datetime] = mapped_column(
+        DateTime(timezone=True), nullable=False, server_default=func.now(), onupdate=func.now()
+    )
+
+    __table_args__ = (Index("ix_dedup_groups_reclaimable", "reclaimable_bytes"),)
+
+
 class Thumbnail(Base):
     __tablename__ = "thumbnails"
 
@@ -268,6 +437,8 @@ class Thumbnail(Base):
     )
     max_dimension: Mapped[int] = mapped_column(Integer, nullable=False, default=256)
     version: Mapped[int] = mapped_column(Integer, nullable=False, default=1)
+    seek_seconds: Mapped[float | None] = mapped_column(Float, nullable=True)
+    sizes: Mapped[str | None] = mapped_column(String(64), nullable=True)
 
     source_size_bytes: Mapped[int] = mapped_column(BigInteger, nullable=False)
     source_mtime_ns: Mapped[int] = mapped_column(BigInteger, nullable=False)
@@ -280,11 +451,13 @@ class Thumbnail(Base):
     error_code: Mapped[str | None] = mapped_column(String(64), nullable=True)
     error_message: Mapped[str | None] = mapped_column(Text, nullable=True)
     error_count: Mapped[int] = mapped_column(Integer, nullable=False, default=0)
+    permanent_failure: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
     retry_after: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
 
     worker_id: Mapped[str | None] = mapped_column(String(128), nullable=True)
     worker_heartbeat_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
     lease_expires_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    task_group_id: Mapped[int | None] = mapped_column(Integer, nullable=True)
 
     created_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
     updated_at: Mapped[datetime] = mapped_column(
@@ -299,6 +472,8 @@ class Thumbnail(Base):
         Index("ix_thumbnails_group_status", "group_key", "status"),
         Index("ix_thumbnails_running_lease", "status", "lease_expires_at"),
         Index("ix_thumbnails_updated", "updated_at"),
+        Index("ix_thumbnails_task_group_id", "task_group_id"),
+        Index("ix_thumbnails_output_relpath", "output_relpath"),
     )
 
 
@@ -307,6 +482,12 @@ class ThumbnailCleanupJob(Base):
 
     id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
     group_key: Mapped[str] = mapped_column(String(256), nullable=False, unique=True)
+    cleanup_kind: Mapped[ThumbnailCleanupKind] = mapped_column(
+        SAEnum(ThumbnailCleanupKind, native_enum=False, values_callable=_enum_values),
+        nullable=False,
+        default=ThumbnailCleanupKind.GROUP,
+        server_default=ThumbnailCleanupKind.GROUP.value,
+    )
     status: Mapped[ThumbnailCleanupStatus] = mapped_column(
         SAEnum(ThumbnailCleanupStatus, native_enum=False, values_callable=_enum_values),
         nullable=False,
@@ -379,6 +560,64 @@ class WalMaintenanceJob(Base):
     )
 
 
+class DbMaintenanceJob(Base):
+    __tablename__ = "db_maintenance_jobs"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, autoincrement=True)
+    operation: Mapped[DbMaintenanceOperation] = mapped_column(
+        SAEnum(DbMaintenanceOperation, native_enum=False, values_callable=_enum_values),
+        nullable=False,
+    )
+    status: Mapped[DbMaintenanceStatus] = mapped_column(
+        SAEnum(DbMaintenanceStatus, native_enum=False, values_callable=_enum_values),
+        nullable=False,
+        default=DbMaintenanceStatus.PENDING,
+    )
+    requested_by: Mapped[str | None] = mapped_column(String(64), nullable=True)
+    reason: Mapped[str | None] = mapped_column(Text, nullable=True)
+    execute_after: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
+
+    worker_id: Mapped[str | None] = mapped_column(String(128), nullable=True)
+    worker_heartbeat_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    lease_expires_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+
+    page_count_before: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    page_count_after: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    freelist_count_before: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    freelist_count_after: Mapped[int | None] = mapped_column(Integer, nullable=True)
+    integrity_result: Mapped[str | None] = mapped_column(Text, nullable=True)
+
+    error_code: Mapped[str | None] = mapped_column(String(64), nullable=True)
+    error_message: Mapped[str | None] = mapped_column(Text, nullable=True)
+
+    created_at: Mapped[datetime] = mapped_column(DateTime(timezone=True), nullable=False, server_default=func.now())
+    updated_at: Mapped[datetime] = mapped_column(
+        DateTime(timezone=True), nullable=False, server_default=func.now(), onupdate=func.now()
+    )
+    started_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+    finished_at: Mapped[datetime | None] = mapped_column(DateTime(timezone=True), nullable=True)
+
+    __table_args__ = (
+        Index("ix_db_jobs_status_execute", "status", "execute_after", "id"),
+        Index("ix_db_jobs_running_lease", "status", "lease_expires_at"),
+        Index("ix_db_jobs_created_at", "created_at"),
+    )
+
+
+class WorkerControl(Base):
+    __tablename__ = "worker_control"
+
+    id: Mapped[int] = mapped_column(Integer, primary_key=True, default=1)
+    paused: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    pause_scan: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    pause_hash: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    pause_thumbnail: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    pause_wal: Mapped[bool] = mapped_column(Boolean, nullable=False, default=False)
+    updated_at: Mapped[datetime] = mapped_column(
+        DateTime(timezone=True), nullable=False, server_default=func.now(), onupdate=func.now()
+    )
+
+
 class SchemaMigration(Base):
     __tablename__ = "schema_migrations"
 
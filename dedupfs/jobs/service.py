@@ -38,11 +38,18 @@ class JobListResult:
 
 ALLOWED_TRANSITIONS: dict[JobStatus, set[JobStatus]] = {
     JobStatus.PENDING: {JobStatus.RUNNING, JobStatus.CANCELLED},
-    JobStatus.RUNNING: {JobStatus.COMPLETED, JobStatus.FAILED, JobStatus.CANCELLED, JobStatus.RETRYABLE},
+    JobStatus.RUNNING: {
+        JobStatus.COMPLETED,
+        JobStatus.FAILED,
+        JobStatus.CANCELLED,
+        JobStatus.RETRYABLE,
+        JobStatus.DEAD_LETTERED,
+    },
     JobStatus.RETRYABLE: {JobStatus.PENDING, JobStatus.CANCELLED, JobStatus.FAILED},
     JobStatus.COMPLETED: set(),
     JobStatus.FAILED: set(),
     JobStatus.CANCELLED: set(),
+    JobStatus.DEAD_LETTERED: {JobStatus.PENDING},
 }
 
 
@@ -65,7 +72,7 @@ class JobService:
         return value
 
     def _requires_scan_hash_mutex(self, kind: JobKind) -> bool:
-        return kind in {JobKind.SCAN, JobKind.HASH}
+        return kind in {JobKind.SCAN, JobKind.HASH, JobKind.VERIFY, JobKind.DEDUP, JobKind.MIGRATE}
 
     def _enforce_job_policy(self, kind: JobKind, dry_run: bool) -> None:
         if self._settings.dry_run and not dry_run:
@@ -82,6 +89,8 @@ class JobService:
         kind: JobKind,
         payload: dict[str, Any] | None = None,
         dry_run: bool | None = None,
+        priority: int = 0,
+        max_retries: int | None = None,
     ) -> JobSnapshot:
         job_id = str(uuid4())
         effective_dry_run = self._settings.dry_run if dry_run is None else dry_run
@@ -91,7 +100,7 @@ class JobService:
                 self.recover_stale_jobs(session=session)
                 active = session.scalar(
                     select(Job.id).where(
-                        Job.kind.in_([JobKind.SCAN, JobKind.HASH]),
+                        Job.kind.in_([JobKind.SCAN, JobKind.HASH, JobKind.VERIFY, JobKind.DEDUP, JobKind.MIGRATE]),
                         Job.status.in_([JobStatus.PENDING, JobStatus.RUNNING, JobStatus.RETRYABLE]),
                     )
                 )
@@ -103,6 +112,8 @@ class JobService:
                 kind=kind,
                 status=JobStatus.PENDING,
                 dry_run=effective_dry_run,
+                priority=priority,
+                max_retries=max_retries,
                 payload=payload or {},
             )
             session.add(job)
@@ -163,7 +174,7 @@ class JobService:
                             FROM jobs
                             WHERE kind IN ('scan', 'hash')
                               AND status = 'pending'
-                            ORDER BY created_at ASC, id ASC
+                            ORDER BY priority DESC, created_at ASC, id ASC
                             LIMIT 1
                         )
                         UPDATE jobs
@@ -262,7 +273,12 @@ class JobService:
                 raise InvalidJobStateError(f"Job {job_id} is not running")
             if job.worker_id != normalized_worker_id:
                 raise JobConflictError("Only current lease owner can finish the job")
-            next_status = JobStatus.COMPLETED if success else JobStatus.FAILED
+            next_status = JobStatus.COMPLETED
+            if not success:
+                job.retry_count += 1
+                max_retries = self._effective_max_retries(job)
+                exhausted = max_retries is not None and job.retry_count >= max_retries
+                next_status = JobStatus.DEAD_LETTERED if exhausted else JobStatus.FAILED
             self._enforce_transition(job.status, next_status)
             now = self._now()
             job.status = next_status
@@ -296,6 +312,47 @@ class JobService:
             session.refresh(job)
             return self._to_snapshot(job)
 
+    def list_dead_lettered_jobs(self) -> list[JobSnapshot]:
+        with self._session_factory() as session:
+            rows = session.scalars(
+                select(Job).where(Job.status == JobStatus.DEAD_LETTERED).order_by(Job.updated_at.desc(), Job.id.desc())
+            ).all()
+            return [self._to_snapshot(row) for row in rows]
+
+    def restore_dead_lettered_job(self, job_id: str) -> JobSnapshot:
+        with self._session_factory() as session:
+            job = session.get(Job, job_id)
+            if job is None:
+                raise JobNotFoundError(f"Job not found: {job_id}")
+            if job.status != JobStatus.DEAD_LETTERED:
+                raise InvalidJobStateError(f"Job {job_id} is not dead-lettered")
+            self._enforce_transition(job.status, JobStatus.PENDING)
+            now = self._now()
+            job.status = JobStatus.PENDING
+            job.retry_count = 0
+            job.worker_id = None
+            job.worker_heartbeat_at = None
+            job.lease_expires_at = None
+            job.error_code = None
+            job.error_message = None
+            job.finished_at = None
+            job.updated_at = now
+            try:
+                session.commit()
+            except IntegrityError as exc:
+                session.rollback()
+                raise JobConflictError("A scan/hash job is already active") from exc
+            session.refresh(job)
+            return self._to_snapshot(job)
+
+    def _effective_max_retries(self, job: Job) -> int | None:
+        if job.max_retries is not None:
+            return job.max_retries
+        payload_value = (job.payload or {}).get("max_retries")
+        if isinstance(payload_value, int) and not isinstance(payload_value, bool) and payload_value > 0:
+            return payload_value
+        return self._settings.job_max_retries
+
     def cancel_job(self, job_id: str, error_message: str | None = None) -> JobSnapshot:
         with self._session_factory() as session:
             job = session.get(Job, job_id)
@@ -322,7 +379,7 @@ class JobService:
             local_session.scalars(
                 select(Job).where(
                     Job.status == JobStatus.RUNNING,
-                    Job.kind.in_([JobKind.SCAN, JobKind.HASH]),
+                    Job.kind.in_([JobKind.SCAN, JobKind.HASH, JobKind.VERIFY, JobKind.DEDUP, JobKind.MIGRATE]),
                     or_(Job.lease_expires_at.is_(None), Job.lease_expires_at <= now),
                 )
             ).all()
@@ -351,6 +408,9 @@ class JobService:
             kind=job.kind,
             status=job.status,
             dry_run=job.dry_run,
+            priority=job.priority,
+            retry_count=job.retry_count,
+            max_retries=job.max_retries,
             worker_id=job.worker_id,
             worker_heartbeat_at=job.worker_heartbeat_at,
             lease_expires_at=job.lease_expires_at,
@@ -373,6 +433,9 @@ def snapshot_to_dict(snapshot: JobSnapshot) -> dict[str, Any]:
         "kind": snapshot.kind.value,
         "status": snapshot.status.value,
         "dry_run": snapshot.dry_run,
+        "priority": snapshot.priority,
+        "retry_count": snapshot.retry_count,
+        "max_retries": snapshot.max_retries,
         "worker_id": snapshot.worker_id,
         "worker_heartbeat_at": snapshot.worker_heartbeat_at,
         "lease_expires_at": snapshot.lease_expires_at,
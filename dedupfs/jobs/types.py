@@ -14,6 +14,9 @@ class JobSnapshot:
     kind: JobKind
     status: JobStatus
     dry_run: bool
+    priority: int
+    retry_count: int
+    max_retries: int | None
     worker_id: str | None
     worker_heartbeat_at: datetime | None
     lease_expires_at: datetime | None
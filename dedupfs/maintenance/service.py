@@ -8,8 +8,21 @@ from sqlalchemy import func, select
 from sqlalchemy.orm import Session, sessionmaker
 
 from dedupfs.core.config import Settings
-from dedupfs.db.models import WalCheckpointMode, WalMaintenanceJob, WalMaintenanceStatus
-from dedupfs.maintenance.types import WalMaintenanceMetrics, WalMaintenanceSnapshot
+from dedupfs.db.models import (
+    DbMaintenanceJob,
+    DbMaintenanceOperation,
+    DbMaintenanceStatus,
+    WalCheckpointMode,
+    WalMaintenanceJob,
+    WalMaintenanceStatus,
+    WorkerControl,
+)
+from dedupfs.maintenance.types import (
+    DbMaintenanceSnapshot,
+    WalMaintenanceMetrics,
+    WalMaintenanceSnapshot,
+    WorkerControlSnapshot,
+)
 
 
 class WalMaintenancePolicyError(RuntimeError):
@@ -166,6 +179,140 @@ class WalMaintenanceService:
         )
 
 
+class DbMaintenanceNotFoundError(RuntimeError):
+    pass
+
+
+class DbMaintenanceService:
+    """Queues VACUUM / ANALYZE / incremental_vacuum / integrity_check requests for the Rust worker."""
+
+    def __init__(self, session_factory: sessionmaker[Session]):
+        self._session_factory = session_factory
+
+    def _to_snapshot(self, row: DbMaintenanceJob) -> DbMaintenanceSnapshot:
+        return DbMaintenanceSnapshot(
+            id=row.id,
+            operation=row.operation,
+            status=row.status,
+            requested_by=row.requested_by,
+            reason=row.reason,
+            execute_after=row.execute_after,
+            worker_id=row.worker_id,
+            worker_heartbeat_at=row.worker_heartbeat_at,
+            lease_expires_at=row.lease_expires_at,
+            page_count_before=row.page_count_before,
+            page_count_after=row.page_count_after,
+            freelist_count_before=row.freelist_count_before,
+            freelist_count_after=row.freelist_count_after,
+            integrity_result=row.integrity_result,
+            error_code=row.error_code,
+            error_message=row.error_message,
+            created_at=row.created_at,
+            updated_at=row.updated_at,
+            started_at=row.started_at,
+            finished_at=row.finished_at,
+        )
+
+    def request_operation(
+        self,
+        *,
+        operation: str,
+        reason: str | None = None,
+        requested_by: str | None = "api",
+    ) -> DbMaintenanceSnapshot:
+        token = operation.strip().lower()
+        try:
+            normalized = DbMaintenanceOperation(token)
+        except ValueError as exc:
+            allowed = ", ".join(item.value for item in DbMaintenanceOperation)
+            raise ValueError(f"Invalid DB maintenance operation: {operation}. Allowed: {allowed}") from exc
+
+        now = datetime.now(tz=timezone.utc)
+        with self._session_factory() as session:
+            active = session.scalar(
+                select(DbMaintenanceJob)
+                .where(DbMaintenanceJob.operation == normalized)
+                .where(DbMaintenanceJob.status.in_([DbMaintenanceStatus.PENDING, DbMaintenanceStatus.RUNNING]))
+                .order_by(DbMaintenanceJob.created_at.desc(), DbMaintenanceJob.id.desc())
+                .limit(1)
+            )
+            if active is not None:
+                return self._to_snapshot(active)
+
+            row = DbMaintenanceJob(
+                operation=normalized,
+                status=DbMaintenanceStatus.PENDING,
+                requested_by=(requested_by or "api").strip()[:64] or "api",
+                reason=reason,
+                execute_after=now,
+                created_at=now,
+                updated_at=now,
+            )
+            session.add(row)
+            session.commit()
+            session.refresh(row)
+            return self._to_snapshot(row)
+
+    def get_latest(self) -> DbMaintenanceSnapshot:
+        with self._session_factory() as session:
+            row = session.scalar(
+                select(DbMaintenanceJob)
+                .order_by(DbMaintenanceJob.created_at.desc(), DbMaintenanceJob.id.desc())
+                .limit(1)
+            )
+            if row is None:
+                raise DbMaintenanceNotFoundError("No DB maintenance jobs found")
+            return self._to_snapshot(row)
+
+
+WORKER_CONTROL_FLAGS: tuple[str, ...] = ("paused", "pause_scan", "pause_hash", "pause_thumbnail", "pause_wal")
+
+
+class WorkerControlService:
+    """Reads and updates the singleton `worker_control` row polled by the Rust worker."""
+
+    def __init__(self, session_factory: sessionmaker[Session]):
+        self._session_factory = session_factory
+
+    def _load_row(self, session: Session) -> WorkerControl:
+        row = session.get(WorkerControl, 1)
+        if row is None:
+            row = WorkerControl(id=1)
+            session.add(row)
+            session.flush()
+        return row
+
+    def _to_snapshot(self, row: WorkerControl) -> WorkerControlSnapshot:
+        return WorkerControlSnapshot(
+            paused=bool(row.paused),
+            pause_scan=bool(row.pause_scan),
+            pause_hash=bool(row.pause_hash),
+            pause_thumbnail=bool(row.pause_thumbnail),
+            pause_wal=bool(row.pause_wal),
+            updated_at=row.updated_at,
+        )
+
+    def get(self) -> WorkerControlSnapshot:
+        with self._session_factory() as session:
+            row = self._load_row(session)
+            session.commit()
+            return self._to_snapshot(row)
+
+    def update(self, **flags: bool | None) -> WorkerControlSnapshot:
+        unknown = sorted(set(flags) - set(WORKER_CONTROL_FLAGS))
+        if unknown:
+            raise ValueError(f"Unknown worker control flags: {', '.join(unknown)}")
+
+        with self._session_factory() as session:
+            row = self._load_row(session)
+            for name, value in flags.items():
+                if value is not None:
+                    setattr(row, name, bool(value))
+            session.commit()
+            session.refresh(row)
+            return self._to_snapshot(row)
+
+
 def wal_maintenance_snapshot_to_dict(snapshot: WalMaintenanceSnapshot) -> dict[str, Any]:
     payload = asdict(snapshot)
     payload["requested_mode"] = snapshot.requested_mode.value
@@ -173,5 +320,16 @@ def wal_maintenance_snapshot_to_dict(snapshot: WalMaintenanceSnapshot) -> dict[s
     return payload
 
 
+def db_maintenance_snapshot_to_dict(snapshot: DbMaintenanceSnapshot) -> dict[str, Any]:
+    payload = asdict(snapshot)
+    payload["operation"] = snapshot.operation.value
+    payload["status"] = snapshot.status.value
+    return payload
+
+
 def wal_maintenance_metrics_to_dict(snapshot: WalMaintenanceMetrics) -> dict[str, Any]:
     return asdict(snapshot)
+
+
+def worker_control_snapshot_to_dict(snapshot: WorkerControlSnapshot) -> dict[str, Any]:
+    return asdict(snapshot)
@@ -3,7 +3,7 @@ from __future__ import annotations
 from dataclasses import dataclass
 from datetime import datetime
 
-from dedupfs.db.models import WalCheckpointMode, WalMaintenanceStatus
+from dedupfs.db.models import DbMaintenanceOperation, DbMaintenanceStatus, WalCheckpointMode, WalMaintenanceStatus
 
 
 @dataclass(slots=True)
@@ -39,3 +39,37 @@ class WalMaintenanceMetrics:
     failed: int
     completed: int
     latest_completed_at: datetime | None
+
+
+@dataclass(slots=True)
+class DbMaintenanceSnapshot:
+    id: int
+    operation: DbMaintenanceOperation
+    status: DbMaintenanceStatus
+    requested_by: str | None
+    reason: str | None
+    execute_after: datetime
+    worker_id: str | None
+    worker_heartbeat_at: datetime | None
+    lease_expires_at: datetime | None
+    page_count_before: int | None
+    page_count_after: int | None
+    freelist_count_before: int | None
+    freelist_count_after: int | None
+    integrity_result: str | None
+    error_code: str | None
+    error_message: str | None
+    created_at: datetime
+    updated_at: datetime
+    started_at: datetime | None
+    finished_at: datetime | None
+
+
+@dataclass(slots=True)
+class WorkerControlSnapshot:
+    paused: bool
+    pause_scan: bool
+    pause_hash: bool
+    pause_thumbnail: bool
+    pause_wal: bool
+    updated_at: datetime | None
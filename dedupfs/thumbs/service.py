@@ -1,6 +1,7 @@
 from __future__ import annotations
 
 import hashlib
+import math
 from datetime import datetime, timedelta, timezone
 from pathlib import Path
 
@@ -15,6 +16,7 @@ from dedupfs.db.models import (
     LibraryRoot,
     Thumbnail,
     ThumbnailCleanupJob,
+    ThumbnailCleanupKind,
     ThumbnailCleanupStatus,
     ThumbnailFormat,
     ThumbnailMediaType,
@@ -23,6 +25,9 @@ from dedupfs.db.models import (
 from dedupfs.thumbs.types import ThumbnailCleanupSnapshot, ThumbnailSnapshot
 from dedupfs.thumbs.types import ThumbnailMetricsSnapshot
 
+# The orphan sweep reuses thumbnail_cleanup_jobs, whose group_key is unique.
+ORPHAN_SWEEP_GROUP_KEY = "__orphan_sweep__"
+
 _IMAGE_EXTENSIONS = {
     ".jpg",
     ".jpeg",
@@ -32,6 +37,9 @@ _IMAGE_EXTENSIONS = {
     ".tif",
     ".tiff",
     ".webp",
+    ".heic",
+    ".heif",
+    ".avif",
 }
 _VIDEO_EXTENSIONS = {
     ".mp4",
@@ -44,6 +52,14 @@ _VIDEO_EXTENSIONS = {
     ".mpg",
     ".wmv",
 }
+# Failures the worker records without retry_after; requeueing would fail the same way.
+_TERMINAL_ERROR_CODES = {
+    "THUMB_DECODE_RESOURCE",
+    "THUMB_VIDEO_FFMPEG_TERMINAL",
+    "THUMB_DIR_PERMISSION",
+    "THUMB_SOURCE_TOO_LARGE",
+    "THUMB_PERMANENT_FAILURE",
+}
 
 
 class ThumbnailNotFoundError(RuntimeError):
@@ -98,7 +114,10 @@ class ThumbnailService:
             )
         return requested
 
-    def _infer_media_type(self, relative_path: str) -> ThumbnailMediaType:
+    def _infer_media_type(self, relative_path: str, detected: str | None = None) -> ThumbnailMediaType:
+        # The worker's magic-byte classification (scan_detect_media_type) wins over the extension.
+        if detected in (ThumbnailMediaType.IMAGE.value, ThumbnailMediaType.VIDEO.value):
+            return ThumbnailMediaType(detected)
         extension = Path(relative_path).suffix.lower()
         if extension in _IMAGE_EXTENSIONS:
             return ThumbnailMediaType.IMAGE
@@ -106,11 +125,16 @@ class ThumbnailService:
             return ThumbnailMediaType.VIDEO
         raise ThumbnailPolicyError(f"Unsupported media type for thumbnail generation: {extension or '<none>'}")
 
-    def _validate_source_path(self, root_path: str, relative_path: str) -> None:
+    def _validate_source_path(self, root_path: str, relative_path: str, link_path: str | None = None) -> None:
         libraries_root = self._settings.libraries_root.resolve(strict=False)
         root_real = Path(root_path).resolve(strict=False)
         if root_real != libraries_root and libraries_root not in root_real.parents:
-            raise ThumbnailPolicyError("Library root path escapes /libraries")
+            # Symlinked roots are recorded by the worker with their /libraries link.
+            link = Path(link_path) if link_path else None
+            if link is None or link.parent.resolve(strict=False) != libraries_root:
+                raise ThumbnailPolicyError("Library root path escapes /libraries")
+            if link.resolve(strict=False) != root_real:
+                raise ThumbnailPolicyError("Library root link no longer points at recorded root")
 
         rel = validate_library_relative_path(relative_path)
         candidate = (root_real / rel).resolve(strict=False)
@@ -122,18 +146,30 @@ class ThumbnailService:
             return None
         return f"{item.hash_algorithm.value}:{item.content_hash.hex()}"
 
-    def _build_thumb_key(self, *, item: LibraryFile, max_dimension: int, output_format: ThumbnailFormat) -> str:
+    def _build_thumb_key(
+        self,
+        *,
+        item: LibraryFile,
+        max_dimension: int,
+        output_format: ThumbnailFormat,
+        sizes: str | None = None,
+    ) -> str:
         if item.hash_algorithm is not None and item.content_hash is not None:
             source_fingerprint = f"{item.hash_algorithm.value}:{item.content_hash.hex()}"
         else:
             source_fingerprint = f"meta:{item.size_bytes}:{item.mtime_ns}"
         material = (
             f"{item.id}:{source_fingerprint}:{max_dimension}:{output_format.value}:thumb-v2"
+            + (f":sizes={sizes}" if sizes else "")
         ).encode("utf-8")
         return hashlib.sha256(material).hexdigest()
 
     def _build_output_relpath(self, thumb_key: str, output_format: ThumbnailFormat) -> str:
-        extension = "jpg" if output_format == ThumbnailFormat.JPEG else "webp"
+        extension = {
+            ThumbnailFormat.JPEG: "jpg",
+            ThumbnailFormat.WEBP: "webp",
+            ThumbnailFormat.PNG: "png",
+        }[output_format]
         return f"{thumb_key[0:2]}/{thumb_key[2:4]}/{thumb_key}.{extension}"
 
     def _to_snapshot(self, item: Thumbnail) -> ThumbnailSnapshot:
@@ -146,6 +182,7 @@ class ThumbnailService:
             media_type=item.media_type,
             format=item.format,
             max_dimension=item.max_dimension,
+            sizes=parse_thumbnail_sizes(item.sizes),
             version=item.version,
             source_size_bytes=item.source_size_bytes,
             source_mtime_ns=item.source_mtime_ns,
@@ -170,6 +207,7 @@ class ThumbnailService:
         return ThumbnailCleanupSnapshot(
             id=item.id,
             group_key=item.group_key,
+            cleanup_kind=item.cleanup_kind,
             status=item.status,
             execute_after=item.execute_after,
             worker_id=item.worker_id,
@@ -188,9 +226,20 @@ class ThumbnailService:
         file_id: int,
         max_dimension: int | None = None,
         output_format: str | None = None,
+        seek_seconds: float | None = None,
+        sizes: list[int] | None = None,
     ) -> ThumbnailSnapshot:
+        if seek_seconds is not None and not (math.isfinite(seek_seconds) and seek_seconds >= 0.0):
+            raise ThumbnailPolicyError("seek_seconds must be a finite, non-negative number")
         normalized_format = self._normalize_format(output_format)
         normalized_dimension = self._normalize_dimension(max_dimension)
+        # Extra sizes share one decode; the largest is the row's own output and max_dimension.
+        normalized_sizes: str | None = None
+        if sizes:
+            distinct = sorted({self._normalize_dimension(size) for size in sizes}, reverse=True)
+            normalized_dimension = distinct[0]
+            if len(distinct) > 1:
+                normalized_sizes = ",".join(str(size) for size in distinct)
         now = self._now()
 
         with self._session_factory() as session:
@@ -204,19 +253,20 @@ class ThumbnailService:
             if root is None:
                 raise ThumbnailPolicyError(f"Library root missing for file {file_id}")
 
-            self._validate_source_path(root.root_path, item.relative_path)
-            media_type = self._infer_media_type(item.relative_path)
+            self._validate_source_path(root.root_path, item.relative_path, root.link_path)
+            media_type = self._infer_media_type(item.relative_path, item.media_type)
             thumb_key = self._build_thumb_key(
                 item=item,
                 max_dimension=normalized_dimension,
                 output_format=normalized_format,
+                sizes=normalized_sizes,
             )
             output_relpath = self._build_output_relpath(thumb_key, normalized_format)
             group_key = self._build_group_key(item)
 
             existing = session.scalar(select(Thumbnail).where(Thumbnail.thumb_key == thumb_key))
             if existing is not None:
-                if existing.status == ThumbnailStatus.FAILED:
+                if existing.status == ThumbnailStatus.FAILED and existing.error_code not in _TERMINAL_ERROR_CODES:
                     retry_after = self._coerce_utc(existing.retry_after)
                     if retry_after is None or retry_after <= now:
                         existing.status = ThumbnailStatus.PENDING
@@ -245,6 +295,8 @@ class ThumbnailService:
                 source_size_bytes=item.size_bytes,
                 source_mtime_ns=item.mtime_ns,
                 output_relpath=output_relpath,
+                seek_seconds=seek_seconds,
+                sizes=normalized_sizes,
             )
 
             try:
@@ -263,6 +315,8 @@ class ThumbnailService:
                             source_size_bytes,
                             source_mtime_ns,
                             output_relpath,
+                            seek_seconds,
+                            sizes,
                             error_count
                         )
                         SELECT
@@ -277,6 +331,8 @@ class ThumbnailService:
                             :source_size_bytes,
                             :source_mtime_ns,
                             :output_relpath,
+                            :seek_seconds,
+                            :sizes,
                             0
                         WHERE (
                             SELECT COUNT(1)
@@ -296,6 +352,8 @@ class ThumbnailService:
                         "source_size_bytes": queued.source_size_bytes,
                         "source_mtime_ns": queued.source_mtime_ns,
                         "output_relpath": queued.output_relpath,
+                        "seek_seconds": queued.seek_seconds,
+                        "sizes": queued.sizes,
                         "queue_capacity": int(self._settings.thumbnail_queue_capacity),
                     },
                 )
@@ -327,11 +385,16 @@ class ThumbnailService:
                 raise ThumbnailNotFoundError(f"Thumbnail not found: {thumb_key}")
             return self._to_snapshot(item)
 
-    def resolve_thumbnail_output_path(self, snapshot: ThumbnailSnapshot) -> Path:
+    def resolve_thumbnail_output_path(self, snapshot: ThumbnailSnapshot, *, size: int | None = None) -> Path:
         if snapshot.output_relpath is None:
             raise ThumbnailPolicyError("Thumbnail output path is empty")
 
-        relative = validate_library_relative_path(snapshot.output_relpath)
+        output_relpath = snapshot.output_relpath
+        if size is not None and size != snapshot.max_dimension:
+            if size not in snapshot.sizes:
+                raise ThumbnailPolicyError(f"Thumbnail has no output for size {size}")
+            output_relpath = thumbnail_sibling_relpath(output_relpath, size)
+        relative = validate_library_relative_path(output_relpath)
         root = self._settings.thumbs_root.resolve(strict=False)
         candidate = (root / relative).resolve(strict=False)
         if candidate != root and root not in candidate.parents:
@@ -347,7 +410,43 @@ class ThumbnailService:
         normalized_group = group_key.strip()
         if not normalized_group:
             raise ThumbnailCleanupError("group_key cannot be blank")
+        if normalized_group == ORPHAN_SWEEP_GROUP_KEY:
+            raise ThumbnailCleanupError("group_key is reserved for the orphan sweep")
+        with self._session_factory() as session:
+            if self._group_has_pinned_files(session, normalized_group):
+                raise ThumbnailCleanupError("group has pinned files; its thumbnails are kept")
+        return self._schedule_cleanup(
+            group_key=normalized_group,
+            cleanup_kind=ThumbnailCleanupKind.GROUP,
+            delay_seconds=delay_seconds,
+        )
+
+    def _group_has_pinned_files(self, session: Session, group_key: str) -> bool:
+        row = session.execute(
+            text(
+                "SELECT 1 FROM library_files "
+                "WHERE pinned = 1 AND content_hash IS NOT NULL "
+                "AND hash_algorithm || ':' || lower(hex(content_hash)) = :group_key "
+                "LIMIT 1"
+            ),
+            {"group_key": group_key},
+        ).first()
+        return row is not None
+
+    def schedule_orphan_sweep(self, *, delay_seconds: int | None = None) -> ThumbnailCleanupSnapshot:
+        return self._schedule_cleanup(
+            group_key=ORPHAN_SWEEP_GROUP_KEY,
+            cleanup_kind=ThumbnailCleanupKind.ORPHAN_SWEEP,
+            delay_seconds=delay_seconds,
+        )
 
+    def _schedule_cleanup(
+        self,
+        *,
+        group_key: str,
+        cleanup_kind: ThumbnailCleanupKind,
+        delay_seconds: int | None,
+    ) -> ThumbnailCleanupSnapshot:
         if delay_seconds is not None and delay_seconds < 0:
             raise ThumbnailCleanupError("delay_seconds cannot be negative")
 
@@ -357,11 +456,12 @@ class ThumbnailService:
 
         with self._session_factory() as session:
             record = session.scalar(
-                select(ThumbnailCleanupJob).where(ThumbnailCleanupJob.group_key == normalized_group)
+                select(ThumbnailCleanupJob).where(ThumbnailCleanupJob.group_key == group_key)
             )
             if record is None:
                 record = ThumbnailCleanupJob(
-                    group_key=normalized_group,
+                    group_key=group_key,
+                    cleanup_kind=cleanup_kind,
                     status=ThumbnailCleanupStatus.PENDING,
                     execute_after=execute_after,
                 )
@@ -387,29 +487,33 @@ class ThumbnailService:
             raise ThumbnailCleanupError("group_key cannot be blank")
 
         with self._session_factory() as session:
+            if self._group_has_pinned_files(session, normalized_group):
+                return 0
             rows = list(
                 session.scalars(
                     select(Thumbnail).where(
                         Thumbnail.group_key == normalized_group,
-                        Thumbnail.status.in_([ThumbnailStatus.READY, ThumbnailStatus.FAILED]),
+                        Thumbnail.status.in_([ThumbnailStatus.READY, ThumbnailStatus.FAILED, ThumbnailStatus.DEAD]),
                     )
                 ).all()
             )
             for row in rows:
                 if not row.output_relpath:
                     continue
-                try:
-                    path = self.resolve_thumbnail_output_path(self._to_snapshot(row))
-                except (ThumbnailPolicyError, PathSafetyError):
-                    continue
-                if path.exists():
-                    path.unlink(missing_ok=True)
+                snapshot = self._to_snapshot(row)
+                for size in [None, *snapshot.sizes]:
+                    try:
+                        path = self.resolve_thumbnail_output_path(snapshot, size=size)
+                    except (ThumbnailPolicyError, PathSafetyError):
+                        continue
+                    if path.exists():
+                        path.unlink(missing_ok=True)
 
             deleted = (
                 session.query(Thumbnail)
                 .filter(
                     Thumbnail.group_key == normalized_group,
-                    Thumbnail.status.in_([ThumbnailStatus.READY, ThumbnailStatus.FAILED]),
+                    Thumbnail.status.in_([ThumbnailStatus.READY, ThumbnailStatus.FAILED, ThumbnailStatus.DEAD]),
                 )
                 .delete()
             )
@@ -501,6 +605,20 @@ class ThumbnailService:
         )
 
 
+def parse_thumbnail_sizes(raw: str | None) -> list[int]:
+    if not raw:
+        return []
+    return [int(part) for part in raw.split(",") if part.strip()]
+
+
+def thumbnail_sibling_relpath(output_relpath: str, size: int) -> str:
+    """Output of one extra size: `ab/cd/<key>.jpg` -> `ab/cd/<key>_<size>.jpg` (same rule as the worker)."""
+    stem, dot, extension = output_relpath.rpartition(".")
+    if not dot or "/" in extension:
+        return f"{output_relpath}_{size}"
+    return f"{stem}_{size}.{extension}"
+
+
 def thumbnail_snapshot_to_dict(snapshot: ThumbnailSnapshot) -> dict[str, object]:
     return {
         "id": snapshot.id,
@@ -511,6 +629,7 @@ def thumbnail_snapshot_to_dict(snapshot: ThumbnailSnapshot) -> dict[str, object]
         "media_type": snapshot.media_type.value,
         "format": snapshot.format.value,
         "max_dimension": snapshot.max_dimension,
+        "sizes": list(snapshot.sizes),
         "version": snapshot.version,
         "source_size_bytes": snapshot.source_size_bytes,
         "source_mtime_ns": snapshot.source_mtime_ns,
@@ -536,6 +655,7 @@ def thumbnail_cleanup_snapshot_to_dict(snapshot: ThumbnailCleanupSnapshot) -> di
     return {
         "id": snapshot.id,
         "group_key": snapshot.group_key,
+        "cleanup_kind": snapshot.cleanup_kind.value,
         "status": snapshot.status.value,
         "execute_after": snapshot.execute_after,
         "worker_id": snapshot.worker_id,
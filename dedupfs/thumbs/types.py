@@ -3,7 +3,13 @@ from __future__ import annotations
 from dataclasses import dataclass
 from datetime import datetime
 
-from dedupfs.db.models import ThumbnailCleanupStatus, ThumbnailFormat, ThumbnailMediaType, ThumbnailStatus
+from dedupfs.db.models import (
+    ThumbnailCleanupKind,
+    ThumbnailCleanupStatus,
+    ThumbnailFormat,
+    ThumbnailMediaType,
+    ThumbnailStatus,
+)
 
 
 @dataclass(frozen=True)
@@ -16,6 +22,7 @@ class ThumbnailSnapshot:
     media_type: ThumbnailMediaType
     format: ThumbnailFormat
     max_dimension: int
+    sizes: list[int]
     version: int
     source_size_bytes: int
     source_mtime_ns: int
@@ -40,6 +47,7 @@ class ThumbnailSnapshot:
 class ThumbnailCleanupSnapshot:
     id: int
     group_key: str
+    cleanup_kind: ThumbnailCleanupKind
     status: ThumbnailCleanupStatus
     execute_after: datetime
     worker_id: str | None
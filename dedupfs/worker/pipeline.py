@@ -19,6 +19,7 @@ def enqueue_scan_job(
     library_names: Sequence[str] | None = None,
     batch_size: int | None = None,
     *,
+    extensions: Sequence[str] | None = None,
     dry_run: bool | None = None,
 ) -> str:
     settings = get_settings()
@@ -27,6 +28,7 @@ def enqueue_scan_job(
     payload: dict[str, Any] = {
         "library_names": list(library_names) if library_names is not None else None,
         "batch_size": batch_size,
+        "extensions": list(extensions) if extensions is not None else None,
     }
     snapshot = job_service.create_job(
         kind=JobKind.SCAN,
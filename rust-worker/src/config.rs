@@ -1,11 +1,15 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use rand::distributions::{Alphanumeric, DistString};
 use serde::Deserialize;
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+use crate::db::WalCheckpointMode;
+use crate::error_codes::ErrorCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum HashAlgorithm {
     Blake3,
@@ -29,80 +33,382 @@ impl HashAlgorithm {
     }
 }
 
+/// What the startup schema-version gate does when `schema_migrations` does not exist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingSchemaVersionPolicy {
+    Warn,
+    Refuse,
+}
+
+impl MissingSchemaVersionPolicy {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "warn" => Ok(Self::Warn),
+            "refuse" => Ok(Self::Refuse),
+            _ => bail!("unsupported schema_version_missing policy: {raw}"),
+        }
+    }
+}
+
+/// Worker log output: human-readable lines or one JSON object per event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("unsupported log_format: {raw}"),
+        }
+    }
+}
+
+/// How a video thumbnail picks its frame: a seek offset, or ffmpeg's `thumbnail`
+/// filter over the start of the clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoFrameStrategy {
+    Fixed,
+    Representative,
+}
+
+impl VideoFrameStrategy {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "fixed" => Ok(Self::Fixed),
+            "representative" => Ok(Self::Representative),
+            _ => bail!("unsupported thumbnail_video_frame_strategy: {raw}"),
+        }
+    }
+}
+
+/// A `[libraries.<name>]` table; unset fields fall back to the global settings.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LibraryConfig {
+    pub hash_algorithm: Option<HashAlgorithm>,
+    pub exclude_patterns: Option<Vec<String>>,
+    /// Files smaller than this are not hashed.
+    pub min_size_bytes: Option<u64>,
+    /// Files larger than this are not hashed; `0` skips hashing the library entirely.
+    pub max_size_bytes: Option<u64>,
+}
+
+/// Settings a scan or hash job applies to one library, see `effective_library_config`.
+#[derive(Debug, Clone)]
+pub struct EffectiveLibraryConfig {
+    pub hash_algorithm: HashAlgorithm,
+    pub exclude_patterns: Vec<String>,
+    pub min_size_bytes: Option<u64>,
+    pub max_size_bytes: Option<u64>,
+}
+
+/// Parser for the `--config` file; `Auto` picks by extension and otherwise tries TOML
+/// before JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Auto,
+}
+
+impl ConfigFormat {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            "auto" => Ok(ConfigFormat::Auto),
+            _ => bail!("unsupported config format: {raw}"),
+        }
+    }
+
+    fn resolve(self, path: &Path) -> Self {
+        if self != ConfigFormat::Auto {
+            return self;
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => ConfigFormat::Json,
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Auto,
+        }
+    }
+}
+
+/// `libraries_root` accepts a single path or a list of roots.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LibrariesRootSetting {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl LibrariesRootSetting {
+    fn into_paths(self) -> Vec<PathBuf> {
+        match self {
+            Self::One(path) => vec![path],
+            Self::Many(paths) => paths,
+        }
+    }
+}
+
 #[derive(Debug, Default, Deserialize)]
 struct PartialWorkerConfig {
     state_root: Option<PathBuf>,
-    libraries_root: Option<PathBuf>,
+    libraries_root: Option<LibrariesRootSetting>,
     database_path: Option<PathBuf>,
+    pid_file: Option<PathBuf>,
     thumbs_root: Option<PathBuf>,
     concurrency: Option<usize>,
     io_rate_limit_mib_per_sec: Option<u64>,
+    hash_io_burst_multiplier: Option<f64>,
     hash_algorithm: Option<HashAlgorithm>,
     scan_write_batch_size: Option<usize>,
+    scan_follow_symlinks: Option<bool>,
+    scan_stay_on_filesystem: Option<bool>,
+    scan_max_depth: Option<usize>,
+    scan_max_runtime_seconds: Option<u64>,
+    scan_per_library_max_seconds: Option<u64>,
+    scan_throughput_sample_seconds: Option<u64>,
+    scan_schedule_interval_seconds: Option<u64>,
+    scan_respect_ignore_files: Option<bool>,
+    allow_symlinked_roots: Option<bool>,
+    scan_include_extensions: Option<Vec<String>>,
+    scan_exclude_extensions: Option<Vec<String>>,
+    scan_store_path_hash: Option<bool>,
+    scan_capture_permissions: Option<bool>,
+    scan_detect_media_type: Option<bool>,
+    scan_probe_image_dimensions: Option<bool>,
+    scan_normalize_unicode: Option<bool>,
+    scan_count_hardlinks_once: Option<bool>,
+    libraries: Option<HashMap<String, LibraryConfig>>,
+    #[serde(rename = "scan_exclude_patterns")]
+    exclude_patterns: Option<Vec<String>>,
     hash_fetch_batch_size: Option<usize>,
+    report_parallelism: Option<usize>,
     hash_read_chunk_bytes: Option<usize>,
+    hash_small_file_threshold_bytes: Option<u64>,
+    hash_max_file_size_bytes: Option<u64>,
+    hash_prefix_bytes: Option<u64>,
+    hash_quick_sample_bytes: Option<u64>,
     hash_claim_ttl_seconds: Option<u64>,
     hash_retry_base_seconds: Option<u64>,
     hash_retry_max_seconds: Option<u64>,
+    hash_max_error_count: Option<i64>,
+    circuit_breaker_failure_threshold: Option<u32>,
     job_lock_ttl_seconds: Option<u64>,
+    job_max_retries: Option<i64>,
+    commit_yield_interval_seconds: Option<u64>,
     thumbnail_image_concurrency: Option<usize>,
     thumbnail_video_concurrency: Option<usize>,
     thumbnail_io_rate_limit_mib_per_sec: Option<u64>,
     thumbnail_retry_base_seconds: Option<u64>,
     thumbnail_retry_max_seconds: Option<u64>,
     thumbnail_ffmpeg_bin: Option<String>,
+    thumbnail_require_ffmpeg: Option<bool>,
+    thumbnail_ffprobe_bin: Option<String>,
     thumbnail_ffmpeg_timeout_seconds: Option<u64>,
+    thumbnail_ffmpeg_terminal_exit_codes: Option<Vec<i32>>,
+    thumbnail_ffmpeg_terminal_stderr_patterns: Option<Vec<String>>,
+    thumbnail_enable_heif_fallback: Option<bool>,
+    thumbnail_apply_exif_orientation: Option<bool>,
+    thumbnail_gif_max_frames: Option<usize>,
+    thumbnail_skip_if_fresh: Option<bool>,
+    thumbnail_fsync: Option<bool>,
+    thumbnail_multi_size: Option<bool>,
+    thumbnail_video_seek_seconds: Option<f64>,
+    thumbnail_video_seek_percent: Option<f64>,
+    thumbnail_video_frame_strategy: Option<VideoFrameStrategy>,
+    thumbnail_video_representative_window_seconds: Option<f64>,
     thumbnail_max_dimension: Option<usize>,
+    thumbnail_max_source_pixels: Option<u64>,
+    thumbnail_png_compression: Option<u32>,
+    thumbnail_jpeg_quality: Option<u8>,
+    thumbnail_webp_lossless: Option<bool>,
+    thumbnail_dir_mode: Option<String>,
+    thumbnail_file_mode: Option<String>,
+    thumbnail_library_offline_retry_seconds: Option<u64>,
+    thumbnail_disk_full_retry_seconds: Option<u64>,
+    thumbnail_max_error_count: Option<i64>,
+    thumbnail_permanent_error_codes: Option<Vec<String>>,
+    cleanup_delete_batch_size: Option<usize>,
+    thumbnail_orphan_grace_seconds: Option<u64>,
     rust_worker_poll_seconds: Option<u64>,
     rust_worker_max_poll_seconds: Option<u64>,
+    rust_worker_error_backoff_multiplier: Option<f64>,
     rust_worker_poll_jitter_millis: Option<u64>,
+    rust_worker_idle_exit_seconds: Option<u64>,
+    max_claims_per_second: Option<f64>,
+    recover_leases_on_startup: Option<bool>,
     wal_checkpoint_retry_seconds: Option<u64>,
+    wal_auto_checkpoint_size_bytes: Option<u64>,
+    wal_allowed_checkpoint_modes: Option<Vec<String>>,
+    completion_webhook_url: Option<String>,
+    completion_webhook_max_attempts: Option<u32>,
+    completion_webhook_timeout_seconds: Option<u64>,
+    schema_version_check: Option<bool>,
+    schema_version_missing: Option<MissingSchemaVersionPolicy>,
+    log_format: Option<LogFormat>,
 }
 
 #[derive(Debug, Clone)]
 pub struct WorkerConfig {
-    pub libraries_root: PathBuf,
-    pub libraries_root_real: PathBuf,
+    pub libraries_roots: Vec<PathBuf>,
+    pub libraries_roots_real: Vec<PathBuf>,
     pub database_path: PathBuf,
+    pub pid_file: Option<PathBuf>,
     pub thumbs_root_real: PathBuf,
     pub concurrency: usize,
     pub io_rate_limit_mib_per_sec: Option<u64>,
+    pub hash_io_burst_multiplier: f64,
     pub hash_algorithm: HashAlgorithm,
     pub scan_write_batch_size: usize,
+    pub scan_follow_symlinks: bool,
+    pub scan_stay_on_filesystem: bool,
+    pub scan_max_depth: Option<usize>,
+    pub scan_max_runtime_seconds: Option<u64>,
+    pub scan_per_library_max_seconds: Option<u64>,
+    pub scan_throughput_sample_seconds: Option<u64>,
+    pub scan_schedule_interval_seconds: Option<u64>,
+    pub scan_respect_ignore_files: bool,
+    pub allow_symlinked_roots: bool,
+    pub scan_include_extensions: Option<Vec<String>>,
+    pub scan_exclude_extensions: Option<Vec<String>>,
+    pub scan_store_path_hash: bool,
+    pub scan_capture_permissions: bool,
+    pub scan_detect_media_type: bool,
+    pub scan_probe_image_dimensions: bool,
+    pub scan_normalize_unicode: bool,
+    pub scan_count_hardlinks_once: bool,
+    pub libraries: HashMap<String, LibraryConfig>,
+    pub scan_exclude_patterns: Vec<String>,
     pub hash_fetch_batch_size: usize,
+    pub report_parallelism: usize,
     pub hash_read_chunk_bytes: usize,
+    pub hash_small_file_threshold_bytes: u64,
+    pub hash_max_file_size_bytes: Option<u64>,
+    pub hash_prefix_bytes: Option<u64>,
+    pub hash_quick_sample_bytes: Option<u64>,
     pub hash_claim_ttl_seconds: u64,
     pub hash_retry_base_seconds: u64,
     pub hash_retry_max_seconds: u64,
+    pub hash_max_error_count: i64,
+    pub circuit_breaker_failure_threshold: u32,
     pub job_lock_ttl_seconds: u64,
+    pub job_max_retries: Option<i64>,
+    pub commit_yield_interval_seconds: u64,
     pub thumbnail_image_concurrency: usize,
     pub thumbnail_video_concurrency: usize,
     pub thumbnail_io_rate_limit_mib_per_sec: Option<u64>,
     pub thumbnail_retry_base_seconds: u64,
     pub thumbnail_retry_max_seconds: u64,
     pub thumbnail_ffmpeg_bin: String,
+    pub thumbnail_require_ffmpeg: bool,
+    pub thumbnail_ffprobe_bin: String,
     pub thumbnail_ffmpeg_timeout_seconds: u64,
+    pub thumbnail_ffmpeg_terminal_exit_codes: Vec<i32>,
+    pub thumbnail_ffmpeg_terminal_stderr_patterns: Vec<String>,
+    pub thumbnail_enable_heif_fallback: bool,
+    pub thumbnail_apply_exif_orientation: bool,
+    pub thumbnail_gif_max_frames: usize,
+    pub thumbnail_skip_if_fresh: bool,
+    pub thumbnail_fsync: bool,
+    pub thumbnail_multi_size: bool,
+    pub thumbnail_video_seek_seconds: f64,
+    pub thumbnail_video_seek_percent: Option<f64>,
+    pub thumbnail_video_frame_strategy: VideoFrameStrategy,
+    pub thumbnail_video_representative_window_seconds: f64,
     pub thumbnail_max_dimension: usize,
+    pub thumbnail_max_source_pixels: u64,
+    pub thumbnail_png_compression: u32,
+    pub thumbnail_jpeg_quality: u8,
+    pub thumbnail_webp_lossless: bool,
+    pub thumbnail_dir_mode: Option<u32>,
+    pub thumbnail_file_mode: Option<u32>,
+    pub thumbnail_library_offline_retry_seconds: u64,
+    pub thumbnail_disk_full_retry_seconds: u64,
+    pub thumbnail_max_error_count: i64,
+    pub thumbnail_permanent_error_codes: Vec<ErrorCode>,
+    pub cleanup_delete_batch_size: usize,
+    pub thumbnail_orphan_grace_seconds: u64,
     pub rust_worker_poll_seconds: u64,
     pub rust_worker_max_poll_seconds: u64,
+    pub rust_worker_error_backoff_multiplier: f64,
     pub rust_worker_poll_jitter_millis: u64,
+    pub rust_worker_idle_exit_seconds: Option<u64>,
+    pub max_claims_per_second: Option<f64>,
+    pub recover_leases_on_startup: bool,
     pub wal_checkpoint_retry_seconds: u64,
+    pub wal_auto_checkpoint_size_bytes: Option<u64>,
+    pub wal_allowed_checkpoint_modes: Vec<WalCheckpointMode>,
+    pub completion_webhook_url: Option<String>,
+    pub completion_webhook_max_attempts: u32,
+    pub completion_webhook_timeout_seconds: u64,
+    pub schema_version_check: bool,
+    pub schema_version_missing: MissingSchemaVersionPolicy,
+    pub log_format: LogFormat,
     pub worker_id: String,
 }
 
 impl WorkerConfig {
-    pub fn load(config_path: Option<&Path>, worker_id_override: Option<&str>) -> Result<Self> {
+    pub fn load(
+        config_path: Option<&Path>,
+        config_format: ConfigFormat,
+        worker_id_override: Option<&str>,
+    ) -> Result<Self> {
+        Self::load_inner(config_path, config_format, worker_id_override, true)
+    }
+
+    /// Merges the `[libraries.<name>]` overrides, if any, over the global settings.
+    pub fn effective_library_config(&self, library_name: &str) -> EffectiveLibraryConfig {
+        let library = self.libraries.get(library_name);
+        EffectiveLibraryConfig {
+            hash_algorithm: library
+                .and_then(|library| library.hash_algorithm)
+                .unwrap_or(self.hash_algorithm),
+            exclude_patterns: library
+                .and_then(|library| library.exclude_patterns.clone())
+                .unwrap_or_else(|| self.scan_exclude_patterns.clone()),
+            min_size_bytes: library.and_then(|library| library.min_size_bytes),
+            max_size_bytes: library
+                .and_then(|library| library.max_size_bytes)
+                .or(self.hash_max_file_size_bytes),
+        }
+    }
+
+    /// Same resolution as `load`, but a missing state or thumbs root is an error instead
+    /// of being created, so `--validate-config` leaves the filesystem untouched.
+    pub fn load_for_validation(
+        config_path: Option<&Path>,
+        config_format: ConfigFormat,
+    ) -> Result<Self> {
+        Self::load_inner(config_path, config_format, None, false)
+    }
+
+    fn load_inner(
+        config_path: Option<&Path>,
+        config_format: ConfigFormat,
+        worker_id_override: Option<&str>,
+        create_roots: bool,
+    ) -> Result<Self> {
         let mut partial = PartialWorkerConfig::default();
 
         if let Some(path) = config_path {
-            let content = fs::read_to_string(path)
-                .with_context(|| format!("failed to read config file: {}", path.display()))?;
-            partial = toml::from_str(&content)
-                .with_context(|| format!("failed to parse config TOML: {}", path.display()))?;
+            partial = read_config_file(path, config_format)?;
         }
 
         if let Ok(value) = std::env::var("DEDUPFS_LIBRARIES_ROOT") {
-            partial.libraries_root = Some(PathBuf::from(value));
+            partial.libraries_root = Some(LibrariesRootSetting::Many(
+                value
+                    .split(':')
+                    .filter(|part| !part.is_empty())
+                    .map(PathBuf::from)
+                    .collect(),
+            ));
         }
         if let Ok(value) = std::env::var("DEDUPFS_STATE_ROOT") {
             let state_root = PathBuf::from(value);
@@ -112,6 +418,9 @@ impl WorkerConfig {
                 partial.thumbs_root = Some(state_root.join("thumbs"));
             }
         }
+        if let Ok(value) = std::env::var("DEDUPFS_PID_FILE") {
+            partial.pid_file = Some(PathBuf::from(value));
+        }
         if let Ok(value) = std::env::var("DEDUPFS_THUMBS_ROOT") {
             partial.thumbs_root = Some(PathBuf::from(value));
         }
@@ -134,6 +443,13 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_RUST_WORKER_IO_RATE_LIMIT_MIB_PER_SEC")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_HASH_IO_BURST_MULTIPLIER") {
+            partial.hash_io_burst_multiplier = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_HASH_IO_BURST_MULTIPLIER")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_DEFAULT_HASH_ALGORITHM") {
             partial.hash_algorithm = Some(HashAlgorithm::parse(&value)?);
         }
@@ -144,6 +460,14 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_SCAN_WRITE_BATCH_SIZE")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_FOLLOW_SYMLINKS") {
+            partial.scan_follow_symlinks =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_FOLLOW_SYMLINKS")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_STAY_ON_FILESYSTEM") {
+            partial.scan_stay_on_filesystem =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_STAY_ON_FILESYSTEM")?);
+        }
         if let Ok(value) = std::env::var("DEDUPFS_HASH_FETCH_BATCH_SIZE") {
             partial.hash_fetch_batch_size = Some(
                 value
@@ -151,6 +475,13 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_HASH_FETCH_BATCH_SIZE")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_REPORT_PARALLELISM") {
+            partial.report_parallelism = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_RUST_WORKER_REPORT_PARALLELISM")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_HASH_READ_CHUNK_BYTES") {
             partial.hash_read_chunk_bytes = Some(
                 value
@@ -158,6 +489,31 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_HASH_READ_CHUNK_BYTES")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_HASH_SMALL_FILE_THRESHOLD_BYTES") {
+            partial.hash_small_file_threshold_bytes = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_HASH_SMALL_FILE_THRESHOLD_BYTES")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_HASH_MAX_FILE_SIZE_BYTES") {
+            partial.hash_max_file_size_bytes = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_HASH_MAX_FILE_SIZE_BYTES")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_HASH_PREFIX_BYTES") {
+            partial.hash_prefix_bytes =
+                Some(value.parse().context("invalid DEDUPFS_HASH_PREFIX_BYTES")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_HASH_QUICK_SAMPLE_BYTES") {
+            partial.hash_quick_sample_bytes = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_HASH_QUICK_SAMPLE_BYTES")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_HASH_CLAIM_TTL_SECONDS") {
             partial.hash_claim_ttl_seconds = Some(
                 value
@@ -179,6 +535,20 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_HASH_RETRY_MAX_SECONDS")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_HASH_MAX_ERROR_COUNT") {
+            partial.hash_max_error_count = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_HASH_MAX_ERROR_COUNT")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_CIRCUIT_BREAKER_THRESHOLD") {
+            partial.circuit_breaker_failure_threshold = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_CIRCUIT_BREAKER_THRESHOLD")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_JOB_LOCK_TTL_SECONDS") {
             partial.job_lock_ttl_seconds = Some(
                 value
@@ -186,6 +556,17 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_JOB_LOCK_TTL_SECONDS")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_JOB_MAX_RETRIES") {
+            partial.job_max_retries =
+                Some(value.parse().context("invalid DEDUPFS_JOB_MAX_RETRIES")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_COMMIT_YIELD_INTERVAL_SECONDS") {
+            partial.commit_yield_interval_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_COMMIT_YIELD_INTERVAL_SECONDS")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_IMAGE_CONCURRENCY") {
             partial.thumbnail_image_concurrency = Some(
                 value
@@ -224,6 +605,13 @@ impl WorkerConfig {
         if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FFMPEG_BIN") {
             partial.thumbnail_ffmpeg_bin = Some(value);
         }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_REQUIRE_FFMPEG") {
+            partial.thumbnail_require_ffmpeg =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_THUMBNAIL_REQUIRE_FFMPEG")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FFPROBE_BIN") {
+            partial.thumbnail_ffprobe_bin = Some(value);
+        }
         if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FFMPEG_TIMEOUT_SECONDS") {
             partial.thumbnail_ffmpeg_timeout_seconds = Some(
                 value
@@ -231,6 +619,76 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_THUMBNAIL_FFMPEG_TIMEOUT_SECONDS")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FFMPEG_TERMINAL_EXIT_CODES") {
+            partial.thumbnail_ffmpeg_terminal_exit_codes = Some(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .context("invalid DEDUPFS_THUMBNAIL_FFMPEG_TERMINAL_EXIT_CODES")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FFMPEG_TERMINAL_STDERR_PATTERNS") {
+            partial.thumbnail_ffmpeg_terminal_stderr_patterns =
+                Some(value.split(',').map(str::to_string).collect());
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_ENABLE_HEIF_FALLBACK") {
+            partial.thumbnail_enable_heif_fallback = Some(
+                parse_bool_flag(&value)
+                    .context("invalid DEDUPFS_THUMBNAIL_ENABLE_HEIF_FALLBACK")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_APPLY_EXIF_ORIENTATION") {
+            partial.thumbnail_apply_exif_orientation = Some(
+                parse_bool_flag(&value)
+                    .context("invalid DEDUPFS_THUMBNAIL_APPLY_EXIF_ORIENTATION")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_GIF_MAX_FRAMES") {
+            partial.thumbnail_gif_max_frames = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_GIF_MAX_FRAMES")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_SKIP_IF_FRESH") {
+            partial.thumbnail_skip_if_fresh =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_THUMBNAIL_SKIP_IF_FRESH")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FSYNC") {
+            partial.thumbnail_fsync =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_THUMBNAIL_FSYNC")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_MULTI_SIZE") {
+            partial.thumbnail_multi_size =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_THUMBNAIL_MULTI_SIZE")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_VIDEO_SEEK_SECONDS") {
+            partial.thumbnail_video_seek_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_VIDEO_SEEK_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_VIDEO_SEEK_PERCENT") {
+            partial.thumbnail_video_seek_percent = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_VIDEO_SEEK_PERCENT")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_VIDEO_FRAME_STRATEGY") {
+            partial.thumbnail_video_frame_strategy = Some(VideoFrameStrategy::parse(&value)?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_VIDEO_REPRESENTATIVE_WINDOW_SECONDS") {
+            partial.thumbnail_video_representative_window_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_VIDEO_REPRESENTATIVE_WINDOW_SECONDS")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_MAX_DIMENSION") {
             partial.thumbnail_max_dimension = Some(
                 value
@@ -238,6 +696,76 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_THUMBNAIL_MAX_DIMENSION")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_MAX_SOURCE_PIXELS") {
+            partial.thumbnail_max_source_pixels = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_MAX_SOURCE_PIXELS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_PNG_COMPRESSION") {
+            partial.thumbnail_png_compression = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_PNG_COMPRESSION")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_JPEG_QUALITY") {
+            partial.thumbnail_jpeg_quality = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_JPEG_QUALITY")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_WEBP_LOSSLESS") {
+            partial.thumbnail_webp_lossless =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_THUMBNAIL_WEBP_LOSSLESS")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_DIR_MODE") {
+            partial.thumbnail_dir_mode = Some(value);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_FILE_MODE") {
+            partial.thumbnail_file_mode = Some(value);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_LIBRARY_OFFLINE_RETRY_SECONDS") {
+            partial.thumbnail_library_offline_retry_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_LIBRARY_OFFLINE_RETRY_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_DISK_FULL_RETRY_SECONDS") {
+            partial.thumbnail_disk_full_retry_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_DISK_FULL_RETRY_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_MAX_ERROR_COUNT") {
+            partial.thumbnail_max_error_count = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_MAX_ERROR_COUNT")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_PERMANENT_ERROR_CODES") {
+            partial.thumbnail_permanent_error_codes =
+                Some(value.split(',').map(str::to_string).collect());
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_CLEANUP_DELETE_BATCH_SIZE") {
+            partial.cleanup_delete_batch_size = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_CLEANUP_DELETE_BATCH_SIZE")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_THUMBNAIL_ORPHAN_GRACE_SECONDS") {
+            partial.thumbnail_orphan_grace_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_THUMBNAIL_ORPHAN_GRACE_SECONDS")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_POLL_SECONDS") {
             partial.rust_worker_poll_seconds = Some(
                 value
@@ -252,6 +780,13 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_RUST_WORKER_MAX_POLL_SECONDS")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_ERROR_BACKOFF_MULTIPLIER") {
+            partial.rust_worker_error_backoff_multiplier = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_RUST_WORKER_ERROR_BACKOFF_MULTIPLIER")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_POLL_JITTER_MILLIS") {
             partial.rust_worker_poll_jitter_millis = Some(
                 value
@@ -259,6 +794,26 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_RUST_WORKER_POLL_JITTER_MILLIS")?,
             );
         }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_IDLE_EXIT_SECONDS") {
+            partial.rust_worker_idle_exit_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_RUST_WORKER_IDLE_EXIT_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_MAX_CLAIMS_PER_SECOND") {
+            partial.max_claims_per_second = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_RUST_WORKER_MAX_CLAIMS_PER_SECOND")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_RECOVER_LEASES_ON_STARTUP") {
+            partial.recover_leases_on_startup = Some(
+                parse_bool_flag(&value)
+                    .context("invalid DEDUPFS_RUST_WORKER_RECOVER_LEASES_ON_STARTUP")?,
+            );
+        }
         if let Ok(value) = std::env::var("DEDUPFS_WAL_CHECKPOINT_RETRY_SECONDS") {
             partial.wal_checkpoint_retry_seconds = Some(
                 value
@@ -266,26 +821,152 @@ impl WorkerConfig {
                     .context("invalid DEDUPFS_WAL_CHECKPOINT_RETRY_SECONDS")?,
             );
         }
-
-        let libraries_root = partial
-            .libraries_root
-            .unwrap_or_else(|| PathBuf::from("/libraries"));
-        if !libraries_root.is_absolute() {
-            bail!("libraries_root must be absolute");
+        if let Ok(value) = std::env::var("DEDUPFS_WAL_AUTO_CHECKPOINT_SIZE_BYTES") {
+            partial.wal_auto_checkpoint_size_bytes = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_WAL_AUTO_CHECKPOINT_SIZE_BYTES")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_WAL_ALLOWED_CHECKPOINT_MODES") {
+            partial.wal_allowed_checkpoint_modes = Some(
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect(),
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_COMPLETION_WEBHOOK_URL") {
+            partial.completion_webhook_url = Some(value);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_COMPLETION_WEBHOOK_MAX_ATTEMPTS") {
+            partial.completion_webhook_max_attempts = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_COMPLETION_WEBHOOK_MAX_ATTEMPTS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_COMPLETION_WEBHOOK_TIMEOUT_SECONDS") {
+            partial.completion_webhook_timeout_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_COMPLETION_WEBHOOK_TIMEOUT_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_SCHEMA_VERSION_CHECK") {
+            partial.schema_version_check = Some(
+                parse_bool_flag(&value)
+                    .context("invalid DEDUPFS_RUST_WORKER_SCHEMA_VERSION_CHECK")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_RUST_WORKER_SCHEMA_VERSION_MISSING") {
+            partial.schema_version_missing = Some(MissingSchemaVersionPolicy::parse(&value)?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_LOG_FORMAT") {
+            partial.log_format = Some(LogFormat::parse(&value)?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_MAX_DEPTH") {
+            partial.scan_max_depth = Some(value.parse().context("invalid DEDUPFS_SCAN_MAX_DEPTH")?);
         }
-        if libraries_root.as_os_str() != "/libraries" {
-            bail!("libraries_root must resolve to /libraries");
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_MAX_RUNTIME_SECONDS") {
+            partial.scan_max_runtime_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_SCAN_MAX_RUNTIME_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_PER_LIBRARY_MAX_SECONDS") {
+            partial.scan_per_library_max_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_SCAN_PER_LIBRARY_MAX_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_THROUGHPUT_SAMPLE_SECONDS") {
+            partial.scan_throughput_sample_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_SCAN_THROUGHPUT_SAMPLE_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_SCHEDULE_INTERVAL_SECONDS") {
+            partial.scan_schedule_interval_seconds = Some(
+                value
+                    .parse()
+                    .context("invalid DEDUPFS_SCAN_SCHEDULE_INTERVAL_SECONDS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_RESPECT_IGNORE_FILES") {
+            partial.scan_respect_ignore_files =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_RESPECT_IGNORE_FILES")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_ALLOW_SYMLINKED_ROOTS") {
+            partial.allow_symlinked_roots =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_ALLOW_SYMLINKED_ROOTS")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_STORE_PATH_HASH") {
+            partial.scan_store_path_hash =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_STORE_PATH_HASH")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_CAPTURE_PERMISSIONS") {
+            partial.scan_capture_permissions =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_CAPTURE_PERMISSIONS")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_COUNT_HARDLINKS_ONCE") {
+            partial.scan_count_hardlinks_once =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_COUNT_HARDLINKS_ONCE")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_DETECT_MEDIA_TYPE") {
+            partial.scan_detect_media_type =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_DETECT_MEDIA_TYPE")?);
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_PROBE_IMAGE_DIMENSIONS") {
+            partial.scan_probe_image_dimensions = Some(
+                parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_PROBE_IMAGE_DIMENSIONS")?,
+            );
+        }
+        if let Ok(value) = std::env::var("DEDUPFS_SCAN_NORMALIZE_UNICODE") {
+            partial.scan_normalize_unicode =
+                Some(parse_bool_flag(&value).context("invalid DEDUPFS_SCAN_NORMALIZE_UNICODE")?);
         }
 
-        let libraries_root_real = match libraries_root.canonicalize() {
-            Ok(path) => {
-                if !path.is_dir() {
-                    bail!("libraries_root is not a directory: {}", path.display());
+        let libraries_roots = partial
+            .libraries_root
+            .map(LibrariesRootSetting::into_paths)
+            .unwrap_or_else(|| vec![PathBuf::from("/libraries")]);
+        if libraries_roots.is_empty() {
+            bail!("libraries_root must list at least one path");
+        }
+        let mut libraries_roots_real = Vec::with_capacity(libraries_roots.len());
+        for libraries_root in &libraries_roots {
+            if !libraries_root.is_absolute() {
+                bail!("libraries_root must be absolute");
+            }
+            if libraries_root.parent().is_none() {
+                bail!("libraries_root cannot be the filesystem root");
+            }
+            let libraries_root_real = match libraries_root.canonicalize() {
+                Ok(path) => {
+                    if !path.is_dir() {
+                        bail!("libraries_root is not a directory: {}", path.display());
+                    }
+                    path
                 }
-                path
+                Err(_) => libraries_root.clone(),
+            };
+            // A root nested in another would show up as one of its libraries.
+            if let Some(other) = libraries_roots_real.iter().find(|other: &&PathBuf| {
+                libraries_root_real.starts_with(other) || other.starts_with(&libraries_root_real)
+            }) {
+                bail!(
+                    "libraries_root entries overlap: {} and {}",
+                    other.display(),
+                    libraries_root_real.display()
+                );
             }
-            Err(_) => libraries_root.clone(),
-        };
+            libraries_roots_real.push(libraries_root_real);
+        }
 
         let database_path = partial
             .database_path
@@ -300,8 +981,11 @@ impl WorkerConfig {
         if !state_root.is_absolute() {
             bail!("state_root must be absolute");
         }
-        fs::create_dir_all(&state_root)
-            .with_context(|| format!("failed to create state_root: {}", state_root.display()))?;
+        if create_roots {
+            fs::create_dir_all(&state_root).with_context(|| {
+                format!("failed to create state_root: {}", state_root.display())
+            })?;
+        }
         let state_root_real = state_root
             .canonicalize()
             .with_context(|| format!("failed to resolve state_root: {}", state_root.display()))?;
@@ -321,8 +1005,11 @@ impl WorkerConfig {
         if !thumbs_root.is_absolute() {
             bail!("thumbs_root must be absolute");
         }
-        fs::create_dir_all(&thumbs_root)
-            .with_context(|| format!("failed to create thumbs_root: {}", thumbs_root.display()))?;
+        if create_roots {
+            fs::create_dir_all(&thumbs_root).with_context(|| {
+                format!("failed to create thumbs_root: {}", thumbs_root.display())
+            })?;
+        }
         let thumbs_root_real = thumbs_root
             .canonicalize()
             .with_context(|| format!("failed to resolve thumbs_root: {}", thumbs_root.display()))?;
@@ -347,18 +1034,77 @@ impl WorkerConfig {
 
         let concurrency = partial.concurrency.unwrap_or(4).max(1);
         let scan_write_batch_size = partial.scan_write_batch_size.unwrap_or(2000).max(1);
+        let scan_follow_symlinks = partial.scan_follow_symlinks.unwrap_or(false);
+        let scan_stay_on_filesystem = partial.scan_stay_on_filesystem.unwrap_or(false);
+        let scan_max_depth = partial.scan_max_depth.map(|value| value.max(1));
+        let scan_max_runtime_seconds = partial.scan_max_runtime_seconds.filter(|value| *value > 0);
+        let scan_per_library_max_seconds = partial
+            .scan_per_library_max_seconds
+            .filter(|value| *value > 0);
+        let scan_throughput_sample_seconds = partial
+            .scan_throughput_sample_seconds
+            .filter(|value| *value > 0);
+        let scan_schedule_interval_seconds = partial
+            .scan_schedule_interval_seconds
+            .filter(|value| *value > 0);
+        let scan_respect_ignore_files = partial.scan_respect_ignore_files.unwrap_or(true);
+        let allow_symlinked_roots = partial.allow_symlinked_roots.unwrap_or(false);
+        let scan_include_extensions = partial
+            .scan_include_extensions
+            .map(|values| normalize_extensions(&values));
+        let scan_exclude_extensions = partial
+            .scan_exclude_extensions
+            .map(|values| normalize_extensions(&values));
+        let scan_store_path_hash = partial.scan_store_path_hash.unwrap_or(false);
+        let scan_capture_permissions = partial.scan_capture_permissions.unwrap_or(false);
+        let scan_detect_media_type = partial.scan_detect_media_type.unwrap_or(false);
+        let scan_probe_image_dimensions = partial.scan_probe_image_dimensions.unwrap_or(false);
+        let scan_normalize_unicode = partial.scan_normalize_unicode.unwrap_or(false);
+        let scan_count_hardlinks_once = partial.scan_count_hardlinks_once.unwrap_or(true);
+        let libraries = partial.libraries.unwrap_or_default();
+        for (name, library) in &libraries {
+            for pattern in library.exclude_patterns.iter().flatten() {
+                glob::Pattern::new(pattern.trim_end_matches('/')).with_context(|| {
+                    format!("invalid libraries.{name}.exclude_patterns entry: {pattern}")
+                })?;
+            }
+            if let (Some(min), Some(max)) = (library.min_size_bytes, library.max_size_bytes) {
+                if min > max {
+                    bail!("libraries.{name}: min_size_bytes {min} exceeds max_size_bytes {max}");
+                }
+            }
+        }
+        let scan_exclude_patterns = partial.exclude_patterns.unwrap_or_default();
+        for pattern in &scan_exclude_patterns {
+            glob::Pattern::new(pattern.trim_end_matches('/'))
+                .with_context(|| format!("invalid scan_exclude_patterns entry: {pattern}"))?;
+        }
         let hash_fetch_batch_size = partial.hash_fetch_batch_size.unwrap_or(512).max(1);
+        let report_parallelism = partial.report_parallelism.unwrap_or(1).clamp(1, 16);
         let hash_read_chunk_bytes = partial
             .hash_read_chunk_bytes
             .unwrap_or(4 * 1024 * 1024)
             .max(1024);
+        let hash_small_file_threshold_bytes =
+            partial.hash_small_file_threshold_bytes.unwrap_or(64 * 1024);
+        let hash_max_file_size_bytes = partial.hash_max_file_size_bytes.filter(|value| *value > 0);
+        let hash_prefix_bytes = partial.hash_prefix_bytes.filter(|value| *value > 0);
+        let hash_quick_sample_bytes = partial
+            .hash_quick_sample_bytes
+            .filter(|value| *value > 0)
+            .map(|value| value.min(16 * 1024 * 1024));
         let hash_claim_ttl_seconds = partial.hash_claim_ttl_seconds.unwrap_or(600).max(1);
         let hash_retry_base_seconds = partial.hash_retry_base_seconds.unwrap_or(30).max(1);
         let hash_retry_max_seconds = partial
             .hash_retry_max_seconds
             .unwrap_or(3600)
             .max(hash_retry_base_seconds);
+        let hash_max_error_count = partial.hash_max_error_count.unwrap_or(10).max(1);
+        let circuit_breaker_failure_threshold =
+            partial.circuit_breaker_failure_threshold.unwrap_or(10);
         let job_lock_ttl_seconds = partial.job_lock_ttl_seconds.unwrap_or(300).max(1);
+        let job_max_retries = partial.job_max_retries.map(|value| value.max(1));
+        let commit_yield_interval_seconds = partial.commit_yield_interval_seconds.unwrap_or(30);
 
         let thumbnail_image_concurrency = partial.thumbnail_image_concurrency.unwrap_or(2).max(1);
         let thumbnail_video_concurrency = partial.thumbnail_video_concurrency.unwrap_or(1).max(1);
@@ -373,50 +1119,633 @@ impl WorkerConfig {
             .unwrap_or_else(|| "ffmpeg".to_string())
             .trim()
             .to_string();
+        let thumbnail_require_ffmpeg = partial.thumbnail_require_ffmpeg.unwrap_or(false);
         if thumbnail_ffmpeg_bin.is_empty() {
             bail!("thumbnail_ffmpeg_bin cannot be blank");
         }
+        let thumbnail_ffprobe_bin = partial
+            .thumbnail_ffprobe_bin
+            .unwrap_or_else(|| "ffprobe".to_string())
+            .trim()
+            .to_string();
+        if thumbnail_ffprobe_bin.is_empty() {
+            bail!("thumbnail_ffprobe_bin cannot be blank");
+        }
         let thumbnail_ffmpeg_timeout_seconds = partial
             .thumbnail_ffmpeg_timeout_seconds
             .unwrap_or(120)
             .max(1);
+        let thumbnail_ffmpeg_terminal_exit_codes = partial
+            .thumbnail_ffmpeg_terminal_exit_codes
+            .unwrap_or_default();
+        let thumbnail_ffmpeg_terminal_stderr_patterns = partial
+            .thumbnail_ffmpeg_terminal_stderr_patterns
+            .unwrap_or_default()
+            .into_iter()
+            .map(|pattern| pattern.trim().to_lowercase())
+            .filter(|pattern| !pattern.is_empty())
+            .collect::<Vec<_>>();
+        let thumbnail_enable_heif_fallback = partial.thumbnail_enable_heif_fallback.unwrap_or(true);
+        let thumbnail_apply_exif_orientation =
+            partial.thumbnail_apply_exif_orientation.unwrap_or(true);
+        let thumbnail_gif_max_frames = partial.thumbnail_gif_max_frames.unwrap_or(1).max(1);
+        let thumbnail_skip_if_fresh = partial.thumbnail_skip_if_fresh.unwrap_or(false);
+        let thumbnail_fsync = partial.thumbnail_fsync.unwrap_or(true);
+        let thumbnail_multi_size = partial.thumbnail_multi_size.unwrap_or(false);
+        let thumbnail_video_seek_seconds = partial
+            .thumbnail_video_seek_seconds
+            .filter(|value| value.is_finite())
+            .unwrap_or(1.0)
+            .max(0.0);
+        let thumbnail_video_seek_percent = partial
+            .thumbnail_video_seek_percent
+            .filter(|value| value.is_finite())
+            .map(|value| value.clamp(0.0, 100.0));
+        let thumbnail_video_frame_strategy = partial
+            .thumbnail_video_frame_strategy
+            .unwrap_or(VideoFrameStrategy::Fixed);
+        let thumbnail_video_representative_window_seconds = partial
+            .thumbnail_video_representative_window_seconds
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .unwrap_or(10.0);
         let thumbnail_max_dimension = partial.thumbnail_max_dimension.unwrap_or(256).max(16);
+        let thumbnail_max_source_pixels = partial
+            .thumbnail_max_source_pixels
+            .unwrap_or(100_000_000)
+            .max(1);
+        let thumbnail_png_compression = partial.thumbnail_png_compression.unwrap_or(6).min(9);
+        let thumbnail_jpeg_quality = partial.thumbnail_jpeg_quality.unwrap_or(80);
+        if !(1..=100).contains(&thumbnail_jpeg_quality) {
+            bail!("thumbnail_jpeg_quality must be between 1 and 100, got {thumbnail_jpeg_quality}");
+        }
+        let thumbnail_webp_lossless = partial.thumbnail_webp_lossless.unwrap_or(true);
+        let thumbnail_dir_mode = partial
+            .thumbnail_dir_mode
+            .as_deref()
+            .map(|raw| parse_octal_mode(raw, "thumbnail_dir_mode"))
+            .transpose()?;
+        let thumbnail_file_mode = partial
+            .thumbnail_file_mode
+            .as_deref()
+            .map(|raw| parse_octal_mode(raw, "thumbnail_file_mode"))
+            .transpose()?;
+        let thumbnail_library_offline_retry_seconds = partial
+            .thumbnail_library_offline_retry_seconds
+            .unwrap_or(3600)
+            .max(thumbnail_retry_base_seconds);
+        let thumbnail_disk_full_retry_seconds = partial
+            .thumbnail_disk_full_retry_seconds
+            .unwrap_or(1800)
+            .max(thumbnail_retry_base_seconds);
+        let thumbnail_max_error_count = partial.thumbnail_max_error_count.unwrap_or(5).max(1);
+        let thumbnail_permanent_error_codes = match partial.thumbnail_permanent_error_codes {
+            Some(raw_codes) => raw_codes
+                .iter()
+                .map(|raw| raw.trim())
+                .filter(|raw| !raw.is_empty())
+                .map(|raw| {
+                    ErrorCode::parse(raw).ok_or_else(|| {
+                        anyhow!("unsupported thumbnail_permanent_error_codes entry: {raw}")
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![
+                ErrorCode::ThumbDecodeFailed,
+                ErrorCode::ThumbPathPolicyRejected,
+            ],
+        };
+        let cleanup_delete_batch_size = partial.cleanup_delete_batch_size.unwrap_or(500).max(1);
+        let thumbnail_orphan_grace_seconds = partial.thumbnail_orphan_grace_seconds.unwrap_or(3600);
         let rust_worker_poll_seconds = partial.rust_worker_poll_seconds.unwrap_or(5).max(1);
         let rust_worker_max_poll_seconds = partial
             .rust_worker_max_poll_seconds
             .unwrap_or(30)
             .max(rust_worker_poll_seconds);
+        let rust_worker_error_backoff_multiplier = partial
+            .rust_worker_error_backoff_multiplier
+            .filter(|value| value.is_finite())
+            .unwrap_or(2.0)
+            .max(1.1);
         let rust_worker_poll_jitter_millis = partial.rust_worker_poll_jitter_millis.unwrap_or(250);
-        let wal_checkpoint_retry_seconds = partial.wal_checkpoint_retry_seconds.unwrap_or(120).max(1);
+        let max_claims_per_second = partial
+            .max_claims_per_second
+            .filter(|value| value.is_finite() && *value > 0.0);
+        let recover_leases_on_startup = partial.recover_leases_on_startup.unwrap_or(false);
+        let wal_checkpoint_retry_seconds =
+            partial.wal_checkpoint_retry_seconds.unwrap_or(120).max(1);
+        let wal_auto_checkpoint_size_bytes = partial
+            .wal_auto_checkpoint_size_bytes
+            .filter(|bytes| *bytes > 0);
+        let wal_allowed_checkpoint_modes =
+            parse_wal_checkpoint_modes(partial.wal_allowed_checkpoint_modes.as_deref())?;
+        let completion_webhook_url = partial
+            .completion_webhook_url
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+        if let Some(url) = &completion_webhook_url {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                bail!("completion_webhook_url must be an http:// or https:// URL");
+            }
+        }
+        let completion_webhook_max_attempts = partial
+            .completion_webhook_max_attempts
+            .unwrap_or(3)
+            .clamp(1, 10);
+        let completion_webhook_timeout_seconds = partial
+            .completion_webhook_timeout_seconds
+            .unwrap_or(5)
+            .max(1);
+        let schema_version_check = partial.schema_version_check.unwrap_or(true);
+        let schema_version_missing = partial
+            .schema_version_missing
+            .unwrap_or(MissingSchemaVersionPolicy::Warn);
+        let log_format = partial.log_format.unwrap_or(LogFormat::Text);
+        let hash_io_burst_multiplier = partial
+            .hash_io_burst_multiplier
+            .filter(|value| value.is_finite())
+            .unwrap_or(1.0)
+            .clamp(1.0, 10.0);
 
         Ok(Self {
-            libraries_root,
-            libraries_root_real,
+            libraries_roots,
+            libraries_roots_real,
             database_path,
+            pid_file: partial.pid_file,
             thumbs_root_real,
             concurrency,
             io_rate_limit_mib_per_sec: partial.io_rate_limit_mib_per_sec,
+            hash_io_burst_multiplier,
             hash_algorithm: partial.hash_algorithm.unwrap_or(HashAlgorithm::Blake3),
             scan_write_batch_size,
+            scan_follow_symlinks,
+            scan_stay_on_filesystem,
+            scan_max_depth,
+            scan_max_runtime_seconds,
+            scan_per_library_max_seconds,
+            scan_throughput_sample_seconds,
+            scan_schedule_interval_seconds,
+            scan_respect_ignore_files,
+            allow_symlinked_roots,
+            scan_include_extensions,
+            scan_exclude_extensions,
+            scan_store_path_hash,
+            scan_capture_permissions,
+            scan_detect_media_type,
+            scan_probe_image_dimensions,
+            scan_normalize_unicode,
+            scan_count_hardlinks_once,
+            libraries,
+            scan_exclude_patterns,
             hash_fetch_batch_size,
+            report_parallelism,
             hash_read_chunk_bytes,
+            hash_small_file_threshold_bytes,
+            hash_max_file_size_bytes,
+            hash_prefix_bytes,
+            hash_quick_sample_bytes,
             hash_claim_ttl_seconds,
             hash_retry_base_seconds,
             hash_retry_max_seconds,
+            hash_max_error_count,
+            circuit_breaker_failure_threshold,
             job_lock_ttl_seconds,
+            job_max_retries,
+            commit_yield_interval_seconds,
             thumbnail_image_concurrency,
             thumbnail_video_concurrency,
             thumbnail_io_rate_limit_mib_per_sec: partial.thumbnail_io_rate_limit_mib_per_sec,
             thumbnail_retry_base_seconds,
             thumbnail_retry_max_seconds,
             thumbnail_ffmpeg_bin,
+            thumbnail_require_ffmpeg,
+            thumbnail_ffprobe_bin,
             thumbnail_ffmpeg_timeout_seconds,
+            thumbnail_ffmpeg_terminal_exit_codes,
+            thumbnail_ffmpeg_terminal_stderr_patterns,
+            thumbnail_enable_heif_fallback,
+            thumbnail_apply_exif_orientation,
+            thumbnail_gif_max_frames,
+            thumbnail_skip_if_fresh,
+            thumbnail_fsync,
+            thumbnail_multi_size,
+            thumbnail_video_seek_seconds,
+            thumbnail_video_seek_percent,
+            thumbnail_video_frame_strategy,
+            thumbnail_video_representative_window_seconds,
             thumbnail_max_dimension,
+            thumbnail_max_source_pixels,
+            thumbnail_png_compression,
+            thumbnail_jpeg_quality,
+            thumbnail_webp_lossless,
+            thumbnail_dir_mode,
+            thumbnail_file_mode,
+            thumbnail_library_offline_retry_seconds,
+            thumbnail_disk_full_retry_seconds,
+            thumbnail_max_error_count,
+            thumbnail_permanent_error_codes,
+            cleanup_delete_batch_size,
+            thumbnail_orphan_grace_seconds,
             rust_worker_poll_seconds,
             rust_worker_max_poll_seconds,
+            rust_worker_error_backoff_multiplier,
             rust_worker_poll_jitter_millis,
+            rust_worker_idle_exit_seconds: partial.rust_worker_idle_exit_seconds,
+            max_claims_per_second,
+            recover_leases_on_startup,
             wal_checkpoint_retry_seconds,
+            wal_auto_checkpoint_size_bytes,
+            wal_allowed_checkpoint_modes,
+            completion_webhook_url,
+            completion_webhook_max_attempts,
+            completion_webhook_timeout_seconds,
+            schema_version_check,
+            schema_version_missing,
+            log_format,
             worker_id,
         })
     }
 }
+
+fn parse_wal_checkpoint_modes(raw: Option<&[String]>) -> Result<Vec<WalCheckpointMode>> {
+    let Some(raw) = raw else {
+        return Ok(vec![
+            WalCheckpointMode::Passive,
+            WalCheckpointMode::Full,
+            WalCheckpointMode::Restart,
+            WalCheckpointMode::Truncate,
+        ]);
+    };
+    let mut modes = Vec::with_capacity(raw.len());
+    for item in raw {
+        let mode = WalCheckpointMode::parse(&item.trim().to_lowercase())
+            .ok_or_else(|| anyhow!("unsupported wal checkpoint mode: {item}"))?;
+        if !modes.contains(&mode) {
+            modes.push(mode);
+        }
+    }
+    if modes.is_empty() {
+        bail!("wal_allowed_checkpoint_modes cannot be empty");
+    }
+    Ok(modes)
+}
+
+/// Lowercases extensions and strips a leading dot so `.JPG` and `jpg` match.
+pub fn normalize_extensions(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .map(|value| value.trim().trim_start_matches('.').to_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn parse_bool_flag(raw: &str) -> Result<bool> {
+    match raw.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => bail!("expected a boolean flag, got: {raw}"),
+    }
+}
+
+fn parse_octal_mode(raw: &str, field: &str) -> Result<u32> {
+    let trimmed = raw.trim();
+    let digits = trimmed.strip_prefix("0o").unwrap_or(trimmed);
+    let mode = u32::from_str_radix(digits, 8)
+        .with_context(|| format!("{field} must be an octal permission mode, got: {raw}"))?;
+    if mode > 0o7777 {
+        bail!("{field} is out of range: {raw}");
+    }
+    Ok(mode)
+}
+
+/// Checks `--validate-config` runs against a loaded config. Unknown `hash_algorithm`
+/// values (global or per library) already fail the load. The only write is a probe file
+/// that each writable-directory check creates and removes again.
+pub fn validate_config_runtime(config: &WorkerConfig) -> Vec<String> {
+    let database_dir = config
+        .database_path
+        .parent()
+        .unwrap_or(Path::new("/"))
+        .to_path_buf();
+    let mut problems: Vec<Option<String>> = config
+        .libraries_roots_real
+        .iter()
+        .map(|root| check_readable_dir("libraries_root", root))
+        .collect();
+    problems.extend([
+        check_writable_dir("database_path parent", &database_dir),
+        check_writable_dir("thumbs_root", &config.thumbs_root_real),
+        check_executable("thumbnail_ffmpeg_bin", &config.thumbnail_ffmpeg_bin),
+    ]);
+    problems.into_iter().flatten().collect()
+}
+
+fn check_readable_dir(label: &str, path: &Path) -> Option<String> {
+    if !path.is_dir() {
+        return Some(format!("{label} is not a directory: {}", path.display()));
+    }
+    fs::read_dir(path)
+        .err()
+        .map(|error| format!("{label} is not readable: {}: {error}", path.display()))
+}
+
+/// Creates and removes a probe file, so ownership, read-only mounts and ACLs are all
+/// covered rather than only the permission bits.
+fn check_writable_dir(label: &str, path: &Path) -> Option<String> {
+    match fs::metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => {
+            return Some(format!("{label} is not a directory: {}", path.display()));
+        }
+        Ok(_) => {}
+        Err(error) => {
+            return Some(format!(
+                "{label} is not accessible: {}: {error}",
+                path.display()
+            ));
+        }
+    }
+    let probe = path.join(format!(".dedupfs-write-probe-{}", std::process::id()));
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(file) => {
+            drop(file);
+            let _ = fs::remove_file(&probe);
+            None
+        }
+        Err(error) => Some(format!(
+            "{label} is not writable: {}: {error}",
+            path.display()
+        )),
+    }
+}
+
+/// Absolute or relative paths are checked directly; bare names are searched on `PATH`.
+fn check_executable(label: &str, bin: &str) -> Option<String> {
+    let candidates: Vec<PathBuf> = if bin.contains(std::path::MAIN_SEPARATOR) {
+        vec![PathBuf::from(bin)]
+    } else {
+        std::env::var_os("PATH")
+            .map(|paths| {
+                std::env::split_paths(&paths)
+                    .map(|dir| dir.join(bin))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    if candidates
+        .iter()
+        .any(|candidate| is_executable_file(candidate))
+    {
+        None
+    } else {
+        Some(format!(
+            "{label} is not an executable file or on PATH: {bin}"
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable_file(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable_file(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn read_config_file(path: &Path, format: ConfigFormat) -> Result<PartialWorkerConfig> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file: {}", path.display()))?;
+    match format.resolve(path) {
+        ConfigFormat::Toml => toml::from_str(&content)
+            .with_context(|| format!("failed to parse config TOML: {}", path.display())),
+        ConfigFormat::Json => serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse config JSON: {}", path.display())),
+        ConfigFormat::Auto => toml::from_str(&content).or_else(|toml_error| {
+            serde_json::from_str(&content).map_err(|json_error| {
+                anyhow!(
+                    "failed to parse config file as TOML ({toml_error}) or JSON ({json_error}): {}",
+                    path.display()
+                )
+            })
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_executable, check_readable_dir, check_writable_dir, parse_octal_mode,
+        read_config_file, ConfigFormat, HashAlgorithm, WorkerConfig,
+    };
+    use crate::test_support::TestDir;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn parse_octal_mode_accepts_common_spellings() {
+        assert_eq!(parse_octal_mode("0755", "mode").unwrap(), 0o755);
+        assert_eq!(parse_octal_mode("0o640", "mode").unwrap(), 0o640);
+        assert!(parse_octal_mode("0999", "mode").is_err());
+        assert!(parse_octal_mode("17777", "mode").is_err());
+    }
+
+    #[test]
+    fn custom_libraries_root_is_accepted() {
+        let dir = TestDir::new("config-libraries-root");
+        let libraries_root = dir.path().join("media");
+        fs::create_dir_all(&libraries_root).expect("create libraries root");
+        let state_root = dir.path().join("state");
+        let config_path = dir.path().join("worker.toml");
+        fs::write(
+            &config_path,
+            format!(
+                "libraries_root = {:?}\nstate_root = {:?}\ndatabase_path = {:?}\nthumbs_root = {:?}\n",
+                libraries_root,
+                state_root,
+                state_root.join("dedupfs.sqlite3"),
+                state_root.join("thumbs"),
+            ),
+        )
+        .expect("write toml config");
+
+        let config = WorkerConfig::load(Some(&config_path), ConfigFormat::Auto, Some("test"))
+            .expect("custom libraries_root accepted");
+        assert_eq!(config.libraries_roots, vec![libraries_root.clone()]);
+        assert_eq!(
+            config.libraries_roots_real,
+            vec![libraries_root.canonicalize().expect("resolve")]
+        );
+
+        fs::write(&config_path, "libraries_root = \"/\"\n").expect("write root config");
+        assert!(WorkerConfig::load(Some(&config_path), ConfigFormat::Auto, Some("test")).is_err());
+    }
+
+    #[test]
+    fn libraries_root_accepts_a_list_and_rejects_nested_entries() {
+        let dir = TestDir::new("config-libraries-roots");
+        let first = dir.path().join("media");
+        let second = dir.path().join("archive");
+        fs::create_dir_all(&first).expect("create first root");
+        fs::create_dir_all(&second).expect("create second root");
+        let state_root = dir.path().join("state");
+        let config_path = dir.path().join("worker.toml");
+        let write_roots = |roots: &[&Path]| {
+            fs::write(
+                &config_path,
+                format!(
+                    "libraries_root = {:?}\nstate_root = {:?}\ndatabase_path = {:?}\nthumbs_root = {:?}\n",
+                    roots,
+                    state_root,
+                    state_root.join("dedupfs.sqlite3"),
+                    state_root.join("thumbs"),
+                ),
+            )
+            .expect("write toml config");
+        };
+
+        write_roots(&[&first, &second]);
+        let config = WorkerConfig::load(Some(&config_path), ConfigFormat::Auto, Some("test"))
+            .expect("root list accepted");
+        assert_eq!(
+            config.libraries_roots_real,
+            vec![
+                first.canonicalize().expect("resolve"),
+                second.canonicalize().expect("resolve"),
+            ]
+        );
+
+        fs::create_dir_all(first.join("nested")).expect("create nested root");
+        write_roots(&[&first, &first.join("nested")]);
+        let error = WorkerConfig::load(Some(&config_path), ConfigFormat::Auto, Some("test"))
+            .expect_err("nested roots rejected");
+        assert!(error.to_string().contains("overlap"));
+    }
+
+    #[test]
+    fn json_config_is_detected_by_extension_and_reports_path_on_type_errors() {
+        let dir = TestDir::new("config-json");
+        let config_path = dir.path().join("worker.json");
+        let content = r#"{"hash_algorithm": "sha256", "concurrency": 8}"#;
+        fs::write(&config_path, content).expect("write json config");
+
+        let partial = read_config_file(&config_path, ConfigFormat::Auto).expect("parse json");
+        assert!(matches!(
+            partial.hash_algorithm,
+            Some(HashAlgorithm::Sha256)
+        ));
+        assert_eq!(partial.concurrency, Some(8));
+
+        // No extension: TOML is tried first, then JSON.
+        let bare_path = dir.path().join("worker-config");
+        fs::write(&bare_path, content).expect("write bare config");
+        let partial = read_config_file(&bare_path, ConfigFormat::Auto).expect("parse bare");
+        assert!(matches!(
+            partial.hash_algorithm,
+            Some(HashAlgorithm::Sha256)
+        ));
+        assert!(read_config_file(&bare_path, ConfigFormat::Toml).is_err());
+
+        fs::write(&config_path, r#"{"concurrency": "four"}"#).expect("write bad config");
+        let error = read_config_file(&config_path, ConfigFormat::Auto).expect_err("wrong type");
+        assert!(format!("{error:#}").contains(&config_path.display().to_string()));
+    }
+
+    #[test]
+    fn library_tables_parse_into_per_library_overrides() {
+        let dir = TestDir::new("config-libraries");
+        let config_path = dir.path().join("worker.toml");
+        let content = r#"
+hash_algorithm = "blake3"
+
+[libraries.photos]
+hash_algorithm = "sha256"
+exclude_patterns = ["*.xmp"]
+
+[libraries.raw-archive]
+max_size_bytes = 0
+"#;
+        fs::write(&config_path, content).expect("write toml config");
+
+        let libraries = read_config_file(&config_path, ConfigFormat::Auto)
+            .expect("parse toml")
+            .libraries
+            .expect("libraries table");
+        assert!(matches!(
+            libraries["photos"].hash_algorithm,
+            Some(HashAlgorithm::Sha256)
+        ));
+        assert_eq!(
+            libraries["photos"].exclude_patterns.as_deref(),
+            Some(&["*.xmp".to_string()][..])
+        );
+        assert_eq!(libraries["raw-archive"].max_size_bytes, Some(0));
+        assert!(libraries["raw-archive"].hash_algorithm.is_none());
+    }
+
+    #[test]
+    fn readable_dir_check_accepts_directories_and_reports_the_rest() {
+        let dir = TestDir::new("config-check-readable");
+        let file = dir.path().join("file.txt");
+        fs::write(&file, b"x").expect("write file");
+
+        assert_eq!(check_readable_dir("libraries_root", dir.path()), None);
+        assert!(
+            check_readable_dir("libraries_root", &dir.path().join("missing"))
+                .expect("missing reported")
+                .contains("libraries_root is not a directory")
+        );
+        assert!(check_readable_dir("libraries_root", &file)
+            .expect("file reported")
+            .contains("libraries_root is not a directory"));
+    }
+
+    #[test]
+    fn writable_dir_check_probes_with_a_real_write() {
+        let dir = TestDir::new("config-check-writable");
+        let file = dir.path().join("file.txt");
+        fs::write(&file, b"x").expect("write file");
+
+        assert_eq!(check_writable_dir("thumbs_root", dir.path()), None);
+        let leftovers: Vec<_> = fs::read_dir(dir.path())
+            .expect("list dir")
+            .map(|entry| entry.expect("entry").file_name())
+            .collect();
+        assert_eq!(leftovers, vec![std::ffi::OsString::from("file.txt")]);
+
+        assert!(
+            check_writable_dir("thumbs_root", &dir.path().join("missing"))
+                .expect("missing reported")
+                .contains("not accessible")
+        );
+        assert!(check_writable_dir("thumbs_root", &file)
+            .expect("file reported")
+            .contains("not a directory"));
+        // procfs refuses new files even for root, which permission bits alone would miss.
+        if Path::new("/proc/self").is_dir() {
+            assert!(check_writable_dir("thumbs_root", Path::new("/proc"))
+                .expect("read-only mount reported")
+                .contains("not writable"));
+        }
+    }
+
+    #[test]
+    fn executable_check_searches_path_and_rejects_plain_files() {
+        let dir = TestDir::new("config-check-executable");
+        let file = dir.path().join("file.txt");
+        fs::write(&file, b"x").expect("write file");
+
+        assert_eq!(check_executable("bin", "sh"), None);
+        assert!(check_executable("bin", "dedupfs-no-such-binary")
+            .expect("unknown binary reported")
+            .contains("not an executable file or on PATH"));
+        assert!(check_executable("bin", &file.display().to_string()).is_some());
+    }
+}
@@ -1,17 +1,25 @@
 use std::fs;
 use std::path::Path;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, bail, Context, Result};
 use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
 use serde_json::Value;
 
-use crate::config::WorkerConfig;
+use crate::config::{HashAlgorithm, MissingSchemaVersionPolicy, WorkerConfig};
+use crate::error_codes::ErrorCode;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum JobKind {
     Scan,
     Hash,
+    Verify,
+    Dedup,
+    Migrate,
 }
 
 impl JobKind {
@@ -19,9 +27,22 @@ impl JobKind {
         match raw {
             "scan" => Some(JobKind::Scan),
             "hash" => Some(JobKind::Hash),
+            "verify" => Some(JobKind::Verify),
+            "dedup" => Some(JobKind::Dedup),
+            "migrate" => Some(JobKind::Migrate),
             _ => None,
         }
     }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::Scan => "scan",
+            JobKind::Hash => "hash",
+            JobKind::Verify => "verify",
+            JobKind::Dedup => "dedup",
+            JobKind::Migrate => "migrate",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +50,69 @@ pub struct JobRecord {
     pub id: String,
     pub kind: JobKind,
     pub payload: Value,
+    /// Claim order weight; higher runs first. `0` when the schema has no `jobs.priority`.
+    pub priority: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobLogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl JobLogLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobLogLevel::Info => "info",
+            JobLogLevel::Warn => "warn",
+            JobLogLevel::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobLogEntry {
+    pub id: i64,
+    pub job_id: String,
+    pub logged_at: String,
+    pub level: String,
+    pub message: String,
+}
+
+/// One entry of an operator batch submission (`--batch-submit-jobs`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewJobSpec {
+    pub kind: JobKind,
+    #[serde(default = "empty_payload")]
+    pub payload: Value,
+    #[serde(default)]
+    pub priority: i64,
+}
+
+fn empty_payload() -> Value {
+    Value::Object(Default::default())
+}
+
+#[derive(Debug, Clone)]
+pub struct QuickHashCandidate {
+    pub id: i64,
+    pub library_id: i64,
+    pub library_name: String,
+    pub relative_path: String,
+    pub expected_size: i64,
+    pub expected_mtime_ns: i64,
+    pub hash_error_count: i64,
+    pub root_path: String,
+}
+
+#[derive(Debug)]
+pub struct MigrateCandidate {
+    pub id: i64,
+    pub relative_path: String,
+    pub expected_size: i64,
+    pub expected_mtime_ns: i64,
+    pub root_path: String,
 }
 
 #[derive(Debug, Clone)]
@@ -45,34 +129,73 @@ pub struct ThumbnailTaskRecord {
     pub source_mtime_ns: i64,
     pub output_relpath: String,
     pub error_count: i64,
+    /// Per-task ffmpeg seek override; `None` falls back to the configured offset.
+    pub seek_seconds: Option<f64>,
+    /// Comma-separated `sizes`: extra max dimensions rendered from the same decode.
+    pub sizes: Option<String>,
+}
+
+/// `thumbnail_cleanup_jobs.cleanup_kind`: prune one group, or sweep the whole thumbs root
+/// for files no `thumbnails` row references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbnailCleanupKind {
+    Group,
+    OrphanSweep,
+}
+
+impl ThumbnailCleanupKind {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "group" => Some(ThumbnailCleanupKind::Group),
+            "orphan_sweep" => Some(ThumbnailCleanupKind::OrphanSweep),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ThumbnailCleanupRecord {
     pub id: i64,
     pub group_key: String,
+    pub kind: ThumbnailCleanupKind,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Declared from least to most aggressive so modes can be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum WalCheckpointMode {
+    /// Copies as many frames as possible without waiting on readers or writers.
     Passive,
+    /// Waits for writers to finish and then for readers, so every frame is copied into the
+    /// database; new writers block while it runs.
+    Full,
+    /// `Full`, then also waits until no reader uses the WAL so the next writer restarts it
+    /// from the beginning.
     Restart,
+    /// `Restart`, then truncates the WAL file to zero bytes.
     Truncate,
 }
 
 impl WalCheckpointMode {
-    fn parse(raw: &str) -> Option<Self> {
+    pub fn parse(raw: &str) -> Option<Self> {
         match raw {
             "passive" => Some(WalCheckpointMode::Passive),
+            "full" => Some(WalCheckpointMode::Full),
             "restart" => Some(WalCheckpointMode::Restart),
             "truncate" => Some(WalCheckpointMode::Truncate),
             _ => None,
         }
     }
 
+    /// Returns the requested mode when allowed, otherwise the most aggressive
+    /// allowed mode that is still weaker than the request.
+    pub fn downgrade_to_allowed(self, allowed: &[WalCheckpointMode]) -> Option<Self> {
+        allowed.iter().copied().filter(|mode| *mode <= self).max()
+    }
+
     fn as_sql_keyword(self) -> &'static str {
         match self {
             WalCheckpointMode::Passive => "PASSIVE",
+            WalCheckpointMode::Full => "FULL",
             WalCheckpointMode::Restart => "RESTART",
             WalCheckpointMode::Truncate => "TRUNCATE",
         }
@@ -93,6 +216,163 @@ pub struct WalCheckpointStats {
     pub checkpointed_frames: i64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbMaintenanceOperation {
+    Vacuum,
+    Analyze,
+    IncrementalVacuum,
+    IntegrityCheck,
+}
+
+/// `PRAGMA integrity_check(N)` stops after this many reported problems.
+const INTEGRITY_CHECK_MAX_PROBLEMS: usize = 5;
+
+/// Appended to lease-loss errors: the maintenance statement is a single call that
+/// cannot refresh its lease while it runs.
+const DB_MAINTENANCE_LEASE_HINT: &str =
+    "long VACUUM or integrity_check runs need job_lock_ttl_seconds above their runtime";
+
+impl DbMaintenanceOperation {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "vacuum" => Some(DbMaintenanceOperation::Vacuum),
+            "analyze" => Some(DbMaintenanceOperation::Analyze),
+            "incremental_vacuum" => Some(DbMaintenanceOperation::IncrementalVacuum),
+            "integrity_check" => Some(DbMaintenanceOperation::IntegrityCheck),
+            _ => None,
+        }
+    }
+
+    fn as_sql(self) -> String {
+        match self {
+            DbMaintenanceOperation::Vacuum => "VACUUM".to_string(),
+            DbMaintenanceOperation::Analyze => "ANALYZE".to_string(),
+            DbMaintenanceOperation::IncrementalVacuum => "PRAGMA incremental_vacuum".to_string(),
+            DbMaintenanceOperation::IntegrityCheck => {
+                format!("PRAGMA integrity_check({INTEGRITY_CHECK_MAX_PROBLEMS})")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbMaintenanceRecord {
+    pub id: i64,
+    pub operation: DbMaintenanceOperation,
+}
+
+#[derive(Debug, Clone)]
+pub struct DbMaintenanceStats {
+    pub page_count_before: i64,
+    pub page_count_after: i64,
+    pub freelist_count_before: i64,
+    pub freelist_count_after: i64,
+    /// `"ok"` or the reported problems joined with `"; "`; `None` for other operations.
+    pub integrity_result: Option<String>,
+}
+
+impl DbMaintenanceStats {
+    pub fn freed_pages(&self) -> i64 {
+        (self.page_count_before - self.page_count_after).max(0)
+    }
+
+    pub fn integrity_failed(&self) -> bool {
+        self.integrity_result
+            .as_deref()
+            .is_some_and(|result| result != "ok")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LibraryRootRecord {
+    pub id: i64,
+    pub name: String,
+    pub root_path: String,
+    pub last_scanned_at: Option<String>,
+    pub file_count: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub content_hash: Vec<u8>,
+    pub algorithm: String,
+    pub file_count: usize,
+    pub total_size_bytes: i64,
+    pub files: Vec<DuplicateFileInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DuplicateFileInfo {
+    pub file_id: i64,
+    pub library_name: String,
+    pub relative_path: String,
+    pub size_bytes: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryQueue {
+    Hash,
+    Thumbnail,
+}
+
+impl RetryQueue {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "hash" => Some(RetryQueue::Hash),
+            "thumbnail" => Some(RetryQueue::Thumbnail),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RetryQueue::Hash => "hash",
+            RetryQueue::Thumbnail => "thumbnail",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryBucketRecord {
+    pub error_count: i64,
+    pub items: i64,
+    pub soonest_retry_after: Option<String>,
+    pub latest_retry_after: Option<String>,
+}
+
+/// Operator pause flags from the singleton `worker_control` row.
+///
+/// `paused` stops every category; the per-kind flags stop one category each. A missing row
+/// means nothing is paused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkerControl {
+    pub paused: bool,
+    pub pause_scan: bool,
+    pub pause_hash: bool,
+    pub pause_thumbnail: bool,
+    pub pause_wal: bool,
+}
+
+impl WorkerControl {
+    pub fn scan_paused(&self) -> bool {
+        self.paused || self.pause_scan
+    }
+
+    /// Covers hash, verify and dedup jobs as well as the quick-hash pass.
+    pub fn hash_paused(&self) -> bool {
+        self.paused || self.pause_hash
+    }
+
+    /// Covers thumbnail tasks and thumbnail cleanup jobs.
+    pub fn thumbnail_paused(&self) -> bool {
+        self.paused || self.pause_thumbnail
+    }
+
+    pub fn wal_paused(&self) -> bool {
+        self.paused || self.pause_wal
+    }
+}
+
 pub fn open_connection(database_path: &Path) -> Result<Connection> {
     if let Some(parent) = database_path.parent() {
         fs::create_dir_all(parent).with_context(|| {
@@ -115,46 +395,125 @@ pub fn open_connection(database_path: &Path) -> Result<Connection> {
     Ok(conn)
 }
 
-pub fn has_runnable_scan_hash_work(conn: &Connection) -> Result<bool> {
+/// Oldest `schema_migrations` version whose columns the worker reads unconditionally.
+pub const MIN_SCHEMA_VERSION: i64 = 42;
+/// Newest Python migration this binary knows about; bump it with every migration.
+pub const MAX_SCHEMA_VERSION: i64 = 44;
+
+/// Highest applied Python migration, or `None` when `schema_migrations` does not exist.
+pub fn read_schema_version(conn: &Connection) -> Result<Option<i64>> {
     let exists = conn
         .query_row(
-            "
-            SELECT 1
-            FROM jobs
-            WHERE kind IN ('scan', 'hash')
-              AND (
-                status = 'pending'
-                OR (
-                    status = 'running'
-                    AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
-                )
-              )
-            LIMIT 1
-            ",
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'",
             [],
-            |row| row.get::<_, i64>(0),
+            |_| Ok(()),
         )
         .optional()?
         .is_some();
-    Ok(exists)
+    if !exists {
+        return Ok(None);
+    }
+    let version = conn.query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+        row.get::<_, Option<i64>>(0)
+    })?;
+    Ok(Some(version.unwrap_or(0)))
 }
 
-pub fn has_runnable_thumbnail_work(conn: &Connection) -> Result<bool> {
+/// Startup gate: refuses a database migrated outside `MIN_SCHEMA_VERSION..=MAX_SCHEMA_VERSION`
+/// before anything is claimed.
+pub fn check_schema_version(
+    conn: &Connection,
+    missing_policy: MissingSchemaVersionPolicy,
+) -> Result<()> {
+    let Some(version) = read_schema_version(conn)? else {
+        match missing_policy {
+            MissingSchemaVersionPolicy::Warn => {
+                tracing::warn!(
+                    "schema_migrations table not found; cannot verify the schema version, continuing"
+                );
+                return Ok(());
+            }
+            MissingSchemaVersionPolicy::Refuse => bail!(
+                "schema_migrations table not found; run the Python migrations first or set schema_version_missing = \"warn\""
+            ),
+        }
+    };
+    if !(MIN_SCHEMA_VERSION..=MAX_SCHEMA_VERSION).contains(&version) {
+        bail!(
+            "database schema version {version} is outside the supported range {MIN_SCHEMA_VERSION}..={MAX_SCHEMA_VERSION}; upgrade the {} before starting this worker",
+            if version < MIN_SCHEMA_VERSION { "database (run the Python migrations)" } else { "worker" }
+        );
+    }
+    Ok(())
+}
+
+/// Tables the worker reads or writes on every run; a database without them was never migrated.
+const REQUIRED_TABLES: &[&str] = &[
+    "jobs",
+    "library_files",
+    "library_roots",
+    "thumbnails",
+    "thumbnail_cleanup_jobs",
+    "wal_maintenance_jobs",
+    "scan_sessions",
+    "library_circuit_breaker",
+];
+
+/// Startup self-test: names every missing required table instead of failing mid-job on
+/// the first query that touches one.
+pub fn check_schema(conn: &Connection) -> Result<()> {
+    let mut missing = Vec::new();
+    for table in REQUIRED_TABLES {
+        let exists = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if !exists {
+            missing.push(*table);
+        }
+    }
+    if !missing.is_empty() {
+        bail!(
+            "database schema is missing required tables: {}; run the Python migrations first",
+            missing.join(", ")
+        );
+    }
+    if !column_exists(conn, "jobs", "kind")? {
+        bail!("database table jobs has no kind column; run the Python migrations first");
+    }
+    Ok(())
+}
+
+/// Optional columns added by newer Python migrations, detected once at startup so the
+/// worker keeps running against an older schema.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SchemaFeatures {
+    pub jobs_priority: bool,
+    /// `library_files.extension` and `mime_type`; scans skip them on older schemas.
+    pub library_files_file_types: bool,
+    /// `library_files.image_width` and `image_height`.
+    pub library_files_image_dimensions: bool,
+}
+
+pub fn detect_schema_features(conn: &Connection) -> Result<SchemaFeatures> {
+    Ok(SchemaFeatures {
+        jobs_priority: column_exists(conn, "jobs", "priority")?,
+        library_files_file_types: column_exists(conn, "library_files", "extension")?
+            && column_exists(conn, "library_files", "mime_type")?,
+        library_files_image_dimensions: column_exists(conn, "library_files", "image_width")?
+            && column_exists(conn, "library_files", "image_height")?,
+    })
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool> {
     let exists = conn
         .query_row(
-            "
-            SELECT 1
-            FROM thumbnails
-            WHERE (
-                status = 'pending'
-                AND (retry_after IS NULL OR datetime(retry_after) <= CURRENT_TIMESTAMP)
-            ) OR (
-                status = 'running'
-                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
-            )
-            LIMIT 1
-            ",
-            [],
+            "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+            params![table, column],
             |row| row.get::<_, i64>(0),
         )
         .optional()?
@@ -162,48 +521,48 @@ pub fn has_runnable_thumbnail_work(conn: &Connection) -> Result<bool> {
     Ok(exists)
 }
 
-pub fn has_runnable_thumbnail_cleanup_work(conn: &Connection) -> Result<bool> {
-    let exists = conn
+pub fn load_worker_control(conn: &Connection) -> Result<WorkerControl> {
+    let control = conn
         .query_row(
             "
-            SELECT 1
-            FROM thumbnail_cleanup_jobs
-            WHERE (
-                status = 'pending'
-                AND datetime(execute_after) <= CURRENT_TIMESTAMP
-            ) OR (
-                status = 'running'
-                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
-            )
-            LIMIT 1
+            SELECT paused, pause_scan, pause_hash, pause_thumbnail, pause_wal
+            FROM worker_control
+            WHERE id = 1
             ",
             [],
-            |row| row.get::<_, i64>(0),
+            |row| {
+                Ok(WorkerControl {
+                    paused: row.get(0)?,
+                    pause_scan: row.get(1)?,
+                    pause_hash: row.get(2)?,
+                    pause_thumbnail: row.get(3)?,
+                    pause_wal: row.get(4)?,
+                })
+            },
         )
-        .optional()?
-        .is_some();
-    Ok(exists)
+        .optional()?;
+    Ok(control.unwrap_or_default())
 }
 
-pub fn has_runnable_wal_maintenance_work(conn: &Connection) -> Result<bool> {
+pub fn has_runnable_scan_hash_work(conn: &Connection, control: &WorkerControl) -> Result<bool> {
     let exists = conn
         .query_row(
             "
             SELECT 1
-            FROM wal_maintenance_jobs
-            WHERE (
+            FROM jobs
+            WHERE kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+              AND (?1 = 0 OR kind <> 'scan')
+              AND (?2 = 0 OR kind = 'scan')
+              AND (
                 status = 'pending'
-                AND datetime(execute_after) <= CURRENT_TIMESTAMP
-            ) OR (
-                status = 'retryable'
-                AND (retry_after IS NULL OR datetime(retry_after) <= CURRENT_TIMESTAMP)
-            ) OR (
-                status = 'running'
-                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
-            )
+                OR (
+                    status = 'running'
+                    AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+                )
+              )
             LIMIT 1
             ",
-            [],
+            params![control.scan_paused(), control.hash_paused()],
             |row| row.get::<_, i64>(0),
         )
         .optional()?
@@ -211,90 +570,641 @@ pub fn has_runnable_wal_maintenance_work(conn: &Connection) -> Result<bool> {
     Ok(exists)
 }
 
-pub fn claim_scan_hash_job(
-    conn: &mut Connection,
-    config: &WorkerConfig,
-    requested_job_id: Option<&str>,
-) -> Result<Option<JobRecord>> {
+/// Inserts a `pending` scan/hash-family job. Requires the `jobs.priority` column.
+///
+/// Jobs are created by the Python control plane in production; this exists for tests.
+#[cfg(test)]
+pub fn insert_job(
+    conn: &Connection,
+    job_id: &str,
+    kind: JobKind,
+    payload: &Value,
+    priority: i64,
+) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO jobs(id, kind, status, payload, priority)
+        VALUES (?1, ?2, 'pending', ?3, ?4)
+        ",
+        params![job_id, kind.as_str(), payload.to_string(), priority],
+    )?;
+    Ok(())
+}
+
+/// Inserts `jobs` as `pending` rows in one transaction and returns how many were inserted.
+///
+/// A spec whose kind and payload (compared as parsed JSON, so key order and spacing do
+/// not matter) match an existing `pending` row, including one inserted earlier in the
+/// same batch, is skipped. Every other spec goes through the control plane's admission
+/// mutex: while a scan/hash-family job is pending, running or retryable (one inserted
+/// earlier in the batch included) the whole batch is rejected, as `JobService` rejects a
+/// single submission. Any error rolls back the whole batch.
+pub fn insert_jobs_batch(conn: &mut Connection, jobs: &[NewJobSpec]) -> Result<usize> {
+    let tx = conn.transaction()?;
+    let mut inserted = jobs.len();
+    {
+        let mut active = tx.prepare_cached(
+            "
+            SELECT id
+            FROM jobs
+            WHERE kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+              AND status IN ('pending', 'running', 'retryable')
+            LIMIT 1
+            ",
+        )?;
+        let mut pending = tx.prepare_cached(
+            "
+            SELECT payload
+            FROM jobs
+            WHERE status = 'pending'
+              AND kind = ?1
+            ",
+        )?;
+        let mut insert = tx.prepare_cached(
+            "
+            INSERT INTO jobs(id, kind, status, payload, priority)
+            VALUES (?1, ?2, 'pending', ?3, ?4)
+            ",
+        )?;
+        for spec in jobs {
+            let existing = pending
+                .query_map(params![spec.kind.as_str()], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let duplicate = existing.iter().any(|raw| {
+                serde_json::from_str::<Value>(raw).is_ok_and(|payload| payload == spec.payload)
+            });
+            if duplicate {
+                inserted -= 1;
+                continue;
+            }
+            if let Some(active_id) = active
+                .query_row([], |row| row.get::<_, String>(0))
+                .optional()?
+            {
+                bail!(
+                    "cannot submit {} job: scan/hash job {active_id} is already active",
+                    spec.kind.as_str()
+                );
+            }
+            insert.execute(params![
+                new_job_id(),
+                spec.kind.as_str(),
+                spec.payload.to_string(),
+                spec.priority
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(inserted)
+}
+
+/// Enqueues a full scan once `scan_schedule_interval_seconds` has passed since the last one.
+///
+/// The `scheduled_jobs` slot is claimed with a conditional update, so only one of several
+/// workers sharing the database wins it. The insert is `INSERT OR IGNORE` against the
+/// single-active scan/hash index; when a scan or hash job is already pending or running
+/// nothing is inserted and the slot is released so a later cycle retries.
+pub fn maybe_enqueue_scheduled_scan(conn: &mut Connection, config: &WorkerConfig) -> Result<bool> {
+    let Some(interval_seconds) = config.scan_schedule_interval_seconds else {
+        return Ok(false);
+    };
     let tx = conn.transaction()?;
     tx.execute(
         "
-        UPDATE jobs
-        SET status = 'retryable',
-            worker_id = NULL,
-            worker_heartbeat_at = NULL,
-            lease_expires_at = NULL,
-            error_code = CASE
-                WHEN error_code IS NULL OR trim(error_code) = ''
-                THEN 'LEASE_EXPIRED'
-                ELSE error_code
-            END,
-            error_message = CASE
-                WHEN error_message IS NULL OR trim(error_message) = ''
-                THEN 'Lease expired and recovered by rust worker claim path'
-                ELSE error_message
-            END,
-            finished_at = COALESCE(finished_at, CURRENT_TIMESTAMP),
-            updated_at = CURRENT_TIMESTAMP
-        WHERE status = 'running'
-          AND kind IN ('scan', 'hash')
-          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+        INSERT INTO scheduled_jobs(kind, interval_seconds)
+        VALUES ('scan', ?1)
+        ON CONFLICT(kind) DO UPDATE SET interval_seconds = excluded.interval_seconds
         ",
-        [],
+        params![interval_seconds as i64],
     )?;
+    let due = tx.execute(
+        "
+        UPDATE scheduled_jobs
+        SET last_enqueued_at = CURRENT_TIMESTAMP
+        WHERE kind = 'scan'
+          AND (
+            last_enqueued_at IS NULL
+            OR datetime(last_enqueued_at, '+' || interval_seconds || ' seconds') <= CURRENT_TIMESTAMP
+          )
+        ",
+        [],
+    )?;
+    if due != 1 {
+        tx.commit()?;
+        return Ok(false);
+    }
 
-    let target_id = if let Some(job_id) = requested_job_id {
-        tx.query_row(
-            "SELECT id FROM jobs WHERE id = ?1 AND status = 'pending' AND kind IN ('scan', 'hash')",
-            params![job_id],
-            |row| row.get::<_, String>(0),
+    let inserted = tx.execute(
+        "
+        INSERT OR IGNORE INTO jobs(id, kind, status, payload)
+        VALUES (?1, 'scan', 'pending', '{}')
+        ",
+        params![new_job_id()],
+    )?;
+    if inserted != 1 {
+        // Dropping the transaction rolls back the slot claim.
+        return Ok(false);
+    }
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Enqueues a `passive` WAL maintenance job once the `-wal` file outgrows
+/// `wal_auto_checkpoint_size_bytes`, unless one is already pending, running or retryable.
+/// A missing `-wal` file counts as empty.
+pub fn maybe_enqueue_wal_checkpoint_if_needed(
+    conn: &Connection,
+    config: &WorkerConfig,
+) -> Result<bool> {
+    let Some(threshold) = config.wal_auto_checkpoint_size_bytes else {
+        return Ok(false);
+    };
+    let mut wal_path = config.database_path.clone().into_os_string();
+    wal_path.push("-wal");
+    let wal_bytes = match fs::metadata(&wal_path) {
+        Ok(metadata) => metadata.len(),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!(
+                    "failed to read WAL file size: {}",
+                    Path::new(&wal_path).display()
+                )
+            })
+        }
+    };
+    if wal_bytes <= threshold {
+        return Ok(false);
+    }
+
+    let inserted = conn.execute(
+        "
+        INSERT INTO wal_maintenance_jobs(requested_mode, status, requested_by, reason)
+        SELECT 'passive', 'pending', 'rust-worker', ?1
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM wal_maintenance_jobs
+            WHERE status IN ('pending', 'running', 'retryable')
         )
-        .optional()?
-    } else {
-        tx.query_row(
-            "SELECT id FROM jobs WHERE status = 'pending' AND kind IN ('scan', 'hash') ORDER BY created_at ASC LIMIT 1",
-            [],
-            |row| row.get::<_, String>(0),
+        ",
+        params![format!(
+            "WAL file is {wal_bytes} bytes, over wal_auto_checkpoint_size_bytes = {threshold}"
+        )],
+    )?;
+    Ok(inserted == 1)
+}
+
+/// Random (version 4) UUID in the hyphenated form the Python control plane uses.
+fn new_job_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Appends one diagnostic line to `job_log_entries`. Written in autocommit between
+/// batches, so entries survive the job being marked failed afterwards.
+pub fn append_job_log(
+    conn: &Connection,
+    job_id: &str,
+    level: JobLogLevel,
+    message: &str,
+) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO job_log_entries(job_id, level, message)
+        VALUES (?1, ?2, ?3)
+        ",
+        params![job_id, level.as_str(), message],
+    )?;
+    Ok(())
+}
+
+pub fn list_job_log_entries(conn: &Connection, job_id: &str) -> Result<Vec<JobLogEntry>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT id, job_id, logged_at, level, message
+        FROM job_log_entries
+        WHERE job_id = ?1
+        ORDER BY id ASC
+        ",
+    )?;
+    let rows = stmt.query_map(params![job_id], |row| {
+        Ok(JobLogEntry {
+            id: row.get(0)?,
+            job_id: row.get(1)?,
+            logged_at: row.get(2)?,
+            level: row.get(3)?,
+            message: row.get(4)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+}
+
+/// Per-library hash I/O limit in whole MiB/s (fractions round up); `None` means the global
+/// `io_rate_limit_mib_per_sec` applies.
+pub fn get_library_io_rate_limit(conn: &Connection, library_id: i64) -> Result<Option<u64>> {
+    let limit: Option<f64> = conn
+        .query_row(
+            "SELECT io_rate_limit_mib_per_sec FROM library_roots WHERE id = ?1",
+            params![library_id],
+            |row| row.get(0),
         )
         .optional()?
-    };
+        .flatten();
+    Ok(limit
+        .filter(|value| value.is_finite() && *value > 0.0)
+        .map(|value| value.ceil() as u64))
+}
 
-    let Some(job_id) = target_id else {
-        tx.commit()?;
-        return Ok(None);
-    };
+/// Sets a library's hash I/O limit; `0` removes the override.
+pub fn set_library_io_rate_limit(
+    conn: &Connection,
+    library_id: i64,
+    mib_per_sec: u64,
+) -> Result<()> {
+    let updated = conn.execute(
+        "
+        UPDATE library_roots
+        SET io_rate_limit_mib_per_sec = ?1,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2
+        ",
+        params![(mib_per_sec > 0).then_some(mib_per_sec as f64), library_id],
+    )?;
+    if updated != 1 {
+        bail!("library {library_id} does not exist");
+    }
+    Ok(())
+}
+
+/// Re-prioritizes a job that is still waiting to be claimed.
+pub fn set_job_priority(conn: &Connection, job_id: &str, priority: i64) -> Result<()> {
+    let updated = conn.execute(
+        "
+        UPDATE jobs
+        SET priority = ?1,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2
+          AND status IN ('pending', 'retryable')
+        ",
+        params![priority, job_id],
+    )?;
+    if updated != 1 {
+        bail!("job {job_id} is not waiting to be claimed; priority unchanged");
+    }
+    Ok(())
+}
+
+/// Cancels a scan/hash-family job that has not finished yet.
+///
+/// Returns `false` when the job is already terminal. A worker still running the job
+/// notices on its next `refresh_job_lease` and stops.
+pub fn cancel_job(conn: &mut Connection, config: &WorkerConfig, job_id: &str) -> Result<bool> {
+    let tx = conn.transaction()?;
+    let exists = tx
+        .query_row(
+            "SELECT 1 FROM jobs WHERE id = ?1 AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')",
+            params![job_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        bail!("job {job_id} not found");
+    }
 
-    let lease_modifier = format!("+{} seconds", config.job_lock_ttl_seconds);
     let updated = tx.execute(
         "
         UPDATE jobs
-        SET status = 'running',
-            worker_id = ?1,
-            worker_heartbeat_at = CURRENT_TIMESTAMP,
-            lease_expires_at = datetime('now', ?2),
-            started_at = COALESCE(started_at, CURRENT_TIMESTAMP),
+        SET status = 'cancelled',
+            error_message = ?2,
+            finished_at = CURRENT_TIMESTAMP,
+            lease_expires_at = NULL,
             updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?3
-          AND status = 'pending'
-          AND kind IN ('scan', 'hash')
+        WHERE id = ?1
+          AND status IN ('pending', 'running', 'retryable')
         ",
-        params![config.worker_id, lease_modifier, job_id],
+        params![job_id, format!("cancelled by worker {}", config.worker_id)],
     )?;
+    tx.commit()?;
+    Ok(updated == 1)
+}
 
+pub fn list_dead_lettered_jobs(conn: &Connection) -> Result<Vec<JobRecord>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT id, kind, COALESCE(payload, '{}'), priority
+        FROM jobs
+        WHERE status = 'dead_lettered'
+          AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+        ORDER BY updated_at DESC, id DESC
+        ",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+
+    let mut jobs = Vec::new();
+    for row in rows {
+        let (id, kind_raw, payload_raw, priority) = row?;
+        let kind =
+            JobKind::parse(&kind_raw).ok_or_else(|| anyhow!("unsupported job kind: {kind_raw}"))?;
+        let payload = serde_json::from_str::<Value>(&payload_raw)
+            .unwrap_or(Value::Object(Default::default()));
+        jobs.push(JobRecord {
+            id,
+            kind,
+            payload,
+            priority,
+        });
+    }
+    Ok(jobs)
+}
+
+/// Puts a dead-lettered job back in the queue with a fresh retry budget.
+pub fn restore_dead_lettered_job(conn: &Connection, job_id: &str) -> Result<()> {
+    let updated = conn.execute(
+        "
+        UPDATE jobs
+        SET status = 'pending',
+            retry_count = 0,
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            error_code = NULL,
+            error_message = NULL,
+            finished_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?1
+          AND status = 'dead_lettered'
+        ",
+        params![job_id],
+    )?;
     if updated != 1 {
-        tx.commit()?;
-        return Ok(None);
+        bail!("job {job_id} is not dead-lettered");
+    }
+    Ok(())
+}
+
+/// Thumbnail tasks parked by `thumbnail_permanent_error_codes`, newest first.
+pub fn list_permanent_thumbnail_failures(conn: &Connection) -> Result<Vec<ThumbnailTaskRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "
+        {THUMBNAIL_TASK_SELECT}
+        WHERE t.permanent_failure = 1
+        ORDER BY t.updated_at DESC, t.id DESC
+        "
+    ))?;
+    let rows = stmt.query_map([], thumbnail_task_from_row)?;
+    let mut tasks = Vec::new();
+    for row in rows {
+        tasks.push(row?);
     }
+    Ok(tasks)
+}
+
+/// Pauses hashing for a library until `reset_after_seconds` from now; re-tripping an open
+/// breaker extends it.
+pub fn trip_circuit_breaker(
+    conn: &Connection,
+    library_id: i64,
+    failure_count: i64,
+    reset_after_seconds: u64,
+) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO library_circuit_breaker(library_id, tripped_at, reset_after, failure_count)
+        VALUES (?1, CURRENT_TIMESTAMP, datetime('now', ?2), ?3)
+        ON CONFLICT(library_id) DO UPDATE SET
+            tripped_at = excluded.tripped_at,
+            reset_after = excluded.reset_after,
+            failure_count = excluded.failure_count
+        ",
+        params![
+            library_id,
+            format!("+{reset_after_seconds} seconds"),
+            failure_count
+        ],
+    )?;
+    Ok(())
+}
+
+/// Closes a tripped breaker early, e.g. once the mount is back. Returns false if none was set.
+pub fn reset_circuit_breaker(conn: &Connection, library_id: i64) -> Result<bool> {
+    let deleted = conn.execute(
+        "DELETE FROM library_circuit_breaker WHERE library_id = ?1",
+        params![library_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Clears `permanent_failure` and requeues the task, e.g. after the source was replaced.
+pub fn reset_permanent_thumbnail(conn: &Connection, thumb_key: &str) -> Result<()> {
+    let updated = conn.execute(
+        "
+        UPDATE thumbnails
+        SET status = 'pending',
+            permanent_failure = 0,
+            error_code = NULL,
+            error_message = NULL,
+            retry_after = NULL,
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            finished_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE thumb_key = ?1
+          AND permanent_failure = 1
+        ",
+        params![thumb_key],
+    )?;
+    if updated != 1 {
+        bail!("thumbnail {thumb_key} is not marked as a permanent failure");
+    }
+    Ok(())
+}
+
+/// The quick-hash pass stays idle while any scan/hash-family job is running, so it
+/// never races a scan's upserts for the same rows. Mirrors the claim's filters,
+/// including the circuit breaker, so the daemon idles when nothing is claimable.
+pub fn has_runnable_quick_hash_work(conn: &Connection, config: &WorkerConfig) -> Result<bool> {
+    let claim_expiry = format!("-{} seconds", config.hash_claim_ttl_seconds);
+    let exists = conn
+        .query_row(
+            "
+            SELECT 1
+            FROM library_files f
+            LEFT JOIN library_circuit_breaker b ON b.library_id = f.library_id
+            WHERE f.quick_hash IS NULL
+              AND f.needs_hash = 1
+              AND f.is_missing = 0
+              AND (f.hash_retry_after IS NULL OR datetime(f.hash_retry_after) <= CURRENT_TIMESTAMP)
+              AND (
+                f.hash_claim_token IS NULL
+                OR f.hash_claimed_at IS NULL
+                OR datetime(f.hash_claimed_at) <= datetime('now', ?1)
+              )
+              AND (b.library_id IS NULL OR datetime(b.reset_after) <= CURRENT_TIMESTAMP)
+              AND NOT EXISTS (
+                SELECT 1
+                FROM jobs
+                WHERE kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+                  AND status = 'running'
+              )
+            LIMIT 1
+            ",
+            params![claim_expiry],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists)
+}
+
+pub fn has_runnable_thumbnail_work(conn: &Connection) -> Result<bool> {
+    let exists = conn
+        .query_row(
+            "
+            SELECT 1
+            FROM thumbnails
+            WHERE (
+                status = 'pending'
+                AND permanent_failure = 0
+                AND (retry_after IS NULL OR datetime(retry_after) <= CURRENT_TIMESTAMP)
+            ) OR (
+                status = 'running'
+                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+            )
+            LIMIT 1
+            ",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists)
+}
+
+pub fn has_runnable_thumbnail_cleanup_work(conn: &Connection) -> Result<bool> {
+    let exists = conn
+        .query_row(
+            "
+            SELECT 1
+            FROM thumbnail_cleanup_jobs
+            WHERE (
+                status = 'pending'
+                AND datetime(execute_after) <= CURRENT_TIMESTAMP
+            ) OR (
+                status = 'running'
+                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+            )
+            LIMIT 1
+            ",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists)
+}
+
+pub fn has_runnable_wal_maintenance_work(conn: &Connection) -> Result<bool> {
+    let exists = conn
+        .query_row(
+            "
+            SELECT 1
+            FROM wal_maintenance_jobs
+            WHERE (
+                status = 'pending'
+                AND datetime(execute_after) <= CURRENT_TIMESTAMP
+            ) OR (
+                status = 'retryable'
+                AND (retry_after IS NULL OR datetime(retry_after) <= CURRENT_TIMESTAMP)
+            ) OR (
+                status = 'running'
+                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+            )
+            LIMIT 1
+            ",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists)
+}
+
+pub fn claim_scan_hash_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    requested_job_id: Option<&str>,
+    control: &WorkerControl,
+    schema: &SchemaFeatures,
+) -> Result<Option<JobRecord>> {
+    let tx = conn.transaction()?;
+    requeue_expired_job_leases(&tx)?;
 
+    let (order_by, priority_column) = if schema.jobs_priority {
+        ("priority DESC, created_at ASC", "priority")
+    } else {
+        ("created_at ASC", "0")
+    };
+    let lease_modifier = format!("+{} seconds", config.job_lock_ttl_seconds);
     let row = tx
         .query_row(
-            "SELECT id, kind, COALESCE(payload, '{}') FROM jobs WHERE id = ?1",
-            params![job_id],
+            &format!(
+                "
+            UPDATE jobs
+            SET status = 'running',
+                worker_id = ?1,
+                worker_heartbeat_at = CURRENT_TIMESTAMP,
+                lease_expires_at = datetime('now', ?2),
+                started_at = COALESCE(started_at, CURRENT_TIMESTAMP),
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE status = 'pending'
+                  AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+                  AND (?3 IS NULL OR id = ?3)
+                  AND (?4 = 0 OR kind <> 'scan')
+                  AND (?5 = 0 OR kind = 'scan')
+                ORDER BY {order_by}
+                LIMIT 1
+            )
+              AND status = 'pending'
+            RETURNING id, kind, COALESCE(payload, '{{}}'), {priority_column}
+            "
+            ),
+            params![
+                config.worker_id,
+                lease_modifier,
+                requested_job_id,
+                control.scan_paused(),
+                control.hash_paused()
+            ],
             |row| {
                 Ok((
                     row.get::<_, String>(0)?,
                     row.get::<_, String>(1)?,
                     row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
                 ))
             },
         )
@@ -302,7 +1212,7 @@ pub fn claim_scan_hash_job(
 
     tx.commit()?;
 
-    let Some((id, kind_raw, payload_raw)) = row else {
+    let Some((id, kind_raw, payload_raw, priority)) = row else {
         return Ok(None);
     };
 
@@ -310,7 +1220,12 @@ pub fn claim_scan_hash_job(
         JobKind::parse(&kind_raw).ok_or_else(|| anyhow!("unsupported job kind: {kind_raw}"))?;
     let payload =
         serde_json::from_str::<Value>(&payload_raw).unwrap_or(Value::Object(Default::default()));
-    Ok(Some(JobRecord { id, kind, payload }))
+    Ok(Some(JobRecord {
+        id,
+        kind,
+        payload,
+        priority,
+    }))
 }
 
 pub fn refresh_job_lease(
@@ -331,83 +1246,519 @@ pub fn refresh_job_lease(
             updated_at = CURRENT_TIMESTAMP
         WHERE id = ?4
           AND status = 'running'
-          AND kind IN ('scan', 'hash')
+          AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
           AND worker_id = ?5
           AND datetime(lease_expires_at) > CURRENT_TIMESTAMP
         ",
-        params![
-            processed_items,
-            progress,
-            lease_modifier,
-            job_id,
-            config.worker_id
-        ],
+        params![
+            processed_items,
+            progress,
+            lease_modifier,
+            job_id,
+            config.worker_id
+        ],
+    )?;
+
+    if updated != 1 {
+        bail!("job {job_id} lease update rejected");
+    }
+
+    Ok(())
+}
+
+pub fn finish_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job_id: &str,
+    success: bool,
+    error_message: Option<&str>,
+) -> Result<()> {
+    if !success && error_message == Some(SHUTDOWN_REQUESTED) {
+        return requeue_interrupted_job(conn, config, job_id);
+    }
+    let status = if success { "completed" } else { "failed" };
+    let error_code = if success {
+        None
+    } else {
+        Some(ErrorCode::WorkerFailure.as_str())
+    };
+    let tx = conn.transaction()?;
+
+    // A failure that reaches the retry budget (column, then payload, then config) is
+    // dead-lettered instead of failed.
+    let updated = tx.execute(
+        "
+        UPDATE jobs
+        SET status = CASE
+                WHEN ?1 = 'failed'
+                 AND retry_count + 1 >= COALESCE(
+                        max_retries,
+                        CAST(json_extract(payload, '$.max_retries') AS INTEGER),
+                        ?6
+                     )
+                THEN 'dead_lettered'
+                ELSE ?1
+            END,
+            retry_count = CASE WHEN ?1 = 'failed' THEN retry_count + 1 ELSE retry_count END,
+            progress = CASE WHEN ?1 = 'completed' THEN 1.0 ELSE progress END,
+            error_code = ?2,
+            error_message = ?3,
+            finished_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP,
+            worker_heartbeat_at = CURRENT_TIMESTAMP,
+            lease_expires_at = NULL
+        WHERE id = ?4
+          AND status = 'running'
+          AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+          AND worker_id = ?5
+        ",
+        params![
+            status,
+            error_code,
+            error_message,
+            job_id,
+            config.worker_id,
+            config.job_max_retries
+        ],
+    )?;
+
+    if updated != 1 {
+        bail!("failed to finish running job {job_id}");
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// A job stopped by shutdown did not fail: it goes back to `pending` with its retry
+/// budget untouched, and the next claim runs it again.
+fn requeue_interrupted_job(conn: &Connection, config: &WorkerConfig, job_id: &str) -> Result<()> {
+    let updated = conn.execute(
+        "
+        UPDATE jobs
+        SET status = 'pending',
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            error_code = NULL,
+            error_message = ?1,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2
+          AND status = 'running'
+          AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+          AND worker_id = ?3
+        ",
+        params![SHUTDOWN_REQUESTED, job_id, config.worker_id],
+    )?;
+
+    if updated != 1 {
+        bail!("failed to requeue running job {job_id}");
+    }
+
+    Ok(())
+}
+
+pub fn claim_quick_hash_candidates(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    batch_size: usize,
+    claim_token: &str,
+) -> Result<Vec<QuickHashCandidate>> {
+    let claim_expiry = format!("-{} seconds", config.hash_claim_ttl_seconds);
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "
+        UPDATE library_files
+        SET hash_claim_token = ?1,
+            hash_claimed_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id IN (
+            SELECT f.id
+            FROM library_files f
+            LEFT JOIN library_circuit_breaker b ON b.library_id = f.library_id
+            WHERE f.quick_hash IS NULL
+              AND f.needs_hash = 1
+              AND f.is_missing = 0
+              AND (f.hash_retry_after IS NULL OR datetime(f.hash_retry_after) <= CURRENT_TIMESTAMP)
+              AND (
+                f.hash_claim_token IS NULL
+                OR f.hash_claimed_at IS NULL
+                OR datetime(f.hash_claimed_at) <= datetime('now', ?2)
+              )
+              AND (b.library_id IS NULL OR datetime(b.reset_after) <= CURRENT_TIMESTAMP)
+            ORDER BY f.id ASC
+            LIMIT ?3
+        )
+        ",
+        params![claim_token, claim_expiry, batch_size as i64],
+    )?;
+
+    let candidates = {
+        let mut stmt = tx.prepare(
+            "
+            SELECT f.id, f.library_id, f.relative_path, f.size_bytes, f.mtime_ns, COALESCE(f.hash_error_count, 0), r.root_path,
+                   r.name
+            FROM library_files f
+            JOIN library_roots r ON r.id = f.library_id
+            WHERE f.hash_claim_token = ?1
+            ORDER BY f.id ASC
+            ",
+        )?;
+        let rows = stmt.query_map(params![claim_token], |row| {
+            Ok(QuickHashCandidate {
+                id: row.get::<_, i64>(0)?,
+                library_id: row.get::<_, i64>(1)?,
+                relative_path: row.get::<_, String>(2)?,
+                expected_size: row.get::<_, i64>(3)?,
+                expected_mtime_ns: row.get::<_, i64>(4)?,
+                hash_error_count: row.get::<_, i64>(5)?,
+                root_path: row.get::<_, String>(6)?,
+                library_name: row.get::<_, String>(7)?,
+            })
+        })?;
+        let mut candidates = Vec::new();
+        for row in rows {
+            candidates.push(row?);
+        }
+        candidates
+    };
+
+    tx.commit()?;
+    Ok(candidates)
+}
+
+/// Claims present rows whose current `content_hash` was computed with `from_algorithm`,
+/// starting after `after_id` so rows a job released unmigrated are not claimed again.
+pub fn claim_migrate_candidates(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    from_algorithm: HashAlgorithm,
+    after_id: i64,
+    batch_size: usize,
+    claim_token: &str,
+) -> Result<Vec<MigrateCandidate>> {
+    let claim_expiry = format!("-{} seconds", config.hash_claim_ttl_seconds);
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "
+        UPDATE library_files
+        SET hash_claim_token = ?1,
+            hash_claimed_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id IN (
+            SELECT id
+            FROM library_files
+            WHERE hash_algorithm = ?4
+              AND id > ?5
+              AND content_hash IS NOT NULL
+              AND needs_hash = 0
+              AND is_missing = 0
+              AND (
+                hash_claim_token IS NULL
+                OR hash_claimed_at IS NULL
+                OR datetime(hash_claimed_at) <= datetime('now', ?2)
+              )
+            ORDER BY id ASC
+            LIMIT ?3
+        )
+        ",
+        params![
+            claim_token,
+            claim_expiry,
+            batch_size as i64,
+            from_algorithm.as_db_value(),
+            after_id
+        ],
+    )?;
+
+    let candidates = {
+        let mut stmt = tx.prepare(
+            "
+            SELECT f.id, f.relative_path, f.size_bytes, f.mtime_ns, r.root_path
+            FROM library_files f
+            JOIN library_roots r ON r.id = f.library_id
+            WHERE f.hash_claim_token = ?1
+            ORDER BY f.id ASC
+            ",
+        )?;
+        let rows = stmt.query_map(params![claim_token], |row| {
+            Ok(MigrateCandidate {
+                id: row.get::<_, i64>(0)?,
+                relative_path: row.get::<_, String>(1)?,
+                expected_size: row.get::<_, i64>(2)?,
+                expected_mtime_ns: row.get::<_, i64>(3)?,
+                root_path: row.get::<_, String>(4)?,
+            })
+        })?;
+        let mut candidates = Vec::new();
+        for row in rows {
+            candidates.push(row?);
+        }
+        candidates
+    };
+
+    tx.commit()?;
+    Ok(candidates)
+}
+
+pub fn claim_thumbnail_task(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+) -> Result<Option<ThumbnailTaskRecord>> {
+    let tx = conn.transaction()?;
+    requeue_expired_thumbnail_leases(&tx)?;
+
+    let Some(task_id) = select_thumbnail_candidate(&tx, config)? else {
+        tx.commit()?;
+        return Ok(None);
+    };
+
+    let lease_modifier = format!("+{} seconds", config.job_lock_ttl_seconds);
+    let claimed = tx.execute(
+        "
+        UPDATE thumbnails
+        SET status = 'running',
+            worker_id = ?1,
+            worker_heartbeat_at = CURRENT_TIMESTAMP,
+            lease_expires_at = datetime('now', ?2),
+            started_at = COALESCE(started_at, CURRENT_TIMESTAMP),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?3
+          AND status = 'pending'
+          AND permanent_failure = 0
+        ",
+        params![config.worker_id, lease_modifier, task_id],
+    )?;
+
+    if claimed != 1 {
+        tx.commit()?;
+        return Ok(None);
+    }
+
+    let row = tx
+        .query_row(
+            &format!("{THUMBNAIL_TASK_SELECT} WHERE t.id = ?1"),
+            params![task_id],
+            thumbnail_task_from_row,
+        )
+        .optional()?;
+
+    tx.commit()?;
+    Ok(row)
+}
+
+/// Claims the next due thumbnail task together with every other due pending task for the
+/// same source state (`file_id`, `source_size_bytes`, `source_mtime_ns`) and seek offset, so
+/// all requested sizes can be rendered from one decode.
+///
+/// Claimed rows are stamped with the leader's id in `task_group_id`. The result is ordered
+/// largest `max_dimension` first and is empty when nothing is claimable.
+pub fn claim_thumbnail_task_group(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+) -> Result<Vec<ThumbnailTaskRecord>> {
+    let tx = conn.transaction()?;
+    requeue_expired_thumbnail_leases(&tx)?;
+
+    let Some(leader_id) = select_thumbnail_candidate(&tx, config)? else {
+        tx.commit()?;
+        return Ok(Vec::new());
+    };
+
+    let lease_modifier = format!("+{} seconds", config.job_lock_ttl_seconds);
+    tx.execute(
+        "
+        UPDATE thumbnails
+        SET status = 'running',
+            worker_id = ?1,
+            worker_heartbeat_at = CURRENT_TIMESTAMP,
+            lease_expires_at = datetime('now', ?2),
+            task_group_id = ?3,
+            started_at = COALESCE(started_at, CURRENT_TIMESTAMP),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE status = 'pending'
+          AND permanent_failure = 0
+          AND (id = ?3 OR retry_after IS NULL OR datetime(retry_after) <= CURRENT_TIMESTAMP)
+          AND EXISTS (
+            SELECT 1
+            FROM thumbnails leader
+            WHERE leader.id = ?3
+              AND leader.file_id = thumbnails.file_id
+              AND leader.media_type = thumbnails.media_type
+              AND leader.source_size_bytes = thumbnails.source_size_bytes
+              AND leader.source_mtime_ns = thumbnails.source_mtime_ns
+              AND leader.seek_seconds IS thumbnails.seek_seconds
+          )
+        ",
+        params![config.worker_id, lease_modifier, leader_id],
+    )?;
+
+    let mut statement = tx.prepare(&format!(
+        "
+        {THUMBNAIL_TASK_SELECT}
+        WHERE t.task_group_id = ?1
+          AND t.status = 'running'
+          AND t.worker_id = ?2
+        ORDER BY t.max_dimension DESC, t.id ASC
+        "
+    ))?;
+    let rows = statement.query_map(
+        params![leader_id, config.worker_id],
+        thumbnail_task_from_row,
+    )?;
+    let mut tasks = Vec::new();
+    for row in rows {
+        tasks.push(row?);
+    }
+    drop(statement);
+
+    tx.commit()?;
+    Ok(tasks)
+}
+
+const THUMBNAIL_TASK_SELECT: &str = "
+    SELECT
+        t.id,
+        t.thumb_key,
+        t.file_id,
+        f.relative_path,
+        r.root_path,
+        t.media_type,
+        t.format,
+        t.max_dimension,
+        t.source_size_bytes,
+        t.source_mtime_ns,
+        COALESCE(t.output_relpath, ''),
+        COALESCE(t.error_count, 0),
+        t.seek_seconds,
+        t.sizes
+    FROM thumbnails t
+    JOIN library_files f ON f.id = t.file_id
+    JOIN library_roots r ON r.id = f.library_id
+";
+
+fn thumbnail_task_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ThumbnailTaskRecord> {
+    Ok(ThumbnailTaskRecord {
+        id: row.get::<_, i64>(0)?,
+        thumb_key: row.get::<_, String>(1)?,
+        file_id: row.get::<_, i64>(2)?,
+        relative_path: row.get::<_, String>(3)?,
+        root_path: row.get::<_, String>(4)?,
+        media_type: row.get::<_, String>(5)?,
+        format: row.get::<_, String>(6)?,
+        max_dimension: row.get::<_, i64>(7)?,
+        source_size_bytes: row.get::<_, i64>(8)?,
+        source_mtime_ns: row.get::<_, i64>(9)?,
+        output_relpath: row.get::<_, String>(10)?,
+        error_count: row.get::<_, i64>(11)?,
+        seek_seconds: row.get::<_, Option<f64>>(12)?,
+        sizes: row.get::<_, Option<String>>(13)?,
+    })
+}
+
+fn requeue_expired_thumbnail_leases(conn: &Connection) -> Result<usize> {
+    let recovered = conn.execute(
+        "
+        UPDATE thumbnails
+        SET status = 'pending',
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            error_code = CASE
+                WHEN error_code IS NULL OR trim(error_code) = ''
+                THEN ?1
+                ELSE error_code
+            END,
+            error_message = CASE
+                WHEN error_message IS NULL OR trim(error_message) = ''
+                THEN 'Lease expired and requeued by rust worker claim path'
+                ELSE error_message
+            END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE status = 'running'
+          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+        ",
+        [ErrorCode::LeaseExpired.as_str()],
     )?;
-
-    if updated != 1 {
-        bail!("job {job_id} lease update rejected");
-    }
-
-    Ok(())
+    Ok(recovered)
 }
 
-pub fn finish_job(
-    conn: &mut Connection,
-    config: &WorkerConfig,
-    job_id: &str,
-    success: bool,
-    error_message: Option<&str>,
-) -> Result<()> {
-    let status = if success { "completed" } else { "failed" };
-    let error_code = if success {
-        None
-    } else {
-        Some("WORKER_FAILURE")
-    };
-    let tx = conn.transaction()?;
-
-    let updated = tx.execute(
+fn requeue_expired_job_leases(conn: &Connection) -> Result<usize> {
+    let recovered = conn.execute(
         "
         UPDATE jobs
-        SET status = ?1,
-            progress = CASE WHEN ?1 = 'completed' THEN 1.0 ELSE progress END,
-            error_code = ?2,
-            error_message = ?3,
-            finished_at = CURRENT_TIMESTAMP,
-            updated_at = CURRENT_TIMESTAMP,
-            worker_heartbeat_at = CURRENT_TIMESTAMP,
-            lease_expires_at = NULL
-        WHERE id = ?4
-          AND status = 'running'
-          AND kind IN ('scan', 'hash')
-          AND worker_id = ?5
+        SET status = 'retryable',
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            error_code = CASE
+                WHEN error_code IS NULL OR trim(error_code) = ''
+                THEN ?1
+                ELSE error_code
+            END,
+            error_message = CASE
+                WHEN error_message IS NULL OR trim(error_message) = ''
+                THEN 'Lease expired and recovered by rust worker claim path'
+                ELSE error_message
+            END,
+            finished_at = COALESCE(finished_at, CURRENT_TIMESTAMP),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE status = 'running'
+          AND kind IN ('scan', 'hash', 'verify', 'dedup', 'migrate')
+          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
         ",
-        params![status, error_code, error_message, job_id, config.worker_id],
+        [ErrorCode::LeaseExpired.as_str()],
     )?;
+    Ok(recovered)
+}
 
-    if updated != 1 {
-        bail!("failed to finish running job {job_id}");
-    }
-
-    tx.commit()?;
-    Ok(())
+fn requeue_expired_thumbnail_cleanup_leases(conn: &Connection) -> Result<usize> {
+    let recovered = conn.execute(
+        "
+        UPDATE thumbnail_cleanup_jobs
+        SET status = 'pending',
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            error_code = CASE
+                WHEN error_code IS NULL OR trim(error_code) = ''
+                THEN ?1
+                ELSE error_code
+            END,
+            error_message = CASE
+                WHEN error_message IS NULL OR trim(error_message) = ''
+                THEN 'Lease expired and requeued by rust worker claim path'
+                ELSE error_message
+            END,
+            finished_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE status = 'running'
+          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+        ",
+        [ErrorCode::LeaseExpired.as_str()],
+    )?;
+    Ok(recovered)
 }
 
-pub fn claim_thumbnail_task(
-    conn: &mut Connection,
+fn requeue_expired_wal_maintenance_leases(
+    conn: &Connection,
     config: &WorkerConfig,
-) -> Result<Option<ThumbnailTaskRecord>> {
-    let tx = conn.transaction()?;
-    tx.execute(
+) -> Result<usize> {
+    let retry_modifier = format!("+{} seconds", config.wal_checkpoint_retry_seconds);
+    let recovered = conn.execute(
         "
-        UPDATE thumbnails
-        SET status = 'pending',
+        UPDATE wal_maintenance_jobs
+        SET status = 'retryable',
+            retry_count = COALESCE(retry_count, 0) + 1,
+            retry_after = datetime('now', ?1),
             worker_id = NULL,
             worker_heartbeat_at = NULL,
             lease_expires_at = NULL,
             error_code = CASE
                 WHEN error_code IS NULL OR trim(error_code) = ''
-                THEN 'LEASE_EXPIRED'
+                THEN ?2
                 ELSE error_code
             END,
             error_message = CASE
@@ -415,19 +1766,71 @@ pub fn claim_thumbnail_task(
                 THEN 'Lease expired and requeued by rust worker claim path'
                 ELSE error_message
             END,
+            finished_at = NULL,
             updated_at = CURRENT_TIMESTAMP
         WHERE status = 'running'
           AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
         ",
-        [],
+        params![retry_modifier, ErrorCode::LeaseExpired.as_str()],
     )?;
+    Ok(recovered)
+}
 
-    let candidate = tx
+fn requeue_expired_db_maintenance_leases(conn: &Connection) -> Result<usize> {
+    let recovered = conn.execute(
+        "
+        UPDATE db_maintenance_jobs
+        SET status = 'pending',
+            worker_id = NULL,
+            worker_heartbeat_at = NULL,
+            lease_expires_at = NULL,
+            error_code = ?2,
+            error_message = 'Lease expired and requeued by rust worker claim path; ' || ?1,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE status = 'running'
+          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+        ",
+        params![DB_MAINTENANCE_LEASE_HINT, ErrorCode::LeaseExpired.as_str()],
+    )?;
+    Ok(recovered)
+}
+
+/// Rows moved out of `running` by `recover_expired_leases`, per table.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LeaseRecoveryCounts {
+    pub jobs: usize,
+    pub thumbnails: usize,
+    pub thumbnail_cleanup_jobs: usize,
+    pub wal_maintenance_jobs: usize,
+    pub db_maintenance_jobs: usize,
+}
+
+/// Runs every category's expired-lease recovery in one transaction. The claim paths
+/// only recover their own category, and only once that category has new work.
+pub fn recover_expired_leases(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+) -> Result<LeaseRecoveryCounts> {
+    let tx = conn.transaction()?;
+    let counts = LeaseRecoveryCounts {
+        jobs: requeue_expired_job_leases(&tx)?,
+        thumbnails: requeue_expired_thumbnail_leases(&tx)?,
+        thumbnail_cleanup_jobs: requeue_expired_thumbnail_cleanup_leases(&tx)?,
+        wal_maintenance_jobs: requeue_expired_wal_maintenance_leases(&tx, config)?,
+        db_maintenance_jobs: requeue_expired_db_maintenance_leases(&tx)?,
+    };
+    tx.commit()?;
+    Ok(counts)
+}
+
+fn select_thumbnail_candidate(conn: &Connection, config: &WorkerConfig) -> Result<Option<i64>> {
+    let candidate = conn
         .query_row(
             "
             SELECT t.id
             FROM thumbnails t
             WHERE t.status = 'pending'
+              AND t.permanent_failure = 0
               AND (t.retry_after IS NULL OR datetime(t.retry_after) <= CURRENT_TIMESTAMP)
               AND (
                 (
@@ -460,76 +1863,45 @@ pub fn claim_thumbnail_task(
             |row| row.get::<_, i64>(0),
         )
         .optional()?;
+    Ok(candidate)
+}
 
-    let Some(task_id) = candidate else {
-        tx.commit()?;
-        return Ok(None);
-    };
-
-    let lease_modifier = format!("+{} seconds", config.job_lock_ttl_seconds);
-    let claimed = tx.execute(
-        "
-        UPDATE thumbnails
-        SET status = 'running',
-            worker_id = ?1,
-            worker_heartbeat_at = CURRENT_TIMESTAMP,
-            lease_expires_at = datetime('now', ?2),
-            started_at = COALESCE(started_at, CURRENT_TIMESTAMP),
-            updated_at = CURRENT_TIMESTAMP
-        WHERE id = ?3
-          AND status = 'pending'
-        ",
-        params![config.worker_id, lease_modifier, task_id],
-    )?;
-
-    if claimed != 1 {
-        tx.commit()?;
-        return Ok(None);
-    }
-
-    let row = tx
+/// Dimensions of a ready thumbnail rendered from the same source state into the
+/// task's output path, if one exists.
+pub fn find_fresh_thumbnail_dimensions(
+    conn: &Connection,
+    task: &ThumbnailTaskRecord,
+) -> Result<Option<(i64, i64)>> {
+    let dimensions = conn
         .query_row(
             "
-            SELECT
-                t.id,
-                t.thumb_key,
-                t.file_id,
-                f.relative_path,
-                r.root_path,
-                t.media_type,
-                t.format,
-                t.max_dimension,
-                t.source_size_bytes,
-                t.source_mtime_ns,
-                COALESCE(t.output_relpath, ''),
-                COALESCE(t.error_count, 0)
-            FROM thumbnails t
-            JOIN library_files f ON f.id = t.file_id
-            JOIN library_roots r ON r.id = f.library_id
-            WHERE t.id = ?1
+            SELECT width, height
+            FROM thumbnails
+            WHERE (status = 'ready' OR id = ?1)
+              AND file_id = ?2
+              AND format = ?3
+              AND max_dimension = ?4
+              AND source_size_bytes = ?5
+              AND source_mtime_ns = ?6
+              AND output_relpath = ?7
+              AND width IS NOT NULL
+              AND height IS NOT NULL
+            ORDER BY finished_at DESC, id DESC
+            LIMIT 1
             ",
-            params![task_id],
-            |row| {
-                Ok(ThumbnailTaskRecord {
-                    id: row.get::<_, i64>(0)?,
-                    thumb_key: row.get::<_, String>(1)?,
-                    file_id: row.get::<_, i64>(2)?,
-                    relative_path: row.get::<_, String>(3)?,
-                    root_path: row.get::<_, String>(4)?,
-                    media_type: row.get::<_, String>(5)?,
-                    format: row.get::<_, String>(6)?,
-                    max_dimension: row.get::<_, i64>(7)?,
-                    source_size_bytes: row.get::<_, i64>(8)?,
-                    source_mtime_ns: row.get::<_, i64>(9)?,
-                    output_relpath: row.get::<_, String>(10)?,
-                    error_count: row.get::<_, i64>(11)?,
-                })
-            },
+            params![
+                task.id,
+                task.file_id,
+                task.format,
+                task.max_dimension,
+                task.source_size_bytes,
+                task.source_mtime_ns,
+                task.output_relpath
+            ],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
         )
         .optional()?;
-
-    tx.commit()?;
-    Ok(row)
+    Ok(dimensions)
 }
 
 pub fn refresh_thumbnail_lease(
@@ -598,27 +1970,96 @@ pub fn finish_thumbnail_success(
     Ok(())
 }
 
+/// Failures that recur on every attempt for the same source bytes.
+pub fn is_terminal_thumbnail_error(error_code: ErrorCode) -> bool {
+    matches!(
+        error_code,
+        ErrorCode::ThumbDecodeResource
+            | ErrorCode::ThumbVideoFfmpegTerminal
+            | ErrorCode::ThumbDirPermission
+            | ErrorCode::ThumbSourceTooLarge
+    )
+}
+
+/// Codes in `thumbnail_permanent_error_codes`: the source will never render, so the task is
+/// parked with `permanent_failure = 1` until an operator resets it.
+pub fn is_permanent_thumbnail_error(config: &WorkerConfig, error_code: ErrorCode) -> bool {
+    config.thumbnail_permanent_error_codes.contains(&error_code)
+}
+
+/// Whether recording this failure moves the task to the terminal `dead` status.
+pub fn thumbnail_failure_exhausts_retries(
+    config: &WorkerConfig,
+    previous_error_count: i64,
+    error_code: ErrorCode,
+) -> bool {
+    if is_permanent_thumbnail_error(config, error_code) {
+        return false;
+    }
+    // Operator-configured ffmpeg dispositions name inputs this build can never handle.
+    if error_code == ErrorCode::ThumbVideoFfmpegTerminal {
+        return true;
+    }
+    !matches!(
+        error_code,
+        ErrorCode::ThumbLibraryOffline | ErrorCode::ThumbDiskFull
+    ) && previous_error_count.saturating_add(1) > config.thumbnail_max_error_count
+}
+
 pub fn finish_thumbnail_failure(
     conn: &mut Connection,
     config: &WorkerConfig,
     task_id: i64,
     previous_error_count: i64,
-    error_code: &str,
+    error_code: ErrorCode,
     error_message: &str,
 ) -> Result<()> {
-    let next_error_count = previous_error_count.saturating_add(1);
-    let retry_seconds = calculate_retry_delay_seconds(
-        config.thumbnail_retry_base_seconds,
-        config.thumbnail_retry_max_seconds,
-        next_error_count as u64,
-    );
-    let retry_modifier = format!("+{} seconds", retry_seconds);
+    // An offline library or a full thumbs disk is an infrastructure outage rather than
+    // a per-file failure, so it waits out a long fixed delay without growing error_count.
+    // Terminal codes get no retry_after; Python will not requeue them. Tasks past
+    // thumbnail_max_error_count become `dead`, which nothing requeues. Permanent codes are
+    // stored as THUMB_PERMANENT_FAILURE with the original code kept in the message.
+    let dead = thumbnail_failure_exhausts_retries(config, previous_error_count, error_code);
+    let permanent = is_permanent_thumbnail_error(config, error_code);
+    let (next_error_count, retry_seconds) = if permanent {
+        (previous_error_count, None)
+    } else if error_code == ErrorCode::ThumbLibraryOffline {
+        (
+            previous_error_count,
+            Some(config.thumbnail_library_offline_retry_seconds),
+        )
+    } else if error_code == ErrorCode::ThumbDiskFull {
+        (
+            previous_error_count,
+            Some(config.thumbnail_disk_full_retry_seconds),
+        )
+    } else if dead || is_terminal_thumbnail_error(error_code) {
+        (previous_error_count.saturating_add(1), None)
+    } else {
+        let next_error_count = previous_error_count.saturating_add(1);
+        let retry_seconds = calculate_retry_delay_seconds(
+            config.thumbnail_retry_base_seconds,
+            config.thumbnail_retry_max_seconds,
+            next_error_count as u64,
+        );
+        (next_error_count, Some(retry_seconds))
+    };
+    let retry_modifier = retry_seconds.map(|seconds| format!("+{seconds} seconds"));
+    let (stored_code, stored_message) = if permanent {
+        (
+            ErrorCode::ThumbPermanentFailure,
+            format!("{}: {error_message}", error_code.as_str()),
+        )
+    } else {
+        (error_code, error_message.to_string())
+    };
 
     let tx = conn.transaction()?;
     let updated = tx.execute(
         "
         UPDATE thumbnails
-        SET status = 'failed',
+        SET status = CASE WHEN ?7 THEN 'dead' ELSE 'failed' END,
+            permanent_failure = ?8,
             error_count = ?1,
             error_code = ?2,
             error_message = ?3,
@@ -633,11 +2074,13 @@ pub fn finish_thumbnail_failure(
         ",
         params![
             next_error_count,
-            error_code,
-            error_message,
+            stored_code.as_str(),
+            stored_message,
             retry_modifier,
             task_id,
-            config.worker_id
+            config.worker_id,
+            dead,
+            permanent
         ],
     )?;
 
@@ -654,35 +2097,12 @@ pub fn claim_thumbnail_cleanup_job(
     config: &WorkerConfig,
 ) -> Result<Option<ThumbnailCleanupRecord>> {
     let tx = conn.transaction()?;
-    tx.execute(
-        "
-        UPDATE thumbnail_cleanup_jobs
-        SET status = 'pending',
-            worker_id = NULL,
-            worker_heartbeat_at = NULL,
-            lease_expires_at = NULL,
-            error_code = CASE
-                WHEN error_code IS NULL OR trim(error_code) = ''
-                THEN 'LEASE_EXPIRED'
-                ELSE error_code
-            END,
-            error_message = CASE
-                WHEN error_message IS NULL OR trim(error_message) = ''
-                THEN 'Lease expired and requeued by rust worker claim path'
-                ELSE error_message
-            END,
-            finished_at = NULL,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE status = 'running'
-          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
-        ",
-        [],
-    )?;
+    requeue_expired_thumbnail_cleanup_leases(&tx)?;
 
     let candidate = tx
         .query_row(
             "
-            SELECT id, group_key
+            SELECT id, group_key, cleanup_kind
             FROM thumbnail_cleanup_jobs c
             WHERE c.status = 'pending'
               AND datetime(c.execute_after) <= CURRENT_TIMESTAMP
@@ -696,11 +2116,17 @@ pub fn claim_thumbnail_cleanup_job(
             LIMIT 1
             ",
             [],
-            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
         )
         .optional()?;
 
-    let Some((job_id, group_key)) = candidate else {
+    let Some((job_id, group_key, kind_raw)) = candidate else {
         tx.commit()?;
         return Ok(None);
     };
@@ -724,47 +2150,24 @@ pub fn claim_thumbnail_cleanup_job(
     if claimed != 1 {
         tx.commit()?;
         return Ok(None);
-    }
-
-    tx.commit()?;
-    Ok(Some(ThumbnailCleanupRecord {
-        id: job_id,
-        group_key,
-    }))
-}
-
-pub fn claim_wal_maintenance_job(
-    conn: &mut Connection,
-    config: &WorkerConfig,
-) -> Result<Option<WalMaintenanceRecord>> {
-    let tx = conn.transaction()?;
-    let retry_modifier = format!("+{} seconds", config.wal_checkpoint_retry_seconds);
-    tx.execute(
-        "
-        UPDATE wal_maintenance_jobs
-        SET status = 'retryable',
-            retry_count = COALESCE(retry_count, 0) + 1,
-            retry_after = datetime('now', ?1),
-            worker_id = NULL,
-            worker_heartbeat_at = NULL,
-            lease_expires_at = NULL,
-            error_code = CASE
-                WHEN error_code IS NULL OR trim(error_code) = ''
-                THEN 'LEASE_EXPIRED'
-                ELSE error_code
-            END,
-            error_message = CASE
-                WHEN error_message IS NULL OR trim(error_message) = ''
-                THEN 'Lease expired and requeued by rust worker claim path'
-                ELSE error_message
-            END,
-            finished_at = NULL,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE status = 'running'
-          AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
-        ",
-        params![retry_modifier],
-    )?;
+    }
+
+    tx.commit()?;
+    let kind = ThumbnailCleanupKind::parse(&kind_raw)
+        .ok_or_else(|| anyhow!("unsupported thumbnail cleanup kind: {kind_raw}"))?;
+    Ok(Some(ThumbnailCleanupRecord {
+        id: job_id,
+        group_key,
+        kind,
+    }))
+}
+
+pub fn claim_wal_maintenance_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+) -> Result<Option<WalMaintenanceRecord>> {
+    let tx = conn.transaction()?;
+    requeue_expired_wal_maintenance_leases(&tx, config)?;
 
     let candidate = tx
         .query_row(
@@ -834,7 +2237,7 @@ pub fn finish_thumbnail_cleanup_job(
     config: &WorkerConfig,
     job_id: i64,
     success: bool,
-    error_code: Option<&str>,
+    error_code: Option<ErrorCode>,
     error_message: Option<&str>,
 ) -> Result<()> {
     let status = if success { "completed" } else { "failed" };
@@ -853,7 +2256,13 @@ pub fn finish_thumbnail_cleanup_job(
           AND status = 'running'
           AND worker_id = ?5
         ",
-        params![status, error_code, error_message, job_id, config.worker_id],
+        params![
+            status,
+            error_code.map(ErrorCode::as_str),
+            error_message,
+            job_id,
+            config.worker_id
+        ],
     )?;
 
     if updated != 1 {
@@ -905,6 +2314,57 @@ pub fn execute_wal_checkpoint(
     Ok(stats)
 }
 
+/// Job error recorded when a scan/hash job stops early because the worker is exiting.
+pub const SHUTDOWN_REQUESTED: &str = "shutdown requested";
+
+/// Gives a PASSIVE checkpoint a window between batches of a long scan/hash job
+/// so the WAL does not grow for the whole job. With a shutdown flag attached it is
+/// also where such a job stops once the worker has been asked to exit.
+pub struct CommitYielder<'a> {
+    interval: Option<Duration>,
+    last_yield_at: Instant,
+    shutdown: Option<&'a AtomicBool>,
+}
+
+impl<'a> CommitYielder<'a> {
+    pub fn new(config: &WorkerConfig) -> Self {
+        Self {
+            interval: (config.commit_yield_interval_seconds > 0)
+                .then(|| Duration::from_secs(config.commit_yield_interval_seconds)),
+            last_yield_at: Instant::now(),
+            shutdown: None,
+        }
+    }
+
+    pub fn with_shutdown(mut self, shutdown: &'a AtomicBool) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    pub fn maybe_yield(&mut self, conn: &Connection) -> Result<()> {
+        if self
+            .shutdown
+            .is_some_and(|shutdown| shutdown.load(Ordering::SeqCst))
+        {
+            bail!(SHUTDOWN_REQUESTED);
+        }
+        let Some(interval) = self.interval else {
+            return Ok(());
+        };
+        if self.last_yield_at.elapsed() < interval {
+            return Ok(());
+        }
+
+        if !conn.is_autocommit() {
+            bail!("commit yield requested while a transaction is still open");
+        }
+        thread::sleep(Duration::from_millis(20));
+        execute_wal_checkpoint(conn, WalCheckpointMode::Passive)?;
+        self.last_yield_at = Instant::now();
+        Ok(())
+    }
+}
+
 pub fn finish_wal_maintenance_success(
     conn: &mut Connection,
     config: &WorkerConfig,
@@ -950,7 +2410,7 @@ pub fn requeue_wal_maintenance_retry(
     config: &WorkerConfig,
     job_id: i64,
     previous_retry_count: i64,
-    error_code: &str,
+    error_code: ErrorCode,
     error_message: &str,
     stats: WalCheckpointStats,
 ) -> Result<()> {
@@ -982,7 +2442,7 @@ pub fn requeue_wal_maintenance_retry(
             stats.busy,
             stats.log_frames,
             stats.checkpointed_frames,
-            error_code,
+            error_code.as_str(),
             error_message,
             job_id,
             config.worker_id
@@ -1000,7 +2460,7 @@ pub fn finish_wal_maintenance_failure(
     conn: &mut Connection,
     config: &WorkerConfig,
     job_id: i64,
-    error_code: &str,
+    error_code: ErrorCode,
     error_message: &str,
 ) -> Result<()> {
     let tx = conn.transaction()?;
@@ -1018,7 +2478,7 @@ pub fn finish_wal_maintenance_failure(
           AND status = 'running'
           AND worker_id = ?4
         ",
-        params![error_code, error_message, job_id, config.worker_id],
+        params![error_code.as_str(), error_message, job_id, config.worker_id],
     )?;
     if updated != 1 {
         bail!("failed to mark wal maintenance job {job_id} as failed");
@@ -1027,22 +2487,261 @@ pub fn finish_wal_maintenance_failure(
     Ok(())
 }
 
+pub fn has_runnable_db_maintenance_work(conn: &Connection) -> Result<bool> {
+    let exists = conn
+        .query_row(
+            "
+            SELECT 1
+            FROM db_maintenance_jobs
+            WHERE (
+                status = 'pending'
+                AND datetime(execute_after) <= CURRENT_TIMESTAMP
+            ) OR (
+                status = 'running'
+                AND (lease_expires_at IS NULL OR datetime(lease_expires_at) <= CURRENT_TIMESTAMP)
+            )
+            LIMIT 1
+            ",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .is_some();
+    Ok(exists)
+}
+
+/// Claims one runnable maintenance job. The claim transaction is committed before
+/// returning because VACUUM cannot run inside an open transaction.
+pub fn claim_db_maintenance_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+) -> Result<Option<DbMaintenanceRecord>> {
+    let tx = conn.transaction()?;
+    requeue_expired_db_maintenance_leases(&tx)?;
+
+    let candidate = tx
+        .query_row(
+            "
+            SELECT id, operation
+            FROM db_maintenance_jobs
+            WHERE status = 'pending'
+              AND datetime(execute_after) <= CURRENT_TIMESTAMP
+            ORDER BY execute_after ASC, id ASC
+            LIMIT 1
+            ",
+            [],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?;
+
+    let Some((job_id, operation_raw)) = candidate else {
+        tx.commit()?;
+        return Ok(None);
+    };
+
+    let lease_modifier = format!("+{} seconds", config.job_lock_ttl_seconds);
+    let claimed = tx.execute(
+        "
+        UPDATE db_maintenance_jobs
+        SET status = 'running',
+            worker_id = ?1,
+            worker_heartbeat_at = CURRENT_TIMESTAMP,
+            lease_expires_at = datetime('now', ?2),
+            error_code = NULL,
+            error_message = NULL,
+            started_at = COALESCE(started_at, CURRENT_TIMESTAMP),
+            updated_at = CURRENT_TIMESTAMP,
+            finished_at = NULL
+        WHERE id = ?3
+          AND status = 'pending'
+        ",
+        params![config.worker_id, lease_modifier, job_id],
+    )?;
+    tx.commit()?;
+
+    if claimed != 1 {
+        return Ok(None);
+    }
+    let operation = DbMaintenanceOperation::parse(&operation_raw)
+        .ok_or_else(|| anyhow!("unsupported db maintenance operation: {operation_raw}"))?;
+    Ok(Some(DbMaintenanceRecord {
+        id: job_id,
+        operation,
+    }))
+}
+
+/// Runs the operation in autocommit mode and reports page counts around it. Freed
+/// pages only show up for VACUUM and for incremental vacuum with auto_vacuum enabled.
+pub fn execute_db_maintenance(
+    conn: &Connection,
+    operation: DbMaintenanceOperation,
+) -> Result<DbMaintenanceStats> {
+    if !conn.is_autocommit() {
+        bail!("db maintenance requested while a transaction is still open");
+    }
+    let page_count_before = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let freelist_count_before = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    // incremental_vacuum and integrity_check return rows, so drain them instead of execute().
+    let mut stmt = conn.prepare(&operation.as_sql())?;
+    let mut rows = stmt.query([])?;
+    let mut lines = Vec::new();
+    while let Some(row) = rows.next()? {
+        if operation == DbMaintenanceOperation::IntegrityCheck {
+            lines.push(row.get::<_, String>(0)?);
+        }
+    }
+    drop(rows);
+    drop(stmt);
+    let page_count_after = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let freelist_count_after = conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?;
+    Ok(DbMaintenanceStats {
+        page_count_before,
+        page_count_after,
+        freelist_count_before,
+        freelist_count_after,
+        integrity_result: (operation == DbMaintenanceOperation::IntegrityCheck)
+            .then(|| lines.join("; ")),
+    })
+}
+
+pub fn finish_db_maintenance_success(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job_id: i64,
+    stats: &DbMaintenanceStats,
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    let updated = tx.execute(
+        "
+        UPDATE db_maintenance_jobs
+        SET status = 'completed',
+            page_count_before = ?1,
+            page_count_after = ?2,
+            freelist_count_before = ?3,
+            freelist_count_after = ?4,
+            integrity_result = ?7,
+            error_code = NULL,
+            error_message = NULL,
+            finished_at = CURRENT_TIMESTAMP,
+            worker_heartbeat_at = CURRENT_TIMESTAMP,
+            lease_expires_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?5
+          AND status = 'running'
+          AND worker_id = ?6
+        ",
+        params![
+            stats.page_count_before,
+            stats.page_count_after,
+            stats.freelist_count_before,
+            stats.freelist_count_after,
+            job_id,
+            config.worker_id,
+            stats.integrity_result
+        ],
+    )?;
+
+    if updated != 1 {
+        bail!(
+            "failed to finish db maintenance job {job_id}: lease lost; {DB_MAINTENANCE_LEASE_HINT}"
+        );
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn finish_db_maintenance_failure(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job_id: i64,
+    error_code: ErrorCode,
+    error_message: &str,
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    let updated = tx.execute(
+        "
+        UPDATE db_maintenance_jobs
+        SET status = 'failed',
+            error_code = ?1,
+            error_message = ?2,
+            finished_at = CURRENT_TIMESTAMP,
+            worker_heartbeat_at = CURRENT_TIMESTAMP,
+            lease_expires_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?3
+          AND status = 'running'
+          AND worker_id = ?4
+        ",
+        params![error_code.as_str(), error_message, job_id, config.worker_id],
+    )?;
+    if updated != 1 {
+        bail!("failed to mark db maintenance job {job_id} as failed: lease lost; {DB_MAINTENANCE_LEASE_HINT}");
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Whether any `thumbnails` row, in any status, still points at `output_relpath`.
+pub fn is_thumbnail_output_referenced(conn: &Connection, output_relpath: &str) -> Result<bool> {
+    let mut stmt =
+        conn.prepare_cached("SELECT 1 FROM thumbnails WHERE output_relpath = ?1 LIMIT 1")?;
+    Ok(stmt.exists(params![output_relpath])?)
+}
+
+/// Whether a pinned `library_files` row belongs to `group_key` (`<hash_algorithm>:<hex>`).
+pub fn group_has_pinned_files(conn: &Connection, group_key: &str) -> Result<bool> {
+    let mut stmt = conn.prepare_cached(
+        "
+        SELECT 1
+        FROM library_files
+        WHERE pinned = 1
+          AND content_hash IS NOT NULL
+          AND hash_algorithm || ':' || lower(hex(content_hash)) = ?1
+        LIMIT 1
+        ",
+    )?;
+    Ok(stmt.exists(params![group_key])?)
+}
+
+/// Whether a row whose `sizes` lists `size` has `output_relpath` as its primary output.
+pub fn is_thumbnail_sibling_referenced(
+    conn: &Connection,
+    output_relpath: &str,
+    size: i64,
+) -> Result<bool> {
+    let mut stmt = conn.prepare_cached(
+        "
+        SELECT 1
+        FROM thumbnails
+        WHERE output_relpath = ?1
+          AND ',' || sizes || ',' LIKE '%,' || ?2 || ',%'
+        LIMIT 1
+        ",
+    )?;
+    Ok(stmt.exists(params![output_relpath, size.to_string()])?)
+}
+
+/// Terminal rows of a group with their primary output and `sizes` (for sibling outputs).
 pub fn list_group_thumbnail_outputs(
     conn: &Connection,
     group_key: &str,
-) -> Result<Vec<(i64, String)>> {
+) -> Result<Vec<(i64, String, Option<String>)>> {
     let mut stmt = conn.prepare(
         "
-        SELECT id, COALESCE(output_relpath, '')
+        SELECT id, COALESCE(output_relpath, ''), sizes
         FROM thumbnails
         WHERE group_key = ?1
-          AND status IN ('ready', 'failed')
+          AND status IN ('ready', 'failed', 'dead')
         ORDER BY id ASC
         ",
     )?;
 
     let rows = stmt.query_map(params![group_key], |row| {
-        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
     })?;
 
     let mut outputs = Vec::new();
@@ -1052,12 +2751,276 @@ pub fn list_group_thumbnail_outputs(
     Ok(outputs)
 }
 
-pub fn delete_group_thumbnail_rows(conn: &Connection, group_key: &str) -> Result<usize> {
-    let deleted = conn.execute(
-        "DELETE FROM thumbnails WHERE group_key = ?1 AND status IN ('ready', 'failed')",
-        params![group_key],
-    )?;
-    Ok(deleted)
+/// Records `alias_relative_path` as a hardlink of `primary_file_id` and makes the
+/// alias row inherit the primary's hash instead of being hashed itself. While the
+/// primary is unhashed the alias keeps `needs_hash = 1`, so it is not stranded if the
+/// primary goes missing before `propagate_hardlink_hash` runs.
+pub fn record_hardlink_alias(
+    conn: &Connection,
+    primary_file_id: i64,
+    alias_library_id: i64,
+    alias_relative_path: &str,
+    scan_session_id: i64,
+) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO hardlink_aliases (
+            primary_file_id,
+            alias_library_id,
+            alias_relative_path,
+            scan_session_id
+        ) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT(alias_library_id, alias_relative_path) DO UPDATE SET
+            primary_file_id = excluded.primary_file_id,
+            scan_session_id = excluded.scan_session_id,
+            updated_at = CURRENT_TIMESTAMP
+        ",
+        params![
+            primary_file_id,
+            alias_library_id,
+            alias_relative_path,
+            scan_session_id
+        ],
+    )?;
+    conn.execute(
+        "
+        UPDATE library_files
+        SET needs_hash = CASE WHEN p.content_hash IS NULL THEN 1 ELSE 0 END,
+            hash_algorithm = p.hash_algorithm,
+            content_hash = p.content_hash,
+            hashed_size_bytes = p.hashed_size_bytes,
+            hashed_mtime_ns = p.hashed_mtime_ns,
+            hashed_at = p.hashed_at,
+            hash_claim_token = NULL,
+            hash_claimed_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        FROM (
+            SELECT hash_algorithm, content_hash, hashed_size_bytes, hashed_mtime_ns, hashed_at
+            FROM library_files
+            WHERE id = ?1
+        ) AS p
+        WHERE library_files.library_id = ?2
+          AND library_files.relative_path = ?3
+        ",
+        params![primary_file_id, alias_library_id, alias_relative_path],
+    )?;
+    Ok(())
+}
+
+pub fn has_hardlink(conn: &Connection, file_id: i64) -> Result<bool> {
+    let exists = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM hardlink_aliases WHERE primary_file_id = ?1)",
+        params![file_id],
+        |row| row.get::<_, i64>(0),
+    )?;
+    Ok(exists == 1)
+}
+
+/// Copies a freshly computed primary hash onto its hardlink aliases.
+pub fn propagate_hardlink_hash(conn: &Connection, primary_file_id: i64) -> Result<usize> {
+    let updated = conn.execute(
+        "
+        UPDATE library_files
+        SET needs_hash = 0,
+            hash_algorithm = p.hash_algorithm,
+            content_hash = p.content_hash,
+            hashed_size_bytes = p.hashed_size_bytes,
+            hashed_mtime_ns = p.hashed_mtime_ns,
+            hashed_at = p.hashed_at,
+            updated_at = CURRENT_TIMESTAMP
+        FROM (
+            SELECT hash_algorithm, content_hash, hashed_size_bytes, hashed_mtime_ns, hashed_at
+            FROM library_files
+            WHERE id = ?1
+        ) AS p
+        WHERE (library_files.library_id, library_files.relative_path) IN (
+            SELECT alias_library_id, alias_relative_path
+            FROM hardlink_aliases
+            WHERE primary_file_id = ?1
+        )
+        ",
+        params![primary_file_id],
+    )?;
+    Ok(updated)
+}
+
+/// Drops aliases of `library_id` that were not re-observed by `scan_session_id`.
+pub fn prune_hardlink_aliases(
+    conn: &Connection,
+    library_id: i64,
+    scan_session_id: i64,
+) -> Result<usize> {
+    let deleted = conn.execute(
+        "
+        DELETE FROM hardlink_aliases
+        WHERE alias_library_id = ?1
+          AND IFNULL(scan_session_id, -1) != ?2
+        ",
+        params![library_id, scan_session_id],
+    )?;
+    Ok(deleted)
+}
+
+/// Deletes one cleanup chunk in a single statement. Ids travel as one JSON array
+/// parameter, so chunk size is not bound by SQLite's host-parameter limit. Rows that
+/// left the terminal statuses since they were listed are kept.
+pub fn delete_thumbnail_rows_by_id(conn: &Connection, ids: &[i64]) -> Result<usize> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut stmt = conn.prepare_cached(
+        "
+        DELETE FROM thumbnails
+        WHERE id IN (SELECT value FROM json_each(?1))
+          AND status IN ('ready', 'failed', 'dead')
+        ",
+    )?;
+    let ids_json = serde_json::to_string(ids)?;
+    Ok(stmt.execute(params![ids_json])?)
+}
+
+pub fn list_library_roots(conn: &Connection) -> Result<Vec<LibraryRootRecord>> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT
+            r.id,
+            r.name,
+            r.root_path,
+            r.last_scanned_at,
+            (
+                SELECT COUNT(1)
+                FROM library_files f
+                WHERE f.library_id = r.id
+                  AND f.is_missing = 0
+            )
+        FROM library_roots r
+        ORDER BY r.name ASC
+        ",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok(LibraryRootRecord {
+            id: row.get::<_, i64>(0)?,
+            name: row.get::<_, String>(1)?,
+            root_path: row.get::<_, String>(2)?,
+            last_scanned_at: row.get::<_, Option<String>>(3)?,
+            file_count: row.get::<_, i64>(4)?,
+        })
+    })?;
+
+    let mut libraries = Vec::new();
+    for row in rows {
+        libraries.push(row?);
+    }
+    Ok(libraries)
+}
+
+/// Groups items that have failed at least once by retry generation.
+pub fn list_retry_buckets(conn: &Connection, queue: RetryQueue) -> Result<Vec<RetryBucketRecord>> {
+    let sql = match queue {
+        RetryQueue::Hash => {
+            "
+            SELECT hash_error_count, COUNT(1), MIN(hash_retry_after), MAX(hash_retry_after)
+            FROM library_files
+            WHERE hash_error_count > 0
+              AND is_missing = 0
+            GROUP BY hash_error_count
+            ORDER BY hash_error_count ASC
+            "
+        }
+        RetryQueue::Thumbnail => {
+            "
+            SELECT error_count, COUNT(1), MIN(retry_after), MAX(retry_after)
+            FROM thumbnails
+            WHERE error_count > 0
+            GROUP BY error_count
+            ORDER BY error_count ASC
+            "
+        }
+    };
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map([], |row| {
+        Ok(RetryBucketRecord {
+            error_count: row.get::<_, i64>(0)?,
+            items: row.get::<_, i64>(1)?,
+            soonest_retry_after: row.get::<_, Option<String>>(2)?,
+            latest_retry_after: row.get::<_, Option<String>>(3)?,
+        })
+    })?;
+
+    let mut buckets = Vec::new();
+    for row in rows {
+        buckets.push(row?);
+    }
+    Ok(buckets)
+}
+
+/// Present, fully hashed files sharing `(hash_algorithm, content_hash)` with at least one
+/// other file, read live from `library_files` rather than the last dedup job's
+/// `dedup_groups`. Files below `min_size_bytes` are left out before grouping.
+pub fn list_duplicate_groups(
+    conn: &Connection,
+    min_size_bytes: Option<i64>,
+) -> Result<Vec<DuplicateGroup>> {
+    let mut stmt = conn.prepare(
+        "
+        WITH hashed AS (
+            SELECT id, library_id, relative_path, size_bytes, hash_algorithm, content_hash
+            FROM library_files
+            WHERE is_missing = 0
+              AND needs_hash = 0
+              AND hash_algorithm IS NOT NULL
+              AND content_hash IS NOT NULL
+              AND (?1 IS NULL OR size_bytes >= ?1)
+        )
+        SELECT h.hash_algorithm, h.content_hash, h.id, r.name, h.relative_path, h.size_bytes
+        FROM hashed h
+        JOIN library_roots r ON r.id = h.library_id
+        WHERE (h.hash_algorithm, h.content_hash) IN (
+            SELECT hash_algorithm, content_hash
+            FROM hashed
+            GROUP BY hash_algorithm, content_hash
+            HAVING COUNT(*) > 1
+        )
+        ORDER BY h.hash_algorithm ASC, h.content_hash ASC, r.name ASC, h.relative_path ASC
+        ",
+    )?;
+    let rows = stmt.query_map(params![min_size_bytes], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Vec<u8>>(1)?,
+            DuplicateFileInfo {
+                file_id: row.get(2)?,
+                library_name: row.get(3)?,
+                relative_path: row.get(4)?,
+                size_bytes: row.get(5)?,
+            },
+        ))
+    })?;
+
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    for row in rows {
+        let (algorithm, content_hash, file) = row?;
+        let group = match groups.last_mut() {
+            Some(group) if group.algorithm == algorithm && group.content_hash == content_hash => {
+                group
+            }
+            _ => {
+                groups.push(DuplicateGroup {
+                    content_hash,
+                    algorithm,
+                    file_count: 0,
+                    total_size_bytes: 0,
+                    files: Vec::new(),
+                });
+                groups.last_mut().expect("group just pushed")
+            }
+        };
+        group.file_count += 1;
+        group.total_size_bytes += file.size_bytes;
+        group.files.push(file);
+    }
+    Ok(groups)
 }
 
 pub fn reserve_global_io_budget(
@@ -1140,9 +3103,106 @@ fn calculate_retry_delay_seconds(base_seconds: u64, max_seconds: u64, error_coun
 
 #[cfg(test)]
 mod tests {
-    use super::delete_group_thumbnail_rows;
+    use super::{
+        append_job_log, cancel_job, check_schema, check_schema_version, claim_db_maintenance_job,
+        claim_scan_hash_job, claim_thumbnail_task, claim_thumbnail_task_group,
+        delete_thumbnail_rows_by_id, detect_schema_features, execute_db_maintenance,
+        execute_wal_checkpoint, finish_db_maintenance_success, finish_job,
+        finish_thumbnail_failure, has_runnable_db_maintenance_work, has_runnable_scan_hash_work,
+        has_runnable_thumbnail_work, insert_job, insert_jobs_batch, list_dead_lettered_jobs,
+        list_duplicate_groups, list_job_log_entries, list_library_roots,
+        list_permanent_thumbnail_failures, list_retry_buckets, load_worker_control,
+        maybe_enqueue_scheduled_scan, maybe_enqueue_wal_checkpoint_if_needed, open_connection,
+        recover_expired_leases, refresh_job_lease, reset_permanent_thumbnail,
+        restore_dead_lettered_job, thumbnail_failure_exhausts_retries, DbMaintenanceOperation,
+        JobKind, JobLogLevel, LeaseRecoveryCounts, NewJobSpec, RetryQueue, WalCheckpointMode,
+        WorkerControl, MAX_SCHEMA_VERSION, MIN_SCHEMA_VERSION, SHUTDOWN_REQUESTED,
+    };
+    use crate::config::MissingSchemaVersionPolicy;
+    use crate::error_codes::ErrorCode;
+    use crate::test_support::{create_worker_schema, insert_library, test_config, TestDir};
     use rusqlite::Connection;
 
+    #[test]
+    fn wal_checkpoint_mode_downgrades_to_allowed() {
+        use super::WalCheckpointMode::{Full, Passive, Restart, Truncate};
+
+        assert_eq!(
+            Truncate.downgrade_to_allowed(&[Passive, Restart]),
+            Some(Restart)
+        );
+        assert_eq!(
+            Restart.downgrade_to_allowed(&[Passive, Truncate]),
+            Some(Passive)
+        );
+        assert_eq!(Passive.downgrade_to_allowed(&[Passive]), Some(Passive));
+        assert_eq!(Passive.downgrade_to_allowed(&[Restart, Truncate]), None);
+        assert_eq!(Restart.downgrade_to_allowed(&[Passive, Full]), Some(Full));
+        assert_eq!(
+            Full.downgrade_to_allowed(&[Passive, Truncate]),
+            Some(Passive)
+        );
+        assert_eq!(WalCheckpointMode::parse("full"), Some(Full));
+    }
+
+    #[test]
+    fn oversized_wal_enqueues_one_passive_checkpoint() {
+        let dir = TestDir::new("wal-auto-checkpoint");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        config.wal_auto_checkpoint_size_bytes = Some(1024);
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let mut wal_path = config.database_path.clone().into_os_string();
+        wal_path.push("-wal");
+        let queued = |conn: &Connection| -> Vec<(String, String)> {
+            let mut stmt = conn
+                .prepare("SELECT requested_mode, status FROM wal_maintenance_jobs")
+                .expect("prepare");
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .expect("query")
+                .map(|row| row.expect("row"))
+                .collect()
+        };
+
+        assert!(!maybe_enqueue_wal_checkpoint_if_needed(&conn, &config).expect("no wal file"));
+        std::fs::write(&wal_path, vec![0_u8; 1024]).expect("write small wal");
+        assert!(!maybe_enqueue_wal_checkpoint_if_needed(&conn, &config).expect("small wal"));
+        assert!(queued(&conn).is_empty());
+
+        std::fs::write(&wal_path, vec![0_u8; 4096]).expect("write large wal");
+        assert!(maybe_enqueue_wal_checkpoint_if_needed(&conn, &config).expect("large wal"));
+        assert!(!maybe_enqueue_wal_checkpoint_if_needed(&conn, &config).expect("already queued"));
+        assert_eq!(
+            queued(&conn),
+            vec![("passive".to_string(), "pending".to_string())]
+        );
+
+        config.wal_auto_checkpoint_size_bytes = None;
+        conn.execute("DELETE FROM wal_maintenance_jobs", [])
+            .expect("clear jobs");
+        assert!(!maybe_enqueue_wal_checkpoint_if_needed(&conn, &config).expect("disabled"));
+    }
+
+    #[test]
+    fn full_wal_checkpoint_copies_every_frame() {
+        // In-memory databases cannot use WAL, so this opens a file-backed one.
+        let dir = TestDir::new("wal-full-checkpoint");
+        let conn = open_connection(&dir.path().join("wal.sqlite3")).expect("open wal db");
+        conn.execute_batch(
+            "
+            CREATE TABLE items (id INTEGER PRIMARY KEY, body TEXT NOT NULL);
+            INSERT INTO items(body) VALUES ('a'), ('b'), ('c');
+            ",
+        )
+        .expect("write frames");
+
+        let stats =
+            execute_wal_checkpoint(&conn, WalCheckpointMode::Full).expect("full checkpoint");
+        assert_eq!(stats.busy, 0);
+        assert!(stats.log_frames > 0);
+        assert_eq!(stats.checkpointed_frames, stats.log_frames);
+    }
+
     #[test]
     fn cleanup_delete_only_removes_terminal_rows() {
         let conn = Connection::open_in_memory().expect("open sqlite in-memory");
@@ -1178,8 +3238,13 @@ mod tests {
         )
         .expect("insert pending row");
 
-        let deleted = delete_group_thumbnail_rows(&conn, "sha256:g").expect("delete terminal rows");
+        let deleted =
+            delete_thumbnail_rows_by_id(&conn, &[1, 2, 3, 4]).expect("delete terminal rows");
         assert_eq!(deleted, 2);
+        assert_eq!(
+            delete_thumbnail_rows_by_id(&conn, &[]).expect("empty chunk"),
+            0
+        );
 
         let running_remaining: i64 = conn
             .query_row(
@@ -1198,4 +3263,923 @@ mod tests {
         assert_eq!(running_remaining, 1);
         assert_eq!(pending_remaining, 1);
     }
+
+    #[test]
+    fn list_library_roots_counts_only_present_files() {
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        conn.execute_batch(
+            "
+            CREATE TABLE library_roots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name VARCHAR(255) NOT NULL,
+                root_path VARCHAR(2048) NOT NULL,
+                last_scanned_at DATETIME
+            );
+            CREATE TABLE library_files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                library_id INTEGER NOT NULL,
+                is_missing BOOLEAN NOT NULL DEFAULT 0
+            );
+            INSERT INTO library_roots(name, root_path, last_scanned_at)
+            VALUES ('photos', '/libraries/photos', '2026-01-01 00:00:00');
+            INSERT INTO library_roots(name, root_path) VALUES ('archive', '/libraries/archive');
+            INSERT INTO library_files(library_id, is_missing) VALUES (1, 0), (1, 0), (1, 1);
+            ",
+        )
+        .expect("create library tables");
+
+        let libraries = list_library_roots(&conn).expect("list library roots");
+        assert_eq!(libraries.len(), 2);
+        assert_eq!(libraries[0].name, "archive");
+        assert_eq!(libraries[0].file_count, 0);
+        assert!(libraries[0].last_scanned_at.is_none());
+        assert_eq!(libraries[1].name, "photos");
+        assert_eq!(libraries[1].file_count, 2);
+    }
+
+    #[test]
+    fn duplicate_groups_list_hashed_present_files_per_hash() {
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let photos = insert_library(&conn, "photos", std::path::Path::new("/libraries/photos"));
+        let archive = insert_library(&conn, "archive", std::path::Path::new("/libraries/archive"));
+        let insert = |library_id: i64, path: &str, size: i64, hash: &[u8], missing: bool| {
+            conn.execute(
+                "
+                INSERT INTO library_files(
+                    library_id, relative_path, size_bytes, mtime_ns, needs_hash,
+                    hash_algorithm, content_hash, is_missing
+                )
+                VALUES (?1, ?2, ?3, 0, 0, 'blake3', ?4, ?5)
+                ",
+                rusqlite::params![library_id, path, size, hash, missing],
+            )
+            .expect("insert file");
+        };
+        insert(photos, "a.jpg", 100, b"big", false);
+        insert(archive, "a-copy.jpg", 100, b"big", false);
+        insert(photos, "b.jpg", 100, b"big", true);
+        insert(photos, "tiny.txt", 1, b"small", false);
+        insert(archive, "tiny.txt", 1, b"small", false);
+        insert(photos, "unique.jpg", 100, b"unique", false);
+
+        let groups = list_duplicate_groups(&conn, None).expect("list duplicates");
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].content_hash, b"big".to_vec());
+        assert_eq!(groups[0].algorithm, "blake3");
+        assert_eq!(groups[0].file_count, 2);
+        assert_eq!(groups[0].total_size_bytes, 200);
+        let files: Vec<(&str, &str)> = groups[0]
+            .files
+            .iter()
+            .map(|file| (file.library_name.as_str(), file.relative_path.as_str()))
+            .collect();
+        assert_eq!(files, vec![("archive", "a-copy.jpg"), ("photos", "a.jpg")]);
+        assert_eq!(groups[1].content_hash, b"small".to_vec());
+
+        let groups = list_duplicate_groups(&conn, Some(2)).expect("list large duplicates");
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].content_hash, b"big".to_vec());
+    }
+
+    #[test]
+    fn retry_buckets_group_by_error_count() {
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute_batch(
+            "
+            INSERT INTO thumbnails(thumb_key, file_id, media_type, source_size_bytes, source_mtime_ns, error_count, retry_after)
+            VALUES
+                ('a', 1, 'image', 1, 1, 1, '2026-01-01 00:00:30'),
+                ('b', 2, 'image', 1, 1, 1, '2026-01-01 00:00:10'),
+                ('c', 3, 'video', 1, 1, 3, '2026-01-01 00:02:00'),
+                ('d', 4, 'image', 1, 1, 0, NULL);
+            INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns, hash_error_count, hash_retry_after, is_missing)
+            VALUES
+                (1, 'a', 1, 1, 2, '2026-01-01 00:01:00', 0),
+                (1, 'b', 1, 1, 2, NULL, 1);
+            ",
+        )
+        .expect("seed rows");
+
+        let thumbnails =
+            list_retry_buckets(&conn, RetryQueue::Thumbnail).expect("thumbnail buckets");
+        assert_eq!(thumbnails.len(), 2);
+        assert_eq!((thumbnails[0].error_count, thumbnails[0].items), (1, 2));
+        assert_eq!(
+            thumbnails[0].soonest_retry_after.as_deref(),
+            Some("2026-01-01 00:00:10")
+        );
+        assert_eq!(
+            thumbnails[0].latest_retry_after.as_deref(),
+            Some("2026-01-01 00:00:30")
+        );
+        assert_eq!((thumbnails[1].error_count, thumbnails[1].items), (3, 1));
+
+        let hashes = list_retry_buckets(&conn, RetryQueue::Hash).expect("hash buckets");
+        assert_eq!(hashes.len(), 1);
+        assert_eq!((hashes[0].error_count, hashes[0].items), (2, 1));
+    }
+
+    #[test]
+    fn terminal_thumbnail_failure_has_no_retry_after() {
+        let dir = TestDir::new("thumb-terminal");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute(
+            "
+            INSERT INTO thumbnails(thumb_key, file_id, status, media_type, source_size_bytes, source_mtime_ns, worker_id)
+            VALUES ('bomb', 1, 'running', 'image', 1, 1, ?1), ('flaky', 2, 'running', 'image', 1, 1, ?1)
+            ",
+            [&config.worker_id],
+        )
+        .expect("seed rows");
+
+        finish_thumbnail_failure(
+            &mut conn,
+            &config,
+            1,
+            0,
+            ErrorCode::ThumbDecodeResource,
+            "bomb",
+        )
+        .expect("terminal failure");
+        finish_thumbnail_failure(
+            &mut conn,
+            &config,
+            2,
+            0,
+            ErrorCode::ThumbDecodeFailed,
+            "flaky",
+        )
+        .expect("retryable failure");
+
+        let rows: Vec<(String, i64, Option<String>)> = conn
+            .prepare("SELECT status, error_count, retry_after FROM thumbnails ORDER BY id")
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .expect("query")
+            .map(|row| row.expect("row"))
+            .collect();
+        assert_eq!(rows[0], ("failed".to_string(), 1, None));
+        assert_eq!(rows[1].1, 1);
+        assert!(rows[1].2.is_some());
+    }
+
+    #[test]
+    fn permanent_thumbnail_failures_are_parked_until_reset() {
+        let dir = TestDir::new("thumb-permanent");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        config.thumbnail_permanent_error_codes = vec![ErrorCode::ThumbDecodeFailed];
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "lib", &dir.path().join("libraries"));
+        conn.execute(
+            "
+            INSERT INTO library_files(id, library_id, relative_path, size_bytes, mtime_ns)
+            VALUES (1, ?1, 'corrupt.jpg', 1, 1)
+            ",
+            [library_id],
+        )
+        .expect("seed file");
+        conn.execute(
+            "
+            INSERT INTO thumbnails(thumb_key, file_id, status, media_type, source_size_bytes, source_mtime_ns, worker_id, error_count)
+            VALUES ('corrupt', 1, 'running', 'image', 1, 1, ?1, 2)
+            ",
+            [&config.worker_id],
+        )
+        .expect("seed row");
+
+        assert!(!thumbnail_failure_exhausts_retries(
+            &config,
+            config.thumbnail_max_error_count,
+            ErrorCode::ThumbDecodeFailed
+        ));
+        finish_thumbnail_failure(
+            &mut conn,
+            &config,
+            1,
+            2,
+            ErrorCode::ThumbDecodeFailed,
+            "truncated scan data",
+        )
+        .expect("permanent failure");
+
+        let row: (String, bool, i64, String, String, Option<String>) = conn
+            .query_row(
+                "
+                SELECT status, permanent_failure, error_count, error_code, error_message, retry_after
+                FROM thumbnails
+                ",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .expect("row");
+        assert_eq!(
+            row,
+            (
+                "failed".to_string(),
+                true,
+                2,
+                "THUMB_PERMANENT_FAILURE".to_string(),
+                "THUMB_DECODE_FAILED: truncated scan data".to_string(),
+                None
+            )
+        );
+        let parked = list_permanent_thumbnail_failures(&conn).expect("list");
+        assert_eq!(parked.len(), 1);
+        assert_eq!(parked[0].thumb_key, "corrupt");
+
+        // Even requeued to pending by hand, the flag keeps it out of every claim path.
+        conn.execute("UPDATE thumbnails SET status = 'pending'", [])
+            .expect("requeue");
+        assert!(!has_runnable_thumbnail_work(&conn).expect("runnable"));
+        assert!(claim_thumbnail_task(&mut conn, &config)
+            .expect("claim")
+            .is_none());
+        assert!(claim_thumbnail_task_group(&mut conn, &config)
+            .expect("claim group")
+            .is_empty());
+
+        reset_permanent_thumbnail(&conn, "corrupt").expect("reset");
+        assert!(list_permanent_thumbnail_failures(&conn)
+            .expect("list")
+            .is_empty());
+        assert!(reset_permanent_thumbnail(&conn, "corrupt").is_err());
+        let claimed = claim_thumbnail_task(&mut conn, &config)
+            .expect("claim")
+            .expect("claimable after reset");
+        assert_eq!(claimed.thumb_key, "corrupt");
+    }
+
+    #[test]
+    fn thumbnail_lands_in_dead_after_max_error_count() {
+        let dir = TestDir::new("thumb-dead");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        config.thumbnail_max_error_count = 2;
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute_batch(
+            "
+            INSERT INTO library_roots(id, name, root_path) VALUES (1, 'lib', '/libraries/lib');
+            INSERT INTO library_files(id, library_id, relative_path, size_bytes, mtime_ns)
+            VALUES (1, 1, 'cursed.jpg', 1, 1);
+            INSERT INTO thumbnails(thumb_key, file_id, status, media_type, source_size_bytes, source_mtime_ns)
+            VALUES ('cursed', 1, 'pending', 'image', 1, 1);
+            ",
+        )
+        .expect("seed rows");
+
+        for attempt in 0..3 {
+            let task = claim_thumbnail_task(&mut conn, &config)
+                .expect("claim")
+                .expect("task claimable");
+            finish_thumbnail_failure(
+                &mut conn,
+                &config,
+                task.id,
+                task.error_count,
+                ErrorCode::ThumbDecodeFailed,
+                "undecodable",
+            )
+            .expect("record failure");
+            if attempt < 2 {
+                // Python requeues retryable failures once retry_after elapses.
+                conn.execute(
+                    "UPDATE thumbnails SET status = 'pending', retry_after = NULL",
+                    [],
+                )
+                .expect("requeue");
+            }
+        }
+
+        let (status, error_count, error_code, retry_after): (String, i64, String, Option<String>) =
+            conn.query_row(
+                "SELECT status, error_count, error_code, retry_after FROM thumbnails",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("row");
+        assert_eq!(status, "dead");
+        assert_eq!(error_count, 3);
+        assert_eq!(error_code, "THUMB_DECODE_FAILED");
+        assert!(retry_after.is_none());
+        assert!(!has_runnable_thumbnail_work(&conn).expect("runnable"));
+        assert!(claim_thumbnail_task(&mut conn, &config)
+            .expect("claim")
+            .is_none());
+    }
+
+    #[test]
+    fn paused_hash_kind_is_skipped_by_claim() {
+        let dir = TestDir::new("worker-control");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute_batch(
+            "
+            INSERT INTO jobs(id, kind, status, created_at)
+            VALUES ('hash-1', 'hash', 'pending', '2024-01-01 00:00:00'),
+                   ('scan-1', 'scan', 'pending', '2024-01-01 00:00:01');
+            UPDATE worker_control SET pause_hash = 1 WHERE id = 1;
+            ",
+        )
+        .expect("seed jobs");
+
+        let control = load_worker_control(&conn).expect("load control");
+        assert!(control.hash_paused());
+        assert!(!control.scan_paused());
+        assert!(claim_scan_hash_job(
+            &mut conn,
+            &config,
+            Some("hash-1"),
+            &control,
+            &Default::default()
+        )
+        .expect("claim requested")
+        .is_none());
+        let job = claim_scan_hash_job(&mut conn, &config, None, &control, &Default::default())
+            .expect("claim")
+            .expect("scan job runnable");
+        assert!(matches!(job.kind, JobKind::Scan));
+
+        conn.execute("UPDATE worker_control SET pause_hash = 0 WHERE id = 1", [])
+            .expect("resume");
+        let control = load_worker_control(&conn).expect("reload control");
+        let job = claim_scan_hash_job(&mut conn, &config, None, &control, &Default::default())
+            .expect("claim")
+            .expect("hash job resumes");
+        assert_eq!(job.id, "hash-1");
+    }
+
+    #[test]
+    fn claim_returns_inserted_payload_and_leases_row() {
+        let dir = TestDir::new("claim-returning");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute(
+            "
+            INSERT INTO jobs(id, kind, status, payload)
+            VALUES ('hash-1', 'hash', 'pending', '{\"fetch_batch_size\": 7, \"library_ids\": [3]}')
+            ",
+            [],
+        )
+        .expect("seed job");
+
+        let job = claim_scan_hash_job(
+            &mut conn,
+            &config,
+            None,
+            &Default::default(),
+            &Default::default(),
+        )
+        .expect("claim")
+        .expect("job claimed");
+        assert_eq!(job.id, "hash-1");
+        assert!(matches!(job.kind, JobKind::Hash));
+        assert_eq!(
+            job.payload,
+            serde_json::json!({ "fetch_batch_size": 7, "library_ids": [3] })
+        );
+
+        let (status, worker_id, leased): (String, String, bool) = conn
+            .query_row(
+                "SELECT status, worker_id, lease_expires_at IS NOT NULL FROM jobs WHERE id = 'hash-1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .expect("job row");
+        assert_eq!(status, "running");
+        assert_eq!(worker_id, config.worker_id);
+        assert!(leased);
+        assert!(claim_scan_hash_job(
+            &mut conn,
+            &config,
+            None,
+            &Default::default(),
+            &Default::default()
+        )
+        .expect("second claim")
+        .is_none());
+    }
+
+    #[test]
+    fn schema_self_test_lists_missing_tables() {
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        let error = check_schema(&conn).expect_err("empty database");
+        assert_eq!(
+            error.to_string(),
+            "database schema is missing required tables: jobs, library_files, library_roots, thumbnails, thumbnail_cleanup_jobs, wal_maintenance_jobs, scan_sessions, library_circuit_breaker; run the Python migrations first"
+        );
+
+        create_worker_schema(&conn);
+        check_schema(&conn).expect("migrated schema passes");
+
+        let legacy = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&legacy);
+        legacy
+            .execute_batch("DROP TABLE jobs; CREATE TABLE jobs (id TEXT PRIMARY KEY);")
+            .expect("replace jobs");
+        let error = check_schema(&legacy).expect_err("jobs without kind");
+        assert!(error.to_string().contains("jobs has no kind column"));
+    }
+
+    #[test]
+    fn schema_version_gate_rejects_unsupported_or_unknown_schemas() {
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        assert!(check_schema_version(&conn, MissingSchemaVersionPolicy::Warn).is_ok());
+        assert!(check_schema_version(&conn, MissingSchemaVersionPolicy::Refuse).is_err());
+
+        conn.execute_batch(
+            "CREATE TABLE schema_migrations (version INTEGER PRIMARY KEY, name VARCHAR(255) NOT NULL)",
+        )
+        .expect("create schema_migrations");
+        let insert = |version: i64| {
+            conn.execute(
+                "INSERT INTO schema_migrations(version, name) VALUES (?1, 'step')",
+                [version],
+            )
+            .expect("insert version");
+        };
+        insert(MIN_SCHEMA_VERSION - 1);
+        let error =
+            check_schema_version(&conn, MissingSchemaVersionPolicy::Refuse).expect_err("too old");
+        assert!(error.to_string().contains("outside the supported range"));
+
+        insert(MAX_SCHEMA_VERSION);
+        assert!(check_schema_version(&conn, MissingSchemaVersionPolicy::Refuse).is_ok());
+
+        insert(MAX_SCHEMA_VERSION + 1);
+        assert!(check_schema_version(&conn, MissingSchemaVersionPolicy::Warn).is_err());
+    }
+
+    #[test]
+    fn higher_priority_job_is_claimed_first() {
+        let dir = TestDir::new("claim-priority");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("detect");
+        assert!(schema.jobs_priority);
+
+        insert_job(&conn, "backfill", JobKind::Scan, &serde_json::json!({}), 0)
+            .expect("insert backfill");
+        insert_job(&conn, "urgent", JobKind::Hash, &serde_json::json!({}), 10)
+            .expect("insert urgent");
+        conn.execute(
+            "UPDATE jobs SET created_at = datetime('now', '+1 minute') WHERE id = 'urgent'",
+            [],
+        )
+        .expect("age backfill");
+
+        let job = claim_scan_hash_job(&mut conn, &config, None, &Default::default(), &schema)
+            .expect("claim")
+            .expect("job claimed");
+        assert_eq!(job.id, "urgent");
+        assert_eq!(job.priority, 10);
+    }
+
+    #[test]
+    fn failures_are_dead_lettered_at_the_retry_budget_and_restorable() {
+        let dir = TestDir::new("dead-letter");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        config.job_max_retries = Some(2);
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("detect");
+        let control = WorkerControl::default();
+        insert_job(&conn, "flaky", JobKind::Hash, &serde_json::json!({}), 0).expect("insert");
+
+        let status_of = |conn: &Connection| -> (String, i64) {
+            conn.query_row(
+                "SELECT status, retry_count FROM jobs WHERE id = 'flaky'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("job row")
+        };
+
+        claim_scan_hash_job(&mut conn, &config, None, &control, &schema)
+            .expect("claim")
+            .expect("first attempt");
+        finish_job(&mut conn, &config, "flaky", false, Some("boom")).expect("fail");
+        assert_eq!(status_of(&conn), ("failed".to_string(), 1));
+
+        conn.execute("UPDATE jobs SET status = 'pending' WHERE id = 'flaky'", [])
+            .expect("requeue");
+        claim_scan_hash_job(&mut conn, &config, None, &control, &schema)
+            .expect("claim")
+            .expect("second attempt");
+        finish_job(&mut conn, &config, "flaky", false, Some("boom")).expect("fail");
+        assert_eq!(status_of(&conn), ("dead_lettered".to_string(), 2));
+        assert!(!has_runnable_scan_hash_work(&conn, &control).expect("runnable"));
+        let dead = list_dead_lettered_jobs(&conn).expect("list");
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, "flaky");
+
+        restore_dead_lettered_job(&conn, "flaky").expect("restore");
+        assert_eq!(status_of(&conn), ("pending".to_string(), 0));
+        let job = claim_scan_hash_job(&mut conn, &config, None, &control, &schema)
+            .expect("claim")
+            .expect("claimable again");
+        assert_eq!(job.id, "flaky");
+        assert!(restore_dead_lettered_job(&conn, "flaky").is_err());
+
+        // A payload budget overrides the configured default.
+        finish_job(&mut conn, &config, "flaky", true, None).expect("complete");
+        insert_job(
+            &conn,
+            "fragile",
+            JobKind::Scan,
+            &serde_json::json!({ "max_retries": 1 }),
+            0,
+        )
+        .expect("insert");
+        claim_scan_hash_job(&mut conn, &config, Some("fragile"), &control, &schema)
+            .expect("claim")
+            .expect("fragile claimed");
+        finish_job(&mut conn, &config, "fragile", false, Some("boom")).expect("fail");
+        let status: String = conn
+            .query_row("SELECT status FROM jobs WHERE id = 'fragile'", [], |row| {
+                row.get(0)
+            })
+            .expect("status");
+        assert_eq!(status, "dead_lettered");
+    }
+
+    #[test]
+    fn shutdown_requeues_the_job_without_spending_a_retry() {
+        let dir = TestDir::new("shutdown-requeue");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        config.job_max_retries = Some(1);
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("detect");
+        let control = WorkerControl::default();
+        insert_job(&conn, "long", JobKind::Scan, &serde_json::json!({}), 0).expect("insert");
+
+        for _ in 0..2 {
+            claim_scan_hash_job(&mut conn, &config, None, &control, &schema)
+                .expect("claim")
+                .expect("claimable");
+            finish_job(&mut conn, &config, "long", false, Some(SHUTDOWN_REQUESTED))
+                .expect("interrupt");
+            let row: (String, i64, Option<String>, Option<String>) = conn
+                .query_row(
+                    "SELECT status, retry_count, worker_id, error_message FROM jobs WHERE id = 'long'",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .expect("job row");
+            assert_eq!(
+                row,
+                (
+                    "pending".to_string(),
+                    0,
+                    None,
+                    Some(SHUTDOWN_REQUESTED.to_string())
+                )
+            );
+        }
+        assert!(has_runnable_scan_hash_work(&conn, &control).expect("runnable"));
+    }
+
+    #[test]
+    fn cancel_job_stops_pending_and_running_jobs_only() {
+        let dir = TestDir::new("cancel-job");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("detect");
+        let control = WorkerControl::default();
+        let status_of = |conn: &Connection, id: &str| -> String {
+            conn.query_row("SELECT status FROM jobs WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .expect("status")
+        };
+
+        insert_job(&conn, "queued", JobKind::Scan, &serde_json::json!({}), 0).expect("insert");
+        assert!(cancel_job(&mut conn, &config, "queued").expect("cancel pending"));
+        assert_eq!(status_of(&conn, "queued"), "cancelled");
+        assert!(!has_runnable_scan_hash_work(&conn, &control).expect("runnable"));
+
+        insert_job(&conn, "active", JobKind::Hash, &serde_json::json!({}), 0).expect("insert");
+        claim_scan_hash_job(&mut conn, &config, None, &control, &schema)
+            .expect("claim")
+            .expect("claimed");
+        assert!(cancel_job(&mut conn, &config, "active").expect("cancel running"));
+        assert_eq!(status_of(&conn, "active"), "cancelled");
+        assert!(refresh_job_lease(&conn, &config, "active", 1, 0.5).is_err());
+
+        insert_job(&conn, "done", JobKind::Hash, &serde_json::json!({}), 0).expect("insert");
+        claim_scan_hash_job(&mut conn, &config, None, &control, &schema)
+            .expect("claim")
+            .expect("claimed");
+        finish_job(&mut conn, &config, "done", true, None).expect("complete");
+        assert!(!cancel_job(&mut conn, &config, "done").expect("cancel completed"));
+        assert_eq!(status_of(&conn, "done"), "completed");
+
+        assert!(cancel_job(&mut conn, &config, "missing").is_err());
+    }
+
+    #[test]
+    fn claim_falls_back_to_created_at_without_priority_column() {
+        let dir = TestDir::new("claim-no-priority");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute_batch(
+            "
+            ALTER TABLE jobs DROP COLUMN priority;
+            INSERT INTO jobs(id, kind, status, created_at)
+            VALUES ('backfill', 'scan', 'pending', '2024-01-01 00:00:00'),
+                   ('urgent', 'hash', 'pending', '2024-01-01 00:05:00');
+            ",
+        )
+        .expect("seed jobs");
+        let schema = detect_schema_features(&conn).expect("detect");
+        assert!(!schema.jobs_priority);
+
+        let job = claim_scan_hash_job(&mut conn, &config, None, &Default::default(), &schema)
+            .expect("claim")
+            .expect("job claimed");
+        assert_eq!(job.id, "backfill");
+        assert_eq!(job.priority, 0);
+    }
+
+    #[test]
+    fn startup_recovery_requeues_expired_leases_in_every_category() {
+        let dir = TestDir::new("lease-recovery");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute_batch(
+            "
+            INSERT INTO jobs(id, kind, status, lease_expires_at)
+            VALUES ('crashed', 'hash', 'running', datetime('now', '-60 seconds')),
+                   ('live', 'scan', 'running', datetime('now', '+600 seconds'));
+            INSERT INTO db_maintenance_jobs(operation, status, lease_expires_at)
+            VALUES ('analyze', 'running', datetime('now', '-60 seconds'));
+            ",
+        )
+        .expect("insert running rows");
+
+        let counts = recover_expired_leases(&mut conn, &config).expect("recover");
+        assert_eq!(
+            counts,
+            LeaseRecoveryCounts {
+                jobs: 1,
+                db_maintenance_jobs: 1,
+                ..LeaseRecoveryCounts::default()
+            }
+        );
+        let status = |id: &str| -> String {
+            conn.query_row("SELECT status FROM jobs WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .expect("job status")
+        };
+        assert_eq!(status("crashed"), "retryable");
+        assert_eq!(status("live"), "running");
+        let maintenance_status: String = conn
+            .query_row("SELECT status FROM db_maintenance_jobs", [], |row| {
+                row.get(0)
+            })
+            .expect("maintenance status");
+        assert_eq!(maintenance_status, "pending");
+
+        let again = recover_expired_leases(&mut conn, &config).expect("recover again");
+        assert_eq!(again, LeaseRecoveryCounts::default());
+    }
+
+    #[test]
+    fn analyze_maintenance_job_completes_and_records_success() {
+        let dir = TestDir::new("db-maintenance");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute(
+            "INSERT INTO db_maintenance_jobs(operation, requested_by) VALUES ('analyze', 'test')",
+            [],
+        )
+        .expect("insert maintenance job");
+
+        assert!(has_runnable_db_maintenance_work(&conn).expect("runnable"));
+        let job = claim_db_maintenance_job(&mut conn, &config)
+            .expect("claim")
+            .expect("claimed job");
+        assert_eq!(job.operation, DbMaintenanceOperation::Analyze);
+        // The claim transaction must be closed so VACUUM-style operations can run.
+        assert!(conn.is_autocommit());
+
+        let stats = execute_db_maintenance(&conn, job.operation).expect("analyze");
+        finish_db_maintenance_success(&mut conn, &config, job.id, &stats).expect("finish");
+
+        let (status, page_count_after, lease): (String, Option<i64>, Option<String>) = conn
+            .query_row(
+                "SELECT status, page_count_after, lease_expires_at FROM db_maintenance_jobs WHERE id = ?1",
+                [job.id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .expect("load job");
+        assert_eq!(status, "completed");
+        assert_eq!(page_count_after, Some(stats.page_count_after));
+        assert!(lease.is_none());
+        assert!(!has_runnable_db_maintenance_work(&conn).expect("runnable"));
+    }
+
+    #[test]
+    fn batch_insert_skips_duplicates_and_honors_the_scan_hash_mutex() {
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, payload) VALUES ('existing', 'verify', 'pending', '{\"b\": 2, \"a\": 1}')",
+            [],
+        )
+        .expect("insert existing job");
+        let spec = |kind, payload: serde_json::Value| NewJobSpec {
+            kind,
+            payload,
+            priority: 0,
+        };
+
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM jobs", [], |row| row.get(0))
+                .expect("count jobs")
+        };
+
+        // Duplicates of the pending job are skipped without tripping the mutex.
+        let inserted = insert_jobs_batch(
+            &mut conn,
+            &[spec(JobKind::Verify, serde_json::json!({"a": 1, "b": 2}))],
+        )
+        .expect("batch insert");
+        assert_eq!(inserted, 0);
+
+        // Anything else is rejected while it is active, and nothing from the batch is kept.
+        let error = insert_jobs_batch(
+            &mut conn,
+            &[
+                spec(JobKind::Verify, serde_json::json!({"a": 1, "b": 2})),
+                spec(JobKind::Dedup, serde_json::json!({})),
+            ],
+        )
+        .expect_err("dedup behind a pending verify");
+        assert!(error.to_string().contains("already active"));
+        assert_eq!(count(&conn), 1);
+
+        for status in ["running", "retryable"] {
+            conn.execute("UPDATE jobs SET status = ?1", [status])
+                .expect("update status");
+            assert!(
+                insert_jobs_batch(&mut conn, &[spec(JobKind::Migrate, serde_json::json!({}))])
+                    .is_err()
+            );
+        }
+
+        // With nothing active the first spec is admitted; a second, different one
+        // conflicts with it and rolls the batch back.
+        conn.execute("UPDATE jobs SET status = 'completed'", [])
+            .expect("finish job");
+        let inserted = insert_jobs_batch(
+            &mut conn,
+            &[
+                spec(JobKind::Hash, serde_json::json!({})),
+                spec(JobKind::Hash, serde_json::json!({})),
+            ],
+        )
+        .expect("batch insert");
+        assert_eq!(inserted, 1);
+        assert_eq!(count(&conn), 2);
+        conn.execute("UPDATE jobs SET status = 'completed'", [])
+            .expect("finish job");
+        let result = insert_jobs_batch(
+            &mut conn,
+            &[
+                spec(JobKind::Scan, serde_json::json!({})),
+                spec(JobKind::Hash, serde_json::json!({"library_ids": [2]})),
+            ],
+        );
+        assert!(result.is_err());
+        assert_eq!(count(&conn), 2);
+    }
+
+    #[test]
+    fn integrity_check_maintenance_job_records_ok() {
+        let dir = TestDir::new("db-integrity");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute(
+            "INSERT INTO db_maintenance_jobs(operation) VALUES ('integrity_check')",
+            [],
+        )
+        .expect("insert maintenance job");
+
+        let job = claim_db_maintenance_job(&mut conn, &config)
+            .expect("claim")
+            .expect("claimed job");
+        assert_eq!(job.operation, DbMaintenanceOperation::IntegrityCheck);
+        let stats = execute_db_maintenance(&conn, job.operation).expect("integrity_check");
+        assert_eq!(stats.integrity_result.as_deref(), Some("ok"));
+        assert!(!stats.integrity_failed());
+        finish_db_maintenance_success(&mut conn, &config, job.id, &stats).expect("finish");
+
+        let (status, result): (String, Option<String>) = conn
+            .query_row(
+                "SELECT status, integrity_result FROM db_maintenance_jobs WHERE id = ?1",
+                [job.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("load job");
+        assert_eq!(status, "completed");
+        assert_eq!(result.as_deref(), Some("ok"));
+    }
+
+    #[test]
+    fn job_log_entries_survive_job_failure() {
+        let dir = TestDir::new("job-log");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        insert_job(&conn, "job-1", JobKind::Hash, &serde_json::json!({}), 0).expect("insert job");
+        insert_job(&conn, "job-2", JobKind::Verify, &serde_json::json!({}), 0).expect("insert job");
+        let schema = detect_schema_features(&conn).expect("detect");
+        let job = claim_scan_hash_job(&mut conn, &config, None, &WorkerControl::default(), &schema)
+            .expect("claim")
+            .expect("claimed job");
+        assert_eq!(job.id, "job-1");
+
+        append_job_log(&conn, &job.id, JobLogLevel::Warn, "a.bin: read error").expect("log");
+        append_job_log(&conn, &job.id, JobLogLevel::Error, "b.bin: PERMANENT: gone").expect("log");
+        append_job_log(&conn, "job-2", JobLogLevel::Info, "other job").expect("log");
+        finish_job(&mut conn, &config, &job.id, false, Some("hash failed")).expect("finish");
+
+        let entries = list_job_log_entries(&conn, "job-1").expect("list entries");
+        let lines = entries
+            .iter()
+            .map(|entry| (entry.level.as_str(), entry.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                ("warn", "a.bin: read error"),
+                ("error", "b.bin: PERMANENT: gone")
+            ]
+        );
+    }
+
+    #[test]
+    fn scheduled_scan_is_enqueued_once_per_interval() {
+        let dir = TestDir::new("scheduled-scan");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute_batch(
+            "
+            CREATE UNIQUE INDEX ix_jobs_single_active_scan_hash ON jobs((1))
+            WHERE lower(status) IN ('pending', 'running')
+              AND lower(kind) IN ('scan', 'hash', 'verify', 'dedup', 'migrate');
+            ",
+        )
+        .expect("create single-active index");
+        assert!(!maybe_enqueue_scheduled_scan(&mut conn, &config).expect("disabled"));
+
+        config.scan_schedule_interval_seconds = Some(3600);
+        assert!(maybe_enqueue_scheduled_scan(&mut conn, &config).expect("first run"));
+        assert!(!maybe_enqueue_scheduled_scan(&mut conn, &config).expect("not due yet"));
+
+        // Due again, but the first scan is still pending: nothing is inserted and the slot stays open.
+        conn.execute(
+            "UPDATE scheduled_jobs SET last_enqueued_at = datetime('now', '-2 hours')",
+            [],
+        )
+        .expect("backdate schedule");
+        assert!(!maybe_enqueue_scheduled_scan(&mut conn, &config).expect("blocked by active scan"));
+
+        conn.execute("UPDATE jobs SET status = 'completed'", [])
+            .expect("finish first scan");
+        assert!(maybe_enqueue_scheduled_scan(&mut conn, &config).expect("interval elapsed"));
+        let pending: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM jobs WHERE kind = 'scan' AND status = 'pending'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("count pending scans");
+        assert_eq!(pending, 1);
+    }
 }
@@ -0,0 +1,381 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+
+use crate::config::WorkerConfig;
+use crate::db::{open_connection, refresh_job_lease, CommitYielder, JobRecord};
+
+/// `report_parallelism` splits the grouping by the first hex digit of `content_hash`.
+const HASH_PREFIX_SHARDS: u8 = 16;
+
+#[derive(Debug, Default)]
+struct DedupCounters {
+    groups: i64,
+    members: i64,
+    reclaimable_bytes: i64,
+    pruned_groups: i64,
+}
+
+/// `content_hash` range `[start, end)` a grouping query is limited to; no `end` is unbounded.
+type HashRange = (Vec<u8>, Option<Vec<u8>>);
+
+#[derive(Debug)]
+struct DedupGroupRow {
+    hash_algorithm: String,
+    content_hash: Vec<u8>,
+    member_count: i64,
+    total_bytes: i64,
+    reclaimable_bytes: i64,
+}
+
+/// Rebuilds `dedup_groups` from hashed `library_files` rows.
+///
+/// Groups are upserted on `group_key` and stamped with the job id, so a rerun after an
+/// interrupted attempt converges on the same rows. Groups not touched by this run no longer
+/// have duplicates and are pruned at the end. With `report_parallelism` > 1 the grouping
+/// query runs per hash-prefix shard on reader threads; writes stay on this connection.
+pub fn run_dedup_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecord) -> Result<()> {
+    let fetch_batch_size = extract_optional_u64(&job.payload, "fetch_batch_size")
+        .map(|value| value.max(1) as usize)
+        .unwrap_or(config.hash_fetch_batch_size);
+
+    let mut counters = DedupCounters::default();
+    let mut yielder = CommitYielder::new(config);
+
+    if config.report_parallelism > 1 {
+        group_sharded(
+            conn,
+            config,
+            job,
+            fetch_batch_size,
+            &mut counters,
+            &mut yielder,
+        )?;
+    } else {
+        let full_range: HashRange = (Vec::new(), None);
+        let mut cursor: (String, Vec<u8>) = (String::new(), Vec::new());
+        loop {
+            let groups = fetch_dedup_groups(conn, fetch_batch_size, &cursor, &full_range)?;
+            let Some(last) = groups.last() else {
+                break;
+            };
+            cursor = (last.hash_algorithm.clone(), last.content_hash.clone());
+            store_group_batch(conn, config, job, &groups, &mut counters, &mut yielder)?;
+        }
+    }
+
+    counters.pruned_groups = conn.execute(
+        "DELETE FROM dedup_groups WHERE job_id IS NULL OR job_id <> ?1",
+        params![job.id],
+    )? as i64;
+
+    refresh_job_lease(conn, config, &job.id, counters.groups, 1.0)?;
+    tracing::info!(
+        job_id = %job.id,
+        groups = counters.groups,
+        members = counters.members,
+        reclaimable_bytes = counters.reclaimable_bytes,
+        pruned = counters.pruned_groups,
+        "dedup summary"
+    );
+    Ok(())
+}
+
+/// Hands shards out to `report_parallelism` reader threads, each with its own connection,
+/// and stores their batches as they arrive.
+fn group_sharded(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job: &JobRecord,
+    fetch_batch_size: usize,
+    counters: &mut DedupCounters,
+    yielder: &mut CommitYielder,
+) -> Result<()> {
+    let next_shard = AtomicU8::new(0);
+    let (sender, receiver) = mpsc::sync_channel(config.report_parallelism * 2);
+    thread::scope(|scope| {
+        for _ in 0..config.report_parallelism {
+            let sender = sender.clone();
+            let next_shard = &next_shard;
+            scope.spawn(move || {
+                if let Err(error) = group_shards(config, fetch_batch_size, next_shard, &sender) {
+                    let _ = sender.send(Err(error));
+                }
+            });
+        }
+        drop(sender);
+        // Returning early drops the receiver, which stops the readers at their next send.
+        for groups in receiver {
+            store_group_batch(conn, config, job, &groups?, counters, yielder)?;
+        }
+        Ok(())
+    })
+}
+
+fn group_shards(
+    config: &WorkerConfig,
+    fetch_batch_size: usize,
+    next_shard: &AtomicU8,
+    sender: &SyncSender<Result<Vec<DedupGroupRow>>>,
+) -> Result<()> {
+    let conn = open_connection(&config.database_path)?;
+    loop {
+        let shard = next_shard.fetch_add(1, Ordering::SeqCst);
+        if shard >= HASH_PREFIX_SHARDS {
+            return Ok(());
+        }
+        let range = shard_range(shard);
+        let mut cursor: (String, Vec<u8>) = (String::new(), Vec::new());
+        loop {
+            let groups = fetch_dedup_groups(&conn, fetch_batch_size, &cursor, &range)?;
+            let Some(last) = groups.last() else {
+                break;
+            };
+            cursor = (last.hash_algorithm.clone(), last.content_hash.clone());
+            if sender.send(Ok(groups)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn shard_range(shard: u8) -> HashRange {
+    let start = if shard == 0 {
+        Vec::new()
+    } else {
+        vec![shard << 4]
+    };
+    let end = (shard + 1 < HASH_PREFIX_SHARDS).then(|| vec![(shard + 1) << 4]);
+    (start, end)
+}
+
+fn store_group_batch(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job: &JobRecord,
+    groups: &[DedupGroupRow],
+    counters: &mut DedupCounters,
+    yielder: &mut CommitYielder,
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    for group in groups {
+        upsert_dedup_group(&tx, &job.id, group)?;
+        counters.groups += 1;
+        counters.members += group.member_count;
+        counters.reclaimable_bytes += group.reclaimable_bytes;
+    }
+    tx.commit()?;
+
+    refresh_job_lease(conn, config, &job.id, counters.groups, 0.0)?;
+    yielder.maybe_yield(conn)
+}
+
+fn fetch_dedup_groups(
+    conn: &Connection,
+    batch_size: usize,
+    cursor: &(String, Vec<u8>),
+    range: &HashRange,
+) -> Result<Vec<DedupGroupRow>> {
+    let mut statement = conn.prepare(
+        "
+        SELECT hash_algorithm, content_hash, COUNT(*), SUM(size_bytes), MIN(size_bytes)
+        FROM library_files
+        WHERE is_missing = 0
+          AND needs_hash = 0
+          AND hash_algorithm IS NOT NULL
+          AND content_hash IS NOT NULL
+          AND (hash_algorithm, content_hash) > (?1, ?2)
+          AND content_hash >= ?4
+          AND (?5 IS NULL OR content_hash < ?5)
+        GROUP BY hash_algorithm, content_hash
+        HAVING COUNT(*) > 1
+        ORDER BY hash_algorithm ASC, content_hash ASC
+        LIMIT ?3
+        ",
+    )?;
+    let query_params = params![cursor.0, cursor.1, batch_size as i64, range.0, range.1];
+    let rows = statement.query_map(query_params, |row| {
+        let total_bytes: i64 = row.get(3)?;
+        let min_bytes: i64 = row.get(4)?;
+        Ok(DedupGroupRow {
+            hash_algorithm: row.get(0)?,
+            content_hash: row.get(1)?,
+            member_count: row.get(2)?,
+            total_bytes,
+            reclaimable_bytes: total_bytes - min_bytes,
+        })
+    })?;
+
+    let mut groups = Vec::new();
+    for row in rows {
+        groups.push(row?);
+    }
+    Ok(groups)
+}
+
+fn upsert_dedup_group(conn: &Connection, job_id: &str, group: &DedupGroupRow) -> Result<()> {
+    conn.execute(
+        "
+        INSERT INTO dedup_groups(
+            group_key, hash_algorithm, content_hash, member_count, total_bytes,
+            reclaimable_bytes, job_id
+        )
+        VALUES (?1 || ':' || lower(hex(?2)), ?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT(group_key) DO UPDATE SET
+            member_count = excluded.member_count,
+            total_bytes = excluded.total_bytes,
+            reclaimable_bytes = excluded.reclaimable_bytes,
+            job_id = excluded.job_id,
+            updated_at = CURRENT_TIMESTAMP
+        ",
+        params![
+            group.hash_algorithm,
+            group.content_hash,
+            group.member_count,
+            group.total_bytes,
+            group.reclaimable_bytes,
+            job_id
+        ],
+    )?;
+    Ok(())
+}
+
+fn extract_optional_u64(payload: &Value, key: &str) -> Option<u64> {
+    payload.get(key).and_then(Value::as_u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::JobKind;
+    use crate::test_support::{create_worker_schema, insert_library, test_config, TestDir};
+
+    fn insert_file(
+        conn: &Connection,
+        library_id: i64,
+        relative_path: &str,
+        size_bytes: i64,
+        content_hash: &[u8],
+    ) {
+        conn.execute(
+            "
+            INSERT INTO library_files(
+                library_id, relative_path, size_bytes, mtime_ns, needs_hash,
+                hash_algorithm, content_hash
+            )
+            VALUES (?1, ?2, ?3, 0, 0, 'blake3', ?4)
+            ",
+            params![library_id, relative_path, size_bytes, content_hash],
+        )
+        .expect("insert file");
+    }
+
+    fn start_job(conn: &Connection, job_id: &str) -> JobRecord {
+        conn.execute(
+            "
+            INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at)
+            VALUES (?1, 'dedup', 'running', 'test-worker', datetime('now', '+300 seconds'))
+            ",
+            params![job_id],
+        )
+        .expect("insert job");
+        JobRecord {
+            id: job_id.to_string(),
+            kind: JobKind::Dedup,
+            payload: serde_json::json!({ "fetch_batch_size": 1 }),
+            priority: 0,
+        }
+    }
+
+    fn group_counts(conn: &Connection) -> Vec<(String, i64, i64)> {
+        let mut statement = conn
+            .prepare(
+                "SELECT group_key, member_count, reclaimable_bytes FROM dedup_groups ORDER BY group_key",
+            )
+            .expect("prepare");
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .expect("query groups");
+        rows.map(|row| row.expect("group row")).collect()
+    }
+
+    #[test]
+    fn dedup_job_materializes_groups_idempotently() {
+        let dir = TestDir::new("dedup-groups");
+        let libraries_root = dir.path().join("libraries");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &libraries_root.join("photos"));
+
+        insert_file(&conn, library_id, "a1.jpg", 100, &[0xaa]);
+        insert_file(&conn, library_id, "a2.jpg", 100, &[0xaa]);
+        insert_file(&conn, library_id, "a3.jpg", 100, &[0xaa]);
+        insert_file(&conn, library_id, "b1.jpg", 40, &[0xbb]);
+        insert_file(&conn, library_id, "b2.jpg", 40, &[0xbb]);
+        insert_file(&conn, library_id, "c1.jpg", 10, &[0xcc]);
+        insert_file(&conn, library_id, "a4.jpg", 100, &[0xaa]);
+        conn.execute(
+            "UPDATE library_files SET is_missing = 1 WHERE relative_path = 'a4.jpg'",
+            [],
+        )
+        .expect("mark missing");
+
+        let job = start_job(&conn, "job-1");
+        run_dedup_job(&mut conn, &config, &job).expect("first run");
+        let expected = vec![
+            ("blake3:aa".to_string(), 3, 200),
+            ("blake3:bb".to_string(), 2, 40),
+        ];
+        assert_eq!(group_counts(&conn), expected);
+
+        run_dedup_job(&mut conn, &config, &job).expect("rerun");
+        assert_eq!(group_counts(&conn), expected);
+
+        conn.execute(
+            "DELETE FROM library_files WHERE relative_path = 'b2.jpg'",
+            [],
+        )
+        .expect("drop duplicate");
+        let job = start_job(&conn, "job-2");
+        run_dedup_job(&mut conn, &config, &job).expect("second job");
+        assert_eq!(group_counts(&conn), vec![("blake3:aa".to_string(), 3, 200)]);
+    }
+
+    #[test]
+    fn sharded_grouping_matches_sequential_grouping() {
+        let dir = TestDir::new("dedup-sharded");
+        let libraries_root = dir.path().join("libraries");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = open_connection(&config.database_path).expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &libraries_root.join("photos"));
+
+        // Duplicates on both sides of every shard boundary, plus singletons.
+        for byte in (0u8..=255).step_by(7) {
+            insert_file(&conn, library_id, &format!("{byte}-a"), 10, &[byte, 1]);
+            insert_file(&conn, library_id, &format!("{byte}-b"), 10, &[byte, 1]);
+            insert_file(&conn, library_id, &format!("{byte}-c"), 10, &[byte, 2]);
+        }
+        for byte in [0x0f, 0x10, 0xef, 0xf0, 0xff] {
+            insert_file(&conn, library_id, &format!("edge-{byte}-a"), 5, &[byte]);
+            insert_file(&conn, library_id, &format!("edge-{byte}-b"), 5, &[byte]);
+        }
+
+        let job = start_job(&conn, "job-sequential");
+        run_dedup_job(&mut conn, &config, &job).expect("sequential run");
+        let sequential = group_counts(&conn);
+        assert_eq!(sequential.len(), 37 + 5);
+
+        conn.execute("DELETE FROM dedup_groups", [])
+            .expect("clear groups");
+        config.report_parallelism = 4;
+        let job = start_job(&conn, "job-sharded");
+        run_dedup_job(&mut conn, &config, &job).expect("sharded run");
+        assert_eq!(group_counts(&conn), sequential);
+    }
+}
@@ -0,0 +1,100 @@
+/// Values the worker writes to `error_code` columns. The strings are shared with the Python
+/// side (`_TERMINAL_ERROR_CODES`, API responses) and must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    WorkerFailure,
+    LeaseExpired,
+    ThumbLibraryOffline,
+    ThumbSourceTooLarge,
+    ThumbDirPermission,
+    ThumbDiskFull,
+    ThumbDecodeResource,
+    ThumbHeifUnsupported,
+    ThumbVideoFfmpegTerminal,
+    ThumbVideoFfmpegFailed,
+    ThumbPathPolicyRejected,
+    ThumbDecodeFailed,
+    ThumbGenerationFailed,
+    ThumbPermanentFailure,
+    ThumbCleanupFailed,
+    WalCheckpointModeForbidden,
+    WalCheckpointBusy,
+    WalCheckpointFailed,
+    DbIntegrityFailed,
+    DbMaintenanceFailed,
+}
+
+impl ErrorCode {
+    pub const ALL: [Self; 20] = [
+        Self::WorkerFailure,
+        Self::LeaseExpired,
+        Self::ThumbLibraryOffline,
+        Self::ThumbSourceTooLarge,
+        Self::ThumbDirPermission,
+        Self::ThumbDiskFull,
+        Self::ThumbDecodeResource,
+        Self::ThumbHeifUnsupported,
+        Self::ThumbVideoFfmpegTerminal,
+        Self::ThumbVideoFfmpegFailed,
+        Self::ThumbPathPolicyRejected,
+        Self::ThumbDecodeFailed,
+        Self::ThumbGenerationFailed,
+        Self::ThumbPermanentFailure,
+        Self::ThumbCleanupFailed,
+        Self::WalCheckpointModeForbidden,
+        Self::WalCheckpointBusy,
+        Self::WalCheckpointFailed,
+        Self::DbIntegrityFailed,
+        Self::DbMaintenanceFailed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::WorkerFailure => "WORKER_FAILURE",
+            Self::LeaseExpired => "LEASE_EXPIRED",
+            Self::ThumbLibraryOffline => "THUMB_LIBRARY_OFFLINE",
+            Self::ThumbSourceTooLarge => "THUMB_SOURCE_TOO_LARGE",
+            Self::ThumbDirPermission => "THUMB_DIR_PERMISSION",
+            Self::ThumbDiskFull => "THUMB_DISK_FULL",
+            Self::ThumbDecodeResource => "THUMB_DECODE_RESOURCE",
+            Self::ThumbHeifUnsupported => "THUMB_HEIF_UNSUPPORTED",
+            Self::ThumbVideoFfmpegTerminal => "THUMB_VIDEO_FFMPEG_TERMINAL",
+            Self::ThumbVideoFfmpegFailed => "THUMB_VIDEO_FFMPEG_FAILED",
+            Self::ThumbPathPolicyRejected => "THUMB_PATH_POLICY_REJECTED",
+            Self::ThumbDecodeFailed => "THUMB_DECODE_FAILED",
+            Self::ThumbGenerationFailed => "THUMB_GENERATION_FAILED",
+            Self::ThumbPermanentFailure => "THUMB_PERMANENT_FAILURE",
+            Self::ThumbCleanupFailed => "THUMB_CLEANUP_FAILED",
+            Self::WalCheckpointModeForbidden => "WAL_CHECKPOINT_MODE_FORBIDDEN",
+            Self::WalCheckpointBusy => "WAL_CHECKPOINT_BUSY",
+            Self::WalCheckpointFailed => "WAL_CHECKPOINT_FAILED",
+            Self::DbIntegrityFailed => "DB_INTEGRITY_FAILED",
+            Self::DbMaintenanceFailed => "DB_MAINTENANCE_FAILED",
+        }
+    }
+
+    /// Reads a stored code back; `None` for codes written by Python or older workers.
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|code| code.as_str() == raw.trim())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorCode;
+
+    #[test]
+    fn every_code_round_trips_through_its_string() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::parse(code.as_str()), Some(code));
+        }
+        assert_eq!(
+            ErrorCode::parse(" THUMB_DISK_FULL "),
+            Some(ErrorCode::ThumbDiskFull)
+        );
+        assert_eq!(ErrorCode::parse("thumb_disk_full"), None);
+        assert_eq!(ErrorCode::parse("SKIPPED_TOO_LARGE"), None);
+    }
+}
@@ -1,6 +1,9 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -11,9 +14,15 @@ use rusqlite::{params, Connection};
 use serde_json::Value;
 use sha2::{Digest, Sha256};
 
-use crate::config::{HashAlgorithm, WorkerConfig};
-use crate::db::{refresh_job_lease, JobRecord};
-use crate::path_safety::{resolve_root_under_libraries, validate_relative_path};
+use crate::config::{EffectiveLibraryConfig, HashAlgorithm, WorkerConfig};
+use crate::db::{
+    append_job_log, claim_migrate_candidates, claim_quick_hash_candidates,
+    get_library_io_rate_limit, has_hardlink, propagate_hardlink_hash, refresh_job_lease,
+    trip_circuit_breaker, CommitYielder, JobLogLevel, JobRecord, MigrateCandidate,
+};
+use crate::path_safety::{
+    find_unicode_variant, resolve_root_under_libraries, resolve_stored_relative_path,
+};
 
 #[derive(Debug)]
 struct HashCandidate {
@@ -23,6 +32,22 @@ struct HashCandidate {
     expected_mtime_ns: i64,
     hash_error_count: i64,
     root_path: String,
+    library_id: i64,
+    library_name: String,
+    /// Already hashed with another algorithm; claimed only by `rehash_if_algorithm_not`.
+    upgrade: bool,
+}
+
+#[derive(Debug)]
+struct VerifyCandidate {
+    id: i64,
+    relative_path: String,
+    expected_size: i64,
+    expected_mtime_ns: i64,
+    hash_algorithm: String,
+    content_hash: Vec<u8>,
+    hashed_at: String,
+    root_path: String,
 }
 
 #[derive(Debug, Default)]
@@ -32,22 +57,64 @@ struct HashCounters {
     requeued_files: i64,
     missing_files: i64,
     failed_files: i64,
+    permanently_failed_files: i64,
+    skipped_files: i64,
+    prefix_unique_files: i64,
+    upgraded_files: i64,
     bytes_hashed: i64,
 }
 
-pub fn run_hash_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecord) -> Result<()> {
+#[derive(Debug, Default)]
+struct VerifyCounters {
+    processed_files: i64,
+    matched_files: i64,
+    mismatched_files: i64,
+    requeued_files: i64,
+    missing_files: i64,
+    failed_files: i64,
+    bytes_read: i64,
+}
+
+#[derive(Default)]
+struct MigrateCounters {
+    processed_files: i64,
+    migrated_files: i64,
+    requeued_files: i64,
+    missing_files: i64,
+    failed_files: i64,
+    bytes_read: i64,
+}
+
+pub fn run_hash_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job: &JobRecord,
+    shutdown: &AtomicBool,
+) -> Result<()> {
     let max_files = extract_optional_u64(&job.payload, "max_files").map(|value| value as i64);
     let fetch_batch_size = extract_optional_u64(&job.payload, "fetch_batch_size")
         .map(|value| value.max(1) as usize)
         .unwrap_or(config.hash_fetch_batch_size);
 
-    let algorithm = extract_optional_string(&job.payload, "algorithm")
+    // Also re-hash rows whose stored digest uses a different algorithm, with the target one.
+    let upgrade_target = extract_optional_string(&job.payload, "rehash_if_algorithm_not")
         .map(|value| HashAlgorithm::parse(&value))
-        .transpose()?
-        .unwrap_or(config.hash_algorithm);
+        .transpose()?;
+    // An algorithm named by the job wins over `[libraries.<name>]` overrides.
+    let algorithm_override = match upgrade_target {
+        Some(target) => Some(target),
+        None => extract_optional_string(&job.payload, "algorithm")
+            .map(|value| HashAlgorithm::parse(&value))
+            .transpose()?,
+    };
+    let mut library_settings: HashMap<String, EffectiveLibraryConfig> = HashMap::new();
+
+    // One limiter per library so a `library_roots.io_rate_limit_mib_per_sec` override only
+    // throttles that library's reads.
+    let mut limiters: HashMap<i64, IoRateLimiter> = HashMap::new();
 
     let mut counters = HashCounters::default();
-    let mut limiter = IoRateLimiter::new(config.io_rate_limit_mib_per_sec);
+    let mut yielder = CommitYielder::new(config).with_shutdown(shutdown);
 
     loop {
         if let Some(limit) = max_files {
@@ -65,7 +132,13 @@ pub fn run_hash_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecor
         }
 
         let claim_token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
-        let candidates = claim_candidates(conn, config, current_batch_size, &claim_token)?;
+        let candidates = claim_candidates(
+            conn,
+            config,
+            current_batch_size,
+            &claim_token,
+            upgrade_target,
+        )?;
         if candidates.is_empty() {
             break;
         }
@@ -73,32 +146,712 @@ pub fn run_hash_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecor
         for candidate in candidates {
             counters.processed_files += 1;
 
-            match process_candidate(conn, config, &candidate, algorithm, &mut limiter)? {
+            let library = library_settings
+                .entry(candidate.library_name.clone())
+                .or_insert_with(|| config.effective_library_config(&candidate.library_name));
+            let algorithm = algorithm_override.unwrap_or(library.hash_algorithm);
+            let limiter = match limiters.entry(candidate.library_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(library_rate_limiter(conn, config, candidate.library_id)?)
+                }
+            };
+            match process_candidate(conn, config, &candidate, library, algorithm, limiter)? {
                 CandidateOutcome::Hashed(bytes_hashed) => {
                     counters.hashed_files += 1;
                     counters.bytes_hashed += bytes_hashed as i64;
+                    if candidate.upgrade {
+                        counters.upgraded_files += 1;
+                    }
                 }
                 CandidateOutcome::Requeued => counters.requeued_files += 1,
                 CandidateOutcome::Missing => counters.missing_files += 1,
-                CandidateOutcome::Failed => counters.failed_files += 1,
+                CandidateOutcome::Failed(message) => {
+                    counters.failed_files += 1;
+                    let line = format!("{}: {message}", candidate.relative_path);
+                    append_job_log(conn, &job.id, JobLogLevel::Warn, &line)?;
+                }
+                CandidateOutcome::PermanentlyFailed(message) => {
+                    counters.permanently_failed_files += 1;
+                    let line = format!("{}: {message}", candidate.relative_path);
+                    append_job_log(conn, &job.id, JobLogLevel::Error, &line)?;
+                }
+                CandidateOutcome::Skipped => counters.skipped_files += 1,
+                CandidateOutcome::PrefixUnique => counters.prefix_unique_files += 1,
+            }
+
+            if counters.processed_files % 64 == 0 {
+                refresh_job_lease(conn, config, &job.id, counters.processed_files, 0.0)?;
+            }
+        }
+
+        yielder.maybe_yield(conn)?;
+    }
+
+    refresh_job_lease(conn, config, &job.id, counters.processed_files, 1.0)?;
+    let summary = format!(
+        "hash summary processed={} hashed={} requeued={} missing={} failed={} permanently_failed={} skipped={} prefix_unique={} upgraded={} bytes_hashed={}",
+        counters.processed_files,
+        counters.hashed_files,
+        counters.requeued_files,
+        counters.missing_files,
+        counters.failed_files,
+        counters.permanently_failed_files,
+        counters.skipped_files,
+        counters.prefix_unique_files,
+        counters.upgraded_files,
+        counters.bytes_hashed
+    );
+    tracing::info!(
+        job_id = %job.id,
+        processed = counters.processed_files,
+        hashed = counters.hashed_files,
+        requeued = counters.requeued_files,
+        missing = counters.missing_files,
+        failed = counters.failed_files,
+        permanently_failed = counters.permanently_failed_files,
+        skipped = counters.skipped_files,
+        prefix_unique = counters.prefix_unique_files,
+        upgraded = counters.upgraded_files,
+        bytes_hashed = counters.bytes_hashed,
+        "hash summary"
+    );
+    append_job_log(conn, &job.id, JobLogLevel::Info, &summary)?;
+    Ok(())
+}
+
+pub fn run_verify_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecord) -> Result<()> {
+    let max_files = extract_optional_u64(&job.payload, "max_files").map(|value| value as i64);
+    let fetch_batch_size = extract_optional_u64(&job.payload, "fetch_batch_size")
+        .map(|value| value.max(1) as usize)
+        .unwrap_or(config.hash_fetch_batch_size);
+
+    let mut counters = VerifyCounters::default();
+    let mut limiter = IoRateLimiter::new(
+        config.io_rate_limit_mib_per_sec,
+        config.hash_io_burst_multiplier,
+    );
+    let mut yielder = CommitYielder::new(config);
+    let mut cursor: Option<(String, i64)> = None;
+
+    loop {
+        if let Some(limit) = max_files {
+            if counters.processed_files >= limit {
+                break;
+            }
+        }
+
+        let remaining = max_files
+            .map(|limit| (limit - counters.processed_files).max(0) as usize)
+            .unwrap_or(fetch_batch_size);
+        let current_batch_size = remaining.min(fetch_batch_size);
+        if current_batch_size == 0 {
+            break;
+        }
+
+        let candidates = fetch_verify_candidates(conn, current_batch_size, cursor.as_ref())?;
+        let Some(last) = candidates.last() else {
+            break;
+        };
+        cursor = Some((last.hashed_at.clone(), last.id));
+
+        for candidate in candidates {
+            counters.processed_files += 1;
+
+            match process_verify_candidate(conn, config, &candidate, &mut limiter)? {
+                VerifyOutcome::Matched(bytes_read) => {
+                    counters.matched_files += 1;
+                    counters.bytes_read += bytes_read as i64;
+                }
+                VerifyOutcome::Mismatched(bytes_read) => {
+                    counters.mismatched_files += 1;
+                    counters.bytes_read += bytes_read as i64;
+                }
+                VerifyOutcome::Requeued => counters.requeued_files += 1,
+                VerifyOutcome::Missing => counters.missing_files += 1,
+                VerifyOutcome::Failed => counters.failed_files += 1,
             }
 
             if counters.processed_files % 64 == 0 {
                 refresh_job_lease(conn, config, &job.id, counters.processed_files, 0.0)?;
             }
         }
+
+        yielder.maybe_yield(conn)?;
+    }
+
+    refresh_job_lease(conn, config, &job.id, counters.processed_files, 1.0)?;
+    tracing::info!(
+        job_id = %job.id,
+        processed = counters.processed_files,
+        matched = counters.matched_files,
+        mismatched = counters.mismatched_files,
+        requeued = counters.requeued_files,
+        missing = counters.missing_files,
+        failed = counters.failed_files,
+        bytes_read = counters.bytes_read,
+        "verify summary"
+    );
+    Ok(())
+}
+
+/// Re-hashes rows hashed with `from_algorithm` using `to_algorithm`. Files that changed since
+/// their last hash are re-queued for a normal hash instead; read failures leave the old
+/// digest in place.
+pub fn run_migrate_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job: &JobRecord,
+    shutdown: &AtomicBool,
+) -> Result<()> {
+    let Some(from_algorithm) = extract_optional_string(&job.payload, "from_algorithm") else {
+        bail!("migrate job payload requires from_algorithm");
+    };
+    let Some(to_algorithm) = extract_optional_string(&job.payload, "to_algorithm") else {
+        bail!("migrate job payload requires to_algorithm");
+    };
+    let from_algorithm = HashAlgorithm::parse(&from_algorithm)?;
+    let to_algorithm = HashAlgorithm::parse(&to_algorithm)?;
+    if from_algorithm == to_algorithm {
+        bail!("migrate job from_algorithm and to_algorithm must differ");
+    }
+    let max_files = extract_optional_u64(&job.payload, "max_files").map(|value| value as i64);
+    let fetch_batch_size = extract_optional_u64(&job.payload, "fetch_batch_size")
+        .map(|value| value.max(1) as usize)
+        .unwrap_or(config.hash_fetch_batch_size);
+
+    let mut counters = MigrateCounters::default();
+    let mut limiter = IoRateLimiter::new(
+        config.io_rate_limit_mib_per_sec,
+        config.hash_io_burst_multiplier,
+    );
+    let mut yielder = CommitYielder::new(config).with_shutdown(shutdown);
+    let mut last_id = 0_i64;
+
+    loop {
+        if let Some(limit) = max_files {
+            if counters.processed_files >= limit {
+                break;
+            }
+        }
+
+        let remaining = max_files
+            .map(|limit| (limit - counters.processed_files).max(0) as usize)
+            .unwrap_or(fetch_batch_size);
+        let current_batch_size = remaining.min(fetch_batch_size);
+        if current_batch_size == 0 {
+            break;
+        }
+
+        let claim_token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let candidates = claim_migrate_candidates(
+            conn,
+            config,
+            from_algorithm,
+            last_id,
+            current_batch_size,
+            &claim_token,
+        )?;
+        if candidates.is_empty() {
+            break;
+        }
+
+        for candidate in candidates {
+            counters.processed_files += 1;
+            last_id = candidate.id;
+
+            match migrate_candidate(
+                conn,
+                config,
+                &candidate,
+                from_algorithm,
+                to_algorithm,
+                &mut limiter,
+            )? {
+                MigrateOutcome::Migrated(bytes_read) => {
+                    counters.migrated_files += 1;
+                    counters.bytes_read += bytes_read as i64;
+                }
+                MigrateOutcome::Requeued => counters.requeued_files += 1,
+                MigrateOutcome::Missing => counters.missing_files += 1,
+                MigrateOutcome::Failed(message) => {
+                    counters.failed_files += 1;
+                    let line = format!("{}: {message}", candidate.relative_path);
+                    append_job_log(conn, &job.id, JobLogLevel::Warn, &line)?;
+                }
+            }
+
+            if counters.processed_files % 64 == 0 {
+                refresh_job_lease(conn, config, &job.id, counters.processed_files, 0.0)?;
+            }
+        }
+
+        yielder.maybe_yield(conn)?;
+    }
+
+    refresh_job_lease(conn, config, &job.id, counters.processed_files, 1.0)?;
+    let summary = format!(
+        "migrate summary from={} to={} processed={} migrated={} requeued={} missing={} failed={} bytes_read={}",
+        from_algorithm.as_db_value(),
+        to_algorithm.as_db_value(),
+        counters.processed_files,
+        counters.migrated_files,
+        counters.requeued_files,
+        counters.missing_files,
+        counters.failed_files,
+        counters.bytes_read
+    );
+    tracing::info!(
+        job_id = %job.id,
+        from = from_algorithm.as_db_value(),
+        to = to_algorithm.as_db_value(),
+        processed = counters.processed_files,
+        migrated = counters.migrated_files,
+        requeued = counters.requeued_files,
+        missing = counters.missing_files,
+        failed = counters.failed_files,
+        bytes_read = counters.bytes_read,
+        "migrate summary"
+    );
+    append_job_log(conn, &job.id, JobLogLevel::Info, &summary)?;
+    Ok(())
+}
+
+enum MigrateOutcome {
+    Migrated(u64),
+    Requeued,
+    Missing,
+    Failed(String),
+}
+
+/// A file that vanished mid-migrate counts as missing; any other stat error fails the row.
+fn migrate_stat_outcome(error: std::io::Error) -> MigrateOutcome {
+    if error.kind() == std::io::ErrorKind::NotFound {
+        MigrateOutcome::Missing
+    } else {
+        MigrateOutcome::Failed(error.to_string())
+    }
+}
+
+fn migrate_candidate(
+    conn: &Connection,
+    config: &WorkerConfig,
+    candidate: &MigrateCandidate,
+    from_algorithm: HashAlgorithm,
+    to_algorithm: HashAlgorithm,
+    limiter: &mut IoRateLimiter,
+) -> Result<MigrateOutcome> {
+    let release_claim = || {
+        conn.execute(
+            "
+            UPDATE library_files
+            SET hash_claim_token = NULL,
+                hash_claimed_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?1
+            ",
+            params![candidate.id],
+        )
+    };
+
+    // Like verify, migration leaves missing-marking and error counters to scan/hash jobs.
+    let path = match resolve_candidate_path(config, &candidate.root_path, &candidate.relative_path)
+    {
+        Ok(path) => path,
+        Err(error) => {
+            release_claim()?;
+            return Ok(MigrateOutcome::Failed(error.to_string()));
+        }
+    };
+    if !path.is_file() {
+        release_claim()?;
+        return Ok(MigrateOutcome::Missing);
     }
 
-    refresh_job_lease(conn, config, &job.id, counters.processed_files, 1.0)?;
-    println!(
-        "hash summary processed={} hashed={} requeued={} missing={} failed={} bytes_hashed={}",
-        counters.processed_files,
-        counters.hashed_files,
-        counters.requeued_files,
-        counters.missing_files,
-        counters.failed_files,
-        counters.bytes_hashed
-    );
+    let stat_before = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            release_claim()?;
+            return Ok(migrate_stat_outcome(error));
+        }
+    };
+    let (size_before, mtime_before, inode_before, device_before) = metadata_to_row(&stat_before)?;
+    if size_before != candidate.expected_size || mtime_before != candidate.expected_mtime_ns {
+        mark_requeue(
+            conn,
+            candidate.id,
+            size_before,
+            mtime_before,
+            inode_before,
+            device_before,
+        )?;
+        return Ok(MigrateOutcome::Requeued);
+    }
+
+    let (digest, bytes_read) = match compute_hash(
+        &path,
+        to_algorithm,
+        config.hash_read_chunk_bytes,
+        config.hash_small_file_threshold_bytes,
+        limiter,
+    ) {
+        Ok(value) => value,
+        Err(error) => {
+            release_claim()?;
+            return Ok(MigrateOutcome::Failed(format!("{error:#}")));
+        }
+    };
+
+    let stat_after = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(error) => {
+            release_claim()?;
+            return Ok(migrate_stat_outcome(error));
+        }
+    };
+    let (size_after, mtime_after, inode_after, device_after) = metadata_to_row(&stat_after)?;
+    if size_after != candidate.expected_size || mtime_after != candidate.expected_mtime_ns {
+        mark_requeue(
+            conn,
+            candidate.id,
+            size_after,
+            mtime_after,
+            inode_after,
+            device_after,
+        )?;
+        return Ok(MigrateOutcome::Requeued);
+    }
+
+    conn.execute(
+        "
+        UPDATE library_files
+        SET hash_algorithm = ?1,
+            content_hash = ?2,
+            hashed_at = CURRENT_TIMESTAMP,
+            verify_status = NULL,
+            verified_at = NULL,
+            hash_claim_token = NULL,
+            hash_claimed_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?3
+          AND hash_algorithm = ?4
+        ",
+        params![
+            to_algorithm.as_db_value(),
+            digest,
+            candidate.id,
+            from_algorithm.as_db_value()
+        ],
+    )?;
+    Ok(MigrateOutcome::Migrated(bytes_read))
+}
+
+fn fetch_verify_candidates(
+    conn: &Connection,
+    batch_size: usize,
+    cursor: Option<&(String, i64)>,
+) -> Result<Vec<VerifyCandidate>> {
+    let (cursor_hashed_at, cursor_id) = match cursor {
+        Some((hashed_at, id)) => (Some(hashed_at.as_str()), *id),
+        None => (None, 0),
+    };
+
+    let mut stmt = conn.prepare(
+        "
+        SELECT f.id, f.relative_path, f.size_bytes, f.mtime_ns, f.hash_algorithm, f.content_hash,
+               COALESCE(f.hashed_at, ''), r.root_path
+        FROM library_files f
+        JOIN library_roots r ON r.id = f.library_id
+        WHERE f.needs_hash = 0
+          AND f.is_missing = 0
+          AND f.content_hash IS NOT NULL
+          AND f.hash_algorithm IS NOT NULL
+          AND (
+            ?1 IS NULL
+            OR COALESCE(f.hashed_at, '') > ?1
+            OR (COALESCE(f.hashed_at, '') = ?1 AND f.id > ?2)
+          )
+        ORDER BY COALESCE(f.hashed_at, '') ASC, f.id ASC
+        LIMIT ?3
+        ",
+    )?;
+
+    let rows = stmt.query_map(
+        params![cursor_hashed_at, cursor_id, batch_size as i64],
+        |row| {
+            Ok(VerifyCandidate {
+                id: row.get::<_, i64>(0)?,
+                relative_path: row.get::<_, String>(1)?,
+                expected_size: row.get::<_, i64>(2)?,
+                expected_mtime_ns: row.get::<_, i64>(3)?,
+                hash_algorithm: row.get::<_, String>(4)?,
+                content_hash: row.get::<_, Vec<u8>>(5)?,
+                hashed_at: row.get::<_, String>(6)?,
+                root_path: row.get::<_, String>(7)?,
+            })
+        },
+    )?;
+
+    let mut candidates = Vec::new();
+    for row in rows {
+        candidates.push(row?);
+    }
+
+    Ok(candidates)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VerifyOutcome {
+    Matched(u64),
+    Mismatched(u64),
+    Requeued,
+    Missing,
+    Failed,
+}
+
+fn process_verify_candidate(
+    conn: &Connection,
+    config: &WorkerConfig,
+    candidate: &VerifyCandidate,
+    limiter: &mut IoRateLimiter,
+) -> Result<VerifyOutcome> {
+    // Verification never marks rows missing or bumps hash error counters;
+    // those transitions stay with the scan and hash jobs.
+    let Ok(algorithm) = HashAlgorithm::parse(&candidate.hash_algorithm) else {
+        return Ok(VerifyOutcome::Failed);
+    };
+    let Ok(path) = resolve_candidate_path(config, &candidate.root_path, &candidate.relative_path)
+    else {
+        return Ok(VerifyOutcome::Failed);
+    };
+    if !path.exists() || !path.is_file() {
+        return Ok(VerifyOutcome::Missing);
+    }
+
+    let Ok(stat_before) = fs::metadata(&path) else {
+        return Ok(VerifyOutcome::Failed);
+    };
+    let (size_before, mtime_before, inode_before, device_before) = metadata_to_row(&stat_before)?;
+    if size_before != candidate.expected_size || mtime_before != candidate.expected_mtime_ns {
+        mark_requeue(
+            conn,
+            candidate.id,
+            size_before,
+            mtime_before,
+            inode_before,
+            device_before,
+        )?;
+        return Ok(VerifyOutcome::Requeued);
+    }
+
+    let Ok((digest, bytes_read)) = compute_hash(
+        &path,
+        algorithm,
+        config.hash_read_chunk_bytes,
+        config.hash_small_file_threshold_bytes,
+        limiter,
+    ) else {
+        return Ok(VerifyOutcome::Failed);
+    };
+
+    let Ok(stat_after) = fs::metadata(&path) else {
+        return Ok(VerifyOutcome::Failed);
+    };
+    let (size_after, mtime_after, inode_after, device_after) = metadata_to_row(&stat_after)?;
+    if size_after != candidate.expected_size || mtime_after != candidate.expected_mtime_ns {
+        mark_requeue(
+            conn,
+            candidate.id,
+            size_after,
+            mtime_after,
+            inode_after,
+            device_after,
+        )?;
+        return Ok(VerifyOutcome::Requeued);
+    }
+
+    let matched = digest == candidate.content_hash;
+    conn.execute(
+        "
+        INSERT INTO hash_verifications(file_id, verified_at, matched, stored_hash, computed_hash)
+        VALUES (?1, CURRENT_TIMESTAMP, ?2, ?3, ?4)
+        ",
+        params![candidate.id, matched, candidate.content_hash, digest],
+    )?;
+
+    // A mismatch keeps the stored digest as the record of the expected content.
+    conn.execute(
+        "
+        UPDATE library_files
+        SET verify_status = ?1,
+            verified_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2
+        ",
+        params![if matched { "ok" } else { "mismatch" }, candidate.id],
+    )?;
+
+    if matched {
+        Ok(VerifyOutcome::Matched(bytes_read))
+    } else {
+        Ok(VerifyOutcome::Mismatched(bytes_read))
+    }
+}
+
+pub fn run_quick_hash_pass(conn: &mut Connection, config: &WorkerConfig) -> Result<usize> {
+    let Some(sample_bytes) = config.hash_quick_sample_bytes else {
+        return Ok(0);
+    };
+
+    let claim_token = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+    let candidates =
+        claim_quick_hash_candidates(conn, config, config.hash_fetch_batch_size, &claim_token)?;
+    let mut limiters: HashMap<i64, IoRateLimiter> = HashMap::new();
+
+    for candidate in &candidates {
+        let path =
+            match resolve_candidate_path(config, &candidate.root_path, &candidate.relative_path) {
+                Ok(path) => path,
+                Err(error) => {
+                    mark_failure(
+                        conn,
+                        config,
+                        candidate.id,
+                        candidate.hash_error_count,
+                        &format!("{error:#}"),
+                    )?;
+                    continue;
+                }
+            };
+        // Missing-marking belongs to scan and hash jobs; the screening pass only backs off.
+        if !path.exists() || !path.is_file() {
+            mark_deferred(conn, config, candidate.id)?;
+            continue;
+        }
+
+        let metadata = match fs::metadata(&path) {
+            Ok(meta) => meta,
+            Err(error) => {
+                mark_failure(
+                    conn,
+                    config,
+                    candidate.id,
+                    candidate.hash_error_count,
+                    &error.to_string(),
+                )?;
+                continue;
+            }
+        };
+        let (size_bytes, mtime_ns, inode, device) = metadata_to_row(&metadata)?;
+        if size_bytes != candidate.expected_size || mtime_ns != candidate.expected_mtime_ns {
+            mark_requeue(conn, candidate.id, size_bytes, mtime_ns, inode, device)?;
+            continue;
+        }
+
+        let limiter = match limiters.entry(candidate.library_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(library_rate_limiter(conn, config, candidate.library_id)?)
+            }
+        };
+        // Quick hashes are compared within a library, so they follow its algorithm override.
+        let algorithm = config
+            .effective_library_config(&candidate.library_name)
+            .hash_algorithm;
+        match compute_sampled_hash(&path, sample_bytes, algorithm, limiter) {
+            Ok(quick_hash) => store_quick_hash(conn, candidate.id, &quick_hash)?,
+            Err(error) => {
+                mark_failure(
+                    conn,
+                    config,
+                    candidate.id,
+                    candidate.hash_error_count,
+                    &error.to_string(),
+                )?;
+            }
+        }
+    }
+
+    Ok(candidates.len())
+}
+
+/// Records the prefix hash and reports whether any other live row shares the
+/// same `(size_bytes, prefix_hash)`. Unique rows are parked with
+/// `needs_hash = 0`; matching peers parked earlier are re-queued so the whole
+/// candidate group gets a full content hash.
+fn store_prefix_hash(
+    conn: &Connection,
+    file_id: i64,
+    size_bytes: i64,
+    prefix_hash: &[u8],
+) -> Result<bool> {
+    conn.execute(
+        "
+        UPDATE library_files
+        SET prefix_hash = ?1,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2
+        ",
+        params![prefix_hash, file_id],
+    )?;
+
+    let peers = conn.query_row(
+        "
+        SELECT COUNT(1)
+        FROM library_files
+        WHERE size_bytes = ?1
+          AND prefix_hash = ?2
+          AND is_missing = 0
+          AND id != ?3
+        ",
+        params![size_bytes, prefix_hash, file_id],
+        |row| row.get::<_, i64>(0),
+    )?;
+    if peers == 0 {
+        conn.execute(
+            "
+            UPDATE library_files
+            SET needs_hash = 0,
+                hash_retry_after = NULL,
+                hash_claim_token = NULL,
+                hash_claimed_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?1
+            ",
+            params![file_id],
+        )?;
+        return Ok(false);
+    }
+
+    conn.execute(
+        "
+        UPDATE library_files
+        SET needs_hash = 1,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE size_bytes = ?1
+          AND prefix_hash = ?2
+          AND is_missing = 0
+          AND needs_hash = 0
+          AND content_hash IS NULL
+          AND id != ?3
+        ",
+        params![size_bytes, prefix_hash, file_id],
+    )?;
+    Ok(true)
+}
+
+fn store_quick_hash(conn: &Connection, file_id: i64, quick_hash: &[u8]) -> Result<()> {
+    conn.execute(
+        "
+        UPDATE library_files
+        SET quick_hash = ?1,
+            hash_claim_token = NULL,
+            hash_claimed_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2
+        ",
+        params![quick_hash, file_id],
+    )?;
     Ok(())
 }
 
@@ -107,31 +860,50 @@ fn claim_candidates(
     config: &WorkerConfig,
     batch_size: usize,
     claim_token: &str,
+    upgrade_target: Option<HashAlgorithm>,
 ) -> Result<Vec<HashCandidate>> {
     let claim_expiry = format!("-{} seconds", config.hash_claim_ttl_seconds);
 
+    // Upgrade rows that failed or were skipped keep hash_last_error and are left alone,
+    // so a job cannot keep re-claiming them. Libraries behind a tripped circuit breaker
+    // are skipped until `reset_after`.
     let mut candidate_ids = Vec::new();
     {
         let mut stmt = conn.prepare(
             "
-            SELECT id
-            FROM library_files
-            WHERE needs_hash = 1
-              AND is_missing = 0
-              AND (hash_retry_after IS NULL OR datetime(hash_retry_after) <= CURRENT_TIMESTAMP)
+            SELECT f.id
+            FROM library_files f
+            LEFT JOIN library_circuit_breaker b ON b.library_id = f.library_id
+            WHERE (
+                f.needs_hash = 1
+                OR (
+                    ?3 IS NOT NULL
+                    AND f.content_hash IS NOT NULL
+                    AND f.hash_algorithm IS NOT ?3
+                    AND f.hash_last_error IS NULL
+                )
+              )
+              AND f.is_missing = 0
+              AND (f.hash_retry_after IS NULL OR datetime(f.hash_retry_after) <= CURRENT_TIMESTAMP)
               AND (
-                hash_claim_token IS NULL
-                OR hash_claimed_at IS NULL
-                OR datetime(hash_claimed_at) <= datetime('now', ?1)
+                f.hash_claim_token IS NULL
+                OR f.hash_claimed_at IS NULL
+                OR datetime(f.hash_claimed_at) <= datetime('now', ?1)
               )
-            ORDER BY id ASC
+              AND (b.library_id IS NULL OR datetime(b.reset_after) <= CURRENT_TIMESTAMP)
+            ORDER BY f.id ASC
             LIMIT ?2
             ",
         )?;
 
-        let rows = stmt.query_map(params![claim_expiry, batch_size as i64], |row| {
-            row.get::<_, i64>(0)
-        })?;
+        let rows = stmt.query_map(
+            params![
+                claim_expiry,
+                batch_size as i64,
+                upgrade_target.map(HashAlgorithm::as_db_value)
+            ],
+            |row| row.get::<_, i64>(0),
+        )?;
         for row in rows {
             candidate_ids.push(row?);
         }
@@ -161,7 +933,8 @@ fn claim_candidates(
 
     let mut stmt = conn.prepare(
         "
-        SELECT f.id, f.relative_path, f.size_bytes, f.mtime_ns, COALESCE(f.hash_error_count, 0), r.root_path
+        SELECT f.id, f.relative_path, f.size_bytes, f.mtime_ns, COALESCE(f.hash_error_count, 0), r.root_path,
+               r.name, f.needs_hash = 0, f.library_id
         FROM library_files f
         JOIN library_roots r ON r.id = f.library_id
         WHERE f.hash_claim_token = ?1
@@ -177,6 +950,9 @@ fn claim_candidates(
             expected_mtime_ns: row.get::<_, i64>(3)?,
             hash_error_count: row.get::<_, i64>(4)?,
             root_path: row.get::<_, String>(5)?,
+            library_name: row.get::<_, String>(6)?,
+            upgrade: row.get::<_, bool>(7)?,
+            library_id: row.get::<_, i64>(8)?,
         })
     })?;
 
@@ -192,20 +968,38 @@ enum CandidateOutcome {
     Hashed(u64),
     Requeued,
     Missing,
-    Failed,
+    /// Carries the error recorded in `hash_last_error`.
+    Failed(String),
+    /// Failed past `hash_max_error_count`; the file is no longer queued for hashing.
+    PermanentlyFailed(String),
+    Skipped,
+    PrefixUnique,
 }
 
 fn process_candidate(
     conn: &Connection,
     config: &WorkerConfig,
     candidate: &HashCandidate,
+    library: &EffectiveLibraryConfig,
     algorithm: HashAlgorithm,
     limiter: &mut IoRateLimiter,
 ) -> Result<CandidateOutcome> {
-    let path = resolve_candidate_path(config, &candidate.root_path, &candidate.relative_path)?;
+    let path = match resolve_candidate_path(config, &candidate.root_path, &candidate.relative_path)
+    {
+        Ok(path) => path,
+        Err(error) => {
+            return mark_failure(
+                conn,
+                config,
+                candidate.id,
+                candidate.hash_error_count,
+                &format!("{error:#}"),
+            );
+        }
+    };
 
     if !path.exists() || !path.is_file() {
-        conn.execute(
+        let marked = conn.execute(
             "
             UPDATE library_files
             SET is_missing = 1,
@@ -215,17 +1009,28 @@ fn process_candidate(
                 hash_retry_after = NULL,
                 updated_at = CURRENT_TIMESTAMP
             WHERE id = ?1
+              AND pinned = 0
             ",
             params![candidate.id],
         )?;
+        if marked == 0 {
+            // Pinned rows stay present and queued until their storage comes back.
+            mark_deferred(conn, config, candidate.id)?;
+            return Ok(CandidateOutcome::Skipped);
+        }
         return Ok(CandidateOutcome::Missing);
     }
 
     let stat_before = match fs::metadata(&path) {
         Ok(meta) => meta,
         Err(error) => {
-            mark_failure(conn, config, candidate, &error.to_string())?;
-            return Ok(CandidateOutcome::Failed);
+            return mark_failure(
+                conn,
+                config,
+                candidate.id,
+                candidate.hash_error_count,
+                &error.to_string(),
+            );
         }
     };
 
@@ -233,7 +1038,7 @@ fn process_candidate(
     if size_before != candidate.expected_size || mtime_before != candidate.expected_mtime_ns {
         mark_requeue(
             conn,
-            candidate,
+            candidate.id,
             size_before,
             mtime_before,
             inode_before,
@@ -242,20 +1047,85 @@ fn process_candidate(
         return Ok(CandidateOutcome::Requeued);
     }
 
-    let (digest, bytes_hashed) =
-        match compute_hash(&path, algorithm, config.hash_read_chunk_bytes, limiter) {
+    if library
+        .max_size_bytes
+        .is_some_and(|limit| stat_before.len() > limit)
+    {
+        mark_skipped(conn, candidate.id, "SKIPPED_TOO_LARGE")?;
+        return Ok(CandidateOutcome::Skipped);
+    }
+    if library
+        .min_size_bytes
+        .is_some_and(|limit| stat_before.len() < limit)
+    {
+        mark_skipped(conn, candidate.id, "SKIPPED_TOO_SMALL")?;
+        return Ok(CandidateOutcome::Skipped);
+    }
+
+    if let Some(sample_bytes) = config.hash_quick_sample_bytes {
+        // A job-level algorithm would mix digests with the quick-hash pass's; use the library's.
+        match compute_sampled_hash(&path, sample_bytes, library.hash_algorithm, limiter) {
+            Ok(quick_hash) => store_quick_hash(conn, candidate.id, &quick_hash)?,
+            Err(error) => {
+                return mark_failure(
+                    conn,
+                    config,
+                    candidate.id,
+                    candidate.hash_error_count,
+                    &error.to_string(),
+                );
+            }
+        }
+    }
+
+    // Upgrade candidates already have a full digest; parking them as prefix-unique would drop it.
+    if let Some(prefix_bytes) = config.hash_prefix_bytes.filter(|_| !candidate.upgrade) {
+        let prefix_hash = match compute_prefix_hash(&path, prefix_bytes, algorithm, limiter) {
             Ok(value) => value,
             Err(error) => {
-                mark_failure(conn, config, candidate, &error.to_string())?;
-                return Ok(CandidateOutcome::Failed);
+                return mark_failure(
+                    conn,
+                    config,
+                    candidate.id,
+                    candidate.hash_error_count,
+                    &error.to_string(),
+                );
             }
         };
+        if !store_prefix_hash(conn, candidate.id, candidate.expected_size, &prefix_hash)? {
+            return Ok(CandidateOutcome::PrefixUnique);
+        }
+    }
+
+    let (digest, bytes_hashed) = match compute_hash(
+        &path,
+        algorithm,
+        config.hash_read_chunk_bytes,
+        config.hash_small_file_threshold_bytes,
+        limiter,
+    ) {
+        Ok(value) => value,
+        Err(error) => {
+            return mark_failure(
+                conn,
+                config,
+                candidate.id,
+                candidate.hash_error_count,
+                &error.to_string(),
+            );
+        }
+    };
 
     let stat_after = match fs::metadata(&path) {
         Ok(meta) => meta,
         Err(error) => {
-            mark_failure(conn, config, candidate, &error.to_string())?;
-            return Ok(CandidateOutcome::Failed);
+            return mark_failure(
+                conn,
+                config,
+                candidate.id,
+                candidate.hash_error_count,
+                &error.to_string(),
+            );
         }
     };
 
@@ -263,7 +1133,7 @@ fn process_candidate(
     if size_after != candidate.expected_size || mtime_after != candidate.expected_mtime_ns {
         mark_requeue(
             conn,
-            candidate,
+            candidate.id,
             size_after,
             mtime_after,
             inode_after,
@@ -282,6 +1152,8 @@ fn process_candidate(
             hashed_size_bytes = ?3,
             hashed_mtime_ns = ?4,
             hashed_at = CURRENT_TIMESTAMP,
+            verify_status = NULL,
+            verified_at = NULL,
             hash_error_count = 0,
             hash_last_error = NULL,
             hash_last_error_at = NULL,
@@ -299,6 +1171,9 @@ fn process_candidate(
             candidate.id
         ],
     )?;
+    if has_hardlink(conn, candidate.id)? {
+        propagate_hardlink_hash(conn, candidate.id)?;
+    }
 
     Ok(CandidateOutcome::Hashed(bytes_hashed))
 }
@@ -308,10 +1183,19 @@ fn resolve_candidate_path(
     root_path: &str,
     relative_path: &str,
 ) -> Result<PathBuf> {
-    let root =
-        resolve_root_under_libraries(&config.libraries_root_real, &PathBuf::from(root_path))?;
-    let relative = validate_relative_path(relative_path)?;
-    let candidate = root.join(relative);
+    let root = resolve_root_under_libraries(
+        &config.libraries_roots_real,
+        &PathBuf::from(root_path),
+        config.allow_symlinked_roots,
+    )?;
+    let relative = resolve_stored_relative_path(&root, relative_path)?;
+    let mut candidate = root.join(&relative);
+    // Rows stored under scan_normalize_unicode hold the NFC form of an NFD name.
+    if !candidate.exists() && config.scan_normalize_unicode {
+        if let Some(variant) = find_unicode_variant(&root, &relative) {
+            candidate = variant;
+        }
+    }
 
     if candidate.exists() {
         let real_candidate = candidate.canonicalize().with_context(|| {
@@ -328,7 +1212,7 @@ fn resolve_candidate_path(
 
 fn mark_requeue(
     conn: &Connection,
-    candidate: &HashCandidate,
+    file_id: i64,
     size_bytes: i64,
     mtime_ns: i64,
     inode: Option<i64>,
@@ -345,9 +1229,13 @@ fn mark_requeue(
             needs_hash = 1,
             hash_algorithm = NULL,
             content_hash = NULL,
+            quick_hash = NULL,
+            prefix_hash = NULL,
             hashed_size_bytes = NULL,
             hashed_mtime_ns = NULL,
             hashed_at = NULL,
+            verify_status = NULL,
+            verified_at = NULL,
             hash_error_count = 0,
             hash_last_error = NULL,
             hash_last_error_at = NULL,
@@ -357,29 +1245,80 @@ fn mark_requeue(
             updated_at = CURRENT_TIMESTAMP
         WHERE id = ?5
         ",
-        params![size_bytes, mtime_ns, inode, device, candidate.id],
+        params![size_bytes, mtime_ns, inode, device, file_id],
+    )?;
+    Ok(())
+}
+
+/// Releases the claim and backs the row off for `hash_retry_base_seconds` without
+/// counting an error or touching `is_missing`.
+fn mark_deferred(conn: &Connection, config: &WorkerConfig, file_id: i64) -> Result<()> {
+    conn.execute(
+        "
+        UPDATE library_files
+        SET hash_retry_after = datetime('now', ?2),
+            hash_claim_token = NULL,
+            hash_claimed_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?1
+        ",
+        params![
+            file_id,
+            format!("+{} seconds", config.hash_retry_base_seconds)
+        ],
+    )?;
+    Ok(())
+}
+
+/// Terminal for the current size/mtime: the next scan re-queues the row only if
+/// the file changes.
+fn mark_skipped(conn: &Connection, file_id: i64, reason: &str) -> Result<()> {
+    conn.execute(
+        "
+        UPDATE library_files
+        SET needs_hash = 0,
+            hash_last_error = ?2,
+            hash_last_error_at = CURRENT_TIMESTAMP,
+            hash_retry_after = NULL,
+            hash_claim_token = NULL,
+            hash_claimed_at = NULL,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?1
+        ",
+        params![file_id, reason],
     )?;
     Ok(())
 }
 
+/// Records a hash failure and schedules a retry, or gives up once the error count passes
+/// `hash_max_error_count`: `needs_hash = 0`, no `hash_retry_after`, `PERMANENT:` message.
 fn mark_failure(
     conn: &Connection,
     config: &WorkerConfig,
-    candidate: &HashCandidate,
+    file_id: i64,
+    hash_error_count: i64,
     message: &str,
-) -> Result<()> {
-    let next_error_count = candidate.hash_error_count.saturating_add(1);
-    let retry_seconds = calculate_retry_delay_seconds(
-        config.hash_retry_base_seconds,
-        config.hash_retry_max_seconds,
-        next_error_count as u64,
-    );
-    let retry_modifier = format!("+{} seconds", retry_seconds);
+) -> Result<CandidateOutcome> {
+    let next_error_count = hash_error_count.saturating_add(1);
+    let permanent = next_error_count > config.hash_max_error_count;
+    let (last_error, retry_modifier) = if permanent {
+        (format!("PERMANENT: {message}"), None)
+    } else {
+        let retry_seconds = calculate_retry_delay_seconds(
+            config.hash_retry_base_seconds,
+            config.hash_retry_max_seconds,
+            next_error_count as u64,
+        );
+        (
+            message.to_string(),
+            Some(format!("+{retry_seconds} seconds")),
+        )
+    };
 
     conn.execute(
         "
         UPDATE library_files
-        SET needs_hash = 1,
+        SET needs_hash = ?5,
             hash_error_count = ?1,
             hash_last_error = ?2,
             hash_last_error_at = CURRENT_TIMESTAMP,
@@ -389,9 +1328,55 @@ fn mark_failure(
             updated_at = CURRENT_TIMESTAMP
         WHERE id = ?4
         ",
-        params![next_error_count, message, retry_modifier, candidate.id],
+        params![
+            next_error_count,
+            last_error,
+            retry_modifier,
+            file_id,
+            !permanent
+        ],
     )?;
+    check_circuit_breaker(conn, config, file_id)?;
 
+    Ok(if permanent {
+        CandidateOutcome::PermanentlyFailed(last_error)
+    } else {
+        CandidateOutcome::Failed(last_error)
+    })
+}
+
+/// Trips the library's breaker once more than `circuit_breaker_failure_threshold` of its
+/// files failed within `60 * threshold` seconds, which usually means the mount is gone.
+fn check_circuit_breaker(conn: &Connection, config: &WorkerConfig, file_id: i64) -> Result<()> {
+    let threshold = config.circuit_breaker_failure_threshold;
+    if threshold == 0 {
+        return Ok(());
+    }
+    let window_seconds = 60 * u64::from(threshold);
+    let (library_id, failure_count): (i64, i64) = conn.query_row(
+        "
+        SELECT f.library_id, COUNT(recent.id)
+        FROM library_files f
+        LEFT JOIN library_files recent
+          ON recent.library_id = f.library_id
+         AND datetime(recent.hash_last_error_at) >= datetime('now', ?2)
+         AND COALESCE(recent.hash_last_error, '') NOT LIKE 'SKIPPED!_%' ESCAPE '!'
+        WHERE f.id = ?1
+        GROUP BY f.library_id
+        ",
+        params![file_id, format!("-{window_seconds} seconds")],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if failure_count <= i64::from(threshold) {
+        return Ok(());
+    }
+    trip_circuit_breaker(conn, library_id, failure_count, window_seconds)?;
+    tracing::warn!(
+        library_id,
+        failure_count,
+        reset_after_seconds = window_seconds,
+        "circuit breaker tripped, pausing hashing for library"
+    );
     Ok(())
 }
 
@@ -399,11 +1384,24 @@ fn compute_hash(
     path: &PathBuf,
     algorithm: HashAlgorithm,
     chunk_size: usize,
+    small_file_threshold: u64,
     limiter: &mut IoRateLimiter,
 ) -> Result<(Vec<u8>, u64)> {
     let mut file = fs::File::open(path)
         .with_context(|| format!("failed to open file for hashing: {}", path.display()))?;
 
+    // Small files are read in one call; the limiter is skipped because their
+    // share of the IO budget is negligible next to the per-chunk overhead.
+    let file_size = file
+        .metadata()
+        .with_context(|| format!("failed to stat file for hashing: {}", path.display()))?
+        .len();
+    if file_size < small_file_threshold {
+        let mut content = Vec::with_capacity(file_size as usize);
+        file.read_to_end(&mut content)?;
+        return Ok((digest_bytes(algorithm, &content), content.len() as u64));
+    }
+
     let mut buffer = vec![0_u8; chunk_size];
     let mut total_bytes = 0_u64;
 
@@ -437,6 +1435,64 @@ fn compute_hash(
     }
 }
 
+/// Digests exactly the first `prefix_bytes` of a file (or the whole file when
+/// shorter), metered through the IO limiter.
+fn compute_prefix_hash(
+    path: &Path,
+    prefix_bytes: u64,
+    algorithm: HashAlgorithm,
+    limiter: &mut IoRateLimiter,
+) -> Result<Vec<u8>> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("failed to open file for prefix hashing: {}", path.display()))?;
+    let mut content = Vec::new();
+    file.take(prefix_bytes)
+        .read_to_end(&mut content)
+        .with_context(|| format!("failed to read file prefix: {}", path.display()))?;
+    limiter.consume(content.len());
+    Ok(digest_bytes(algorithm, &content))
+}
+
+/// Digests the head, middle and tail `sample_bytes` of a file. Files no larger
+/// than three samples are digested whole. Every read is metered through `limiter`.
+fn compute_sampled_hash(
+    path: &Path,
+    sample_bytes: u64,
+    algorithm: HashAlgorithm,
+    limiter: &mut IoRateLimiter,
+) -> Result<Vec<u8>> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("failed to open file for quick hashing: {}", path.display()))?;
+    let file_size = file
+        .metadata()
+        .with_context(|| format!("failed to stat file for quick hashing: {}", path.display()))?
+        .len();
+
+    let mut samples = Vec::new();
+    if file_size <= sample_bytes.saturating_mul(3) {
+        file.take(file_size).read_to_end(&mut samples)?;
+        limiter.consume(samples.len());
+    } else {
+        let offsets = [0, (file_size - sample_bytes) / 2, file_size - sample_bytes];
+        let mut buffer = vec![0_u8; sample_bytes as usize];
+        for offset in offsets {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut buffer)?;
+            limiter.consume(buffer.len());
+            samples.extend_from_slice(&buffer);
+        }
+    }
+
+    Ok(digest_bytes(algorithm, &samples))
+}
+
+fn digest_bytes(algorithm: HashAlgorithm, content: &[u8]) -> Vec<u8> {
+    match algorithm {
+        HashAlgorithm::Blake3 => blake3::hash(content).as_bytes().to_vec(),
+        HashAlgorithm::Sha256 => Sha256::digest(content).to_vec(),
+    }
+}
+
 fn calculate_retry_delay_seconds(base_seconds: u64, max_seconds: u64, error_count: u64) -> u64 {
     let capped_power = error_count.saturating_sub(1).min(10);
     let delay = base_seconds.saturating_mul(1_u64 << capped_power);
@@ -462,7 +1518,7 @@ fn metadata_to_row(metadata: &fs::Metadata) -> Result<(i64, i64, Option<i64>, Op
     let mtime_ns = metadata
         .mtime()
         .saturating_mul(1_000_000_000)
-        .saturating_add(i64::from(metadata.mtime_nsec()));
+        .saturating_add(metadata.mtime_nsec());
     let inode = Some(i64::try_from(metadata.ino()).context("inode over i64 range")?);
     let device = Some(i64::try_from(metadata.dev()).context("device over i64 range")?);
     Ok((size_bytes, mtime_ns, inode, device))
@@ -481,18 +1537,42 @@ fn metadata_to_row(metadata: &fs::Metadata) -> Result<(i64, i64, Option<i64>, Op
     Ok((size_bytes, mtime_ns, None, None))
 }
 
+fn library_rate_limiter(
+    conn: &Connection,
+    config: &WorkerConfig,
+    library_id: i64,
+) -> Result<IoRateLimiter> {
+    let mib_per_sec =
+        get_library_io_rate_limit(conn, library_id)?.or(config.io_rate_limit_mib_per_sec);
+    Ok(IoRateLimiter::new(
+        mib_per_sec,
+        config.hash_io_burst_multiplier,
+    ))
+}
+
+/// Token bucket over read bytes. Tokens refill at the configured rate up to `bucket_capacity`
+/// (`hash_io_burst_multiplier` seconds of budget), so an idle gap earns at most that burst.
 struct IoRateLimiter {
     bytes_per_second: Option<f64>,
-    window_start: Instant,
-    bytes_in_window: u64,
+    bucket_capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
 }
 
 impl IoRateLimiter {
-    fn new(mib_per_sec: Option<u64>) -> Self {
+    fn new(mib_per_sec: Option<u64>, burst_multiplier: f64) -> Self {
+        Self::with_bytes_per_second(
+            mib_per_sec.map(|mib| mib.saturating_mul(1024 * 1024) as f64),
+            burst_multiplier,
+        )
+    }
+
+    fn with_bytes_per_second(bytes_per_second: Option<f64>, burst_multiplier: f64) -> Self {
         Self {
-            bytes_per_second: mib_per_sec.map(|mib| (mib * 1024 * 1024) as f64),
-            window_start: Instant::now(),
-            bytes_in_window: 0,
+            bytes_per_second,
+            bucket_capacity: bytes_per_second.unwrap_or(0.0) * burst_multiplier,
+            tokens: 0.0,
+            last_refill: Instant::now(),
         }
     }
 
@@ -501,17 +1581,1077 @@ impl IoRateLimiter {
             return;
         };
 
-        self.bytes_in_window = self.bytes_in_window.saturating_add(bytes as u64);
-        let elapsed = self.window_start.elapsed().as_secs_f64();
-        let expected = (self.bytes_in_window as f64) / limit;
+        let now = Instant::now();
+        let refill = now.duration_since(self.last_refill).as_secs_f64() * limit;
+        self.tokens = (self.tokens + refill).min(self.bucket_capacity);
+        self.last_refill = now;
+
+        // A negative balance is paid back by sleeping; the next refill counts any oversleep.
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            thread::sleep(Duration::from_secs_f64(-self.tokens / limit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LibraryConfig;
+    use crate::db::{
+        has_runnable_quick_hash_work, reset_circuit_breaker, set_library_io_rate_limit, JobKind,
+    };
+    use crate::test_support::{create_worker_schema, insert_library, test_config, TestDir};
+
+    fn insert_hashed_file(
+        conn: &Connection,
+        library_id: i64,
+        path: &std::path::Path,
+        relative_path: &str,
+        content_hash: &[u8],
+    ) -> i64 {
+        let (size_bytes, mtime_ns, _, _) =
+            metadata_to_row(&fs::metadata(path).expect("stat file")).expect("metadata row");
+        conn.execute(
+            "
+            INSERT INTO library_files(
+                library_id, relative_path, size_bytes, mtime_ns, needs_hash,
+                hash_algorithm, content_hash, hashed_size_bytes, hashed_mtime_ns, hashed_at
+            )
+            VALUES (?1, ?2, ?3, ?4, 0, 'blake3', ?5, ?3, ?4, CURRENT_TIMESTAMP)
+            ",
+            params![
+                library_id,
+                relative_path,
+                size_bytes,
+                mtime_ns,
+                content_hash
+            ],
+        )
+        .expect("insert file");
+        conn.last_insert_rowid()
+    }
+
+    #[test]
+    fn verify_records_match_and_keeps_hash() {
+        let dir = TestDir::new("verify-match");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("photos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let file_path = library_root.join("a.bin");
+        fs::write(&file_path, b"stable content").expect("write file");
+
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &library_root);
+        let stored = blake3::hash(b"stable content").as_bytes().to_vec();
+        let file_id = insert_hashed_file(&conn, library_id, &file_path, "a.bin", &stored);
+
+        let candidates = fetch_verify_candidates(&conn, 10, None).expect("fetch");
+        assert_eq!(candidates.len(), 1);
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let outcome =
+            process_verify_candidate(&conn, &config, &candidates[0], &mut limiter).expect("verify");
+        assert_eq!(outcome, VerifyOutcome::Matched(14));
+
+        let (needs_hash, content_hash): (bool, Option<Vec<u8>>) = conn
+            .query_row(
+                "SELECT needs_hash, content_hash FROM library_files WHERE id = ?1",
+                params![file_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("file row");
+        assert!(!needs_hash);
+        assert_eq!(content_hash, Some(stored));
+        let verify_status: Option<String> = conn
+            .query_row(
+                "SELECT verify_status FROM library_files WHERE id = ?1",
+                params![file_id],
+                |row| row.get(0),
+            )
+            .expect("verify status");
+        assert_eq!(verify_status.as_deref(), Some("ok"));
+
+        let matched: bool = conn
+            .query_row(
+                "SELECT matched FROM hash_verifications WHERE file_id = ?1",
+                params![file_id],
+                |row| row.get(0),
+            )
+            .expect("verification row");
+        assert!(matched);
+    }
+
+    #[test]
+    fn verify_mismatch_flags_row_and_keeps_hash() {
+        let dir = TestDir::new("verify-mismatch");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("photos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let file_path = library_root.join("a.bin");
+        fs::write(&file_path, b"rotted content").expect("write file");
+
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &library_root);
+        let stored = blake3::hash(b"original content").as_bytes().to_vec();
+        let file_id = insert_hashed_file(&conn, library_id, &file_path, "a.bin", &stored);
+
+        let candidates = fetch_verify_candidates(&conn, 10, None).expect("fetch");
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let outcome =
+            process_verify_candidate(&conn, &config, &candidates[0], &mut limiter).expect("verify");
+        assert_eq!(outcome, VerifyOutcome::Mismatched(14));
+
+        let (needs_hash, content_hash, verify_status, verified_at): (
+            bool,
+            Option<Vec<u8>>,
+            Option<String>,
+            Option<String>,
+        ) = conn
+            .query_row(
+                "SELECT needs_hash, content_hash, verify_status, verified_at FROM library_files WHERE id = ?1",
+                params![file_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("file row");
+        assert!(!needs_hash);
+        assert_eq!(content_hash, Some(stored.clone()));
+        assert_eq!(verify_status.as_deref(), Some("mismatch"));
+        assert!(verified_at.is_some());
+
+        let (matched, stored_hash, computed_hash): (bool, Vec<u8>, Vec<u8>) = conn
+            .query_row(
+                "SELECT matched, stored_hash, computed_hash FROM hash_verifications WHERE file_id = ?1",
+                params![file_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .expect("verification row");
+        assert!(!matched);
+        assert_eq!(stored_hash, stored);
+        assert_eq!(
+            computed_hash,
+            blake3::hash(b"rotted content").as_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn sampled_hash_reads_head_middle_and_tail() {
+        let dir = TestDir::new("quick-hash");
+        let content: Vec<u8> = (0..1000_u32).map(|value| (value % 251) as u8).collect();
+        let path = dir.path().join("large.bin");
+        fs::write(&path, &content).expect("write file");
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&content[0..100]);
+        expected.extend_from_slice(&content[450..550]);
+        expected.extend_from_slice(&content[900..1000]);
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let digest = compute_sampled_hash(&path, 100, HashAlgorithm::Blake3, &mut limiter)
+            .expect("quick hash");
+        assert_eq!(digest, blake3::hash(&expected).as_bytes().to_vec());
+
+        let whole = compute_sampled_hash(&path, 400, HashAlgorithm::Sha256, &mut limiter)
+            .expect("quick hash");
+        assert_eq!(whole, Sha256::digest(&content).to_vec());
+    }
+
+    #[test]
+    fn quick_hash_pass_leaves_missing_files_to_hash_jobs() {
+        let dir = TestDir::new("quick-hash-missing");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("videos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let file_path = library_root.join("present.bin");
+        fs::write(&file_path, vec![3_u8; 2048]).expect("write file");
+
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.hash_quick_sample_bytes = Some(256);
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "videos", &library_root);
+        let (size_bytes, mtime_ns, _, _) =
+            metadata_to_row(&fs::metadata(&file_path).expect("stat file")).expect("metadata row");
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'present.bin', ?2, ?3), (?1, 'gone.bin', 10, 10)",
+            params![library_id, size_bytes, mtime_ns],
+        )
+        .expect("insert files");
+
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, payload) VALUES ('scan-1', 'scan', 'running', '{}')",
+            [],
+        )
+        .expect("insert running scan");
+        assert!(!has_runnable_quick_hash_work(&conn, &config).expect("runnable"));
+        conn.execute("UPDATE jobs SET status = 'succeeded'", [])
+            .expect("finish scan");
+        assert!(has_runnable_quick_hash_work(&conn, &config).expect("runnable"));
+
+        // An open breaker hides the library's rows from the runnable check, as from the claim.
+        conn.execute(
+            "INSERT INTO library_circuit_breaker(library_id, tripped_at, reset_after, failure_count) VALUES (?1, CURRENT_TIMESTAMP, datetime('now', '+60 seconds'), 3)",
+            params![library_id],
+        )
+        .expect("trip breaker");
+        assert!(!has_runnable_quick_hash_work(&conn, &config).expect("runnable"));
+        assert!(reset_circuit_breaker(&conn, library_id).expect("reset"));
+        assert!(has_runnable_quick_hash_work(&conn, &config).expect("runnable"));
+
+        assert_eq!(
+            run_quick_hash_pass(&mut conn, &config).expect("quick pass"),
+            2
+        );
+        let rows: Vec<(String, bool, bool, bool, bool)> = conn
+            .prepare(
+                "SELECT relative_path, is_missing, needs_hash, quick_hash IS NOT NULL, hash_retry_after IS NOT NULL FROM library_files ORDER BY relative_path",
+            )
+            .expect("prepare")
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            rows,
+            vec![
+                ("gone.bin".to_string(), false, true, false, true),
+                ("present.bin".to_string(), false, true, true, false),
+            ]
+        );
+        assert!(!has_runnable_quick_hash_work(&conn, &config).expect("runnable"));
+    }
+
+    #[test]
+    fn absent_pinned_files_are_deferred_instead_of_marked_missing() {
+        let dir = TestDir::new("hash-pinned");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("videos");
+        fs::create_dir_all(&library_root).expect("create library");
+
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "videos", &library_root);
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns, pinned) VALUES (?1, 'gone.bin', 10, 10, 0), (?1, 'offline.bin', 10, 10, 1)",
+            params![library_id],
+        )
+        .expect("insert files");
+
+        let candidates = claim_candidates(&conn, &config, 10, "token-1", None).expect("claim");
+        assert_eq!(candidates.len(), 2);
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let outcomes: Vec<CandidateOutcome> = candidates
+            .iter()
+            .map(|candidate| {
+                process_candidate(
+                    &conn,
+                    &config,
+                    candidate,
+                    &config.effective_library_config(&candidate.library_name),
+                    HashAlgorithm::Blake3,
+                    &mut limiter,
+                )
+                .expect("process")
+            })
+            .collect();
+        assert!(matches!(outcomes[0], CandidateOutcome::Missing));
+        assert!(matches!(outcomes[1], CandidateOutcome::Skipped));
+
+        let rows: Vec<(String, bool, bool, i64)> = conn
+            .prepare(
+                "SELECT relative_path, is_missing, needs_hash, hash_error_count FROM library_files ORDER BY relative_path",
+            )
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            rows,
+            vec![
+                ("gone.bin".to_string(), true, false, 0),
+                ("offline.bin".to_string(), false, true, 0),
+            ]
+        );
+        assert!(claim_candidates(&conn, &config, 10, "token-2", None)
+            .expect("claim again")
+            .is_empty());
+    }
+
+    #[test]
+    fn rejected_relative_paths_fail_only_their_row() {
+        let dir = TestDir::new("hash-rejected-path");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("disk");
+        fs::create_dir_all(&library_root).expect("create library");
+        fs::write(library_root.join("a\\b.jpg"), b"literal name").expect("write file");
+
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "disk", &library_root);
+        let (size_bytes, mtime_ns, _, _) =
+            metadata_to_row(&fs::metadata(library_root.join("a\\b.jpg")).expect("stat file"))
+                .expect("metadata row");
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'C:notes.txt', 1, 1), (?1, 'a\\b.jpg', ?2, ?3)",
+            params![library_id, size_bytes, mtime_ns],
+        )
+        .expect("insert files");
+
+        let candidates = claim_candidates(&conn, &config, 10, "token-1", None).expect("claim");
+        assert_eq!(candidates.len(), 2);
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let outcomes: Vec<CandidateOutcome> = candidates
+            .iter()
+            .map(|candidate| {
+                process_candidate(
+                    &conn,
+                    &config,
+                    candidate,
+                    &config.effective_library_config(&candidate.library_name),
+                    HashAlgorithm::Blake3,
+                    &mut limiter,
+                )
+                .expect("process")
+            })
+            .collect();
+        assert!(
+            matches!(&outcomes[0], CandidateOutcome::Failed(message) if message.contains("drive"))
+        );
+        assert!(matches!(outcomes[1], CandidateOutcome::Hashed(_)));
+
+        let is_missing: bool = conn
+            .query_row(
+                "SELECT is_missing FROM library_files WHERE relative_path = 'a\\b.jpg'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("literal row");
+        assert!(!is_missing);
+    }
+
+    #[test]
+    fn small_file_path_matches_chunked_digest() {
+        let dir = TestDir::new("small-file");
+        let path = dir.path().join("small.bin");
+        fs::write(&path, b"tiny file body").expect("write file");
+
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let whole = compute_hash(&path, algorithm, 4, 1024, &mut limiter).expect("whole");
+            let chunked = compute_hash(&path, algorithm, 4, 0, &mut limiter).expect("chunked");
+            assert_eq!(whole, chunked);
+        }
+    }
+
+    #[test]
+    fn files_above_size_cap_are_skipped_without_retry() {
+        let dir = TestDir::new("hash-size-cap");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("videos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let file_path = library_root.join("movie.mp4");
+        fs::write(&file_path, vec![7_u8; 4096]).expect("write file");
+
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.hash_max_file_size_bytes = Some(1024);
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "videos", &library_root);
+        let (size_bytes, mtime_ns, _, _) =
+            metadata_to_row(&fs::metadata(&file_path).expect("stat file")).expect("metadata row");
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'movie.mp4', ?2, ?3)",
+            params![library_id, size_bytes, mtime_ns],
+        )
+        .expect("insert file");
+
+        let candidates = claim_candidates(&conn, &config, 10, "token-1", None).expect("claim");
+        assert_eq!(candidates.len(), 1);
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let outcome = process_candidate(
+            &conn,
+            &config,
+            &candidates[0],
+            &config.effective_library_config(&candidates[0].library_name),
+            HashAlgorithm::Blake3,
+            &mut limiter,
+        )
+        .expect("process");
+        assert!(matches!(outcome, CandidateOutcome::Skipped));
+
+        let (needs_hash, content_hash, last_error, error_count): (
+            bool,
+            Option<Vec<u8>>,
+            Option<String>,
+            i64,
+        ) = conn
+            .query_row(
+                "SELECT needs_hash, content_hash, hash_last_error, hash_error_count FROM library_files",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("file row");
+        assert!(!needs_hash);
+        assert_eq!(content_hash, None);
+        assert_eq!(last_error.as_deref(), Some("SKIPPED_TOO_LARGE"));
+        assert_eq!(error_count, 0);
+
+        assert!(claim_candidates(&conn, &config, 10, "token-2", None)
+            .expect("claim again")
+            .is_empty());
+    }
+
+    #[test]
+    fn library_hash_algorithm_override_applies_to_that_library_only() {
+        let dir = TestDir::new("hash-library-override");
+        let libraries_root = dir.path().join("libraries");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.libraries.insert(
+            "archive".to_string(),
+            LibraryConfig {
+                hash_algorithm: Some(HashAlgorithm::Sha256),
+                ..LibraryConfig::default()
+            },
+        );
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        for name in ["photos", "archive"] {
+            let library_root = libraries_root.join(name);
+            fs::create_dir_all(&library_root).expect("create library");
+            let file_path = library_root.join("same.bin");
+            fs::write(&file_path, b"identical bytes").expect("write file");
+            let library_id = insert_library(&conn, name, &library_root);
+            let (size_bytes, mtime_ns, _, _) =
+                metadata_to_row(&fs::metadata(&file_path).expect("stat")).expect("metadata row");
+            conn.execute(
+                "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'same.bin', ?2, ?3)",
+                params![library_id, size_bytes, mtime_ns],
+            )
+            .expect("insert file");
+        }
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at) VALUES ('job-1', 'hash', 'running', 'test-worker', datetime('now', '+300 seconds'))",
+            [],
+        )
+        .expect("insert job");
+        let job = JobRecord {
+            id: "job-1".to_string(),
+            kind: JobKind::Hash,
+            payload: Value::Null,
+            priority: 0,
+        };
+
+        run_hash_job(&mut conn, &config, &job, &AtomicBool::new(false)).expect("hash job");
+        let algorithms: Vec<(String, String)> = conn
+            .prepare(
+                "SELECT r.name, f.hash_algorithm FROM library_files f JOIN library_roots r ON r.id = f.library_id ORDER BY r.name",
+            )
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            algorithms,
+            vec![
+                ("archive".to_string(), "sha256".to_string()),
+                ("photos".to_string(), "blake3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quick_hashes_follow_the_library_algorithm() {
+        let dir = TestDir::new("quick-hash-library-algorithm");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("archive");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.hash_quick_sample_bytes = Some(1024);
+        config.libraries.insert(
+            "archive".to_string(),
+            LibraryConfig {
+                hash_algorithm: Some(HashAlgorithm::Sha256),
+                ..LibraryConfig::default()
+            },
+        );
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "archive", &library_root);
+        for name in ["a.bin", "b.bin"] {
+            let file_path = library_root.join(name);
+            fs::write(&file_path, b"identical bytes").expect("write file");
+            let (size_bytes, mtime_ns, _, _) =
+                metadata_to_row(&fs::metadata(&file_path).expect("stat")).expect("metadata row");
+            conn.execute(
+                "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, ?2, ?3, ?4)",
+                params![library_id, name, size_bytes, mtime_ns],
+            )
+            .expect("insert file");
+        }
+
+        // The pass screens a.bin; a job naming blake3 hashes b.bin.
+        config.hash_fetch_batch_size = 1;
+        assert_eq!(
+            run_quick_hash_pass(&mut conn, &config).expect("quick pass"),
+            1
+        );
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at) VALUES ('job-1', 'hash', 'running', 'test-worker', datetime('now', '+300 seconds'))",
+            [],
+        )
+        .expect("insert job");
+        let job = JobRecord {
+            id: "job-1".to_string(),
+            kind: JobKind::Hash,
+            payload: serde_json::json!({"algorithm": "blake3"}),
+            priority: 0,
+        };
+        run_hash_job(&mut conn, &config, &job, &AtomicBool::new(false)).expect("hash job");
+
+        let quick_hashes: Vec<Vec<u8>> = conn
+            .prepare("SELECT quick_hash FROM library_files ORDER BY relative_path")
+            .expect("prepare")
+            .query_map([], |row| row.get(0))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        let expected = Sha256::digest(b"identical bytes").to_vec();
+        assert_eq!(quick_hashes, vec![expected.clone(), expected]);
+    }
+
+    #[test]
+    fn library_io_rate_limit_override_throttles_only_that_library() {
+        let dir = TestDir::new("hash-library-rate-limit");
+        let libraries_root = dir.path().join("libraries");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.io_rate_limit_mib_per_sec = Some(1024);
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let mut library_ids = HashMap::new();
+        for name in ["nas", "ssd"] {
+            let library_root = libraries_root.join(name);
+            fs::create_dir_all(&library_root).expect("create library");
+            let file_path = library_root.join("big.bin");
+            fs::write(&file_path, vec![7u8; 512 * 1024]).expect("write file");
+            let library_id = insert_library(&conn, name, &library_root);
+            let (size_bytes, mtime_ns, _, _) =
+                metadata_to_row(&fs::metadata(&file_path).expect("stat")).expect("metadata row");
+            conn.execute(
+                "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'big.bin', ?2, ?3)",
+                params![library_id, size_bytes, mtime_ns],
+            )
+            .expect("insert file");
+            library_ids.insert(name, library_id);
+        }
+        set_library_io_rate_limit(&conn, library_ids["nas"], 1).expect("set override");
+        assert_eq!(
+            get_library_io_rate_limit(&conn, library_ids["nas"]).expect("nas limit"),
+            Some(1)
+        );
+        assert_eq!(
+            get_library_io_rate_limit(&conn, library_ids["ssd"]).expect("ssd limit"),
+            None
+        );
+        let mib = 1024.0 * 1024.0;
+        let limiter = |conn: &Connection, library_id| {
+            library_rate_limiter(conn, &config, library_id)
+                .expect("limiter")
+                .bytes_per_second
+        };
+        assert_eq!(limiter(&conn, library_ids["nas"]), Some(mib));
+        assert_eq!(limiter(&conn, library_ids["ssd"]), Some(1024.0 * mib));
+
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at) VALUES ('job-1', 'hash', 'running', 'test-worker', datetime('now', '+300 seconds'))",
+            [],
+        )
+        .expect("insert job");
+        let job = JobRecord {
+            id: "job-1".to_string(),
+            kind: JobKind::Hash,
+            payload: Value::Null,
+            priority: 0,
+        };
+        let started = Instant::now();
+        run_hash_job(&mut conn, &config, &job, &AtomicBool::new(false)).expect("hash job");
+        // 512 KiB at the 1 MiB/s override; the ssd file at 1 GiB/s adds well under that.
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(450), "took {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "took {elapsed:?}");
+        let hashed: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM library_files WHERE content_hash IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .expect("hashed count");
+        assert_eq!(hashed, 2);
+
+        set_library_io_rate_limit(&conn, library_ids["nas"], 0).expect("clear override");
+        assert_eq!(limiter(&conn, library_ids["nas"]), Some(1024.0 * mib));
+        assert!(set_library_io_rate_limit(&conn, 999, 5).is_err());
+    }
+
+    #[test]
+    fn migrate_job_rehashes_rows_from_one_algorithm_to_another() {
+        let dir = TestDir::new("hash-migrate");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("photos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &library_root);
+        for (name, algorithm) in [
+            ("a.bin", "sha256"),
+            ("b.bin", "sha256"),
+            ("c.bin", "sha256"),
+            ("modern.bin", "blake3"),
+        ] {
+            let path = library_root.join(name);
+            fs::write(&path, name.as_bytes()).expect("write file");
+            let id = insert_hashed_file(&conn, library_id, &path, name, b"old digest");
+            conn.execute(
+                "UPDATE library_files SET hash_algorithm = ?1 WHERE id = ?2",
+                params![algorithm, id],
+            )
+            .expect("set algorithm");
+        }
+        conn.execute(
+            "UPDATE library_files SET mtime_ns = mtime_ns - 1 WHERE relative_path = 'c.bin'",
+            [],
+        )
+        .expect("make c.bin stale");
+
+        let run = |conn: &mut Connection, job_id: &str, payload: Value| {
+            conn.execute(
+                "INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at) VALUES (?1, 'migrate', 'running', 'test-worker', datetime('now', '+300 seconds'))",
+                params![job_id],
+            )
+            .expect("insert job");
+            let job = JobRecord {
+                id: job_id.to_string(),
+                kind: JobKind::Migrate,
+                payload,
+                priority: 0,
+            };
+            run_migrate_job(conn, &config, &job, &AtomicBool::new(false))
+        };
+        let bad = serde_json::json!({"from_algorithm": "sha256", "to_algorithm": "sha256"});
+        assert!(run(&mut conn, "job-0", bad).is_err());
+        let payload = serde_json::json!({"from_algorithm": "sha256", "to_algorithm": "blake3"});
+        let mut limited = payload.clone();
+        limited["max_files"] = serde_json::json!(1);
+        run(&mut conn, "job-1", limited).expect("limited migrate job");
+        let sha256_left: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM library_files WHERE hash_algorithm = 'sha256'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("count");
+        assert_eq!(sha256_left, 2);
+        run(&mut conn, "job-2", payload).expect("migrate job");
+
+        let rows: Vec<(String, Option<String>, bool)> = conn
+            .prepare(
+                "SELECT relative_path, hash_algorithm, needs_hash FROM library_files ORDER BY relative_path",
+            )
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            rows,
+            vec![
+                ("a.bin".to_string(), Some("blake3".to_string()), false),
+                ("b.bin".to_string(), Some("blake3".to_string()), false),
+                ("c.bin".to_string(), None, true),
+                ("modern.bin".to_string(), Some("blake3".to_string()), false),
+            ]
+        );
+        for name in ["a.bin", "b.bin", "modern.bin"] {
+            let digest: Vec<u8> = conn
+                .query_row(
+                    "SELECT content_hash FROM library_files WHERE relative_path = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )
+                .expect("digest");
+            let expected = if name == "modern.bin" {
+                b"old digest".to_vec()
+            } else {
+                blake3::hash(name.as_bytes()).as_bytes().to_vec()
+            };
+            assert_eq!(digest, expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn migrate_job_moves_past_missing_files() {
+        let dir = TestDir::new("hash-migrate-missing");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("photos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &library_root);
+        for name in ["gone.bin", "kept.bin"] {
+            let path = library_root.join(name);
+            fs::write(&path, name.as_bytes()).expect("write file");
+            insert_hashed_file(&conn, library_id, &path, name, b"old digest");
+        }
+        conn.execute("UPDATE library_files SET hash_algorithm = 'sha256'", [])
+            .expect("set algorithm");
+        fs::remove_file(library_root.join("gone.bin")).expect("remove file");
+
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at) VALUES ('job-1', 'migrate', 'running', 'test-worker', datetime('now', '+300 seconds'))",
+            [],
+        )
+        .expect("insert job");
+        let job = JobRecord {
+            id: "job-1".to_string(),
+            kind: JobKind::Migrate,
+            payload: serde_json::json!({
+                "from_algorithm": "sha256",
+                "to_algorithm": "blake3",
+                "fetch_batch_size": 1
+            }),
+            priority: 0,
+        };
+        run_migrate_job(&mut conn, &config, &job, &AtomicBool::new(false)).expect("migrate job");
+
+        let rows: Vec<(String, String, bool)> = conn
+            .prepare(
+                "SELECT relative_path, hash_algorithm, is_missing FROM library_files ORDER BY relative_path",
+            )
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            rows,
+            vec![
+                ("gone.bin".to_string(), "sha256".to_string(), false),
+                ("kept.bin".to_string(), "blake3".to_string(), false),
+            ]
+        );
+        let summary: String = conn
+            .query_row(
+                "SELECT message FROM job_log_entries WHERE job_id = 'job-1' AND message LIKE 'migrate summary%'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("summary");
+        assert!(
+            summary.contains("processed=2 migrated=1 requeued=0 missing=1"),
+            "{summary}"
+        );
+    }
+
+    #[test]
+    fn failures_past_max_error_count_are_permanent() {
+        let dir = TestDir::new("hash-max-errors");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("disk");
+        fs::create_dir_all(&library_root).expect("create library");
+
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.hash_max_error_count = 2;
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "disk", &library_root);
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns, hash_error_count) VALUES (?1, 'bad-sector.bin', 1, 1, 1)",
+            params![library_id],
+        )
+        .expect("insert file");
+
+        let candidates = claim_candidates(&conn, &config, 10, "token-1", None).expect("claim");
+        let outcome =
+            mark_failure(&conn, &config, candidates[0].id, 1, "read error").expect("failure");
+        assert!(matches!(outcome, CandidateOutcome::Failed(_)));
+        conn.execute("UPDATE library_files SET hash_retry_after = NULL", [])
+            .expect("expire backoff");
+
+        let candidates = claim_candidates(&conn, &config, 10, "token-2", None).expect("claim");
+        assert_eq!(candidates.len(), 1);
+        let outcome =
+            mark_failure(&conn, &config, candidates[0].id, 2, "read error").expect("failure");
+        assert!(matches!(outcome, CandidateOutcome::PermanentlyFailed(_)));
+
+        let (needs_hash, last_error, retry_after, error_count): (
+            bool,
+            String,
+            Option<String>,
+            i64,
+        ) = conn
+            .query_row(
+                "SELECT needs_hash, hash_last_error, hash_retry_after, hash_error_count FROM library_files",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("file row");
+        assert!(!needs_hash);
+        assert_eq!(last_error, "PERMANENT: read error");
+        assert_eq!(retry_after, None);
+        assert_eq!(error_count, 3);
+        assert!(claim_candidates(&conn, &config, 10, "token-3", None)
+            .expect("claim again")
+            .is_empty());
+    }
+
+    #[test]
+    fn circuit_breaker_stops_claims_for_a_failing_library() {
+        let dir = TestDir::new("hash-circuit-breaker");
+        let libraries_root = dir.path().join("libraries");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.circuit_breaker_failure_threshold = 2;
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let offline = insert_library(&conn, "nfs", &libraries_root.join("nfs"));
+        let healthy = insert_library(&conn, "local", &libraries_root.join("local"));
+        for (library_id, name) in [
+            (offline, "a.bin"),
+            (offline, "b.bin"),
+            (offline, "c.bin"),
+            (offline, "d.bin"),
+            (healthy, "e.bin"),
+        ] {
+            conn.execute(
+                "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, ?2, 1, 1)",
+                params![library_id, name],
+            )
+            .expect("insert file");
+        }
+
+        let candidates = claim_candidates(&conn, &config, 3, "token-1", None).expect("claim");
+        assert_eq!(candidates.len(), 3);
+        for candidate in &candidates {
+            mark_failure(&conn, &config, candidate.id, 0, "stale file handle").expect("failure");
+        }
+        let failure_count: i64 = conn
+            .query_row(
+                "SELECT failure_count FROM library_circuit_breaker WHERE library_id = ?1",
+                params![offline],
+                |row| row.get(0),
+            )
+            .expect("breaker row");
+        assert_eq!(failure_count, 3);
+
+        conn.execute("UPDATE library_files SET hash_retry_after = NULL", [])
+            .expect("expire backoff");
+        let claimed: Vec<String> = claim_candidates(&conn, &config, 10, "token-2", None)
+            .expect("claim")
+            .into_iter()
+            .map(|candidate| candidate.library_name)
+            .collect();
+        assert_eq!(claimed, vec!["local".to_string()]);
+
+        assert!(reset_circuit_breaker(&conn, offline).expect("reset"));
+        assert!(!reset_circuit_breaker(&conn, offline).expect("reset again"));
+        assert_eq!(
+            claim_candidates(&conn, &config, 10, "token-3", None)
+                .expect("claim")
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn size_skips_do_not_count_toward_the_circuit_breaker() {
+        let dir = TestDir::new("hash-breaker-skips");
+        let libraries_root = dir.path().join("libraries");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.circuit_breaker_failure_threshold = 2;
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "archive", &libraries_root.join("archive"));
+        for name in ["big-1.bin", "big-2.bin", "tiny.bin", "flaky.bin"] {
+            conn.execute(
+                "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, ?2, 1, 1)",
+                params![library_id, name],
+            )
+            .expect("insert file");
+        }
+        let candidates = claim_candidates(&conn, &config, 10, "token-1", None).expect("claim");
+        assert_eq!(candidates.len(), 4);
+        mark_skipped(&conn, candidates[0].id, "SKIPPED_TOO_LARGE").expect("skip");
+        mark_skipped(&conn, candidates[1].id, "SKIPPED_TOO_LARGE").expect("skip");
+        mark_skipped(&conn, candidates[2].id, "SKIPPED_TOO_SMALL").expect("skip");
+        mark_failure(&conn, &config, candidates[3].id, 0, "stale file handle").expect("failure");
+
+        let tripped: i64 = conn
+            .query_row("SELECT COUNT(*) FROM library_circuit_breaker", [], |row| {
+                row.get(0)
+            })
+            .expect("breaker rows");
+        assert_eq!(tripped, 0);
+    }
+
+    #[test]
+    fn rehash_if_algorithm_not_upgrades_only_other_algorithm_rows() {
+        let dir = TestDir::new("hash-upgrade");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("photos");
+        fs::create_dir_all(&library_root).expect("create library");
+        let current_path = library_root.join("current.bin");
+        let legacy_path = library_root.join("legacy.bin");
+        fs::write(&current_path, b"already blake3").expect("write file");
+        fs::write(&legacy_path, b"still sha256").expect("write file");
 
-        if expected > elapsed {
-            thread::sleep(Duration::from_secs_f64(expected - elapsed));
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &library_root);
+        insert_hashed_file(&conn, library_id, &current_path, "current.bin", &[1; 32]);
+        let legacy_id = insert_hashed_file(&conn, library_id, &legacy_path, "legacy.bin", &[2; 32]);
+        conn.execute(
+            "UPDATE library_files SET hash_algorithm = 'sha256' WHERE id = ?1",
+            params![legacy_id],
+        )
+        .expect("mark legacy");
+
+        assert!(claim_candidates(&conn, &config, 10, "token-1", None)
+            .expect("claim")
+            .is_empty());
+        let candidates =
+            claim_candidates(&conn, &config, 10, "token-2", Some(HashAlgorithm::Blake3))
+                .expect("claim upgrades");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].id, legacy_id);
+        assert!(candidates[0].upgrade);
+
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        let outcome = process_candidate(
+            &conn,
+            &config,
+            &candidates[0],
+            &config.effective_library_config(&candidates[0].library_name),
+            HashAlgorithm::Blake3,
+            &mut limiter,
+        )
+        .expect("process");
+        assert!(matches!(outcome, CandidateOutcome::Hashed(_)));
+        let (algorithm, digest): (String, Vec<u8>) = conn
+            .query_row(
+                "SELECT hash_algorithm, content_hash FROM library_files WHERE id = ?1",
+                params![legacy_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("legacy row");
+        assert_eq!(algorithm, "blake3");
+        assert_eq!(digest, blake3::hash(b"still sha256").as_bytes().to_vec());
+
+        assert!(
+            claim_candidates(&conn, &config, 10, "token-3", Some(HashAlgorithm::Blake3))
+                .expect("claim again")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn uniquely_sized_file_gets_prefix_hash_only() {
+        let dir = TestDir::new("hash-prefix");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("photos");
+        fs::create_dir_all(&library_root).expect("create library");
+        fs::write(library_root.join("unique.bin"), vec![1_u8; 300]).expect("write file");
+        fs::write(library_root.join("a.bin"), vec![2_u8; 500]).expect("write file");
+        fs::write(library_root.join("b.bin"), vec![2_u8; 500]).expect("write file");
+
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.hash_prefix_bytes = Some(64);
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "photos", &library_root);
+        for name in ["unique.bin", "a.bin", "b.bin"] {
+            let (size_bytes, mtime_ns, _, _) =
+                metadata_to_row(&fs::metadata(library_root.join(name)).expect("stat file"))
+                    .expect("metadata row");
+            conn.execute(
+                "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, ?2, ?3, ?4)",
+                params![library_id, name, size_bytes, mtime_ns],
+            )
+            .expect("insert file");
+        }
+
+        let mut limiter = IoRateLimiter::new(None, 1.0);
+        for token in ["token-1", "token-2"] {
+            for candidate in claim_candidates(&conn, &config, 10, token, None).expect("claim") {
+                process_candidate(
+                    &conn,
+                    &config,
+                    &candidate,
+                    &config.effective_library_config(&candidate.library_name),
+                    HashAlgorithm::Blake3,
+                    &mut limiter,
+                )
+                .expect("process");
+            }
         }
 
-        if self.window_start.elapsed().as_secs_f64() >= 1.0 {
-            self.window_start = Instant::now();
-            self.bytes_in_window = 0;
+        let rows: Vec<(String, bool, bool, bool)> = conn
+            .prepare(
+                "SELECT relative_path, needs_hash, prefix_hash IS NOT NULL, content_hash IS NOT NULL FROM library_files ORDER BY relative_path",
+            )
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .expect("query")
+            .map(|row| row.expect("row"))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("a.bin".to_string(), false, true, true),
+                ("b.bin".to_string(), false, true, true),
+                ("unique.bin".to_string(), false, true, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn io_rate_limiter_holds_sustained_throughput_to_the_limit() {
+        let limit = 4.0 * 1024.0 * 1024.0;
+        let chunk = 64 * 1024;
+        let mut limiter = IoRateLimiter::with_bytes_per_second(Some(limit), 1.0);
+        let started = Instant::now();
+        for _ in 0..10 {
+            limiter.consume(chunk);
         }
+        let throughput = (10 * chunk) as f64 / started.elapsed().as_secs_f64();
+        assert!(
+            (throughput - limit).abs() <= limit * 0.05,
+            "throughput {throughput:.0} B/s is not within 5% of {limit:.0} B/s"
+        );
+    }
+
+    #[test]
+    fn io_rate_limiter_caps_idle_credit_at_the_burst_capacity() {
+        let limit = 1024.0 * 1024.0;
+        let mut limiter = IoRateLimiter::with_bytes_per_second(Some(limit), 2.0);
+        limiter.last_refill -= Duration::from_secs(60);
+        let started = Instant::now();
+        limiter.consume((2.0 * limit) as usize);
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(limiter.tokens.abs() < limit * 0.01);
+    }
+
+    #[test]
+    fn io_rate_limiter_saturates_huge_limits() {
+        let limiter = IoRateLimiter::new(Some(u64::MAX), 1.0);
+        assert_eq!(limiter.bytes_per_second, Some(u64::MAX as f64));
     }
 }
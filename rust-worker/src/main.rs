@@ -1,30 +1,60 @@
 mod config;
 mod db;
+mod dedup;
+mod error_codes;
 mod hash;
 mod path_safety;
+mod pid_file;
 mod scan;
+#[cfg(test)]
+mod test_support;
 mod thumbnail;
+mod webhook;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser;
 use rand::Rng;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
 
-use crate::config::WorkerConfig;
+use crate::config::{validate_config_runtime, ConfigFormat, LogFormat, WorkerConfig};
 use crate::db::{
-    claim_scan_hash_job, claim_thumbnail_cleanup_job, claim_thumbnail_task,
-    claim_wal_maintenance_job, execute_wal_checkpoint, finish_job,
-    finish_thumbnail_cleanup_job, finish_thumbnail_failure, finish_thumbnail_success,
+    cancel_job, check_schema, check_schema_version, claim_db_maintenance_job, claim_scan_hash_job,
+    claim_thumbnail_cleanup_job, claim_thumbnail_task, claim_thumbnail_task_group,
+    claim_wal_maintenance_job, detect_schema_features, execute_db_maintenance,
+    execute_wal_checkpoint, finish_db_maintenance_failure, finish_db_maintenance_success,
+    finish_job, finish_thumbnail_cleanup_job, finish_thumbnail_failure, finish_thumbnail_success,
     finish_wal_maintenance_failure, finish_wal_maintenance_success,
-    has_runnable_scan_hash_work, has_runnable_thumbnail_cleanup_work, has_runnable_thumbnail_work,
-    has_runnable_wal_maintenance_work, open_connection, requeue_wal_maintenance_retry, JobKind,
+    has_runnable_db_maintenance_work, has_runnable_quick_hash_work, has_runnable_scan_hash_work,
+    has_runnable_thumbnail_cleanup_work, has_runnable_thumbnail_work,
+    has_runnable_wal_maintenance_work, insert_jobs_batch, is_permanent_thumbnail_error,
+    is_terminal_thumbnail_error, list_dead_lettered_jobs, list_duplicate_groups,
+    list_job_log_entries, list_library_roots, list_permanent_thumbnail_failures,
+    list_retry_buckets, load_worker_control, maybe_enqueue_scheduled_scan,
+    maybe_enqueue_wal_checkpoint_if_needed, open_connection, recover_expired_leases,
+    requeue_wal_maintenance_retry, reset_circuit_breaker, reset_permanent_thumbnail,
+    restore_dead_lettered_job, set_job_priority, set_library_io_rate_limit,
+    thumbnail_failure_exhausts_retries, JobKind, JobRecord, NewJobSpec, RetryQueue, SchemaFeatures,
+    ThumbnailCleanupKind, ThumbnailTaskRecord, SHUTDOWN_REQUESTED,
 };
-use crate::hash::run_hash_job;
+use crate::dedup::run_dedup_job;
+use crate::error_codes::ErrorCode;
+use crate::hash::{run_hash_job, run_migrate_job, run_quick_hash_pass, run_verify_job};
+use crate::path_safety::resolve_root_under_libraries;
+use crate::pid_file::PidFileGuard;
 use crate::scan::run_scan_job;
-use crate::thumbnail::{classify_thumbnail_error, run_thumbnail_cleanup_task, run_thumbnail_task};
+use crate::thumbnail::{
+    classify_thumbnail_error, delete_orphan_thumbnails, probe_ffmpeg, run_orphan_thumbnail_scan,
+    run_thumbnail_cleanup_task, run_thumbnail_orphan_sweep, run_thumbnail_task,
+    run_thumbnail_task_group,
+};
+use crate::webhook::{job_counters, notify_completion, wait_for_in_flight};
 
 #[derive(Debug, Parser)]
 #[command(name = "dedupfs-rust-worker", version)]
@@ -32,14 +62,74 @@ struct Cli {
     #[arg(long)]
     config: Option<PathBuf>,
 
+    #[arg(long, value_name = "toml|json|auto", default_value = "auto")]
+    config_format: String,
+
     #[arg(long)]
     job_id: Option<String>,
 
+    #[arg(long, requires = "job_id", allow_hyphen_values = true)]
+    job_priority: Option<i64>,
+
     #[arg(long)]
     worker_id: Option<String>,
 
     #[arg(long, default_value_t = false)]
     daemon: bool,
+
+    #[arg(long, default_value_t = false)]
+    validate_config: bool,
+
+    #[arg(long, default_value_t = false)]
+    list_libraries: bool,
+
+    #[arg(long, default_value_t = false)]
+    retry_histogram: bool,
+
+    #[arg(long, requires = "retry_histogram")]
+    kind: Option<String>,
+
+    #[arg(long, value_name = "JOB_ID")]
+    cancel_job: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    list_dead_lettered: bool,
+
+    #[arg(long, value_name = "JSON_FILE")]
+    batch_submit_jobs: Option<PathBuf>,
+
+    #[arg(long, value_name = "JOB_ID")]
+    job_log: Option<String>,
+
+    #[arg(long, default_value_t = false, requires = "job_id")]
+    restore_dead_lettered: bool,
+
+    #[arg(long, default_value_t = false)]
+    list_permanent_thumbnail_failures: bool,
+
+    #[arg(long, value_name = "THUMB_KEY")]
+    reset_permanent_thumbnail: Option<String>,
+
+    #[arg(long, value_name = "LIBRARY_NAME")]
+    reset_circuit_breaker: Option<String>,
+
+    #[arg(long, num_args = 2, value_names = ["LIBRARY_NAME", "MIB_PER_SEC"])]
+    set_library_rate_limit: Option<Vec<String>>,
+
+    #[arg(long, default_value_t = false)]
+    list_duplicates: bool,
+
+    #[arg(long, requires = "list_duplicates")]
+    min_size_bytes: Option<i64>,
+
+    #[arg(long, default_value_t = false)]
+    scan_orphan_thumbnails: bool,
+
+    #[arg(long, default_value_t = false, requires = "scan_orphan_thumbnails")]
+    delete_orphans: bool,
+
+    #[arg(long, default_value_t = false)]
+    output_json: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,96 +140,350 @@ enum CycleOutcome {
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = WorkerConfig::load(cli.config.as_deref(), cli.worker_id.as_deref())?;
+    if cli.validate_config {
+        run_validate_config(cli.config.as_deref(), &cli.config_format);
+    }
+    let config = WorkerConfig::load(
+        cli.config.as_deref(),
+        ConfigFormat::parse(&cli.config_format)?,
+        cli.worker_id.as_deref(),
+    )?;
+    tracing::subscriber::set_global_default(build_log_subscriber(config.log_format))
+        .context("failed to install log subscriber")?;
 
     let mut conn = open_connection(&config.database_path)?;
+    if config.schema_version_check {
+        check_schema_version(&conn, config.schema_version_missing)?;
+    }
+    if let Err(error) = check_schema(&conn) {
+        eprintln!("{error:#}");
+        std::process::exit(2);
+    }
+    let schema = detect_schema_features(&conn)?;
+
+    if cli.list_libraries {
+        return run_list_libraries(&conn, &config, cli.output_json);
+    }
+
+    if cli.retry_histogram {
+        return run_retry_histogram(&conn, cli.kind.as_deref(), cli.output_json);
+    }
+
+    if let Some(job_id) = cli.cancel_job.as_deref() {
+        if cancel_job(&mut conn, &config, job_id)? {
+            println!("job {job_id} cancelled");
+        } else {
+            println!("job {job_id} already finished; nothing to cancel");
+        }
+        return Ok(());
+    }
+
+    if cli.list_dead_lettered {
+        return run_list_dead_lettered(&conn, cli.output_json);
+    }
+
+    if let Some(job_id) = cli.job_log.as_deref() {
+        return run_job_log(&conn, job_id, cli.output_json);
+    }
+
+    if let Some(path) = cli.batch_submit_jobs.as_deref() {
+        if !schema.jobs_priority {
+            bail!("--batch-submit-jobs requires the jobs.priority column; run the Python migrations first");
+        }
+        return run_batch_submit_jobs(&mut conn, path);
+    }
+
+    if cli.restore_dead_lettered {
+        let job_id = cli.job_id.as_deref().unwrap_or_default();
+        restore_dead_lettered_job(&conn, job_id)?;
+        println!("job {job_id} restored to pending");
+        return Ok(());
+    }
+
+    if cli.list_permanent_thumbnail_failures {
+        return run_list_permanent_thumbnail_failures(&conn, cli.output_json);
+    }
+
+    if cli.list_duplicates {
+        return run_list_duplicates(&conn, cli.min_size_bytes, cli.output_json);
+    }
+
+    if cli.scan_orphan_thumbnails {
+        return run_scan_orphan_thumbnails(&conn, &config, cli.delete_orphans, cli.output_json);
+    }
+
+    if let Some(thumb_key) = cli.reset_permanent_thumbnail.as_deref() {
+        reset_permanent_thumbnail(&conn, thumb_key)?;
+        println!("thumbnail {thumb_key} reset to pending");
+        return Ok(());
+    }
+
+    if let Some(library_name) = cli.reset_circuit_breaker.as_deref() {
+        if reset_circuit_breaker(&conn, library_id_by_name(&conn, library_name)?)? {
+            println!("circuit breaker for library {library_name} reset");
+        } else {
+            println!("circuit breaker for library {library_name} was not tripped");
+        }
+        return Ok(());
+    }
+
+    if let Some([library_name, mib_per_sec]) = cli.set_library_rate_limit.as_deref() {
+        let mib_per_sec: u64 = mib_per_sec
+            .parse()
+            .with_context(|| format!("invalid MIB_PER_SEC: {mib_per_sec}"))?;
+        set_library_io_rate_limit(&conn, library_id_by_name(&conn, library_name)?, mib_per_sec)?;
+        if mib_per_sec == 0 {
+            println!("library {library_name} now uses the global hash I/O limit");
+        } else {
+            println!("library {library_name} hash I/O limited to {mib_per_sec} MiB/s");
+        }
+        return Ok(());
+    }
+
+    // SIGTERM only raises the flag: the running task finishes (scan/hash stop at their
+    // next batch boundary) and no further work is claimed.
+    let shutdown = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&shutdown))
+        .context("failed to install SIGTERM handler")?;
 
     if cli.daemon {
         if cli.job_id.is_some() {
             bail!("--job-id cannot be used with --daemon");
         }
-        return run_daemon_loop(&mut conn, &config);
+        let probe = probe_ffmpeg(&config.thumbnail_ffmpeg_bin, |command| command.output());
+        let config = apply_ffmpeg_probe(&config, probe)?;
+        return run_daemon_loop(&mut conn, &config, &schema, &shutdown);
+    }
+
+    if let (Some(job_id), Some(priority)) = (cli.job_id.as_deref(), cli.job_priority) {
+        if !schema.jobs_priority {
+            bail!(
+                "--job-priority requires the jobs.priority column; run the Python migrations first"
+            );
+        }
+        set_job_priority(&conn, job_id, priority)?;
     }
 
-    match run_worker_cycle(&mut conn, &config, cli.job_id.as_deref(), true) {
+    let outcome = run_worker_cycle(
+        &mut conn,
+        &config,
+        &schema,
+        cli.job_id.as_deref(),
+        true,
+        &shutdown,
+    );
+    wait_for_in_flight();
+    match outcome {
         Ok(CycleOutcome::DidWork) => Ok(()),
         Ok(CycleOutcome::Idle) => {
-            println!("no runnable rust tasks found");
+            tracing::info!(worker_id = %config.worker_id, "no runnable rust tasks found");
             Ok(())
         }
         Err(error) => Err(error),
     }
 }
 
-fn run_daemon_loop(conn: &mut rusqlite::Connection, config: &WorkerConfig) -> Result<()> {
-    let mut idle_backoff_seconds = config.rust_worker_poll_seconds.max(1);
+/// Returns the daemon's session config: unchanged when ffmpeg answered, otherwise either an
+/// error (`thumbnail_require_ffmpeg`) or a copy with no video thumbnail slots, so
+/// `claim_thumbnail_task` leaves video rows pending instead of failing each one.
+fn apply_ffmpeg_probe(config: &WorkerConfig, probe: Result<String>) -> Result<WorkerConfig> {
+    match probe {
+        Ok(version) => {
+            tracing::info!(worker_id = %config.worker_id, version = %version, "detected ffmpeg");
+            Ok(config.clone())
+        }
+        Err(error) if config.thumbnail_require_ffmpeg => {
+            Err(error.context("ffmpeg is required (thumbnail_require_ffmpeg) but unusable"))
+        }
+        Err(error) => {
+            tracing::warn!(
+                worker_id = %config.worker_id,
+                error = %format!("{error:#}"),
+                "ffmpeg unusable; video thumbnails disabled for this session"
+            );
+            let mut degraded = config.clone();
+            degraded.thumbnail_video_concurrency = 0;
+            Ok(degraded)
+        }
+    }
+}
+
+fn run_daemon_loop(
+    conn: &mut rusqlite::Connection,
+    config: &WorkerConfig,
+    schema: &SchemaFeatures,
+    shutdown: &AtomicBool,
+) -> Result<()> {
+    let _pid_file = config
+        .pid_file
+        .as_deref()
+        .map(PidFileGuard::acquire)
+        .transpose()?;
+    if config.recover_leases_on_startup {
+        let recovered = recover_expired_leases(conn, config)?;
+        tracing::info!(
+            worker_id = %config.worker_id,
+            jobs = recovered.jobs,
+            thumbnails = recovered.thumbnails,
+            thumbnail_cleanup_jobs = recovered.thumbnail_cleanup_jobs,
+            wal_maintenance_jobs = recovered.wal_maintenance_jobs,
+            db_maintenance_jobs = recovered.db_maintenance_jobs,
+            "recovered expired leases at startup"
+        );
+    }
+    let mut idle_backoff_seconds = config.rust_worker_poll_seconds;
+    let mut first_idle_at: Option<Instant> = None;
+    let mut claim_throttle = config
+        .max_claims_per_second
+        .map(|rate| ClaimThrottle::new(rate, Instant::now()));
 
     loop {
-        match run_worker_cycle(conn, config, None, false) {
+        if let Some(throttle) = claim_throttle.as_mut() {
+            thread::sleep(throttle.take(Instant::now()));
+        }
+        // A freshly scheduled scan counts as work so it is picked up without a backoff sleep.
+        let outcome =
+            run_worker_cycle(conn, config, schema, None, false, shutdown).and_then(|outcome| {
+                if !shutdown.load(Ordering::SeqCst) && maybe_enqueue_scheduled_scan(conn, config)? {
+                    tracing::info!(worker_id = %config.worker_id, "enqueued scheduled scan job");
+                    return Ok(CycleOutcome::DidWork);
+                }
+                Ok(outcome)
+            });
+        match outcome {
             Ok(CycleOutcome::DidWork) => {
-                idle_backoff_seconds = config.rust_worker_poll_seconds.max(1);
+                idle_backoff_seconds = config.rust_worker_poll_seconds;
+                first_idle_at = None;
+                match maybe_enqueue_wal_checkpoint_if_needed(conn, config) {
+                    Ok(true) => {
+                        tracing::info!(
+                            worker_id = %config.worker_id,
+                            "enqueued passive wal checkpoint for oversized WAL file"
+                        );
+                    }
+                    Ok(false) => {}
+                    Err(error) => {
+                        tracing::warn!(
+                            worker_id = %config.worker_id,
+                            error = %sanitize_error_message(&error.to_string(), config),
+                            "wal size check failed"
+                        );
+                    }
+                }
             }
             Ok(CycleOutcome::Idle) => {
-                sleep_with_jitter(idle_backoff_seconds, config.rust_worker_poll_jitter_millis);
+                if shutdown.load(Ordering::SeqCst) {
+                    tracing::info!(worker_id = %config.worker_id, "shutdown requested; exiting");
+                    break;
+                }
+                let idle_since = *first_idle_at.get_or_insert_with(Instant::now);
+                sleep_with_jitter(
+                    idle_backoff_seconds,
+                    config.rust_worker_poll_jitter_millis,
+                    shutdown,
+                );
+                // A supervisor restarts the daemon later; exiting frees its resources meanwhile.
+                if let Some(idle_exit_seconds) = config.rust_worker_idle_exit_seconds {
+                    if idle_since.elapsed() >= Duration::from_secs(idle_exit_seconds) {
+                        tracing::info!(
+                            worker_id = %config.worker_id,
+                            idle_exit_seconds,
+                            "idle for too long; exiting"
+                        );
+                        break;
+                    }
+                }
                 idle_backoff_seconds = next_idle_backoff_seconds(
                     idle_backoff_seconds,
                     config.rust_worker_poll_seconds,
                     config.rust_worker_max_poll_seconds,
+                    config.rust_worker_error_backoff_multiplier,
                 );
             }
             Err(error) => {
-                let error_message = sanitize_error_message(&error.to_string(), &config);
-                eprintln!(
-                    "worker={} daemon-cycle-error={}",
-                    config.worker_id, error_message
+                let error_message = sanitize_error_message(&error.to_string(), config);
+                tracing::error!(
+                    worker_id = %config.worker_id,
+                    error = %error_message,
+                    "daemon cycle failed"
+                );
+                sleep_with_jitter(
+                    idle_backoff_seconds,
+                    config.rust_worker_poll_jitter_millis,
+                    shutdown,
                 );
-                sleep_with_jitter(idle_backoff_seconds, config.rust_worker_poll_jitter_millis);
                 idle_backoff_seconds = next_idle_backoff_seconds(
                     idle_backoff_seconds,
                     config.rust_worker_poll_seconds,
                     config.rust_worker_max_poll_seconds,
+                    config.rust_worker_error_backoff_multiplier,
                 );
             }
         }
     }
+    wait_for_in_flight();
+    Ok(())
 }
 
 fn run_worker_cycle(
     conn: &mut rusqlite::Connection,
     config: &WorkerConfig,
+    schema: &SchemaFeatures,
     requested_job_id: Option<&str>,
     propagate_task_errors: bool,
+    shutdown: &AtomicBool,
 ) -> Result<CycleOutcome> {
+    if shutdown.load(Ordering::SeqCst) {
+        return Ok(CycleOutcome::Idle);
+    }
+    let control = load_worker_control(conn)?;
     let scan_hash_runnable = if requested_job_id.is_some() {
         true
     } else {
-        has_runnable_scan_hash_work(conn)?
+        has_runnable_scan_hash_work(conn, &control)?
     };
     if scan_hash_runnable {
-        if let Some(job) = claim_scan_hash_job(conn, config, requested_job_id)? {
-            println!(
-                "worker={} backend=rust concurrency={} job={} kind={:?}",
-                config.worker_id, config.concurrency, job.id, job.kind
+        if let Some(job) = claim_scan_hash_job(conn, config, requested_job_id, &control, schema)? {
+            tracing::info!(
+                worker_id = %config.worker_id,
+                concurrency = config.concurrency,
+                job_id = %job.id,
+                kind = ?job.kind,
+                priority = job.priority,
+                "job claimed"
             );
 
             let result = match job.kind {
-                JobKind::Scan => run_scan_job(conn, config, &job),
-                JobKind::Hash => run_hash_job(conn, config, &job),
+                JobKind::Scan => run_scan_job(conn, config, &job, schema, shutdown),
+                JobKind::Hash => run_hash_job(conn, config, &job, shutdown),
+                JobKind::Verify => run_verify_job(conn, config, &job),
+                JobKind::Dedup => run_dedup_job(conn, config, &job),
+                JobKind::Migrate => run_migrate_job(conn, config, &job, shutdown),
             };
 
             return match result {
                 Ok(()) => {
                     finish_job(conn, config, &job.id, true, None)?;
-                    println!("job {} finished successfully", job.id);
+                    tracing::info!(job_id = %job.id, "job finished successfully");
+                    notify_job_completion(conn, config, &job, "completed", None);
                     Ok(CycleOutcome::DidWork)
                 }
                 Err(error) => {
                     let message = sanitize_error_message(&error.to_string(), config);
                     let _ = finish_job(conn, config, &job.id, false, Some(&message));
+                    if message == SHUTDOWN_REQUESTED {
+                        tracing::info!(job_id = %job.id, "job interrupted by shutdown and requeued");
+                        return Ok(CycleOutcome::DidWork);
+                    }
+                    notify_job_completion(conn, config, &job, "failed", Some(&message));
                     if propagate_task_errors {
                         Err(error)
                     } else {
-                        eprintln!("job {} failed and persisted as failed: {}", job.id, message);
+                        tracing::error!(
+                            job_id = %job.id,
+                            error = %message,
+                            "job failed and persisted as failed"
+                        );
                         Ok(CycleOutcome::DidWork)
                     }
                 }
@@ -147,20 +491,47 @@ fn run_worker_cycle(
         }
     }
 
-    if has_runnable_thumbnail_work(conn)? {
-        if let Some(task) = claim_thumbnail_task(conn, config)? {
-            println!(
-                "worker={} thumbnail_task={} file_id={} media_type={}",
-                config.worker_id, task.thumb_key, task.file_id, task.media_type
+    if config.hash_quick_sample_bytes.is_some()
+        && !control.hash_paused()
+        && has_runnable_quick_hash_work(conn, config)?
+    {
+        let processed = run_quick_hash_pass(conn, config)?;
+        if processed > 0 {
+            tracing::info!(
+                worker_id = %config.worker_id,
+                files = processed,
+                "quick hash batch finished"
+            );
+            return Ok(CycleOutcome::DidWork);
+        }
+    }
+
+    if !control.thumbnail_paused() && has_runnable_thumbnail_work(conn)? {
+        if config.thumbnail_multi_size {
+            let tasks = claim_thumbnail_task_group(conn, config)?;
+            if !tasks.is_empty() {
+                return run_thumbnail_group_cycle(conn, config, &tasks, propagate_task_errors);
+            }
+        } else if let Some(task) = claim_thumbnail_task(conn, config)? {
+            tracing::info!(
+                worker_id = %config.worker_id,
+                thumb_key = %task.thumb_key,
+                file_id = task.file_id,
+                media_type = %task.media_type,
+                "thumbnail task claimed"
             );
 
             return match run_thumbnail_task(conn, config, &task) {
                 Ok((width, height, bytes_size)) => {
                     finish_thumbnail_success(conn, config, task.id, width, height, bytes_size)?;
-                    println!(
-                        "thumbnail task {} finished successfully ({}x{}, {} bytes)",
-                        task.thumb_key, width, height, bytes_size
+                    tracing::info!(
+                        thumb_key = %task.thumb_key,
+                        width,
+                        height,
+                        bytes_size,
+                        "thumbnail task finished successfully"
                     );
+                    notify_thumbnail_success(config, &task, width, height, bytes_size);
                     Ok(CycleOutcome::DidWork)
                 }
                 Err(error) => {
@@ -174,12 +545,15 @@ fn run_worker_cycle(
                         error_code,
                         &error_message,
                     );
+                    notify_thumbnail_failure(config, &task, error_code, &error_message);
                     if propagate_task_errors {
                         Err(error)
                     } else {
-                        eprintln!(
-                            "thumbnail task {} failed and persisted as failed: {}",
-                            task.thumb_key, error_message
+                        tracing::error!(
+                            thumb_key = %task.thumb_key,
+                            error_code = error_code.as_str(),
+                            error = %error_message,
+                            "thumbnail task failed and persisted as failed"
                         );
                         Ok(CycleOutcome::DidWork)
                     }
@@ -188,19 +562,42 @@ fn run_worker_cycle(
         }
     }
 
-    if has_runnable_thumbnail_cleanup_work(conn)? {
+    if !control.thumbnail_paused() && has_runnable_thumbnail_cleanup_work(conn)? {
         if let Some(cleanup) = claim_thumbnail_cleanup_job(conn, config)? {
-            println!(
-                "worker={} thumbnail_cleanup_job={} group_key={}",
-                config.worker_id, cleanup.id, cleanup.group_key
+            tracing::info!(
+                worker_id = %config.worker_id,
+                cleanup_job_id = cleanup.id,
+                kind = ?cleanup.kind,
+                group_key = %cleanup.group_key,
+                "thumbnail cleanup job claimed"
             );
 
-            return match run_thumbnail_cleanup_task(conn, config, &cleanup) {
-                Ok(removed_rows) => {
+            let (result, counter) = match cleanup.kind {
+                ThumbnailCleanupKind::Group => (
+                    run_thumbnail_cleanup_task(conn, config, &cleanup),
+                    "removed_rows",
+                ),
+                ThumbnailCleanupKind::OrphanSweep => (
+                    run_thumbnail_orphan_sweep(conn, config, &cleanup),
+                    "removed_files",
+                ),
+            };
+            return match result {
+                Ok(removed) => {
                     finish_thumbnail_cleanup_job(conn, config, cleanup.id, true, None, None)?;
-                    println!(
-                        "thumbnail cleanup job {} finished successfully (removed rows={})",
-                        cleanup.id, removed_rows
+                    tracing::info!(
+                        cleanup_job_id = cleanup.id,
+                        counter,
+                        removed,
+                        "thumbnail cleanup job finished successfully"
+                    );
+                    notify_completion(
+                        config,
+                        cleanup.id.into(),
+                        "thumbnail_cleanup",
+                        "completed",
+                        serde_json::json!({ counter: removed }),
+                        None,
                     );
                     Ok(CycleOutcome::DidWork)
                 }
@@ -211,15 +608,24 @@ fn run_worker_cycle(
                         config,
                         cleanup.id,
                         false,
-                        Some("THUMB_CLEANUP_FAILED"),
+                        Some(ErrorCode::ThumbCleanupFailed),
+                        Some(&error_message),
+                    );
+                    notify_completion(
+                        config,
+                        cleanup.id.into(),
+                        "thumbnail_cleanup",
+                        "failed",
+                        serde_json::Value::Null,
                         Some(&error_message),
                     );
                     if propagate_task_errors {
                         Err(error)
                     } else {
-                        eprintln!(
-                            "thumbnail cleanup job {} failed and persisted as failed: {}",
-                            cleanup.id, error_message
+                        tracing::error!(
+                            cleanup_job_id = cleanup.id,
+                            error = %error_message,
+                            "thumbnail cleanup job failed and persisted as failed"
                         );
                         Ok(CycleOutcome::DidWork)
                     }
@@ -228,14 +634,55 @@ fn run_worker_cycle(
         }
     }
 
-    if has_runnable_wal_maintenance_work(conn)? {
+    if !control.wal_paused() && has_runnable_wal_maintenance_work(conn)? {
         if let Some(maintenance_job) = claim_wal_maintenance_job(conn, config)? {
-            println!(
-                "worker={} wal_maintenance_job={} mode={:?}",
-                config.worker_id, maintenance_job.id, maintenance_job.requested_mode
+            tracing::info!(
+                worker_id = %config.worker_id,
+                maintenance_job_id = maintenance_job.id,
+                mode = ?maintenance_job.requested_mode,
+                "wal maintenance job claimed"
             );
 
-            return match execute_wal_checkpoint(conn, maintenance_job.requested_mode) {
+            let Some(checkpoint_mode) = maintenance_job
+                .requested_mode
+                .downgrade_to_allowed(&config.wal_allowed_checkpoint_modes)
+            else {
+                let message = format!(
+                    "WAL checkpoint mode {:?} is not allowed by wal_allowed_checkpoint_modes",
+                    maintenance_job.requested_mode
+                );
+                finish_wal_maintenance_failure(
+                    conn,
+                    config,
+                    maintenance_job.id,
+                    ErrorCode::WalCheckpointModeForbidden,
+                    &message,
+                )?;
+                notify_completion(
+                    config,
+                    maintenance_job.id.into(),
+                    "wal_maintenance",
+                    "failed",
+                    serde_json::Value::Null,
+                    Some(&message),
+                );
+                tracing::warn!(
+                    maintenance_job_id = maintenance_job.id,
+                    error = %message,
+                    "wal maintenance job rejected"
+                );
+                return Ok(CycleOutcome::DidWork);
+            };
+            if checkpoint_mode != maintenance_job.requested_mode {
+                tracing::info!(
+                    maintenance_job_id = maintenance_job.id,
+                    requested_mode = ?maintenance_job.requested_mode,
+                    mode = ?checkpoint_mode,
+                    "wal maintenance job mode downgraded"
+                );
+            }
+
+            return match execute_wal_checkpoint(conn, checkpoint_mode) {
                 Ok(stats) => {
                     if stats.busy > 0 {
                         let busy_message = format!(
@@ -247,20 +694,34 @@ fn run_worker_cycle(
                             config,
                             maintenance_job.id,
                             maintenance_job.retry_count,
-                            "WAL_CHECKPOINT_BUSY",
+                            ErrorCode::WalCheckpointBusy,
                             &busy_message,
                             stats,
                         );
-                        eprintln!(
-                            "wal maintenance job {} busy; requeued for retry",
-                            maintenance_job.id
+                        tracing::warn!(
+                            maintenance_job_id = maintenance_job.id,
+                            busy = stats.busy,
+                            "wal maintenance job busy; requeued for retry"
                         );
                         Ok(CycleOutcome::DidWork)
                     } else {
                         finish_wal_maintenance_success(conn, config, maintenance_job.id, stats)?;
-                        println!(
-                            "wal maintenance job {} finished successfully (log_frames={}, checkpointed_frames={})",
-                            maintenance_job.id, stats.log_frames, stats.checkpointed_frames
+                        tracing::info!(
+                            maintenance_job_id = maintenance_job.id,
+                            log_frames = stats.log_frames,
+                            checkpointed_frames = stats.checkpointed_frames,
+                            "wal maintenance job finished successfully"
+                        );
+                        notify_completion(
+                            config,
+                            maintenance_job.id.into(),
+                            "wal_maintenance",
+                            "completed",
+                            serde_json::json!({
+                                "log_frames": stats.log_frames,
+                                "checkpointed_frames": stats.checkpointed_frames,
+                            }),
+                            None,
                         );
                         Ok(CycleOutcome::DidWork)
                     }
@@ -271,15 +732,114 @@ fn run_worker_cycle(
                         conn,
                         config,
                         maintenance_job.id,
-                        "WAL_CHECKPOINT_FAILED",
+                        ErrorCode::WalCheckpointFailed,
+                        &message,
+                    );
+                    notify_completion(
+                        config,
+                        maintenance_job.id.into(),
+                        "wal_maintenance",
+                        "failed",
+                        serde_json::Value::Null,
+                        Some(&message),
+                    );
+                    if propagate_task_errors {
+                        Err(error)
+                    } else {
+                        tracing::error!(
+                            maintenance_job_id = maintenance_job.id,
+                            error = %message,
+                            "wal maintenance job failed and persisted as failed"
+                        );
+                        Ok(CycleOutcome::DidWork)
+                    }
+                }
+            };
+        }
+    }
+
+    if !control.wal_paused() && has_runnable_db_maintenance_work(conn)? {
+        if let Some(maintenance_job) = claim_db_maintenance_job(conn, config)? {
+            tracing::info!(
+                worker_id = %config.worker_id,
+                maintenance_job_id = maintenance_job.id,
+                operation = ?maintenance_job.operation,
+                "db maintenance job claimed"
+            );
+
+            return match execute_db_maintenance(conn, maintenance_job.operation) {
+                Ok(stats) if stats.integrity_failed() => {
+                    let message = stats.integrity_result.unwrap_or_default();
+                    finish_db_maintenance_failure(
+                        conn,
+                        config,
+                        maintenance_job.id,
+                        ErrorCode::DbIntegrityFailed,
+                        &message,
+                    )?;
+                    notify_completion(
+                        config,
+                        maintenance_job.id.into(),
+                        "db_maintenance",
+                        "failed",
+                        serde_json::Value::Null,
+                        Some(&message),
+                    );
+                    tracing::error!(
+                        maintenance_job_id = maintenance_job.id,
+                        problems = %message,
+                        "db maintenance integrity_check reported problems"
+                    );
+                    Ok(CycleOutcome::DidWork)
+                }
+                Ok(stats) => {
+                    finish_db_maintenance_success(conn, config, maintenance_job.id, &stats)?;
+                    tracing::info!(
+                        maintenance_job_id = maintenance_job.id,
+                        page_count_before = stats.page_count_before,
+                        page_count_after = stats.page_count_after,
+                        freed_pages = stats.freed_pages(),
+                        "db maintenance job finished successfully"
+                    );
+                    notify_completion(
+                        config,
+                        maintenance_job.id.into(),
+                        "db_maintenance",
+                        "completed",
+                        serde_json::json!({
+                            "page_count_before": stats.page_count_before,
+                            "page_count_after": stats.page_count_after,
+                            "freed_pages": stats.freed_pages(),
+                            "integrity_result": stats.integrity_result,
+                        }),
+                        None,
+                    );
+                    Ok(CycleOutcome::DidWork)
+                }
+                Err(error) => {
+                    let message = sanitize_error_message(&error.to_string(), config);
+                    let _ = finish_db_maintenance_failure(
+                        conn,
+                        config,
+                        maintenance_job.id,
+                        ErrorCode::DbMaintenanceFailed,
                         &message,
                     );
+                    notify_completion(
+                        config,
+                        maintenance_job.id.into(),
+                        "db_maintenance",
+                        "failed",
+                        serde_json::Value::Null,
+                        Some(&message),
+                    );
                     if propagate_task_errors {
                         Err(error)
                     } else {
-                        eprintln!(
-                            "wal maintenance job {} failed and persisted as failed: {}",
-                            maintenance_job.id, message
+                        tracing::error!(
+                            maintenance_job_id = maintenance_job.id,
+                            error = %message,
+                            "db maintenance job failed and persisted as failed"
                         );
                         Ok(CycleOutcome::DidWork)
                     }
@@ -291,29 +851,591 @@ fn run_worker_cycle(
     Ok(CycleOutcome::Idle)
 }
 
-fn sleep_with_jitter(base_seconds: u64, jitter_millis: u64) {
+fn run_thumbnail_group_cycle(
+    conn: &mut rusqlite::Connection,
+    config: &WorkerConfig,
+    tasks: &[ThumbnailTaskRecord],
+    propagate_task_errors: bool,
+) -> Result<CycleOutcome> {
+    let leader = &tasks[0];
+    tracing::info!(
+        worker_id = %config.worker_id,
+        thumb_key = %leader.thumb_key,
+        file_id = leader.file_id,
+        media_type = %leader.media_type,
+        sizes = tasks.len(),
+        "thumbnail task group claimed"
+    );
+
+    let results = match run_thumbnail_task_group(conn, config, tasks) {
+        Ok(results) => results,
+        Err(error) => {
+            let error_code = classify_thumbnail_error(&error);
+            let error_message = sanitize_error_message(&error.to_string(), config);
+            for task in tasks {
+                let _ = finish_thumbnail_failure(
+                    conn,
+                    config,
+                    task.id,
+                    task.error_count,
+                    error_code,
+                    &error_message,
+                );
+                notify_thumbnail_failure(config, task, error_code, &error_message);
+            }
+            if propagate_task_errors {
+                return Err(error);
+            }
+            tracing::error!(
+                thumb_key = %leader.thumb_key,
+                error_code = error_code.as_str(),
+                error = %error_message,
+                "thumbnail task group failed and persisted as failed"
+            );
+            return Ok(CycleOutcome::DidWork);
+        }
+    };
+
+    let mut first_error = None;
+    for (task, result) in tasks.iter().zip(results) {
+        match result {
+            Ok((width, height, bytes_size)) => {
+                finish_thumbnail_success(conn, config, task.id, width, height, bytes_size)?;
+                tracing::info!(
+                    thumb_key = %task.thumb_key,
+                    width,
+                    height,
+                    bytes_size,
+                    "thumbnail task finished successfully"
+                );
+                notify_thumbnail_success(config, task, width, height, bytes_size);
+            }
+            Err(error) => {
+                let error_code = classify_thumbnail_error(&error);
+                let error_message = sanitize_error_message(&error.to_string(), config);
+                let _ = finish_thumbnail_failure(
+                    conn,
+                    config,
+                    task.id,
+                    task.error_count,
+                    error_code,
+                    &error_message,
+                );
+                notify_thumbnail_failure(config, task, error_code, &error_message);
+                tracing::error!(
+                    thumb_key = %task.thumb_key,
+                    error_code = error_code.as_str(),
+                    error = %error_message,
+                    "thumbnail task failed and persisted as failed"
+                );
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+
+    match first_error {
+        Some(error) if propagate_task_errors => Err(error),
+        _ => Ok(CycleOutcome::DidWork),
+    }
+}
+
+fn notify_job_completion(
+    conn: &rusqlite::Connection,
+    config: &WorkerConfig,
+    job: &JobRecord,
+    outcome: &str,
+    error: Option<&str>,
+) {
+    if config.completion_webhook_url.is_none() {
+        return;
+    }
+    notify_completion(
+        config,
+        job.id.clone().into(),
+        job.kind.as_str(),
+        outcome,
+        job_counters(conn, &job.id),
+        error,
+    );
+}
+
+fn notify_thumbnail_success(
+    config: &WorkerConfig,
+    task: &ThumbnailTaskRecord,
+    width: i64,
+    height: i64,
+    bytes_size: i64,
+) {
+    notify_completion(
+        config,
+        task.thumb_key.clone().into(),
+        "thumbnail",
+        "completed",
+        serde_json::json!({ "width": width, "height": height, "bytes_size": bytes_size }),
+        None,
+    );
+}
+
+/// Retryable thumbnail failures go back to the queue, so only terminal and permanent codes
+/// and tasks that just went `dead` are reported.
+fn notify_thumbnail_failure(
+    config: &WorkerConfig,
+    task: &ThumbnailTaskRecord,
+    error_code: ErrorCode,
+    error_message: &str,
+) {
+    if !is_terminal_thumbnail_error(error_code)
+        && !is_permanent_thumbnail_error(config, error_code)
+        && !thumbnail_failure_exhausts_retries(config, task.error_count, error_code)
+    {
+        return;
+    }
+    notify_completion(
+        config,
+        task.thumb_key.clone().into(),
+        "thumbnail",
+        "failed",
+        serde_json::json!({ "error_code": error_code.as_str() }),
+        Some(error_message),
+    );
+}
+
+/// Exits the process: 0 with "config OK", 1 after printing every issue to stderr.
+fn run_validate_config(config_path: Option<&Path>, config_format: &str) -> ! {
+    let issues = match ConfigFormat::parse(config_format)
+        .and_then(|format| WorkerConfig::load_for_validation(config_path, format))
+    {
+        Ok(config) => validate_config_runtime(&config),
+        Err(error) => vec![format!("failed to load config: {error:#}")],
+    };
+    if issues.is_empty() {
+        println!("config OK");
+        std::process::exit(0);
+    }
+    for issue in &issues {
+        eprintln!("{issue}");
+    }
+    std::process::exit(1);
+}
+
+fn run_list_libraries(
+    conn: &rusqlite::Connection,
+    config: &WorkerConfig,
+    output_json: bool,
+) -> Result<()> {
+    let libraries = list_library_roots(conn)?;
+    let mut entries = Vec::with_capacity(libraries.len());
+    for library in libraries {
+        let reachable = resolve_root_under_libraries(
+            &config.libraries_roots_real,
+            Path::new(&library.root_path),
+            config.allow_symlinked_roots,
+        )
+        .map(|path| path.is_dir())
+        .unwrap_or(false);
+        entries.push((library, reachable));
+    }
+
+    if output_json {
+        let payload = entries
+            .iter()
+            .map(|(library, reachable)| {
+                serde_json::json!({
+                    "id": library.id,
+                    "name": library.name,
+                    "root_path": library.root_path,
+                    "reachable": reachable,
+                    "last_scanned_at": library.last_scanned_at,
+                    "file_count": library.file_count,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("no libraries recorded");
+        return Ok(());
+    }
+    for (library, reachable) in &entries {
+        println!(
+            "library={} root_path={} reachable={} last_scanned_at={} files={}",
+            library.name,
+            library.root_path,
+            reachable,
+            library.last_scanned_at.as_deref().unwrap_or("never"),
+            library.file_count
+        );
+    }
+    Ok(())
+}
+
+fn run_job_log(conn: &rusqlite::Connection, job_id: &str, output_json: bool) -> Result<()> {
+    let entries = list_job_log_entries(conn, job_id)?;
+    if output_json {
+        let payload = entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "id": entry.id,
+                    "job_id": entry.job_id,
+                    "logged_at": entry.logged_at,
+                    "level": entry.level,
+                    "message": entry.message,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("no log entries for job {job_id}");
+    }
+    for entry in &entries {
+        println!("{} [{}] {}", entry.logged_at, entry.level, entry.message);
+    }
+    Ok(())
+}
+
+fn run_batch_submit_jobs(conn: &mut rusqlite::Connection, path: &Path) -> Result<()> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read job specs: {}", path.display()))?;
+    let specs: Vec<NewJobSpec> = serde_json::from_str(&raw)
+        .with_context(|| format!("invalid job spec array: {}", path.display()))?;
+    let inserted = insert_jobs_batch(conn, &specs)?;
+    println!(
+        "submitted {inserted} job(s); skipped {} duplicate(s) of pending jobs",
+        specs.len() - inserted
+    );
+    Ok(())
+}
+
+fn run_list_dead_lettered(conn: &rusqlite::Connection, output_json: bool) -> Result<()> {
+    let jobs = list_dead_lettered_jobs(conn)?;
+    if output_json {
+        let payload = jobs
+            .iter()
+            .map(|job| {
+                serde_json::json!({
+                    "id": job.id,
+                    "kind": job.kind.as_str(),
+                    "priority": job.priority,
+                    "payload": job.payload,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if jobs.is_empty() {
+        println!("no dead-lettered jobs");
+    }
+    for job in &jobs {
+        println!(
+            "job={} kind={} priority={}",
+            job.id,
+            job.kind.as_str(),
+            job.priority
+        );
+    }
+    Ok(())
+}
+
+fn library_id_by_name(conn: &rusqlite::Connection, library_name: &str) -> Result<i64> {
+    list_library_roots(conn)?
+        .into_iter()
+        .find(|library| library.name == library_name)
+        .map(|library| library.id)
+        .ok_or_else(|| anyhow!("unknown library: {library_name}"))
+}
+
+fn run_list_permanent_thumbnail_failures(
+    conn: &rusqlite::Connection,
+    output_json: bool,
+) -> Result<()> {
+    let tasks = list_permanent_thumbnail_failures(conn)?;
+    if output_json {
+        let payload = tasks
+            .iter()
+            .map(|task| {
+                serde_json::json!({
+                    "thumb_key": task.thumb_key,
+                    "file_id": task.file_id,
+                    "media_type": task.media_type,
+                    "relative_path": task.relative_path,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if tasks.is_empty() {
+        println!("no permanent thumbnail failures");
+    }
+    for task in &tasks {
+        println!(
+            "thumb_key={} file_id={} media_type={} path={}",
+            task.thumb_key, task.file_id, task.media_type, task.relative_path
+        );
+    }
+    Ok(())
+}
+
+fn run_list_duplicates(
+    conn: &rusqlite::Connection,
+    min_size_bytes: Option<i64>,
+    output_json: bool,
+) -> Result<()> {
+    let groups = list_duplicate_groups(conn, min_size_bytes)?;
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    };
+    if output_json {
+        let payload = groups
+            .iter()
+            .map(|group| {
+                serde_json::json!({
+                    "content_hash": hex(&group.content_hash),
+                    "algorithm": group.algorithm,
+                    "file_count": group.file_count,
+                    "total_size_bytes": group.total_size_bytes,
+                    "files": group
+                        .files
+                        .iter()
+                        .map(|file| {
+                            serde_json::json!({
+                                "file_id": file.file_id,
+                                "library_name": file.library_name,
+                                "relative_path": file.relative_path,
+                                "size_bytes": file.size_bytes,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    if groups.is_empty() {
+        println!("no duplicate files");
+    }
+    for group in &groups {
+        println!(
+            "{}:{} files={} total_bytes={}",
+            group.algorithm,
+            hex(&group.content_hash),
+            group.file_count,
+            group.total_size_bytes
+        );
+        for file in &group.files {
+            println!(
+                "  file_id={} library={} path={}",
+                file.file_id, file.library_name, file.relative_path
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_scan_orphan_thumbnails(
+    conn: &rusqlite::Connection,
+    config: &WorkerConfig,
+    delete: bool,
+    output_json: bool,
+) -> Result<()> {
+    let report = if delete {
+        delete_orphan_thumbnails(conn, config)?
+    } else {
+        run_orphan_thumbnail_scan(conn, config)?
+    };
+    if output_json {
+        let payload = serde_json::json!({
+            "orphan_count": report.orphan_count,
+            "orphan_bytes": report.orphan_bytes,
+            "sample_paths": report
+                .orphan_paths
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect::<Vec<_>>(),
+            "deleted_count": report.deleted_count,
+        });
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    println!(
+        "orphan thumbnails: {} files, {} bytes",
+        report.orphan_count, report.orphan_bytes
+    );
+    for path in &report.orphan_paths {
+        println!("{}", path.display());
+    }
+    if delete {
+        println!("deleted {} orphan thumbnails", report.deleted_count);
+    }
+    Ok(())
+}
+
+fn run_retry_histogram(
+    conn: &rusqlite::Connection,
+    kind: Option<&str>,
+    output_json: bool,
+) -> Result<()> {
+    let queues = match kind {
+        Some(raw) => vec![RetryQueue::parse(raw)
+            .ok_or_else(|| anyhow::anyhow!("--kind must be one of: hash, thumbnail"))?],
+        None => vec![RetryQueue::Hash, RetryQueue::Thumbnail],
+    };
+
+    let mut histograms = Vec::with_capacity(queues.len());
+    for queue in queues {
+        histograms.push((queue, list_retry_buckets(conn, queue)?));
+    }
+
+    if output_json {
+        let payload = histograms
+            .iter()
+            .map(|(queue, buckets)| {
+                serde_json::json!({
+                    "kind": queue.as_str(),
+                    "buckets": buckets
+                        .iter()
+                        .map(|bucket| {
+                            serde_json::json!({
+                                "error_count": bucket.error_count,
+                                "items": bucket.items,
+                                "soonest_retry_after": bucket.soonest_retry_after,
+                                "latest_retry_after": bucket.latest_retry_after,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    for (queue, buckets) in &histograms {
+        if buckets.is_empty() {
+            println!("kind={} no retrying items", queue.as_str());
+            continue;
+        }
+        for bucket in buckets {
+            println!(
+                "kind={} error_count={} items={} soonest_retry_after={} latest_retry_after={}",
+                queue.as_str(),
+                bucket.error_count,
+                bucket.items,
+                bucket.soonest_retry_after.as_deref().unwrap_or("none"),
+                bucket.latest_retry_after.as_deref().unwrap_or("none")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Events go to stdout; `RUST_LOG` filters them (default `info`).
+fn build_log_subscriber(format: LogFormat) -> Box<dyn tracing::Subscriber + Send + Sync> {
+    build_log_subscriber_with_writer(format, std::io::stdout)
+}
+
+fn build_log_subscriber_with_writer<W>(
+    format: LogFormat,
+    writer: W,
+) -> Box<dyn tracing::Subscriber + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(builder.with_target(false).finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+/// Sleeps in short slices so a shutdown request does not wait out a long backoff.
+fn sleep_with_jitter(base_seconds: u64, jitter_millis: u64, shutdown: &AtomicBool) {
     let bounded_base = base_seconds.max(1);
     let jitter = if jitter_millis == 0 {
         0
     } else {
         rand::thread_rng().gen_range(0..=jitter_millis)
     };
-    thread::sleep(Duration::from_secs(bounded_base) + Duration::from_millis(jitter));
+    let deadline =
+        Instant::now() + Duration::from_secs(bounded_base) + Duration::from_millis(jitter);
+    while !shutdown.load(Ordering::SeqCst) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        thread::sleep(remaining.min(Duration::from_millis(200)));
+    }
 }
 
-fn next_idle_backoff_seconds(current: u64, base: u64, max: u64) -> u64 {
+/// Token bucket pacing daemon cycles, each of which makes at most one claim. The bucket
+/// holds one second of tokens (at least one), so a backlog is drained at `rate` instead
+/// of in a burst.
+struct ClaimThrottle {
+    rate: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl ClaimThrottle {
+    fn new(rate: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.max(1.0),
+            updated_at: now,
+        }
+    }
+
+    /// Spends one token and returns how long to wait before claiming. A negative balance
+    /// carries over, so the wait is charged to this call and not the next.
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        self.updated_at = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Grows the backoff by `multiplier`, rounding down but always by at least a second.
+fn next_idle_backoff_seconds(current: u64, base: u64, max: u64, multiplier: f64) -> u64 {
     let bounded_base = base.max(1);
     let bounded_max = max.max(bounded_base);
-    current.max(bounded_base).saturating_mul(2).min(bounded_max)
+    let current = current.max(bounded_base);
+    let grown = (current as f64 * multiplier.max(1.0)).floor() as u64;
+    grown.max(current.saturating_add(1)).min(bounded_max)
 }
 
 fn sanitize_error_message(raw: &str, config: &WorkerConfig) -> String {
     let mut sanitized = raw.to_string();
-    let libraries_real = config.libraries_root_real.to_string_lossy().to_string();
-    let thumbs_real = config.thumbs_root_real.to_string_lossy().to_string();
-    if !libraries_real.is_empty() {
-        sanitized = sanitized.replace(&libraries_real, "/libraries");
+    for libraries_root_real in &config.libraries_roots_real {
+        let libraries_real = libraries_root_real.to_string_lossy().to_string();
+        if !libraries_real.is_empty() {
+            sanitized = sanitized.replace(&libraries_real, "/libraries");
+        }
     }
+    let thumbs_real = config.thumbs_root_real.to_string_lossy().to_string();
     if !thumbs_real.is_empty() {
         sanitized = sanitized.replace(&thumbs_real, "/state/thumbs");
     }
@@ -326,15 +1448,218 @@ fn sanitize_error_message(raw: &str, config: &WorkerConfig) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::next_idle_backoff_seconds;
+    use super::{
+        apply_ffmpeg_probe, build_log_subscriber_with_writer, next_idle_backoff_seconds,
+        run_daemon_loop, run_worker_cycle, ClaimThrottle, CycleOutcome,
+    };
+    use crate::config::LogFormat;
+    use crate::db::{claim_thumbnail_task, detect_schema_features, open_connection};
+    use crate::test_support::{create_worker_schema, test_config, TestDir};
+    use anyhow::anyhow;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn idle_backoff_is_bounded_and_monotonic() {
         let base = 5;
         let max = 20;
-        assert_eq!(next_idle_backoff_seconds(5, base, max), 10);
-        assert_eq!(next_idle_backoff_seconds(10, base, max), 20);
-        assert_eq!(next_idle_backoff_seconds(20, base, max), 20);
-        assert_eq!(next_idle_backoff_seconds(30, base, max), 20);
+        assert_eq!(next_idle_backoff_seconds(5, base, max, 2.0), 10);
+        assert_eq!(next_idle_backoff_seconds(10, base, max, 2.0), 20);
+        assert_eq!(next_idle_backoff_seconds(20, base, max, 2.0), 20);
+        assert_eq!(next_idle_backoff_seconds(30, base, max, 2.0), 20);
+    }
+
+    #[test]
+    fn missing_ffmpeg_disables_video_claims_or_stops_the_daemon() {
+        let dir = TestDir::new("main-ffmpeg-probe");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        config.thumbnail_video_concurrency = 2;
+
+        let session = apply_ffmpeg_probe(&config, Ok("6.1".to_string())).expect("ffmpeg found");
+        assert_eq!(session.thumbnail_video_concurrency, 2);
+
+        let session = apply_ffmpeg_probe(&config, Err(anyhow!("not found"))).expect("degrade");
+        assert_eq!(session.thumbnail_video_concurrency, 0);
+
+        let mut conn = rusqlite::Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+        conn.execute(
+            "
+            INSERT INTO thumbnails (thumb_key, file_id, media_type, source_size_bytes, source_mtime_ns)
+            VALUES ('v1', 1, 'video', 1, 1)
+            ",
+            [],
+        )
+        .expect("insert video task");
+        assert!(claim_thumbnail_task(&mut conn, &session)
+            .expect("claim")
+            .is_none());
+        claim_thumbnail_task(&mut conn, &config).expect("claim");
+        let status: String = conn
+            .query_row(
+                "SELECT status FROM thumbnails WHERE thumb_key = 'v1'",
+                [],
+                |row| row.get(0),
+            )
+            .expect("read status");
+        assert_eq!(status, "running");
+
+        config.thumbnail_require_ffmpeg = true;
+        let error = apply_ffmpeg_probe(&config, Err(anyhow!("not found"))).expect_err("required");
+        assert!(format!("{error:#}").contains("thumbnail_require_ffmpeg"));
+    }
+
+    #[test]
+    fn backoff_follows_the_configured_multiplier_up_to_the_cap() {
+        let mut series = vec![5];
+        for _ in 0..8 {
+            let current = *series.last().unwrap();
+            series.push(next_idle_backoff_seconds(current, 5, 60, 1.5));
+        }
+        assert_eq!(series, vec![5, 7, 10, 15, 22, 33, 49, 60, 60]);
+        // A small multiplier still makes progress instead of rounding back down.
+        assert_eq!(next_idle_backoff_seconds(5, 5, 60, 1.1), 6);
+    }
+
+    #[test]
+    fn claim_throttle_allows_a_burst_then_paces_to_rate() {
+        let start = Instant::now();
+        let mut throttle = ClaimThrottle::new(2.0, start);
+        assert_eq!(throttle.take(start), Duration::ZERO);
+        assert_eq!(throttle.take(start), Duration::ZERO);
+        assert_eq!(throttle.take(start), Duration::from_millis(500));
+        // The caller slept 500ms for the third claim; the fourth waits one more interval.
+        let after_sleep = start + Duration::from_millis(500);
+        assert_eq!(throttle.take(after_sleep), Duration::from_millis(500));
+
+        // A long idle gap refills at most one second of tokens.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(throttle.take(later), Duration::ZERO);
+        assert_eq!(throttle.take(later), Duration::ZERO);
+        assert!(throttle.take(later) > Duration::ZERO);
+
+        // Sub-1/s rates still allow a single claim up front.
+        let mut slow = ClaimThrottle::new(0.5, start);
+        assert_eq!(slow.take(start), Duration::ZERO);
+        assert_eq!(slow.take(start), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn daemon_loop_exits_once_shutdown_is_requested() {
+        let dir = TestDir::new("daemon-shutdown");
+        let libraries = dir.path().join("libraries");
+        std::fs::create_dir_all(&libraries).expect("create libraries");
+        let mut config = test_config(&libraries, dir.path());
+        config.rust_worker_poll_seconds = 30;
+        let mut conn = open_connection(&config.database_path).expect("open db");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("schema features");
+
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let signaller = {
+            let shutdown = Arc::clone(&shutdown);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                shutdown.store(true, Ordering::SeqCst);
+            })
+        };
+        let started = Instant::now();
+        run_daemon_loop(&mut conn, &config, &schema, &shutdown).expect("daemon loop");
+        signaller.join().expect("signaller thread");
+        // The loop was idling in a 30s backoff; it must not wait that out.
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn daemon_loop_exits_after_the_idle_timeout() {
+        let dir = TestDir::new("daemon-idle-exit");
+        let libraries = dir.path().join("libraries");
+        std::fs::create_dir_all(&libraries).expect("create libraries");
+        let mut config = test_config(&libraries, dir.path());
+        config.rust_worker_poll_seconds = 1;
+        config.rust_worker_max_poll_seconds = 1;
+        config.rust_worker_idle_exit_seconds = Some(2);
+        let mut conn = open_connection(&config.database_path).expect("open db");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("schema features");
+
+        // An empty queue makes every cycle idle.
+        let shutdown = AtomicBool::new(false);
+        let started = Instant::now();
+        run_daemon_loop(&mut conn, &config, &schema, &shutdown).expect("daemon loop");
+        let elapsed = started.elapsed();
+        assert!(
+            elapsed >= Duration::from_secs(2),
+            "exited early: {elapsed:?}"
+        );
+        assert!(
+            elapsed < Duration::from_secs(10),
+            "exited late: {elapsed:?}"
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct CapturedLog(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().expect("log buffer").extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_log_subscriber_writes_one_json_object_per_line() {
+        let dir = TestDir::new("json-logging");
+        let libraries = dir.path().join("libraries");
+        std::fs::create_dir_all(&libraries).expect("create libraries");
+        let config = test_config(&libraries, dir.path());
+        let mut conn = open_connection(&config.database_path).expect("open db");
+        create_worker_schema(&conn);
+        let schema = detect_schema_features(&conn).expect("schema features");
+        conn.execute(
+            "INSERT INTO jobs(id, kind, status, payload) VALUES ('job-1', 'hash', 'pending', '{}')",
+            [],
+        )
+        .expect("insert job");
+
+        let captured = CapturedLog::default();
+        let writer = captured.clone();
+        let subscriber = build_log_subscriber_with_writer(LogFormat::Json, move || writer.clone());
+        let outcomes = tracing::subscriber::with_default(subscriber, || {
+            let shutdown = AtomicBool::new(false);
+            let first = run_worker_cycle(&mut conn, &config, &schema, None, true, &shutdown);
+            let second = run_worker_cycle(&mut conn, &config, &schema, None, true, &shutdown);
+            (first.expect("job cycle"), second.expect("idle cycle"))
+        });
+        assert_eq!(outcomes, (CycleOutcome::DidWork, CycleOutcome::Idle));
+
+        let output = String::from_utf8(captured.0.lock().expect("log buffer").clone())
+            .expect("utf-8 log output");
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line is a JSON object"))
+            .collect();
+        assert!(!lines.is_empty());
+        for line in &lines {
+            assert!(line["timestamp"].is_string(), "{line}");
+            assert!(line["level"].is_string(), "{line}");
+            assert!(line["target"].is_string(), "{line}");
+            assert!(line["fields"]["message"].is_string(), "{line}");
+        }
+        let claimed = lines
+            .iter()
+            .find(|line| line["fields"]["message"] == "job claimed")
+            .expect("job claimed event");
+        assert_eq!(claimed["level"], "INFO");
+        assert_eq!(claimed["fields"]["worker_id"], config.worker_id.as_str());
+        assert_eq!(claimed["fields"]["job_id"], "job-1");
     }
 }
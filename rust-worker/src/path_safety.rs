@@ -1,6 +1,7 @@
 use std::path::{Component, Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
+use unicode_normalization::UnicodeNormalization;
 
 pub fn normalize_library_name(raw_name: &str) -> Result<String> {
     let name = raw_name.trim();
@@ -19,10 +20,25 @@ pub fn normalize_library_name(raw_name: &str) -> Result<String> {
     Ok(name.to_string())
 }
 
+/// Validates a stored relative path and returns it with backslashes turned into `/`, so
+/// paths recorded from Windows-synced libraries resolve on Unix.
 pub fn validate_relative_path(raw_path: &str) -> Result<PathBuf> {
-    if raw_path.starts_with('/') {
+    let normalized = raw_path.replace('\\', "/");
+    if normalized.starts_with("//") {
+        bail!("UNC paths are not allowed");
+    }
+    if normalized.starts_with('/') {
         bail!("path must be relative");
     }
+    if has_drive_prefix(&normalized) {
+        bail!("Windows drive prefixes are not allowed");
+    }
+    validate_literal_relative_path(&normalized)
+}
+
+/// Validates a stored relative path as-is. `\` and `:` are ordinary name bytes on Unix,
+/// so `a\b.jpg` stays one component here.
+pub fn validate_literal_relative_path(raw_path: &str) -> Result<PathBuf> {
     if raw_path.contains('~') {
         bail!("home expansion is not allowed");
     }
@@ -30,7 +46,7 @@ pub fn validate_relative_path(raw_path: &str) -> Result<PathBuf> {
         bail!("environment variable expansion is not allowed");
     }
 
-    let path = Path::new(raw_path);
+    let path = PathBuf::from(raw_path);
     for component in path.components() {
         match component {
             Component::Normal(_) | Component::CurDir => {}
@@ -39,13 +55,35 @@ pub fn validate_relative_path(raw_path: &str) -> Result<PathBuf> {
         }
     }
 
-    Ok(path.to_path_buf())
+    Ok(path)
+}
+
+/// Picks the relative path for a stored row under `root`: the literal name when it exists
+/// on disk, otherwise the backslash-normalized form from [`validate_relative_path`].
+pub fn resolve_stored_relative_path(root: &Path, raw_path: &str) -> Result<PathBuf> {
+    if let Ok(literal) = validate_literal_relative_path(raw_path) {
+        if std::fs::symlink_metadata(root.join(&literal)).is_ok() {
+            return Ok(literal);
+        }
+    }
+    validate_relative_path(raw_path)
+}
+
+/// `C:` and friends, which Unix would otherwise accept as an ordinary first component.
+fn has_drive_prefix(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
 }
 
-pub fn to_posix_relative_path(path: &Path) -> Result<String> {
+/// Joins `path` with `/`. With `normalize_unicode`, each component is stored in NFC, so
+/// the key no longer matches the on-disk bytes of NFD names; see [`find_unicode_variant`].
+pub fn to_posix_relative_path(path: &Path, normalize_unicode: bool) -> Result<String> {
     let mut parts = Vec::new();
     for component in path.components() {
         match component {
+            Component::Normal(value) if normalize_unicode => {
+                parts.push(value.to_string_lossy().nfc().collect::<String>())
+            }
             Component::Normal(value) => parts.push(value.to_string_lossy().to_string()),
             Component::CurDir => {}
             _ => bail!("relative path contains forbidden component"),
@@ -59,21 +97,104 @@ pub fn to_posix_relative_path(path: &Path) -> Result<String> {
     Ok(parts.join("/"))
 }
 
-pub fn resolve_root_under_libraries(libraries_root_real: &Path, root: &Path) -> Result<PathBuf> {
+/// Finds the on-disk path under `root` for a relative path stored in NFC, matching each
+/// component against directory entries by NFC form. `None` when nothing matches.
+pub fn find_unicode_variant(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut current = root.to_path_buf();
+    for component in relative.components() {
+        let Component::Normal(wanted) = component else {
+            continue;
+        };
+        let direct = current.join(wanted);
+        if std::fs::symlink_metadata(&direct).is_ok() {
+            current = direct;
+            continue;
+        }
+        let wanted = wanted.to_string_lossy().nfc().collect::<String>();
+        let matched = std::fs::read_dir(&current).ok()?.find_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            (name.to_string_lossy().nfc().collect::<String>() == wanted).then(|| entry.path())
+        })?;
+        current = matched;
+    }
+    Some(current)
+}
+
+/// Fixed-size lookup key for a normalized POSIX relative path: the first
+/// eight bytes of its blake3 digest.
+pub fn relative_path_hash(relative_path: &str) -> i64 {
+    let digest = blake3::hash(relative_path.as_bytes());
+    let mut prefix = [0_u8; 8];
+    prefix.copy_from_slice(&digest.as_bytes()[..8]);
+    i64::from_le_bytes(prefix)
+}
+
+/// Resolves a library root and requires it to sit under one of the configured libraries
+/// roots (or, with `allow_symlinked_roots`, to be the target of a direct-child symlink).
+pub fn resolve_root_under_libraries(
+    libraries_roots_real: &[PathBuf],
+    root: &Path,
+    allow_symlinked_roots: bool,
+) -> Result<PathBuf> {
     let root_real = root
         .canonicalize()
         .with_context(|| format!("failed to resolve library root: {}", root.display()))?;
 
-    if !root_real.starts_with(libraries_root_real) {
-        bail!("path escapes /libraries: {}", root_real.display());
+    for libraries_root_real in libraries_roots_real {
+        if root_real.starts_with(libraries_root_real) {
+            return Ok(root_real);
+        }
+        if allow_symlinked_roots && is_symlinked_root_target(libraries_root_real, root, &root_real)?
+        {
+            return Ok(root_real);
+        }
     }
 
-    Ok(root_real)
+    bail!("path escapes /libraries: {}", root_real.display());
+}
+
+/// True when `root` is a direct-child symlink of /libraries, or `root_real` is
+/// the resolved target of one.
+fn is_symlinked_root_target(
+    libraries_root_real: &Path,
+    root: &Path,
+    root_real: &Path,
+) -> Result<bool> {
+    if root.parent() == Some(libraries_root_real) && is_symlink(root) {
+        return Ok(true);
+    }
+
+    let entries = std::fs::read_dir(libraries_root_real).with_context(|| {
+        format!(
+            "failed to read libraries root: {}",
+            libraries_root_real.display()
+        )
+    })?;
+    for entry in entries {
+        let link = entry?.path();
+        if is_symlink(&link) && link.canonicalize().ok().as_deref() == Some(root_real) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn is_symlink(path: &Path) -> bool {
+    std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::validate_relative_path;
+    use super::{
+        find_unicode_variant, relative_path_hash, resolve_root_under_libraries,
+        resolve_stored_relative_path, to_posix_relative_path, validate_relative_path,
+    };
+    use crate::test_support::TestDir;
+    use std::path::Path;
 
     #[test]
     fn validate_relative_path_rejects_path_traversal() {
@@ -91,4 +212,111 @@ mod tests {
     fn validate_relative_path_accepts_normal_relative_path() {
         assert!(validate_relative_path("media/photo.jpg").is_ok());
     }
+
+    #[test]
+    fn validate_relative_path_normalizes_backslashes_and_rejects_windows_roots() {
+        assert_eq!(
+            validate_relative_path("a\\b\\c.jpg").expect("backslash path"),
+            std::path::PathBuf::from("a/b/c.jpg")
+        );
+        assert!(validate_relative_path("C:\\evil").is_err());
+        assert!(validate_relative_path("c:evil").is_err());
+        assert!(validate_relative_path("\\\\server\\share\\file.jpg").is_err());
+        assert!(validate_relative_path("\\rooted.jpg").is_err());
+        assert!(validate_relative_path("..\\escape.jpg").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stored_relative_paths_prefer_literal_names_on_disk() {
+        let dir = TestDir::new("literal-relative");
+        let root = dir.path();
+        std::fs::write(root.join("a\\b.jpg"), b"literal").expect("write literal");
+        std::fs::write(root.join("C:notes.txt"), b"colon").expect("write colon");
+        std::fs::create_dir_all(root.join("x")).expect("create dir");
+        std::fs::write(root.join("x/y.jpg"), b"nested").expect("write nested");
+
+        assert_eq!(
+            resolve_stored_relative_path(root, "a\\b.jpg").expect("literal"),
+            Path::new("a\\b.jpg")
+        );
+        assert_eq!(
+            resolve_stored_relative_path(root, "C:notes.txt").expect("colon"),
+            Path::new("C:notes.txt")
+        );
+        assert_eq!(
+            resolve_stored_relative_path(root, "x\\y.jpg").expect("normalized"),
+            Path::new("x/y.jpg")
+        );
+        assert!(resolve_stored_relative_path(root, "D:absent.txt").is_err());
+        assert!(resolve_stored_relative_path(root, "../escape.jpg").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_roots_require_opt_in() {
+        let dir = TestDir::new("symlinked-root");
+        let libraries = dir.path().join("libraries");
+        let external = dir.path().join("volume");
+        std::fs::create_dir_all(&libraries).expect("create libraries");
+        std::fs::create_dir_all(&external).expect("create volume");
+        let libraries_real = vec![libraries.canonicalize().expect("resolve libraries")];
+        let link = libraries_real[0].join("nas");
+        std::os::unix::fs::symlink(&external, &link).expect("create root symlink");
+        let external_real = external.canonicalize().expect("resolve volume");
+
+        assert!(resolve_root_under_libraries(&libraries_real, &link, false).is_err());
+        assert_eq!(
+            resolve_root_under_libraries(&libraries_real, &link, true).expect("link root"),
+            external_real
+        );
+        assert_eq!(
+            resolve_root_under_libraries(&libraries_real, &external_real, true)
+                .expect("stored target root"),
+            external_real
+        );
+        assert!(resolve_root_under_libraries(&libraries_real, dir.path(), true).is_err());
+    }
+
+    #[test]
+    fn nfc_and_nfd_paths_share_one_stored_key() {
+        let nfc = "photos/caf\u{e9}.jpg";
+        let nfd = "photos/cafe\u{301}.jpg";
+        assert_ne!(
+            to_posix_relative_path(Path::new(nfc), false).expect("nfc key"),
+            to_posix_relative_path(Path::new(nfd), false).expect("nfd key")
+        );
+        assert_eq!(
+            to_posix_relative_path(Path::new(nfd), true).expect("normalized nfd key"),
+            nfc
+        );
+        assert_eq!(
+            to_posix_relative_path(Path::new(nfc), true).expect("normalized nfc key"),
+            nfc
+        );
+
+        let dir = TestDir::new("unicode-variant");
+        std::fs::create_dir_all(dir.path().join("photos")).expect("create dir");
+        std::fs::write(dir.path().join(nfd), b"x").expect("write nfd file");
+        assert_eq!(
+            find_unicode_variant(dir.path(), Path::new(nfc)),
+            Some(dir.path().join(nfd))
+        );
+        assert_eq!(
+            find_unicode_variant(dir.path(), Path::new("photos/missing.jpg")),
+            None
+        );
+    }
+
+    #[test]
+    fn relative_path_hash_is_stable_and_path_sensitive() {
+        assert_eq!(
+            relative_path_hash("media/photo.jpg"),
+            relative_path_hash("media/photo.jpg")
+        );
+        assert_ne!(
+            relative_path_hash("media/photo.jpg"),
+            relative_path_hash("media/Photo.jpg")
+        );
+    }
 }
@@ -0,0 +1,122 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// Holds the daemon's pid file and removes it when dropped, including on panic unwind.
+#[derive(Debug)]
+pub struct PidFileGuard {
+    path: PathBuf,
+}
+
+impl PidFileGuard {
+    /// Fails when the file names another running process; a stale or unreadable pid is
+    /// replaced. Liveness is read from `/proc`, so elsewhere every pid counts as stale.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        if let Some(content) = read_pid_file(path)? {
+            let pid = content.trim().parse::<u32>().ok();
+            // A restarted container reuses the same pid, so our own pid is stale too.
+            if let Some(pid) =
+                pid.filter(|&pid| pid != std::process::id() && process_is_running(pid))
+            {
+                bail!(
+                    "another daemon (pid {pid}) holds pid file {}",
+                    path.display()
+                );
+            }
+            fs::remove_file(path)
+                .with_context(|| format!("failed to remove stale pid file: {}", path.display()))?;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed to create pid file directory: {}", parent.display())
+            })?;
+        }
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                bail!(
+                    "pid file {} was created by another daemon starting concurrently",
+                    path.display()
+                );
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to create pid file: {}", path.display()))
+            }
+        };
+        writeln!(file, "{}", std::process::id())
+            .with_context(|| format!("failed to write pid file: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for PidFileGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// `None` when the file is missing.
+fn read_pid_file(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => {
+            Err(error).with_context(|| format!("failed to read pid file: {}", path.display()))
+        }
+    }
+}
+
+fn process_is_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PidFileGuard;
+    use crate::test_support::TestDir;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn guard_writes_own_pid_and_removes_the_file_on_drop() {
+        let dir = TestDir::new("pid-file-fresh");
+        let path = dir.path().join("run").join("worker.pid");
+        let guard = PidFileGuard::acquire(&path).expect("acquire fresh pid file");
+        let content = fs::read_to_string(&path).expect("read pid file");
+        assert_eq!(content.trim(), std::process::id().to_string());
+        drop(guard);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn stale_pid_files_are_replaced_and_live_ones_refused() {
+        let dir = TestDir::new("pid-file-stale");
+        let path = dir.path().join("worker.pid");
+        fs::write(&path, "999999999\n").expect("write stale pid");
+        let guard = PidFileGuard::acquire(&path).expect("stale pid is overwritten");
+        drop(guard);
+
+        fs::write(&path, "not a pid").expect("write garbage");
+        drop(PidFileGuard::acquire(&path).expect("garbage is treated as stale"));
+
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .expect("spawn sleep");
+        fs::write(&path, format!("{}\n", child.id())).expect("write live pid");
+        let error = PidFileGuard::acquire(&path).expect_err("live pid refused");
+        assert!(error.to_string().contains(&child.id().to_string()));
+        assert!(
+            path.exists(),
+            "a refused acquire leaves the other daemon's file"
+        );
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
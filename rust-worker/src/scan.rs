@@ -1,23 +1,205 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Context, Result};
-use rusqlite::{params, Connection};
+use glob::Pattern;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use image::ImageReader;
+use rusqlite::{params, Connection, ToSql};
 use serde_json::Value;
 
-use crate::config::WorkerConfig;
-use crate::db::{refresh_job_lease, JobRecord};
+use crate::config::{normalize_extensions, WorkerConfig};
+use crate::db::{
+    append_job_log, prune_hardlink_aliases, record_hardlink_alias, refresh_job_lease,
+    CommitYielder, JobLogLevel, JobRecord, SchemaFeatures,
+};
 use crate::path_safety::{
-    normalize_library_name, resolve_root_under_libraries, to_posix_relative_path,
+    normalize_library_name, relative_path_hash, resolve_root_under_libraries,
+    to_posix_relative_path,
 };
 
 #[derive(Debug, Clone)]
 struct LibraryTarget {
     id: i64,
+    name: String,
     root_path_real: PathBuf,
 }
 
+#[derive(Debug, Clone)]
+struct ScannedFileRow {
+    library_id: i64,
+    relative_path: String,
+    relative_path_hash: Option<i64>,
+    size_bytes: i64,
+    mtime_ns: i64,
+    inode: Option<i64>,
+    device: Option<i64>,
+    permissions: Option<FilePermissions>,
+    media_type: Option<&'static str>,
+    extension: Option<String>,
+    mime_type: Option<&'static str>,
+    image_dimensions: Option<(u32, u32)>,
+    scan_session_id: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FilePermissions {
+    mode: i64,
+    uid: i64,
+    gid: i64,
+}
+
+#[derive(Debug)]
+struct ScanOptions {
+    batch_size: usize,
+    exclude: ExcludeMatcher,
+    follow_symlinks: bool,
+    /// The library root is depth 1; directories at this depth are not descended.
+    max_depth: Option<usize>,
+    respect_ignore_files: bool,
+    extensions: ExtensionFilter,
+    job_deadline: Option<Instant>,
+    library_max_runtime: Option<Duration>,
+    /// `[libraries.<name>]` exclude overrides by library id; used instead of `exclude`.
+    library_excludes: HashMap<i64, ExcludeMatcher>,
+    optional_columns: OptionalFileColumns,
+}
+
+/// `library_files` columns the upsert writes only when the schema has them (and, for
+/// dimensions, when probing is enabled).
+#[derive(Debug, Clone, Copy, Default)]
+struct OptionalFileColumns {
+    /// `extension`, `mime_type`
+    file_types: bool,
+    /// `image_width`, `image_height`
+    image_dimensions: bool,
+}
+
+/// Extension allow/deny lists, already normalized. A non-empty include list
+/// also rejects files without an extension.
+#[derive(Debug, Default)]
+struct ExtensionFilter {
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+}
+
+impl ExtensionFilter {
+    fn new(include: Option<&[String]>, exclude: Option<&[String]>) -> Self {
+        Self {
+            include: include
+                .map(normalize_extensions)
+                .filter(|values| !values.is_empty())
+                .map(|values| values.into_iter().collect()),
+            exclude: exclude
+                .map(normalize_extensions)
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn allows(&self, relative_path: &str) -> bool {
+        match file_extension(relative_path) {
+            Some(extension) => {
+                !self.exclude.contains(&extension)
+                    && self
+                        .include
+                        .as_ref()
+                        .is_none_or(|include| include.contains(&extension))
+            }
+            None => self.include.is_none(),
+        }
+    }
+}
+
+/// Lowercased extension of the file name; `None` for dotfiles and names without one.
+fn file_extension(relative_path: &str) -> Option<String> {
+    let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    name.rsplit_once('.')
+        .filter(|(stem, extension)| !stem.is_empty() && !extension.is_empty())
+        .map(|(_, extension)| extension.to_lowercase())
+}
+
+/// MIME type guessed from a lowercased extension, without opening the file.
+fn mime_type_for_extension(extension: &str) -> Option<&'static str> {
+    let mime = match extension {
+        "jpg" | "jpeg" | "jpe" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        "heif" => "image/heif",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "mp4" | "m4v" => "video/mp4",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "avi" => "video/x-msvideo",
+        "wmv" => "video/x-ms-wmv",
+        "mpg" | "mpeg" => "video/mpeg",
+        "ts" | "m2ts" => "video/mp2t",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "7z" => "application/x-7z-compressed",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+const IGNORE_FILE_NAME: &str = ".dedupfsignore";
+/// Filesystem errors written to `job_log_entries` per library walk; the rest are only counted.
+const SCAN_ERROR_LOG_LIMIT: i64 = 1000;
+
+/// Compiled `scan_exclude_patterns`. A trailing `/` limits a pattern to
+/// directories; each pattern is tried against both the library-relative
+/// path and the entry name.
+#[derive(Debug, Default)]
+struct ExcludeMatcher {
+    patterns: Vec<(Pattern, bool)>,
+}
+
+impl ExcludeMatcher {
+    fn compile(raw_patterns: &[String]) -> Result<Self> {
+        let mut patterns = Vec::with_capacity(raw_patterns.len());
+        for raw in raw_patterns {
+            let trimmed = raw.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let directory_only = trimmed.ends_with('/');
+            let pattern = Pattern::new(trimmed.trim_end_matches('/'))
+                .with_context(|| format!("invalid exclude pattern: {raw}"))?;
+            patterns.push((pattern, directory_only));
+        }
+        Ok(Self { patterns })
+    }
+
+    fn is_excluded(&self, relative_path: &str, is_dir: bool) -> bool {
+        let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+        self.patterns.iter().any(|(pattern, directory_only)| {
+            (!directory_only || is_dir) && (pattern.matches(relative_path) || pattern.matches(name))
+        })
+    }
+}
+
 #[derive(Debug, Default)]
 struct ScanCounters {
     files_seen: i64,
@@ -25,26 +207,203 @@ struct ScanCounters {
     bytes_seen: i64,
     batch_writes: i64,
     missing_marked: i64,
+    depth_skipped: i64,
+    extension_filtered: i64,
+    symlinks_escaped: i64,
+    /// Followed symlinks to a directory the walk already entered (aliases and loops).
+    symlinks_skipped: i64,
+    hardlinks_seen: i64,
+    mount_points_skipped: i64,
+    /// Relative paths of the skipped mount points; their subtrees keep their rows.
+    mount_point_paths: Vec<String>,
+    libraries_timed_out: i64,
     error_count: i64,
     error_samples: Vec<String>,
 }
 
-pub fn run_scan_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecord) -> Result<()> {
+/// Records per-interval scan rates into `scan_throughput_samples` when
+/// `scan_throughput_sample_seconds` is set, tagged with the directory being walked.
+struct ThroughputSampler {
+    interval: Option<Duration>,
+    last_at: Instant,
+    last_files: i64,
+    last_directories: i64,
+    last_bytes: i64,
+}
+
+impl ThroughputSampler {
+    fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last_at: Instant::now(),
+            last_files: 0,
+            last_directories: 0,
+            last_bytes: 0,
+        }
+    }
+
+    fn maybe_record(
+        &mut self,
+        conn: &Connection,
+        scan_session_id: i64,
+        library_id: i64,
+        counters: &ScanCounters,
+        current_dir: &str,
+    ) -> Result<()> {
+        let Some(interval) = self.interval else {
+            return Ok(());
+        };
+        let elapsed = self.last_at.elapsed();
+        if elapsed < interval {
+            return Ok(());
+        }
+
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let files = counters.files_seen - self.last_files;
+        let directories = counters.directories_seen - self.last_directories;
+        let bytes = counters.bytes_seen - self.last_bytes;
+        conn.execute(
+            "
+            INSERT INTO scan_throughput_samples (
+                scan_session_id,
+                library_id,
+                interval_ms,
+                files_delta,
+                directories_delta,
+                bytes_delta,
+                files_per_sec,
+                directories_per_sec,
+                bytes_per_sec,
+                current_dir
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ",
+            params![
+                scan_session_id,
+                library_id,
+                elapsed.as_millis() as i64,
+                files,
+                directories,
+                bytes,
+                files as f64 / seconds,
+                directories as f64 / seconds,
+                bytes as f64 / seconds,
+                current_dir
+            ],
+        )?;
+
+        self.last_at = Instant::now();
+        self.last_files = counters.files_seen;
+        self.last_directories = counters.directories_seen;
+        self.last_bytes = counters.bytes_seen;
+        Ok(())
+    }
+}
+
+pub fn run_scan_job(
+    conn: &mut Connection,
+    config: &WorkerConfig,
+    job: &JobRecord,
+    schema: &SchemaFeatures,
+    shutdown: &AtomicBool,
+) -> Result<()> {
     let batch_size = extract_optional_u64(&job.payload, "batch_size")
         .map(|v| v.max(1) as usize)
         .unwrap_or(config.scan_write_batch_size);
     let library_names = extract_library_names(&job.payload)?;
+    let exclude_patterns = extract_string_array(&job.payload, "exclude_patterns")?;
+    let include_extensions = match extract_string_array(&job.payload, "include_extensions")? {
+        Some(values) => Some(values),
+        None => extract_string_array(&job.payload, "extensions")?,
+    };
+    let exclude_extensions = extract_string_array(&job.payload, "exclude_extensions")?;
+    // Payload filters narrow a one-off walk; what they skip is not evidence of deletion.
+    let payload_filtered =
+        exclude_patterns.is_some() || include_extensions.is_some() || exclude_extensions.is_some();
+    let mut options = ScanOptions {
+        batch_size,
+        exclude: ExcludeMatcher::compile(
+            exclude_patterns
+                .as_deref()
+                .unwrap_or(&config.scan_exclude_patterns),
+        )?,
+        follow_symlinks: config.scan_follow_symlinks,
+        max_depth: config.scan_max_depth,
+        respect_ignore_files: config.scan_respect_ignore_files,
+        extensions: ExtensionFilter::new(
+            include_extensions
+                .as_deref()
+                .or(config.scan_include_extensions.as_deref()),
+            exclude_extensions
+                .as_deref()
+                .or(config.scan_exclude_extensions.as_deref()),
+        ),
+        job_deadline: config
+            .scan_max_runtime_seconds
+            .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+        library_max_runtime: config.scan_per_library_max_seconds.map(Duration::from_secs),
+        library_excludes: HashMap::new(),
+        optional_columns: OptionalFileColumns {
+            file_types: schema.library_files_file_types,
+            image_dimensions: config.scan_probe_image_dimensions
+                && schema.library_files_image_dimensions,
+        },
+    };
+    if config.scan_probe_image_dimensions && !schema.library_files_image_dimensions {
+        tracing::warn!(
+            job_id = %job.id,
+            "scan_probe_image_dimensions is set but library_files has no image_width/image_height; run the Python migrations"
+        );
+    }
 
     let targets = prepare_targets(conn, config, library_names.as_deref())?;
+    // Patterns given in the payload apply to every library, overrides included.
+    if exclude_patterns.is_none() {
+        for target in &targets {
+            let has_override = config
+                .libraries
+                .get(&target.name)
+                .is_some_and(|library| library.exclude_patterns.is_some());
+            if has_override {
+                let effective = config.effective_library_config(&target.name);
+                options.library_excludes.insert(
+                    target.id,
+                    ExcludeMatcher::compile(&effective.exclude_patterns)?,
+                );
+            }
+        }
+    }
     let scan_session_id = create_scan_session(conn)?;
 
     let mut counters = ScanCounters::default();
+    let mut yielder = CommitYielder::new(config).with_shutdown(shutdown);
+    let mut timed_out_libraries = HashSet::new();
+    let mut skipped_mounts: HashMap<i64, Vec<String>> = HashMap::new();
     for target in &targets {
-        let local = scan_single_library(conn, config, job, target, scan_session_id, batch_size)?;
+        let local = scan_single_library(
+            conn,
+            config,
+            job,
+            target,
+            scan_session_id,
+            &options,
+            &mut yielder,
+        )?;
+        if local.libraries_timed_out > 0 {
+            counters.libraries_timed_out += 1;
+            timed_out_libraries.insert(target.id);
+        }
         counters.files_seen += local.files_seen;
         counters.directories_seen += local.directories_seen;
         counters.bytes_seen += local.bytes_seen;
         counters.batch_writes += local.batch_writes;
+        counters.depth_skipped += local.depth_skipped;
+        counters.extension_filtered += local.extension_filtered;
+        counters.symlinks_escaped += local.symlinks_escaped;
+        counters.symlinks_skipped += local.symlinks_skipped;
+        counters.hardlinks_seen += local.hardlinks_seen;
+        counters.mount_points_skipped += local.mount_points_skipped;
+        skipped_mounts.insert(target.id, local.mount_point_paths);
         counters.error_count += local.error_count;
 
         for sample in local.error_samples {
@@ -54,11 +413,32 @@ pub fn run_scan_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecor
         }
     }
 
+    let depth_skipped = options.max_depth.map(|_| counters.depth_skipped);
     if counters.error_count == 0 {
         for target in &targets {
-            counters.missing_marked += mark_missing_files(conn, target.id, scan_session_id)?;
+            if timed_out_libraries.contains(&target.id) {
+                // A partial walk must not mark unvisited files missing.
+                conn.execute(
+                    "UPDATE library_roots SET last_scan_timed_out_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                    params![target.id],
+                )?;
+                continue;
+            }
+            if !payload_filtered {
+                counters.missing_marked += mark_missing_files(
+                    conn,
+                    target.id,
+                    scan_session_id,
+                    options.max_depth,
+                    skipped_mounts
+                        .get(&target.id)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                )?;
+                prune_hardlink_aliases(conn, target.id, scan_session_id)?;
+            }
             conn.execute(
-                "UPDATE library_roots SET last_scanned_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
+                "UPDATE library_roots SET last_scanned_at = CURRENT_TIMESTAMP, last_scan_timed_out_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
                 params![target.id],
             )?;
         }
@@ -71,14 +451,16 @@ pub fn run_scan_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecor
                 files_seen = ?1,
                 directories_seen = ?2,
                 bytes_seen = ?3,
+                depth_skipped = ?4,
                 error_count = 0,
                 error_message = NULL
-            WHERE id = ?4
+            WHERE id = ?5
             ",
             params![
                 counters.files_seen,
                 counters.directories_seen,
                 counters.bytes_seen,
+                depth_skipped,
                 scan_session_id
             ],
         )?;
@@ -92,14 +474,16 @@ pub fn run_scan_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecor
                 files_seen = ?1,
                 directories_seen = ?2,
                 bytes_seen = ?3,
-                error_count = ?4,
-                error_message = ?5
-            WHERE id = ?6
+                depth_skipped = ?4,
+                error_count = ?5,
+                error_message = ?6
+            WHERE id = ?7
             ",
             params![
                 counters.files_seen,
                 counters.directories_seen,
                 counters.bytes_seen,
+                depth_skipped,
                 counters.error_count,
                 error_message,
                 scan_session_id
@@ -114,6 +498,34 @@ pub fn run_scan_job(conn: &mut Connection, config: &WorkerConfig, job: &JobRecor
     }
 
     refresh_job_lease(conn, config, &job.id, counters.files_seen, 1.0)?;
+    let summary = format!(
+        "scan summary files_seen={} directories_seen={} bytes_seen={} missing_marked={} depth_skipped={} symlinks_escaped={} symlinks_skipped={} hardlinks_seen={} mount_points_skipped={} libraries_timed_out={}",
+        counters.files_seen,
+        counters.directories_seen,
+        counters.bytes_seen,
+        counters.missing_marked,
+        counters.depth_skipped,
+        counters.symlinks_escaped,
+        counters.symlinks_skipped,
+        counters.hardlinks_seen,
+        counters.mount_points_skipped,
+        counters.libraries_timed_out
+    );
+    tracing::info!(
+        job_id = %job.id,
+        files_seen = counters.files_seen,
+        directories_seen = counters.directories_seen,
+        bytes_seen = counters.bytes_seen,
+        missing_marked = counters.missing_marked,
+        depth_skipped = counters.depth_skipped,
+        symlinks_escaped = counters.symlinks_escaped,
+        symlinks_skipped = counters.symlinks_skipped,
+        hardlinks_seen = counters.hardlinks_seen,
+        mount_points_skipped = counters.mount_points_skipped,
+        libraries_timed_out = counters.libraries_timed_out,
+        "scan summary"
+    );
+    append_job_log(conn, &job.id, JobLogLevel::Info, &summary)?;
     Ok(())
 }
 
@@ -133,6 +545,7 @@ fn prepare_targets(
     config: &WorkerConfig,
     library_names: Option<&[String]>,
 ) -> Result<Vec<LibraryTarget>> {
+    let explicit = library_names.is_some();
     let names = if let Some(names) = library_names {
         names.to_vec()
     } else {
@@ -152,21 +565,42 @@ fn prepare_targets(
 
     let mut targets = Vec::with_capacity(dedup.len());
     for name in dedup {
-        let root = config.libraries_root.join(&name);
-        let root_real = resolve_root_under_libraries(&config.libraries_root_real, &root)?;
+        let root = libraries_root_for(config, &name)?.join(&name);
+        let root_real = resolve_root_under_libraries(
+            &config.libraries_roots_real,
+            &root,
+            config.allow_symlinked_roots,
+        )?;
+        // A library resolving to /libraries itself would scan every library as one
+        // flat tree under a single library_roots row.
+        if config.libraries_roots_real.contains(&root_real) {
+            if explicit {
+                bail!("library {name} resolves to the libraries root itself");
+            }
+            tracing::warn!(
+                library = %name,
+                "skipping library: resolves to the libraries root itself"
+            );
+            continue;
+        }
         if !root_real.is_dir() {
             bail!("library root is not a directory: {}", root_real.display());
         }
+        let link_path = fs::symlink_metadata(&root)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false)
+            .then(|| root.to_string_lossy().to_string());
 
         conn.execute(
             "
-            INSERT INTO library_roots (name, root_path)
-            VALUES (?1, ?2)
+            INSERT INTO library_roots (name, root_path, link_path)
+            VALUES (?1, ?2, ?3)
             ON CONFLICT(name) DO UPDATE SET
                 root_path = excluded.root_path,
+                link_path = excluded.link_path,
                 updated_at = CURRENT_TIMESTAMP
             ",
-            params![name, root_real.to_string_lossy().to_string()],
+            params![name, root_real.to_string_lossy().to_string(), link_path],
         )?;
 
         let id = conn.query_row(
@@ -177,6 +611,7 @@ fn prepare_targets(
 
         targets.push(LibraryTarget {
             id,
+            name,
             root_path_real: root_real,
         });
     }
@@ -184,26 +619,53 @@ fn prepare_targets(
     Ok(targets)
 }
 
+/// The libraries root holding library `name`. Library names are unique across roots;
+/// unknown names map to the first root so resolution reports the missing directory.
+fn libraries_root_for<'a>(config: &'a WorkerConfig, name: &str) -> Result<&'a Path> {
+    let mut found: Option<&Path> = None;
+    for libraries_root in &config.libraries_roots {
+        if !libraries_root.join(name).is_dir() {
+            continue;
+        }
+        if let Some(previous) = found {
+            bail!(
+                "library name {name} exists under more than one libraries root: {} and {}",
+                previous.display(),
+                libraries_root.display()
+            );
+        }
+        found = Some(libraries_root);
+    }
+    Ok(found.unwrap_or(&config.libraries_roots[0]))
+}
+
 fn discover_library_names(config: &WorkerConfig) -> Result<Vec<String>> {
     let mut names = Vec::new();
-    for entry in fs::read_dir(&config.libraries_root_real).with_context(|| {
-        format!(
-            "failed to read libraries root: {}",
-            config.libraries_root_real.display()
-        )
-    })? {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(_) => continue,
-        };
-        let file_type = match entry.file_type() {
-            Ok(value) => value,
-            Err(_) => continue,
-        };
-        if !file_type.is_dir() || file_type.is_symlink() {
-            continue;
+    for libraries_root_real in &config.libraries_roots_real {
+        for entry in fs::read_dir(libraries_root_real).with_context(|| {
+            format!(
+                "failed to read libraries root: {}",
+                libraries_root_real.display()
+            )
+        })? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            let file_type = match entry.file_type() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let is_library_dir = if file_type.is_symlink() {
+                config.allow_symlinked_roots && entry.path().is_dir()
+            } else {
+                file_type.is_dir()
+            };
+            if !is_library_dir {
+                continue;
+            }
+            names.push(entry.file_name().to_string_lossy().to_string());
         }
-        names.push(entry.file_name().to_string_lossy().to_string());
     }
     Ok(names)
 }
@@ -214,21 +676,68 @@ fn scan_single_library(
     job: &JobRecord,
     target: &LibraryTarget,
     scan_session_id: i64,
-    batch_size: usize,
+    options: &ScanOptions,
+    yielder: &mut CommitYielder,
 ) -> Result<ScanCounters> {
+    let batch_size = options.batch_size;
+    let exclude = options
+        .library_excludes
+        .get(&target.id)
+        .unwrap_or(&options.exclude);
+    // Once the job budget is spent, remaining libraries time out before their first directory.
+    let library_deadline = options
+        .library_max_runtime
+        .map(|runtime| Instant::now() + runtime);
+    let deadline = [options.job_deadline, library_deadline]
+        .into_iter()
+        .flatten()
+        .min();
     let mut counters = ScanCounters::default();
-    let mut stack = vec![target.root_path_real.clone()];
-    let mut batch: Vec<(i64, String, i64, i64, Option<i64>, Option<i64>, i64)> =
-        Vec::with_capacity(batch_size);
+    let ignore_matcher = if options.respect_ignore_files {
+        load_ignore_file(&target.root_path_real)?
+    } else {
+        Gitignore::empty()
+    };
+    let root_device = if config.scan_stay_on_filesystem {
+        let metadata = fs::metadata(&target.root_path_real).with_context(|| {
+            format!(
+                "failed to read library root: {}",
+                target.root_path_real.display()
+            )
+        })?;
+        metadata_to_row(&metadata)?.3
+    } else {
+        None
+    };
+    let mut stack = vec![(target.root_path_real.clone(), 1_usize)];
+    let mut visited_dirs = HashSet::from([target.root_path_real.clone()]);
+    let mut batch: Vec<ScannedFileRow> = Vec::with_capacity(batch_size);
+    let mut hardlinks: HashMap<(i64, i64), i64> = HashMap::new();
+    // Inodes already counted in this walk; later links to one add no bytes_seen.
+    let mut seen_inodes: HashSet<(i64, i64)> = HashSet::new();
+    let mut sampler = ThroughputSampler::new(
+        config
+            .scan_throughput_sample_seconds
+            .map(Duration::from_secs),
+    );
 
-    while let Some(current) = stack.pop() {
+    'walk: while let Some((current, depth)) = stack.pop() {
+        if deadline_passed(deadline) {
+            counters.libraries_timed_out = 1;
+            break;
+        }
         counters.directories_seen += 1;
+        // Sampled on entering each directory so a slow subtree shows up by name.
+        let current_dir = current
+            .strip_prefix(&target.root_path_real)
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or_default();
+        sampler.maybe_record(conn, scan_session_id, target.id, &counters, &current_dir)?;
 
         let entries = match fs::read_dir(&current) {
             Ok(entries) => entries,
             Err(error) => {
-                counters.error_count += 1;
-                push_error_sample(&mut counters.error_samples, &current, &error.to_string());
+                record_scan_error(conn, &job.id, &mut counters, &current, &error.to_string())?;
                 continue;
             }
         };
@@ -237,8 +746,7 @@ fn scan_single_library(
             let entry = match entry {
                 Ok(entry) => entry,
                 Err(error) => {
-                    counters.error_count += 1;
-                    push_error_sample(&mut counters.error_samples, &current, &error.to_string());
+                    record_scan_error(conn, &job.id, &mut counters, &current, &error.to_string())?;
                     continue;
                 }
             };
@@ -247,35 +755,64 @@ fn scan_single_library(
             let metadata = match fs::symlink_metadata(&entry_path) {
                 Ok(metadata) => metadata,
                 Err(error) => {
-                    counters.error_count += 1;
-                    push_error_sample(&mut counters.error_samples, &entry_path, &error.to_string());
+                    record_scan_error(
+                        conn,
+                        &job.id,
+                        &mut counters,
+                        &entry_path,
+                        &error.to_string(),
+                    )?;
                     continue;
                 }
             };
 
-            if metadata.file_type().is_symlink() {
+            let is_symlink = metadata.file_type().is_symlink();
+            if is_symlink && !options.follow_symlinks {
                 continue;
             }
 
-            let resolved = match entry_path.canonicalize() {
+            let resolved = match resolve_entry_path(&current, &entry_path, is_symlink) {
                 Ok(path) => path,
                 Err(error) => {
-                    counters.error_count += 1;
-                    push_error_sample(&mut counters.error_samples, &entry_path, &error.to_string());
+                    record_scan_error(
+                        conn,
+                        &job.id,
+                        &mut counters,
+                        &entry_path,
+                        &error.to_string(),
+                    )?;
                     continue;
                 }
             };
 
             if !resolved.starts_with(&target.root_path_real) {
+                if is_symlink {
+                    counters.symlinks_escaped += 1;
+                }
                 continue;
             }
 
-            if metadata.is_dir() {
-                stack.push(resolved);
-                continue;
-            }
+            let metadata = if is_symlink {
+                match fs::metadata(&resolved) {
+                    Ok(metadata) => metadata,
+                    Err(error) => {
+                        record_scan_error(
+                            conn,
+                            &job.id,
+                            &mut counters,
+                            &entry_path,
+                            &error.to_string(),
+                        )?;
+                        continue;
+                    }
+                }
+            } else {
+                metadata
+            };
 
-            if !metadata.is_file() {
+            // An alias of a visited directory or a loop back to an ancestor; not an error.
+            if metadata.is_dir() && visited_dirs.contains(&resolved) {
+                counters.symlinks_skipped += 1;
                 continue;
             }
 
@@ -284,52 +821,178 @@ fn scan_single_library(
                 .with_context(|| {
                     format!("failed to compute relative path for {}", resolved.display())
                 })?;
-            let relative_path = to_posix_relative_path(relative)?;
+            let relative_path = to_posix_relative_path(relative, config.scan_normalize_unicode)?;
+            if exclude.is_excluded(&relative_path, metadata.is_dir())
+                || ignore_matcher
+                    .matched(relative, metadata.is_dir())
+                    .is_ignore()
+            {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                if crosses_filesystem(root_device, metadata_to_row(&metadata)?.3) {
+                    counters.mount_points_skipped += 1;
+                    counters.mount_point_paths.push(relative_path);
+                    continue;
+                }
+                if options
+                    .max_depth
+                    .is_some_and(|max_depth| depth >= max_depth)
+                {
+                    counters.depth_skipped += 1;
+                    continue;
+                }
+                visited_dirs.insert(resolved.clone());
+                stack.push((resolved, depth + 1));
+                continue;
+            }
+
+            if !metadata.is_file() {
+                continue;
+            }
+
+            if !options.extensions.allows(&relative_path) {
+                counters.extension_filtered += 1;
+                continue;
+            }
 
             let (size_bytes, mtime_ns, inode, device) = metadata_to_row(&metadata)?;
-            batch.push((
-                target.id,
+            let relative_path_hash = config
+                .scan_store_path_hash
+                .then(|| relative_path_hash(&relative_path));
+            let extension = file_extension(&relative_path);
+            let mime_type = extension.as_deref().and_then(mime_type_for_extension);
+            let media_type = if config.scan_detect_media_type {
+                detect_media_type(&resolved)
+            } else {
+                None
+            };
+            let looks_like_image = match media_type {
+                Some(media_type) => media_type == "image",
+                None => mime_type.is_some_and(|mime| mime.starts_with("image/")),
+            };
+            let image_dimensions = if options.optional_columns.image_dimensions && looks_like_image
+            {
+                probe_image_dimensions(&resolved)
+            } else {
+                None
+            };
+            batch.push(ScannedFileRow {
+                library_id: target.id,
                 relative_path,
+                relative_path_hash,
                 size_bytes,
                 mtime_ns,
                 inode,
                 device,
+                permissions: if config.scan_capture_permissions {
+                    metadata_permissions(&metadata)
+                } else {
+                    None
+                },
+                media_type,
+                extension,
+                mime_type,
+                image_dimensions,
                 scan_session_id,
-            ));
+            });
 
             counters.files_seen += 1;
-            counters.bytes_seen = counters.bytes_seen.saturating_add(size_bytes);
+            let first_link = match (device, inode) {
+                (Some(device), Some(inode)) => seen_inodes.insert((device, inode)),
+                _ => true,
+            };
+            if !first_link {
+                counters.hardlinks_seen += 1;
+            }
+            if first_link || !config.scan_count_hardlinks_once {
+                counters.bytes_seen = counters.bytes_seen.saturating_add(size_bytes);
+            }
 
             if counters.files_seen % 256 == 0 {
                 refresh_job_lease(conn, config, &job.id, counters.files_seen, 0.0)?;
+                sampler.maybe_record(conn, scan_session_id, target.id, &counters, &current_dir)?;
+                if deadline_passed(deadline) {
+                    counters.libraries_timed_out = 1;
+                    break 'walk;
+                }
             }
 
             if batch.len() >= batch_size {
-                upsert_file_batch(conn, &batch)?;
+                upsert_file_batch(conn, &batch, options.optional_columns, &mut hardlinks)?;
                 batch.clear();
                 counters.batch_writes += 1;
+                yielder.maybe_yield(conn)?;
             }
         }
     }
 
     if !batch.is_empty() {
-        upsert_file_batch(conn, &batch)?;
+        upsert_file_batch(conn, &batch, options.optional_columns, &mut hardlinks)?;
         counters.batch_writes += 1;
     }
 
     Ok(counters)
 }
 
+fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+fn load_ignore_file(root: &Path) -> Result<Gitignore> {
+    let ignore_path = root.join(IGNORE_FILE_NAME);
+    if !ignore_path.is_file() {
+        return Ok(Gitignore::empty());
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(error) = builder.add(&ignore_path) {
+        return Err(error).with_context(|| format!("failed to parse {}", ignore_path.display()));
+    }
+    builder
+        .build()
+        .with_context(|| format!("failed to compile {}", ignore_path.display()))
+}
+
+fn resolve_entry_path(current: &Path, entry_path: &Path, is_symlink: bool) -> Result<PathBuf> {
+    if !is_symlink {
+        return Ok(entry_path.canonicalize()?);
+    }
+    let link_target = fs::read_link(entry_path)?;
+    let absolute_target = if link_target.is_absolute() {
+        link_target
+    } else {
+        current.join(link_target)
+    };
+    Ok(absolute_target.canonicalize()?)
+}
+
 fn upsert_file_batch(
     conn: &mut Connection,
-    rows: &[(i64, String, i64, i64, Option<i64>, Option<i64>, i64)],
+    rows: &[ScannedFileRow],
+    optional_columns: OptionalFileColumns,
+    hardlinks: &mut HashMap<(i64, i64), i64>,
 ) -> Result<()> {
     if rows.is_empty() {
         return Ok(());
     }
 
-    let tx = conn.transaction()?;
-    let mut stmt = tx.prepare_cached(
+    let mut extra_columns = Vec::new();
+    if optional_columns.file_types {
+        extra_columns.extend(["extension", "mime_type"]);
+    }
+    if optional_columns.image_dimensions {
+        extra_columns.extend(["image_width", "image_height"]);
+    }
+    let mut optional_names = String::new();
+    let mut optional_values = String::new();
+    let mut optional_updates = String::new();
+    for (offset, column) in extra_columns.iter().enumerate() {
+        optional_names.push_str(&format!(",\n            {column}"));
+        optional_values.push_str(&format!(", ?{}", 13 + offset));
+        optional_updates.push_str(&format!("{column} = excluded.{column},\n            "));
+    }
+    let upsert_sql = format!(
         "
         INSERT INTO library_files (
             library_id,
@@ -340,9 +1003,20 @@ fn upsert_file_batch(
             device,
             is_missing,
             needs_hash,
-            last_seen_scan_id
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, 1, ?7)
+            last_seen_scan_id,
+            relative_path_hash,
+            file_mode,
+            owner_uid,
+            owner_gid,
+            media_type{optional_names}
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, 1, ?7, ?8, ?9, ?10, ?11, ?12{optional_values})
         ON CONFLICT(library_id, relative_path) DO UPDATE SET
+            relative_path_hash = excluded.relative_path_hash,
+            {optional_updates}
+            file_mode = COALESCE(excluded.file_mode, library_files.file_mode),
+            owner_uid = COALESCE(excluded.owner_uid, library_files.owner_uid),
+            owner_gid = COALESCE(excluded.owner_gid, library_files.owner_gid),
+            media_type = COALESCE(excluded.media_type, library_files.media_type),
             size_bytes = excluded.size_bytes,
             mtime_ns = excluded.mtime_ns,
             inode = excluded.inode,
@@ -373,6 +1047,22 @@ fn upsert_file_batch(
                   OR library_files.is_missing = 1
                 THEN NULL ELSE library_files.content_hash
             END,
+            quick_hash = CASE
+                WHEN library_files.size_bytes != excluded.size_bytes
+                  OR library_files.mtime_ns != excluded.mtime_ns
+                  OR IFNULL(library_files.inode, -1) != IFNULL(excluded.inode, -1)
+                  OR IFNULL(library_files.device, -1) != IFNULL(excluded.device, -1)
+                  OR library_files.is_missing = 1
+                THEN NULL ELSE library_files.quick_hash
+            END,
+            prefix_hash = CASE
+                WHEN library_files.size_bytes != excluded.size_bytes
+                  OR library_files.mtime_ns != excluded.mtime_ns
+                  OR IFNULL(library_files.inode, -1) != IFNULL(excluded.inode, -1)
+                  OR IFNULL(library_files.device, -1) != IFNULL(excluded.device, -1)
+                  OR library_files.is_missing = 1
+                THEN NULL ELSE library_files.prefix_hash
+            END,
             hashed_size_bytes = CASE
                 WHEN library_files.size_bytes != excluded.size_bytes
                   OR library_files.mtime_ns != excluded.mtime_ns
@@ -446,27 +1136,121 @@ fn upsert_file_batch(
                 THEN NULL ELSE library_files.hash_claimed_at
             END,
             updated_at = CURRENT_TIMESTAMP
+        RETURNING id
+        "
+    );
+
+    let tx = conn.transaction()?;
+    let mut stmt = tx.prepare_cached(&upsert_sql)?;
+
+    // A rename keeps inode, device, size and mtime: a new path reuses the hash of a row
+    // with the same identity that this scan has not seen (missing, or about to be).
+    let mut carry_rename_stmt = tx.prepare_cached(
+        "
+        UPDATE library_files
+        SET needs_hash = 0,
+            hash_algorithm = p.hash_algorithm,
+            content_hash = p.content_hash,
+            hashed_size_bytes = p.hashed_size_bytes,
+            hashed_mtime_ns = p.hashed_mtime_ns,
+            hashed_at = p.hashed_at,
+            updated_at = CURRENT_TIMESTAMP
+        FROM (
+            SELECT hash_algorithm, content_hash, hashed_size_bytes, hashed_mtime_ns, hashed_at
+            FROM library_files
+            WHERE library_id = ?1
+              AND inode = ?3
+              AND device = ?4
+              AND size_bytes = ?5
+              AND mtime_ns = ?6
+              AND relative_path <> ?2
+              AND needs_hash = 0
+              AND content_hash IS NOT NULL
+              AND (last_seen_scan_id IS NULL OR last_seen_scan_id <> ?7)
+            LIMIT 1
+        ) AS p
+        WHERE library_files.library_id = ?1
+          AND library_files.relative_path = ?2
+          AND library_files.needs_hash = 1
+          AND library_files.content_hash IS NULL
         ",
     )?;
 
-    for (library_id, relative_path, size_bytes, mtime_ns, inode, device, scan_id) in rows {
-        stmt.execute(params![
-            library_id,
-            relative_path,
-            size_bytes,
-            mtime_ns,
+    for row in rows {
+        let file_mode = row.permissions.map(|value| value.mode);
+        let owner_uid = row.permissions.map(|value| value.uid);
+        let owner_gid = row.permissions.map(|value| value.gid);
+        let image_width = row.image_dimensions.map(|(width, _)| width);
+        let image_height = row.image_dimensions.map(|(_, height)| height);
+        let mut values = params![
+            row.library_id,
+            row.relative_path,
+            row.size_bytes,
+            row.mtime_ns,
+            row.inode,
+            row.device,
+            row.scan_session_id,
+            row.relative_path_hash,
+            file_mode,
+            owner_uid,
+            owner_gid,
+            row.media_type
+        ]
+        .to_vec();
+        if optional_columns.file_types {
+            values.extend([&row.extension as &dyn ToSql, &row.mime_type]);
+        }
+        if optional_columns.image_dimensions {
+            values.extend([&image_width as &dyn ToSql, &image_height]);
+        }
+        let file_id = stmt.query_row(values.as_slice(), |row| row.get::<_, i64>(0))?;
+
+        let (Some(device), Some(inode)) = (row.device, row.inode) else {
+            continue;
+        };
+        carry_rename_stmt.execute(params![
+            row.library_id,
+            row.relative_path,
             inode,
             device,
-            scan_id
+            row.size_bytes,
+            row.mtime_ns,
+            row.scan_session_id
         ])?;
+        match hardlinks.get(&(device, inode)) {
+            Some(&primary_file_id) if primary_file_id != file_id => {
+                record_hardlink_alias(
+                    &tx,
+                    primary_file_id,
+                    row.library_id,
+                    &row.relative_path,
+                    row.scan_session_id,
+                )?;
+            }
+            Some(_) => {}
+            None => {
+                hardlinks.insert((device, inode), file_id);
+            }
+        }
     }
 
+    drop(carry_rename_stmt);
     drop(stmt);
     tx.commit()?;
     Ok(())
 }
 
-fn mark_missing_files(conn: &Connection, library_id: i64, scan_session_id: i64) -> Result<i64> {
+/// Pinned rows are left as they are, so intermittently mounted storage does not churn.
+/// With `max_depth`, rows below the depth the walk reached are left alone: a file in a
+/// directory at depth `d` has `d - 1` slashes in its relative path. Rows under
+/// `skipped_subtrees` (directories the walk did not enter) are left alone too.
+fn mark_missing_files(
+    conn: &Connection,
+    library_id: i64,
+    scan_session_id: i64,
+    max_depth: Option<usize>,
+    skipped_subtrees: &[String],
+) -> Result<i64> {
     let affected = conn.execute(
         "
         UPDATE library_files
@@ -479,17 +1263,45 @@ fn mark_missing_files(conn: &Connection, library_id: i64, scan_session_id: i64)
         WHERE library_id = ?1
           AND (last_seen_scan_id IS NULL OR last_seen_scan_id != ?2)
           AND is_missing = 0
+          AND pinned = 0
+          AND (
+              ?3 IS NULL
+              OR length(relative_path) - length(replace(relative_path, '/', '')) < ?3
+          )
+          AND NOT EXISTS (
+              SELECT 1
+              FROM json_each(?4) AS skipped
+              WHERE substr(relative_path, 1, length(skipped.value) + 1) = skipped.value || '/'
+          )
         ",
-        params![library_id, scan_session_id],
+        params![
+            library_id,
+            scan_session_id,
+            max_depth.map(|depth| depth as i64),
+            serde_json::to_string(skipped_subtrees)?
+        ],
     )?;
     Ok(affected as i64)
 }
 
-fn push_error_sample(samples: &mut Vec<String>, path: &Path, message: &str) {
-    if samples.len() >= 20 {
-        return;
+/// Counts a filesystem error and writes it to the job log. Only the first few are also
+/// kept as samples for the job's `error_message`.
+fn record_scan_error(
+    conn: &Connection,
+    job_id: &str,
+    counters: &mut ScanCounters,
+    path: &Path,
+    message: &str,
+) -> Result<()> {
+    counters.error_count += 1;
+    let sample = format!("{}: {}", path.display(), message);
+    if counters.error_count <= SCAN_ERROR_LOG_LIMIT {
+        append_job_log(conn, job_id, JobLogLevel::Error, &sample)?;
+    }
+    if counters.error_samples.len() < 20 {
+        counters.error_samples.push(sample);
     }
-    samples.push(format!("{}: {}", path.display(), message));
+    Ok(())
 }
 
 fn format_error_message(error_count: i64, samples: &[String]) -> String {
@@ -530,6 +1342,30 @@ fn extract_library_names(payload: &Value) -> Result<Option<Vec<String>>> {
     Ok(Some(names))
 }
 
+fn extract_string_array(payload: &Value, key: &str) -> Result<Option<Vec<String>>> {
+    let Some(value) = payload.get(key) else {
+        return Ok(None);
+    };
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let array = value
+        .as_array()
+        .ok_or_else(|| anyhow!("payload.{key} must be an array"))?;
+
+    let mut items = Vec::new();
+    for item in array {
+        items.push(
+            item.as_str()
+                .ok_or_else(|| anyhow!("payload.{key} must contain strings"))?
+                .to_string(),
+        );
+    }
+
+    Ok(Some(items))
+}
+
 #[cfg(unix)]
 fn metadata_to_row(metadata: &fs::Metadata) -> Result<(i64, i64, Option<i64>, Option<i64>)> {
     use std::os::unix::fs::MetadataExt;
@@ -538,12 +1374,65 @@ fn metadata_to_row(metadata: &fs::Metadata) -> Result<(i64, i64, Option<i64>, Op
     let mtime_ns = metadata
         .mtime()
         .saturating_mul(1_000_000_000)
-        .saturating_add(i64::from(metadata.mtime_nsec()));
+        .saturating_add(metadata.mtime_nsec());
     let inode = Some(i64::try_from(metadata.ino()).context("inode over i64 range")?);
     let device = Some(i64::try_from(metadata.dev()).context("device over i64 range")?);
     Ok((size_bytes, mtime_ns, inode, device))
 }
 
+/// True for a directory on another device than the library root; never true when either
+/// device is unknown (non-Unix, or `scan_stay_on_filesystem` off).
+fn crosses_filesystem(root_device: Option<i64>, device: Option<i64>) -> bool {
+    matches!((root_device, device), (Some(root), Some(device)) if root != device)
+}
+
+#[cfg(unix)]
+fn metadata_permissions(metadata: &fs::Metadata) -> Option<FilePermissions> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(FilePermissions {
+        mode: i64::from(metadata.mode()),
+        uid: i64::from(metadata.uid()),
+        gid: i64::from(metadata.gid()),
+    })
+}
+
+#[cfg(not(unix))]
+fn metadata_permissions(_metadata: &fs::Metadata) -> Option<FilePermissions> {
+    None
+}
+
+/// Bytes read from each file to classify it; every image/video signature fits well within.
+const MEDIA_TYPE_SNIFF_BYTES: u64 = 8192;
+
+/// `image`, `video` or `other` from the file's magic bytes; `None` when the header cannot be
+/// read, which never fails the scan.
+fn detect_media_type(path: &Path) -> Option<&'static str> {
+    let mut header = Vec::new();
+    fs::File::open(path)
+        .and_then(|file| file.take(MEDIA_TYPE_SNIFF_BYTES).read_to_end(&mut header))
+        .ok()?;
+    Some(classify_media_header(&header))
+}
+
+/// Width and height from the image header alone (no pixel decode); `None` for formats the
+/// decoder does not know or unreadable headers, which never fails the scan.
+fn probe_image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
+fn classify_media_header(header: &[u8]) -> &'static str {
+    match infer::get(header).map(|kind| kind.matcher_type()) {
+        Some(infer::MatcherType::Image) => "image",
+        Some(infer::MatcherType::Video) => "video",
+        _ => "other",
+    }
+}
+
 #[cfg(not(unix))]
 fn metadata_to_row(metadata: &fs::Metadata) -> Result<(i64, i64, Option<i64>, Option<i64>)> {
     let size_bytes = i64::try_from(metadata.len()).context("file size over i64 range")?;
@@ -556,3 +1445,1107 @@ fn metadata_to_row(metadata: &fs::Metadata) -> Result<(i64, i64, Option<i64>, Op
     let mtime_ns = i64::try_from(duration.as_nanos()).context("mtime_ns over i64 range")?;
     Ok((size_bytes, mtime_ns, None, None))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{detect_schema_features, insert_job, list_job_log_entries, JobKind};
+    use crate::test_support::{create_worker_schema, insert_library, test_config, TestDir};
+
+    fn scan_fixture(root: &Path, options: &ScanOptions) -> (Vec<String>, ScanCounters) {
+        let (conn, counters) = scan_fixture_db(root, options, |_| {});
+        let mut stmt = conn
+            .prepare("SELECT relative_path FROM library_files ORDER BY relative_path")
+            .expect("prepare");
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .expect("query");
+        let paths = rows.map(|row| row.expect("row")).collect();
+        (paths, counters)
+    }
+
+    fn scan_fixture_db(
+        root: &Path,
+        options: &ScanOptions,
+        configure: impl FnOnce(&mut WorkerConfig),
+    ) -> (Connection, ScanCounters) {
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let root_real = root.canonicalize().expect("resolve root");
+        let library_id = insert_library(&conn, "lib", &root_real);
+        let scan_session_id = create_scan_session(&conn).expect("scan session");
+        let libraries_root = root.parent().expect("libraries root");
+        let mut config = test_config(libraries_root, &libraries_root.join("state"));
+        configure(&mut config);
+        let job = JobRecord {
+            id: "job-1".to_string(),
+            kind: JobKind::Scan,
+            payload: Value::Null,
+            priority: 0,
+        };
+        insert_job(&conn, &job.id, job.kind, &job.payload, job.priority).expect("insert job");
+        let target = LibraryTarget {
+            id: library_id,
+            name: "lib".to_string(),
+            root_path_real: root_real,
+        };
+        let mut yielder = CommitYielder::new(&config);
+        let counters = scan_single_library(
+            &mut conn,
+            &config,
+            &job,
+            &target,
+            scan_session_id,
+            options,
+            &mut yielder,
+        )
+        .expect("scan library");
+        (conn, counters)
+    }
+
+    /// Runs a whole scan job over `config`'s libraries with the given payload.
+    fn run_scan(conn: &mut Connection, config: &WorkerConfig, job_id: &str, payload: Value) {
+        conn.execute(
+            "
+            INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at)
+            VALUES (?1, 'scan', 'running', 'test-worker', datetime('now', '+300 seconds'))
+            ",
+            params![job_id],
+        )
+        .expect("insert job");
+        let job = JobRecord {
+            id: job_id.to_string(),
+            kind: JobKind::Scan,
+            payload,
+            priority: 0,
+        };
+        let schema = detect_schema_features(conn).expect("schema features");
+        run_scan_job(conn, config, &job, &schema, &AtomicBool::new(false)).expect("scan job");
+    }
+
+    fn missing_flags(conn: &Connection) -> Vec<(String, bool)> {
+        conn.prepare("SELECT relative_path, is_missing FROM library_files ORDER BY relative_path")
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows")
+    }
+
+    #[test]
+    fn exclude_patterns_skip_directories_and_files() {
+        let dir = TestDir::new("scan-exclude");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(root.join("app/node_modules/pkg")).expect("create tree");
+        fs::write(root.join("app/node_modules/pkg/index.js"), b"x").expect("write");
+        fs::write(root.join("app/main.js"), b"x").expect("write");
+        fs::write(root.join("app/build.tmp"), b"x").expect("write");
+        fs::write(root.join("photo.jpg"), b"x").expect("write");
+
+        let options = ScanOptions {
+            batch_size: 2,
+            exclude: ExcludeMatcher::compile(&["node_modules/".to_string(), "*.tmp".to_string()])
+                .expect("compile patterns"),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        assert_eq!(
+            scan_fixture(&root, &options).0,
+            vec!["app/main.js".to_string(), "photo.jpg".to_string()]
+        );
+    }
+
+    #[test]
+    fn directory_only_pattern_does_not_match_files() {
+        let matcher = ExcludeMatcher::compile(&["cache/".to_string()]).expect("compile");
+        assert!(matcher.is_excluded("a/cache", true));
+        assert!(!matcher.is_excluded("a/cache", false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn library_resolving_to_libraries_root_is_rejected_or_skipped() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TestDir::new("scan-root-as-library");
+        let libraries_root = dir.path().join("libraries");
+        fs::create_dir_all(libraries_root.join("lib")).expect("create lib");
+        symlink(&libraries_root, libraries_root.join("whole")).expect("root symlink");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.allow_symlinked_roots = true;
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+
+        let error = prepare_targets(&conn, &config, Some(&["whole".to_string()]))
+            .expect_err("explicit root target rejected");
+        assert!(error.to_string().contains("libraries root itself"));
+
+        let targets = prepare_targets(&conn, &config, None).expect("discovered targets");
+        assert_eq!(targets.len(), 1);
+        assert_eq!(
+            targets[0].root_path_real,
+            config.libraries_roots_real[0].join("lib")
+        );
+    }
+
+    #[test]
+    fn libraries_are_discovered_across_every_libraries_root() {
+        let dir = TestDir::new("scan-multiple-roots");
+        let first = dir.path().join("media");
+        let second = dir.path().join("archive");
+        fs::create_dir_all(first.join("photos")).expect("create photos");
+        fs::create_dir_all(second.join("videos")).expect("create videos");
+        let mut config = test_config(&first, &dir.path().join("state"));
+        let second_real = second.canonicalize().expect("resolve second root");
+        config.libraries_roots.push(second_real.clone());
+        config.libraries_roots_real.push(second_real.clone());
+        let conn = Connection::open_in_memory().expect("open sqlite in-memory");
+        create_worker_schema(&conn);
+
+        let targets = prepare_targets(&conn, &config, None).expect("discovered targets");
+        let found: Vec<(String, PathBuf)> = targets
+            .into_iter()
+            .map(|target| (target.name, target.root_path_real))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "photos".to_string(),
+                    config.libraries_roots_real[0].join("photos")
+                ),
+                ("videos".to_string(), second_real.join("videos")),
+            ]
+        );
+
+        fs::create_dir_all(second.join("photos")).expect("create colliding library");
+        let error = prepare_targets(&conn, &config, None).expect_err("name collision rejected");
+        assert!(error.to_string().contains("more than one libraries root"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_symlinks_stays_inside_root_and_breaks_loops() {
+        use std::os::unix::fs::symlink;
+
+        let dir = TestDir::new("scan-symlinks");
+        let root = dir.path().join("libraries").join("lib");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("real")).expect("create tree");
+        fs::create_dir_all(&outside).expect("create outside");
+        fs::write(root.join("real/a.txt"), b"x").expect("write");
+        fs::write(outside.join("secret.txt"), b"x").expect("write");
+        symlink("real", root.join("alias")).expect("dir symlink");
+        symlink("real/a.txt", root.join("a-link.txt")).expect("file symlink");
+        symlink(&root, root.join("real/loop")).expect("loop symlink");
+        symlink(&outside, root.join("escape")).expect("escape symlink");
+
+        let mut options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(paths, vec!["real/a.txt".to_string()]);
+        assert_eq!(counters.error_count, 0);
+
+        options.follow_symlinks = true;
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(paths, vec!["real/a.txt".to_string()]);
+        assert_eq!(counters.symlinks_escaped, 1);
+        // `alias` repeats `real`; `real/loop` leads back to the root.
+        assert_eq!(counters.symlinks_skipped, 2);
+        assert_eq!(counters.error_count, 0);
+        assert!(!paths.iter().any(|path| path.contains("secret")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn self_referential_symlink_scan_terminates() {
+        let dir = TestDir::new("scan-self-link");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(root.join("nested")).expect("create tree");
+        fs::write(root.join("nested/a.txt"), b"x").expect("write");
+        std::os::unix::fs::symlink(".", root.join("nested/self")).expect("self symlink");
+
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: true,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(paths, vec!["nested/a.txt".to_string()]);
+        assert_eq!(counters.directories_seen, 2);
+        assert_eq!(counters.symlinks_escaped, 0);
+        assert_eq!(counters.symlinks_skipped, 1);
+
+        // The loop must not fail the job: the session succeeds and nothing is logged as an error.
+        let mut config = test_config(
+            root.parent().expect("libraries root"),
+            &dir.path().join("state"),
+        );
+        config.scan_follow_symlinks = true;
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        run_scan(&mut conn, &config, "job-1", serde_json::json!({}));
+        let status: String = conn
+            .query_row("SELECT status FROM scan_sessions", [], |row| row.get(0))
+            .expect("scan session");
+        assert_eq!(status, "succeeded");
+        let entries = list_job_log_entries(&conn, "job-1").expect("job log");
+        assert!(entries.iter().all(|entry| entry.level != "error"));
+    }
+
+    #[test]
+    fn throughput_samples_are_recorded_only_when_enabled() {
+        let dir = TestDir::new("scan-throughput");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(root.join("one/two")).expect("create tree");
+        fs::write(root.join("top.jpg"), b"xx").expect("write");
+        fs::write(root.join("one/two/deep.jpg"), b"xxx").expect("write");
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let sample_count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM scan_throughput_samples", [], |row| {
+                row.get(0)
+            })
+            .expect("count samples")
+        };
+
+        let (conn, _) = scan_fixture_db(&root, &options, |_| {});
+        assert_eq!(sample_count(&conn), 0);
+
+        // A zero interval samples on entering every directory.
+        let (conn, counters) = scan_fixture_db(&root, &options, |config| {
+            config.scan_throughput_sample_seconds = Some(0);
+        });
+        assert_eq!(sample_count(&conn), counters.directories_seen);
+        let (deep_dir, files_before_deep): (String, i64) = conn
+            .query_row(
+                "SELECT current_dir, SUM(files_delta) OVER (ORDER BY id) FROM scan_throughput_samples ORDER BY id DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("last sample");
+        assert_eq!(deep_dir, "one/two");
+        assert_eq!(files_before_deep, 1);
+    }
+
+    #[test]
+    fn max_depth_one_only_discovers_top_level_files() {
+        let dir = TestDir::new("scan-depth");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(root.join("one/two")).expect("create tree");
+        fs::write(root.join("top.jpg"), b"x").expect("write");
+        fs::write(root.join("one/mid.jpg"), b"x").expect("write");
+        fs::write(root.join("one/two/deep.jpg"), b"x").expect("write");
+
+        let mut options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: Some(1),
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(paths, vec!["top.jpg".to_string()]);
+        assert_eq!(counters.depth_skipped, 1);
+
+        options.max_depth = None;
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(paths.len(), 3);
+        assert_eq!(counters.depth_skipped, 0);
+    }
+
+    #[test]
+    fn max_depth_does_not_mark_deeper_files_missing() {
+        let dir = TestDir::new("scan-depth-missing");
+        let libraries_root = dir.path().join("libraries");
+        let root = libraries_root.join("lib");
+        fs::create_dir_all(root.join("one/two")).expect("create tree");
+        fs::write(root.join("top.jpg"), b"x").expect("write");
+        fs::write(root.join("gone.jpg"), b"x").expect("write");
+        fs::write(root.join("one/mid.jpg"), b"x").expect("write");
+        fs::write(root.join("one/two/deep.jpg"), b"x").expect("write");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+
+        run_scan(&mut conn, &config, "job-1", serde_json::json!({}));
+        fs::remove_file(root.join("gone.jpg")).expect("remove file");
+        config.scan_max_depth = Some(2);
+        run_scan(&mut conn, &config, "job-2", serde_json::json!({}));
+        assert_eq!(
+            missing_flags(&conn),
+            vec![
+                ("gone.jpg".to_string(), true),
+                ("one/mid.jpg".to_string(), false),
+                ("one/two/deep.jpg".to_string(), false),
+                ("top.jpg".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn stay_on_filesystem_skips_only_other_devices() {
+        assert!(crosses_filesystem(Some(1), Some(2)));
+        assert!(!crosses_filesystem(Some(1), Some(1)));
+        assert!(!crosses_filesystem(None, Some(2)));
+        assert!(!crosses_filesystem(Some(1), None));
+
+        let dir = TestDir::new("scan-stay-on-filesystem");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(root.join("one/two")).expect("create tree");
+        fs::write(root.join("one/two/deep.jpg"), b"x").expect("write");
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (_, counters) = scan_fixture_db(&root, &options, |config| {
+            config.scan_stay_on_filesystem = true;
+        });
+        assert_eq!(counters.files_seen, 1);
+        assert_eq!(counters.mount_points_skipped, 0);
+    }
+
+    #[test]
+    fn files_under_skipped_mount_points_are_not_marked_missing() {
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "lib", Path::new("/libraries/lib"));
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'gone.jpg', 1, 1), (?1, 'mnt/nas/a.jpg', 1, 1), (?1, 'mnt/nas2.jpg', 1, 1)",
+            params![library_id],
+        )
+        .expect("insert files");
+        let scan_session_id = create_scan_session(&conn).expect("scan session");
+
+        let marked = mark_missing_files(
+            &conn,
+            library_id,
+            scan_session_id,
+            None,
+            &["mnt/nas".to_string()],
+        )
+        .expect("mark missing");
+        assert_eq!(marked, 2);
+        assert_eq!(
+            missing_flags(&conn),
+            vec![
+                ("gone.jpg".to_string(), true),
+                ("mnt/nas/a.jpg".to_string(), false),
+                ("mnt/nas2.jpg".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_file_excludes_whole_subtree() {
+        let dir = TestDir::new("scan-ignore");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(root.join("cache/thumbs")).expect("create tree");
+        fs::create_dir_all(root.join("albums")).expect("create tree");
+        fs::write(root.join("cache/thumbs/a.jpg"), b"x").expect("write");
+        fs::write(root.join("albums/b.jpg"), b"x").expect("write");
+        fs::write(root.join(IGNORE_FILE_NAME), b"cache/\n").expect("write ignore file");
+
+        let mut options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: true,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (paths, _) = scan_fixture(&root, &options);
+        assert_eq!(
+            paths,
+            vec![".dedupfsignore".to_string(), "albums/b.jpg".to_string()]
+        );
+
+        options.respect_ignore_files = false;
+        let (paths, _) = scan_fixture(&root, &options);
+        assert!(paths.contains(&"cache/thumbs/a.jpg".to_string()));
+    }
+
+    #[test]
+    fn extension_filters_include_and_exclude() {
+        let dir = TestDir::new("scan-extensions");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(&root).expect("create root");
+        for name in ["a.JPG", "b.mp4", "c.jpg.tmp", "README"] {
+            fs::write(root.join(name), b"x").expect("write");
+        }
+
+        let mut options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::new(Some(&[".jpg".to_string()]), None),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(paths, vec!["a.JPG".to_string()]);
+        assert_eq!(counters.extension_filtered, 3);
+
+        options.extensions = ExtensionFilter::new(None, Some(&["tmp".to_string()]));
+        let (paths, counters) = scan_fixture(&root, &options);
+        assert_eq!(
+            paths,
+            vec![
+                "README".to_string(),
+                "a.JPG".to_string(),
+                "b.mp4".to_string()
+            ]
+        );
+        assert_eq!(counters.extension_filtered, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_are_recorded_as_aliases_and_hashed_once() {
+        let dir = TestDir::new("scan-hardlinks");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(&root).expect("create root");
+        fs::write(root.join("a.bin"), b"shared body").expect("write file");
+        fs::hard_link(root.join("a.bin"), root.join("b.bin")).expect("create hardlink");
+        fs::write(root.join("c.bin"), b"other body").expect("write file");
+
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (conn, counters) = scan_fixture_db(&root, &options, |_| {});
+        assert_eq!(counters.files_seen, 3);
+        assert_eq!(counters.hardlinks_seen, 1);
+        assert_eq!(counters.bytes_seen, 21);
+        let (_, logical) = scan_fixture_db(&root, &options, |config| {
+            config.scan_count_hardlinks_once = false;
+        });
+        assert_eq!(logical.bytes_seen, 32);
+
+        // The primary is unhashed yet, so its alias stays queued as well.
+        let needs_hash: i64 = conn
+            .query_row(
+                "SELECT COUNT(1) FROM library_files WHERE needs_hash = 1",
+                [],
+                |row| row.get(0),
+            )
+            .expect("count");
+        assert_eq!(needs_hash, 3);
+
+        let (primary_file_id, alias_path): (i64, String) = conn
+            .query_row(
+                "SELECT primary_file_id, alias_relative_path FROM hardlink_aliases",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("alias row");
+        assert!(crate::db::has_hardlink(&conn, primary_file_id).expect("has hardlink"));
+        let alias_state = |conn: &Connection| -> (bool, Option<Vec<u8>>) {
+            conn.query_row(
+                "SELECT needs_hash, content_hash FROM library_files WHERE relative_path = ?1",
+                params![alias_path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("alias file")
+        };
+        assert_eq!(alias_state(&conn), (true, None));
+
+        conn.execute(
+            "UPDATE library_files SET needs_hash = 0, hash_algorithm = 'blake3', content_hash = x'0102' WHERE id = ?1",
+            params![primary_file_id],
+        )
+        .expect("hash primary");
+        assert_eq!(
+            crate::db::propagate_hardlink_hash(&conn, primary_file_id).expect("propagate"),
+            1
+        );
+        assert_eq!(alias_state(&conn), (false, Some(vec![1, 2])));
+
+        // Once the primary has a hash, a re-recorded alias inherits it without queuing.
+        conn.execute(
+            "UPDATE library_files SET needs_hash = 1, content_hash = NULL WHERE relative_path = ?1",
+            params![alias_path],
+        )
+        .expect("reset alias");
+        crate::db::record_hardlink_alias(&conn, primary_file_id, 1, &alias_path, 1)
+            .expect("record alias");
+        assert_eq!(alias_state(&conn), (false, Some(vec![1, 2])));
+    }
+
+    #[test]
+    fn extension_allowlist_is_case_insensitive_and_requires_extension() {
+        let filter = ExtensionFilter::new(
+            Some(&["JPG".to_string(), ".png".to_string(), " Mp4 ".to_string()]),
+            None,
+        );
+        assert!(filter.allows("a/photo.jpg"));
+        assert!(filter.allows("a/PHOTO.JPG"));
+        assert!(filter.allows("scan.Png"));
+        assert!(filter.allows("clips/movie.MP4"));
+        assert!(!filter.allows("notes.txt"));
+        assert!(!filter.allows("logs/app.log"));
+        assert!(!filter.allows("Makefile"));
+        assert!(!filter.allows(".jpg"));
+        assert!(!filter.allows("archive.jpg.gz"));
+
+        let unrestricted = ExtensionFilter::new(None, None);
+        assert!(unrestricted.allows("Makefile"));
+        assert!(unrestricted.allows("notes.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permission_changes_are_recorded_without_rehash() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("scan-permissions");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(&root).expect("create root");
+        let file = root.join("a.bin");
+        fs::write(&file, b"x").expect("write");
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).expect("chmod");
+
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (mut conn, _) = scan_fixture_db(&root, &options, |config| {
+            config.scan_capture_permissions = true;
+        });
+        let (mode, uid): (i64, Option<i64>) = conn
+            .query_row(
+                "SELECT file_mode, owner_uid FROM library_files",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("file row");
+        assert_eq!(mode & 0o7777, 0o640);
+        assert!(uid.is_some());
+
+        conn.execute("UPDATE library_files SET needs_hash = 0", [])
+            .expect("mark hashed");
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o600)).expect("chmod");
+        let metadata = fs::metadata(&file).expect("stat");
+        let (size_bytes, mtime_ns, inode, device) = metadata_to_row(&metadata).expect("row");
+        let row = ScannedFileRow {
+            library_id: 1,
+            relative_path: "a.bin".to_string(),
+            relative_path_hash: None,
+            size_bytes,
+            mtime_ns,
+            inode,
+            device,
+            permissions: metadata_permissions(&metadata),
+            media_type: None,
+            extension: None,
+            mime_type: None,
+            image_dimensions: None,
+            scan_session_id: 1,
+        };
+        upsert_file_batch(
+            &mut conn,
+            &[row],
+            OptionalFileColumns::default(),
+            &mut HashMap::new(),
+        )
+        .expect("rescan");
+
+        let (mode, needs_hash): (i64, bool) = conn
+            .query_row(
+                "SELECT file_mode, needs_hash FROM library_files",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .expect("file row");
+        assert_eq!(mode & 0o7777, 0o600);
+        assert!(!needs_hash);
+    }
+
+    #[test]
+    fn media_type_is_classified_from_magic_bytes() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let jpeg = b"\xff\xd8\xff\xe0\0\x10JFIF\0";
+        let gif = b"GIF89a\x01\0\x01\0";
+        let mp4 = b"\0\0\0\x18ftypmp42\0\0\0\0mp42isom";
+        let mkv = b"\x1a\x45\xdf\xa3\x93\x42\x82\x88matroska";
+        assert_eq!(classify_media_header(png), "image");
+        assert_eq!(classify_media_header(jpeg), "image");
+        assert_eq!(classify_media_header(gif), "image");
+        assert_eq!(classify_media_header(mp4), "video");
+        assert_eq!(classify_media_header(mkv), "video");
+        assert_eq!(classify_media_header(b"%PDF-1.7\n"), "other");
+        assert_eq!(classify_media_header(b"plain text"), "other");
+        assert_eq!(classify_media_header(b""), "other");
+
+        let dir = TestDir::new("scan-media-type");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(&root).expect("create root");
+        fs::write(root.join("photo.bin"), png).expect("write png");
+        fs::write(root.join("notes.txt"), b"hello").expect("write text");
+        assert_eq!(detect_media_type(&root.join("photo.bin")), Some("image"));
+        assert_eq!(detect_media_type(&root.join("missing.bin")), None);
+
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns::default(),
+        };
+        let (conn, _) = scan_fixture_db(&root, &options, |config| {
+            config.scan_detect_media_type = true;
+        });
+        let mut stmt = conn
+            .prepare("SELECT relative_path, media_type FROM library_files ORDER BY relative_path")
+            .expect("prepare");
+        let rows: Vec<(String, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            rows,
+            vec![
+                ("notes.txt".to_string(), Some("other".to_string())),
+                ("photo.bin".to_string(), Some("image".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn extension_and_mime_type_are_recorded_when_the_schema_has_them() {
+        assert_eq!(file_extension("a/photo.JPG"), Some("jpg".to_string()));
+        assert_eq!(file_extension("a/.hidden"), None);
+        assert_eq!(file_extension("README"), None);
+        assert_eq!(mime_type_for_extension("mkv"), Some("video/x-matroska"));
+        assert_eq!(mime_type_for_extension("xyz"), None);
+
+        let dir = TestDir::new("scan-file-types");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(&root).expect("create root");
+        fs::write(root.join("photo.JPG"), b"x").expect("write photo");
+        fs::write(root.join("README"), b"x").expect("write readme");
+
+        let file_types = |file_types: bool| {
+            let options = ScanOptions {
+                batch_size: 16,
+                exclude: ExcludeMatcher::default(),
+                follow_symlinks: false,
+                max_depth: None,
+                respect_ignore_files: false,
+                extensions: ExtensionFilter::default(),
+                job_deadline: None,
+                library_max_runtime: None,
+                library_excludes: HashMap::new(),
+                optional_columns: OptionalFileColumns {
+                    file_types,
+                    image_dimensions: false,
+                },
+            };
+            let (conn, _) = scan_fixture_db(&root, &options, |_| {});
+            let mut stmt = conn
+                .prepare(
+                    "SELECT relative_path, extension, mime_type FROM library_files ORDER BY relative_path",
+                )
+                .expect("prepare");
+            let rows: Vec<(String, Option<String>, Option<String>)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .expect("query")
+                .collect::<rusqlite::Result<_>>()
+                .expect("rows");
+            rows
+        };
+        assert_eq!(
+            file_types(true),
+            vec![
+                ("README".to_string(), None, None),
+                (
+                    "photo.JPG".to_string(),
+                    Some("jpg".to_string()),
+                    Some("image/jpeg".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            file_types(false),
+            vec![
+                ("README".to_string(), None, None),
+                ("photo.JPG".to_string(), None, None),
+            ]
+        );
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        assert!(
+            detect_schema_features(&conn)
+                .expect("features")
+                .library_files_file_types
+        );
+        conn.execute_batch(
+            "ALTER TABLE library_files DROP COLUMN mime_type; ALTER TABLE library_files DROP COLUMN extension;",
+        )
+        .expect("drop columns");
+        assert!(
+            !detect_schema_features(&conn)
+                .expect("features")
+                .library_files_file_types
+        );
+    }
+
+    #[test]
+    fn image_dimensions_are_probed_from_headers() {
+        let dir = TestDir::new("scan-image-dimensions");
+        let root = dir.path().join("libraries").join("lib");
+        fs::create_dir_all(&root).expect("create root");
+        image::RgbImage::new(3, 2)
+            .save(root.join("small.png"))
+            .expect("write png");
+        fs::write(root.join("broken.png"), b"not a png").expect("write broken png");
+        fs::write(root.join("notes.txt"), b"hello").expect("write text");
+        assert_eq!(
+            probe_image_dimensions(&root.join("small.png")),
+            Some((3, 2))
+        );
+        assert_eq!(probe_image_dimensions(&root.join("broken.png")), None);
+
+        let options = ScanOptions {
+            batch_size: 16,
+            exclude: ExcludeMatcher::default(),
+            follow_symlinks: false,
+            max_depth: None,
+            respect_ignore_files: false,
+            extensions: ExtensionFilter::default(),
+            job_deadline: None,
+            library_max_runtime: None,
+            library_excludes: HashMap::new(),
+            optional_columns: OptionalFileColumns {
+                file_types: false,
+                image_dimensions: true,
+            },
+        };
+        let (conn, counters) = scan_fixture_db(&root, &options, |_| {});
+        assert_eq!(counters.files_seen, 3);
+        let mut stmt = conn
+            .prepare(
+                "SELECT relative_path, image_width, image_height FROM library_files ORDER BY relative_path",
+            )
+            .expect("prepare");
+        let rows: Vec<(String, Option<u32>, Option<u32>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            rows,
+            vec![
+                ("broken.png".to_string(), None, None),
+                ("notes.txt".to_string(), None, None),
+                ("small.png".to_string(), Some(3), Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn library_runtime_cap_skips_missing_marking() {
+        let dir = TestDir::new("scan-runtime-cap");
+        let libraries_root = dir.path().join("libraries");
+        for name in ["a", "b"] {
+            fs::create_dir_all(libraries_root.join(name)).expect("create library");
+            fs::write(libraries_root.join(name).join("keep.jpg"), b"x").expect("write");
+        }
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.scan_per_library_max_seconds = Some(0);
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(
+            &conn,
+            "a",
+            &libraries_root.join("a").canonicalize().expect("resolve"),
+        );
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'gone.jpg', 1, 0)",
+            params![library_id],
+        )
+        .expect("insert stale row");
+
+        let run = |conn: &mut Connection, config: &WorkerConfig, job_id: &str| {
+            conn.execute(
+                "
+                INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at)
+                VALUES (?1, 'scan', 'running', 'test-worker', datetime('now', '+300 seconds'))
+                ",
+                params![job_id],
+            )
+            .expect("insert job");
+            let job = JobRecord {
+                id: job_id.to_string(),
+                kind: JobKind::Scan,
+                payload: serde_json::json!({}),
+                priority: 0,
+            };
+            let schema = detect_schema_features(conn).expect("schema features");
+            run_scan_job(conn, config, &job, &schema, &AtomicBool::new(false)).expect("scan job");
+        };
+        let state = |conn: &Connection| -> (bool, i64) {
+            let missing = conn
+                .query_row(
+                    "SELECT is_missing FROM library_files WHERE relative_path = 'gone.jpg'",
+                    [],
+                    |row| row.get(0),
+                )
+                .expect("stale row");
+            let timed_out = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM library_roots WHERE last_scan_timed_out_at IS NOT NULL",
+                    [],
+                    |row| row.get(0),
+                )
+                .expect("timed out count");
+            (missing, timed_out)
+        };
+
+        run(&mut conn, &config, "job-1");
+        assert_eq!(state(&conn), (false, 2));
+
+        config.scan_per_library_max_seconds = None;
+        run(&mut conn, &config, "job-2");
+        assert_eq!(state(&conn), (true, 0));
+    }
+
+    #[test]
+    fn payload_exclude_patterns_do_not_mark_excluded_files_missing() {
+        let dir = TestDir::new("scan-payload-exclude");
+        let libraries_root = dir.path().join("libraries");
+        let root = libraries_root.join("lib");
+        fs::create_dir_all(root.join("raw")).expect("create library");
+        fs::write(root.join("raw/a.cr2"), b"x").expect("write");
+        fs::write(root.join("b.jpg"), b"x").expect("write");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+
+        run_scan(&mut conn, &config, "job-1", serde_json::json!({}));
+        fs::remove_file(root.join("b.jpg")).expect("remove file");
+        run_scan(
+            &mut conn,
+            &config,
+            "job-2",
+            serde_json::json!({"exclude_patterns": ["raw/**"]}),
+        );
+        assert_eq!(
+            missing_flags(&conn),
+            vec![
+                ("b.jpg".to_string(), false),
+                ("raw/a.cr2".to_string(), false)
+            ]
+        );
+
+        run_scan(&mut conn, &config, "job-3", serde_json::json!({}));
+        assert_eq!(
+            missing_flags(&conn),
+            vec![
+                ("b.jpg".to_string(), true),
+                ("raw/a.cr2".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn payload_extension_allowlist_does_not_mark_other_files_missing() {
+        let dir = TestDir::new("scan-payload-extensions");
+        let libraries_root = dir.path().join("libraries");
+        let root = libraries_root.join("lib");
+        fs::create_dir_all(&root).expect("create library");
+        fs::write(root.join("a.jpg"), b"x").expect("write");
+        fs::write(root.join("clip.mp4"), b"x").expect("write");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+
+        run_scan(&mut conn, &config, "job-1", serde_json::json!({}));
+        run_scan(
+            &mut conn,
+            &config,
+            "job-2",
+            serde_json::json!({"extensions": ["jpg"]}),
+        );
+        assert_eq!(
+            missing_flags(&conn),
+            vec![
+                ("a.jpg".to_string(), false),
+                ("clip.mp4".to_string(), false)
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn renamed_file_keeps_its_hash() {
+        let dir = TestDir::new("scan-rename");
+        let libraries_root = dir.path().join("libraries");
+        let root = libraries_root.join("lib");
+        fs::create_dir_all(&root).expect("create library");
+        fs::write(root.join("old.jpg"), b"unchanged body").expect("write file");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+
+        let run = |conn: &mut Connection, job_id: &str| {
+            conn.execute(
+                "
+                INSERT INTO jobs(id, kind, status, worker_id, lease_expires_at)
+                VALUES (?1, 'scan', 'running', 'test-worker', datetime('now', '+300 seconds'))
+                ",
+                params![job_id],
+            )
+            .expect("insert job");
+            let job = JobRecord {
+                id: job_id.to_string(),
+                kind: JobKind::Scan,
+                payload: serde_json::json!({}),
+                priority: 0,
+            };
+            let schema = detect_schema_features(conn).expect("schema features");
+            run_scan_job(conn, &config, &job, &schema, &AtomicBool::new(false)).expect("scan job");
+        };
+        let state = |conn: &Connection, path: &str| -> (bool, bool, Option<Vec<u8>>) {
+            conn.query_row(
+                "SELECT is_missing, needs_hash, content_hash FROM library_files WHERE relative_path = ?1",
+                params![path],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .expect("file row")
+        };
+
+        run(&mut conn, "job-1");
+        // Stands in for the hash job; the scan itself never computes hashes.
+        conn.execute(
+            "
+            UPDATE library_files
+            SET needs_hash = 0, hash_algorithm = 'blake3', content_hash = x'0102',
+                hashed_size_bytes = size_bytes, hashed_mtime_ns = mtime_ns,
+                hashed_at = CURRENT_TIMESTAMP
+            ",
+            [],
+        )
+        .expect("record hash");
+
+        fs::rename(root.join("old.jpg"), root.join("new.jpg")).expect("rename file");
+        run(&mut conn, "job-2");
+        assert_eq!(state(&conn, "old.jpg"), (true, false, Some(vec![1, 2])));
+        assert_eq!(state(&conn, "new.jpg"), (false, false, Some(vec![1, 2])));
+
+        // A different file reusing neither inode nor timestamps still needs hashing.
+        fs::write(root.join("other.jpg"), b"unchanged body").expect("write copy");
+        run(&mut conn, "job-3");
+        assert_eq!(state(&conn, "other.jpg"), (false, true, None));
+    }
+
+    #[test]
+    fn pinned_files_are_never_marked_missing() {
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "removable", Path::new("/libraries/removable"));
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns, pinned) VALUES (?1, 'gone.jpg', 1, 1, 0), (?1, 'offline.jpg', 1, 1, 1)",
+            params![library_id],
+        )
+        .expect("insert files");
+        let scan_session_id = create_scan_session(&conn).expect("scan session");
+
+        assert_eq!(
+            mark_missing_files(&conn, library_id, scan_session_id, None, &[])
+                .expect("mark missing"),
+            1
+        );
+        let missing: Vec<(String, bool)> = conn
+            .prepare("SELECT relative_path, is_missing FROM library_files ORDER BY relative_path")
+            .expect("prepare")
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("query")
+            .collect::<rusqlite::Result<_>>()
+            .expect("rows");
+        assert_eq!(
+            missing,
+            vec![
+                ("gone.jpg".to_string(), true),
+                ("offline.jpg".to_string(), false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::distributions::{Alphanumeric, DistString};
+use rusqlite::Connection;
+
+use crate::config::{
+    HashAlgorithm, LogFormat, MissingSchemaVersionPolicy, VideoFrameStrategy, WorkerConfig,
+};
+use crate::db::WalCheckpointMode;
+
+/// Scratch directory removed on drop.
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(prefix: &str) -> Self {
+        let suffix = Alphanumeric.sample_string(&mut rand::thread_rng(), 12);
+        let path = std::env::temp_dir().join(format!("dedupfs-{prefix}-{suffix}"));
+        fs::create_dir_all(&path).expect("create test dir");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+pub fn test_config(libraries_root: &Path, state_root: &Path) -> WorkerConfig {
+    fs::create_dir_all(libraries_root).expect("create libraries root");
+    let thumbs_root = state_root.join("thumbs");
+    fs::create_dir_all(&thumbs_root).expect("create thumbs root");
+    let libraries_root_real = libraries_root
+        .canonicalize()
+        .expect("resolve libraries root");
+
+    WorkerConfig {
+        libraries_roots: vec![libraries_root_real.clone()],
+        libraries_roots_real: vec![libraries_root_real],
+        database_path: state_root.join("dedupfs.sqlite3"),
+        pid_file: None,
+        thumbs_root_real: thumbs_root.canonicalize().expect("resolve thumbs root"),
+        concurrency: 1,
+        io_rate_limit_mib_per_sec: None,
+        hash_io_burst_multiplier: 1.0,
+        hash_algorithm: HashAlgorithm::Blake3,
+        scan_write_batch_size: 2000,
+        scan_follow_symlinks: false,
+        scan_stay_on_filesystem: false,
+        scan_max_depth: None,
+        scan_max_runtime_seconds: None,
+        scan_per_library_max_seconds: None,
+        scan_throughput_sample_seconds: None,
+        scan_schedule_interval_seconds: None,
+        scan_respect_ignore_files: true,
+        allow_symlinked_roots: false,
+        scan_include_extensions: None,
+        scan_exclude_extensions: None,
+        scan_store_path_hash: false,
+        scan_capture_permissions: false,
+        scan_detect_media_type: false,
+        scan_probe_image_dimensions: false,
+        scan_normalize_unicode: false,
+        scan_count_hardlinks_once: true,
+        libraries: HashMap::new(),
+        scan_exclude_patterns: Vec::new(),
+        hash_fetch_batch_size: 512,
+        report_parallelism: 1,
+        hash_read_chunk_bytes: 4 * 1024 * 1024,
+        hash_small_file_threshold_bytes: 64 * 1024,
+        hash_max_file_size_bytes: None,
+        hash_prefix_bytes: None,
+        hash_quick_sample_bytes: None,
+        hash_claim_ttl_seconds: 600,
+        hash_retry_base_seconds: 30,
+        hash_retry_max_seconds: 3600,
+        hash_max_error_count: 10,
+        circuit_breaker_failure_threshold: 10,
+        job_lock_ttl_seconds: 300,
+        job_max_retries: None,
+        commit_yield_interval_seconds: 30,
+        thumbnail_image_concurrency: 2,
+        thumbnail_video_concurrency: 1,
+        thumbnail_io_rate_limit_mib_per_sec: None,
+        thumbnail_retry_base_seconds: 30,
+        thumbnail_retry_max_seconds: 1800,
+        thumbnail_ffmpeg_bin: "ffmpeg".to_string(),
+        thumbnail_require_ffmpeg: false,
+        thumbnail_ffprobe_bin: "ffprobe".to_string(),
+        thumbnail_ffmpeg_timeout_seconds: 120,
+        thumbnail_ffmpeg_terminal_exit_codes: Vec::new(),
+        thumbnail_ffmpeg_terminal_stderr_patterns: Vec::new(),
+        thumbnail_enable_heif_fallback: true,
+        thumbnail_apply_exif_orientation: true,
+        thumbnail_gif_max_frames: 1,
+        thumbnail_skip_if_fresh: false,
+        thumbnail_fsync: false,
+        thumbnail_multi_size: false,
+        thumbnail_video_seek_seconds: 1.0,
+        thumbnail_video_seek_percent: None,
+        thumbnail_video_frame_strategy: VideoFrameStrategy::Fixed,
+        thumbnail_video_representative_window_seconds: 10.0,
+        thumbnail_max_dimension: 256,
+        thumbnail_max_source_pixels: 100_000_000,
+        thumbnail_png_compression: 6,
+        thumbnail_jpeg_quality: 80,
+        thumbnail_webp_lossless: true,
+        thumbnail_dir_mode: None,
+        thumbnail_file_mode: None,
+        thumbnail_library_offline_retry_seconds: 3600,
+        thumbnail_disk_full_retry_seconds: 1800,
+        thumbnail_max_error_count: 5,
+        thumbnail_permanent_error_codes: Vec::new(),
+        cleanup_delete_batch_size: 500,
+        thumbnail_orphan_grace_seconds: 3600,
+        rust_worker_poll_seconds: 5,
+        rust_worker_max_poll_seconds: 30,
+        rust_worker_error_backoff_multiplier: 1.5,
+        rust_worker_poll_jitter_millis: 0,
+        rust_worker_idle_exit_seconds: None,
+        max_claims_per_second: None,
+        recover_leases_on_startup: false,
+        wal_checkpoint_retry_seconds: 120,
+        wal_auto_checkpoint_size_bytes: None,
+        wal_allowed_checkpoint_modes: vec![
+            WalCheckpointMode::Passive,
+            WalCheckpointMode::Full,
+            WalCheckpointMode::Restart,
+            WalCheckpointMode::Truncate,
+        ],
+        completion_webhook_url: None,
+        completion_webhook_max_attempts: 1,
+        completion_webhook_timeout_seconds: 1,
+        schema_version_check: true,
+        schema_version_missing: MissingSchemaVersionPolicy::Warn,
+        log_format: LogFormat::Text,
+        worker_id: "test-worker".to_string(),
+    }
+}
+
+/// Mirrors the columns the worker touches in the Python-owned schema.
+pub fn create_worker_schema(conn: &Connection) {
+    conn.execute_batch(
+        "
+        CREATE TABLE jobs (
+            id VARCHAR(36) PRIMARY KEY,
+            kind VARCHAR(16) NOT NULL,
+            status VARCHAR(16) NOT NULL,
+            dry_run BOOLEAN NOT NULL DEFAULT 1,
+            priority INTEGER NOT NULL DEFAULT 0,
+            retry_count INTEGER NOT NULL DEFAULT 0,
+            max_retries INTEGER,
+            worker_id VARCHAR(128),
+            worker_heartbeat_at DATETIME,
+            lease_expires_at DATETIME,
+            progress FLOAT NOT NULL DEFAULT 0.0,
+            total_items INTEGER,
+            processed_items INTEGER NOT NULL DEFAULT 0,
+            payload JSON NOT NULL DEFAULT '{}',
+            error_code VARCHAR(64),
+            error_message TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            started_at DATETIME,
+            finished_at DATETIME
+        );
+        CREATE TABLE scheduled_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind VARCHAR(16) NOT NULL UNIQUE,
+            last_enqueued_at DATETIME,
+            interval_seconds INTEGER NOT NULL
+        );
+        CREATE TABLE library_circuit_breaker (
+            library_id INTEGER PRIMARY KEY REFERENCES library_roots(id) ON DELETE CASCADE,
+            tripped_at DATETIME NOT NULL,
+            reset_after DATETIME NOT NULL,
+            failure_count INTEGER NOT NULL
+        );
+        CREATE TABLE job_log_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            job_id VARCHAR(36) NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+            logged_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            level VARCHAR(8) NOT NULL,
+            message TEXT NOT NULL
+        );
+        CREATE TABLE library_roots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name VARCHAR(255) NOT NULL UNIQUE,
+            root_path VARCHAR(2048) NOT NULL UNIQUE,
+            link_path VARCHAR(2048),
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            last_scanned_at DATETIME,
+            last_scan_timed_out_at DATETIME,
+            io_rate_limit_mib_per_sec REAL
+        );
+        CREATE TABLE scan_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            status VARCHAR(16) NOT NULL,
+            started_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            finished_at DATETIME,
+            error_message TEXT,
+            files_seen BIGINT NOT NULL DEFAULT 0,
+            directories_seen BIGINT NOT NULL DEFAULT 0,
+            bytes_seen BIGINT NOT NULL DEFAULT 0,
+            depth_skipped INTEGER,
+            error_count INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE scan_throughput_samples (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            scan_session_id INTEGER NOT NULL REFERENCES scan_sessions(id) ON DELETE CASCADE,
+            library_id INTEGER NOT NULL,
+            sampled_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            interval_ms INTEGER NOT NULL,
+            files_delta BIGINT NOT NULL,
+            directories_delta BIGINT NOT NULL,
+            bytes_delta BIGINT NOT NULL,
+            files_per_sec FLOAT NOT NULL,
+            directories_per_sec FLOAT NOT NULL,
+            bytes_per_sec FLOAT NOT NULL,
+            current_dir TEXT
+        );
+        CREATE TABLE library_files (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            library_id INTEGER NOT NULL,
+            relative_path VARCHAR(4096) NOT NULL,
+            relative_path_hash BIGINT,
+            size_bytes BIGINT NOT NULL,
+            mtime_ns BIGINT NOT NULL,
+            inode BIGINT,
+            device BIGINT,
+            file_mode INTEGER,
+            owner_uid INTEGER,
+            owner_gid INTEGER,
+            media_type VARCHAR(16),
+            extension VARCHAR(32),
+            mime_type VARCHAR(128),
+            image_width INTEGER,
+            image_height INTEGER,
+            is_missing BOOLEAN NOT NULL DEFAULT 0,
+            needs_hash BOOLEAN NOT NULL DEFAULT 1,
+            pinned BOOLEAN NOT NULL DEFAULT 0,
+            last_seen_scan_id INTEGER,
+            hash_algorithm VARCHAR(16),
+            content_hash BLOB,
+            quick_hash BLOB,
+            prefix_hash BLOB,
+            hashed_size_bytes BIGINT,
+            hashed_mtime_ns BIGINT,
+            hashed_at DATETIME,
+            verify_status VARCHAR(16),
+            verified_at DATETIME,
+            hash_error_count INTEGER NOT NULL DEFAULT 0,
+            hash_last_error TEXT,
+            hash_last_error_at DATETIME,
+            hash_retry_after DATETIME,
+            hash_claim_token VARCHAR(64),
+            hash_claimed_at DATETIME,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (library_id, relative_path)
+        );
+        CREATE TABLE hardlink_aliases (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            primary_file_id INTEGER NOT NULL,
+            alias_library_id INTEGER NOT NULL,
+            alias_relative_path VARCHAR(4096) NOT NULL,
+            scan_session_id INTEGER,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (alias_library_id, alias_relative_path)
+        );
+        CREATE TABLE hash_verifications (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            file_id INTEGER NOT NULL,
+            verified_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            matched BOOLEAN NOT NULL,
+            stored_hash BLOB,
+            computed_hash BLOB
+        );
+        CREATE TABLE dedup_groups (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            group_key VARCHAR(256) NOT NULL UNIQUE,
+            hash_algorithm VARCHAR(16) NOT NULL,
+            content_hash BLOB NOT NULL,
+            member_count INTEGER NOT NULL,
+            total_bytes BIGINT NOT NULL,
+            reclaimable_bytes BIGINT NOT NULL,
+            job_id VARCHAR(36),
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE worker_control (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            paused BOOLEAN NOT NULL DEFAULT 0,
+            pause_scan BOOLEAN NOT NULL DEFAULT 0,
+            pause_hash BOOLEAN NOT NULL DEFAULT 0,
+            pause_thumbnail BOOLEAN NOT NULL DEFAULT 0,
+            pause_wal BOOLEAN NOT NULL DEFAULT 0,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        INSERT INTO worker_control(id) VALUES (1);
+        CREATE TABLE thumbnails (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            thumb_key VARCHAR(128) NOT NULL UNIQUE,
+            file_id INTEGER NOT NULL,
+            group_key VARCHAR(256),
+            status VARCHAR(16) NOT NULL DEFAULT 'pending',
+            media_type VARCHAR(16) NOT NULL,
+            format VARCHAR(16) NOT NULL DEFAULT 'jpeg',
+            max_dimension INTEGER NOT NULL DEFAULT 256,
+            version INTEGER NOT NULL DEFAULT 1,
+            seek_seconds FLOAT,
+            sizes VARCHAR(64),
+            task_group_id INTEGER,
+            source_size_bytes BIGINT NOT NULL,
+            source_mtime_ns BIGINT NOT NULL,
+            output_relpath VARCHAR(1024),
+            width INTEGER,
+            height INTEGER,
+            bytes_size BIGINT,
+            error_code VARCHAR(64),
+            error_message TEXT,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            permanent_failure BOOLEAN NOT NULL DEFAULT 0,
+            retry_after DATETIME,
+            worker_id VARCHAR(128),
+            worker_heartbeat_at DATETIME,
+            lease_expires_at DATETIME,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            started_at DATETIME,
+            finished_at DATETIME
+        );
+        CREATE TABLE thumbnail_cleanup_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            group_key VARCHAR(256) NOT NULL UNIQUE,
+            cleanup_kind VARCHAR(16) NOT NULL DEFAULT 'group',
+            status VARCHAR(16) NOT NULL DEFAULT 'pending',
+            execute_after DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            worker_id VARCHAR(128),
+            worker_heartbeat_at DATETIME,
+            lease_expires_at DATETIME,
+            error_code VARCHAR(64),
+            error_message TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            finished_at DATETIME
+        );
+        CREATE TABLE wal_maintenance_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            requested_mode VARCHAR(16) NOT NULL DEFAULT 'passive',
+            status VARCHAR(16) NOT NULL DEFAULT 'pending',
+            requested_by VARCHAR(64),
+            reason TEXT,
+            execute_after DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            retry_count INTEGER NOT NULL DEFAULT 0,
+            retry_after DATETIME,
+            worker_id VARCHAR(128),
+            worker_heartbeat_at DATETIME,
+            lease_expires_at DATETIME,
+            checkpoint_busy INTEGER,
+            checkpoint_log_frames INTEGER,
+            checkpointed_frames INTEGER,
+            error_code VARCHAR(64),
+            error_message TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            started_at DATETIME,
+            finished_at DATETIME
+        );
+
+        CREATE TABLE db_maintenance_jobs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            operation VARCHAR(32) NOT NULL,
+            status VARCHAR(16) NOT NULL DEFAULT 'pending',
+            requested_by VARCHAR(64),
+            reason TEXT,
+            execute_after DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            worker_id VARCHAR(128),
+            worker_heartbeat_at DATETIME,
+            lease_expires_at DATETIME,
+            page_count_before INTEGER,
+            page_count_after INTEGER,
+            freelist_count_before INTEGER,
+            freelist_count_after INTEGER,
+            integrity_result TEXT,
+            error_code VARCHAR(64),
+            error_message TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            started_at DATETIME,
+            finished_at DATETIME
+        );
+        ",
+    )
+    .expect("create worker schema");
+}
+
+pub fn insert_library(conn: &Connection, name: &str, root_path: &Path) -> i64 {
+    conn.execute(
+        "INSERT INTO library_roots(name, root_path) VALUES (?1, ?2)",
+        rusqlite::params![name, root_path.to_string_lossy().to_string()],
+    )
+    .expect("insert library root");
+    conn.last_insert_rowid()
+}
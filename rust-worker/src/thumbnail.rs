@@ -1,20 +1,30 @@
 use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::io::{self, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::{bail, Context, Result};
-use image::{ImageFormat, ImageReader};
+use anyhow::{anyhow, bail, Context, Result};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{AnimationDecoder, DynamicImage, ImageError, ImageFormat, ImageReader};
+use rand::distributions::{Alphanumeric, DistString};
 use rusqlite::Connection;
 
-use crate::config::WorkerConfig;
+use crate::config::{VideoFrameStrategy, WorkerConfig};
 use crate::db::{
-    delete_group_thumbnail_rows, list_group_thumbnail_outputs, refresh_thumbnail_cleanup_lease,
-    refresh_thumbnail_lease, reserve_global_io_budget, ThumbnailCleanupRecord, ThumbnailTaskRecord,
+    delete_thumbnail_rows_by_id, find_fresh_thumbnail_dimensions, group_has_pinned_files,
+    is_thumbnail_output_referenced, is_thumbnail_sibling_referenced, list_group_thumbnail_outputs,
+    refresh_thumbnail_cleanup_lease, refresh_thumbnail_lease, reserve_global_io_budget,
+    ThumbnailCleanupRecord, ThumbnailTaskRecord,
+};
+use crate::error_codes::ErrorCode;
+use crate::path_safety::{
+    find_unicode_variant, resolve_root_under_libraries, resolve_stored_relative_path,
+    to_posix_relative_path, validate_relative_path,
 };
-use crate::path_safety::{resolve_root_under_libraries, validate_relative_path};
 
 pub fn run_thumbnail_task(
     conn: &Connection,
@@ -22,38 +32,50 @@ pub fn run_thumbnail_task(
     task: &ThumbnailTaskRecord,
 ) -> Result<(i64, i64, i64)> {
     refresh_thumbnail_lease(conn, config, task.id)?;
-    let mut lease_refresher = LeaseRefresher::new(conn, config, task.id);
+    let mut lease_refresher = LeaseRefresher::new(conn, config, vec![task.id]);
     lease_refresher.maybe_refresh()?;
 
-    let source_path = resolve_source_path(config, task)?;
-    let metadata = fs::metadata(&source_path)
-        .with_context(|| format!("failed to read source metadata: {}", source_path.display()))?;
+    let (source_path, metadata) = validate_task_source(config, task)?;
 
-    let source_size =
-        i64::try_from(metadata.len()).context("thumbnail source size over i64 range")?;
-    if source_size != task.source_size_bytes {
-        bail!("source size changed before thumbnail generation");
-    }
-    let source_mtime_ns = metadata_mtime_ns(&metadata)?;
-    if source_mtime_ns != task.source_mtime_ns {
-        bail!("source mtime changed before thumbnail generation");
+    let specs = task_size_specs(config, task)?;
+    if specs.len() > 1 {
+        if config.thumbnail_skip_if_fresh {
+            if let Some(reused) = reuse_task_outputs(conn, config, task, &specs)? {
+                return Ok(reused);
+            }
+        }
+        reserve_thumbnail_io_budget(conn, config, metadata.len())?;
+        let results = generate_thumbnail_multi(
+            config,
+            &source_path,
+            &task.media_type,
+            &specs,
+            task.seek_seconds,
+            &mut lease_refresher,
+        )?;
+        lease_refresher.maybe_refresh()?;
+        reserve_thumbnail_io_budget(conn, config, metadata.len())?;
+        return combine_size_results(results);
     }
 
     let output_path = resolve_output_path(config, task)?;
     let output_path = normalize_output_target(config, &output_path)?;
 
-    let temp_path = output_path.with_file_name(format!("{}.tmp", task.thumb_key));
+    if config.thumbnail_skip_if_fresh {
+        if let Some(reused) = reuse_fresh_output(conn, task, &output_path)? {
+            return Ok(reused);
+        }
+    }
+
+    let temp_path = unique_scratch_path(&output_path, &task.thumb_key, ".tmp");
     let _temp_guard = TempFileGuard::new(temp_path.clone());
-    let max_dimension = usize::try_from(task.max_dimension)
-        .ok()
-        .map(|value| value.min(config.thumbnail_max_dimension))
-        .unwrap_or(config.thumbnail_max_dimension)
-        .max(16);
+    let max_dimension = clamp_max_dimension(config, task.max_dimension);
 
     reserve_thumbnail_io_budget(conn, config, metadata.len())?;
 
     let (width, height) = match task.media_type.as_str() {
         "image" => generate_image_thumbnail(
+            config,
             &source_path,
             &temp_path,
             max_dimension,
@@ -66,6 +88,7 @@ pub fn run_thumbnail_task(
             &temp_path,
             max_dimension,
             &task.format,
+            task.seek_seconds,
             &mut lease_refresher,
         )?,
         _ => bail!("unsupported thumbnail media_type: {}", task.media_type),
@@ -73,29 +96,241 @@ pub fn run_thumbnail_task(
     lease_refresher.maybe_refresh()?;
     reserve_thumbnail_io_budget(conn, config, metadata.len())?;
 
-    if output_path.exists() {
-        fs::remove_file(&output_path).with_context(|| {
+    let output_bytes = finalize_thumbnail_output(config, &temp_path, &output_path)?;
+    Ok((i64::from(width), i64::from(height), output_bytes))
+}
+
+/// Runs a task group claimed by `claim_thumbnail_task_group`, decoding the shared source once.
+///
+/// An `Err` means the whole group failed (source changed, decode failed); otherwise there is
+/// one result per task, in the order given.
+pub fn run_thumbnail_task_group(
+    conn: &Connection,
+    config: &WorkerConfig,
+    tasks: &[ThumbnailTaskRecord],
+) -> Result<Vec<Result<(i64, i64, i64)>>> {
+    let Some(leader) = tasks.first() else {
+        return Ok(Vec::new());
+    };
+    for task in tasks {
+        refresh_thumbnail_lease(conn, config, task.id)?;
+    }
+    let mut lease_refresher =
+        LeaseRefresher::new(conn, config, tasks.iter().map(|task| task.id).collect());
+
+    let (source_path, metadata) = validate_task_source(config, leader)?;
+
+    // Per task: its reused result, or how many specs (own output plus extra sizes) it added.
+    let mut reused = Vec::with_capacity(tasks.len());
+    let mut specs = Vec::new();
+    for task in tasks {
+        let task_specs = task_size_specs(config, task)?;
+        if config.thumbnail_skip_if_fresh {
+            if let Some(dimensions) = reuse_task_outputs(conn, config, task, &task_specs)? {
+                reused.push(Ok(dimensions));
+                continue;
+            }
+        }
+        reused.push(Err(task_specs.len()));
+        specs.extend(task_specs);
+    }
+
+    let mut rendered = Vec::new().into_iter();
+    if !specs.is_empty() {
+        reserve_thumbnail_io_budget(conn, config, metadata.len())?;
+        rendered = generate_thumbnail_multi(
+            config,
+            &source_path,
+            &leader.media_type,
+            &specs,
+            leader.seek_seconds,
+            &mut lease_refresher,
+        )?
+        .into_iter();
+        lease_refresher.maybe_refresh()?;
+        reserve_thumbnail_io_budget(conn, config, metadata.len())?;
+    }
+
+    Ok(reused
+        .into_iter()
+        .map(|reused| match reused {
+            Ok(dimensions) => Ok(dimensions),
+            Err(spec_count) => combine_size_results(rendered.by_ref().take(spec_count).collect()),
+        })
+        .collect())
+}
+
+/// The task's own output first, then one sibling per extra entry in `sizes`.
+fn task_size_specs(
+    config: &WorkerConfig,
+    task: &ThumbnailTaskRecord,
+) -> Result<Vec<ThumbnailSizeSpec>> {
+    let mut specs = vec![ThumbnailSizeSpec {
+        max_dimension: clamp_max_dimension(config, task.max_dimension),
+        output_relpath: task.output_relpath.clone(),
+        format: task.format.clone(),
+    }];
+    for size in parse_thumbnail_sizes(task.sizes.as_deref())? {
+        if size == task.max_dimension {
+            continue;
+        }
+        specs.push(ThumbnailSizeSpec {
+            max_dimension: clamp_max_dimension(config, size),
+            output_relpath: sibling_output_relpath(&task.output_relpath, size),
+            format: task.format.clone(),
+        });
+    }
+    Ok(specs)
+}
+
+/// Parses `thumbnails.sizes`; duplicates are dropped and the order is largest first.
+fn parse_thumbnail_sizes(raw: Option<&str>) -> Result<Vec<i64>> {
+    let mut sizes = Vec::new();
+    for part in raw.unwrap_or_default().split(',') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        let size = part
+            .parse::<i64>()
+            .ok()
+            .filter(|size| *size > 0)
+            .ok_or_else(|| anyhow!("invalid thumbnail size: {part}"))?;
+        sizes.push(size);
+    }
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes.dedup();
+    Ok(sizes)
+}
+
+/// Output of one extra size: `ab/cd/<key>.jpg` -> `ab/cd/<key>_<size>.jpg`. Python derives
+/// the same name in `thumbnail_sibling_relpath`.
+fn sibling_output_relpath(output_relpath: &str, size: i64) -> String {
+    match output_relpath.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains('/') => {
+            format!("{stem}_{size}.{extension}")
+        }
+        _ => format!("{output_relpath}_{size}"),
+    }
+}
+
+/// Inverse of [`sibling_output_relpath`], used by the orphan sweep.
+fn sibling_primary_relpath(relpath: &str) -> Option<(String, i64)> {
+    let (stem, extension) = match relpath.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains('/') => (stem, Some(extension)),
+        _ => (relpath, None),
+    };
+    let (base, size) = stem.rsplit_once('_')?;
+    if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let size = size.parse().ok()?;
+    let primary = match extension {
+        Some(extension) => format!("{base}.{extension}"),
+        None => base.to_string(),
+    };
+    Some((primary, size))
+}
+
+/// Fresh-output reuse for a task; with extra sizes, every sibling file must exist too.
+fn reuse_task_outputs(
+    conn: &Connection,
+    config: &WorkerConfig,
+    task: &ThumbnailTaskRecord,
+    specs: &[ThumbnailSizeSpec],
+) -> Result<Option<(i64, i64, i64)>> {
+    for spec in specs.iter().skip(1) {
+        let relative = validate_relative_path(&spec.output_relpath)?;
+        if !config.thumbs_root_real.join(relative).is_file() {
+            return Ok(None);
+        }
+    }
+    let output_path = normalize_output_target(config, &resolve_output_path(config, task)?)?;
+    reuse_fresh_output(conn, task, &output_path)
+}
+
+/// Reduces one task's per-size results to its own output's; any failed size fails the task.
+fn combine_size_results(results: Vec<Result<(u32, u32, i64)>>) -> Result<(i64, i64, i64)> {
+    let mut primary = None;
+    for result in results {
+        let (width, height, bytes) = result?;
+        primary.get_or_insert((i64::from(width), i64::from(height), bytes));
+    }
+    primary.ok_or_else(|| anyhow!("thumbnail output was not rendered"))
+}
+
+/// One output size rendered by [`generate_thumbnail_multi`].
+#[derive(Debug, Clone)]
+struct ThumbnailSizeSpec {
+    max_dimension: usize,
+    output_relpath: String,
+    format: String,
+}
+
+/// Renders every spec from a single image decode or ffmpeg frame extraction.
+///
+/// Output paths are validated up front; after the shared decode each spec is resized,
+/// written and moved into place independently, so one failed write does not fail the rest.
+fn generate_thumbnail_multi(
+    config: &WorkerConfig,
+    source_path: &Path,
+    media_type: &str,
+    specs: &[ThumbnailSizeSpec],
+    seek_override: Option<f64>,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Result<Vec<Result<(u32, u32, i64)>>> {
+    let mut output_paths = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let relative = validate_relative_path(&spec.output_relpath).with_context(|| {
             format!(
-                "failed to replace existing thumbnail output file: {}",
-                output_path.display()
+                "invalid thumbnail output relative path: {}",
+                spec.output_relpath
             )
         })?;
+        output_paths.push(normalize_output_target(
+            config,
+            &config.thumbs_root_real.join(relative),
+        )?);
     }
-    fs::rename(&temp_path, &output_path).with_context(|| {
-        format!(
-            "failed to move thumbnail temp output into final path: {}",
-            output_path.display()
-        )
-    })?;
+    let Some(scratch_path) = output_paths.first() else {
+        return Ok(Vec::new());
+    };
 
-    let output_bytes = i64::try_from(
-        fs::metadata(&output_path)
-            .with_context(|| format!("failed to stat thumbnail output: {}", output_path.display()))?
-            .len(),
-    )
-    .context("thumbnail output size over i64 range")?;
+    let source = match media_type {
+        "image" => decode_source_image(config, source_path, scratch_path, lease_refresher)?,
+        "video" => extract_video_frame(
+            config,
+            source_path,
+            scratch_path,
+            seek_override,
+            lease_refresher,
+        )?,
+        _ => bail!("unsupported thumbnail media_type: {media_type}"),
+    };
 
-    Ok((i64::from(width), i64::from(height), output_bytes))
+    let mut results = Vec::with_capacity(specs.len());
+    for (spec, output_path) in specs.iter().zip(&output_paths) {
+        let temp_path = unique_scratch_path(
+            output_path,
+            &file_stem_or(output_path, "thumb"),
+            ".multi.tmp",
+        );
+        let _temp_guard = TempFileGuard::new(temp_path.clone());
+        let result = render_thumbnail(
+            config,
+            &source,
+            &temp_path,
+            spec.max_dimension,
+            &spec.format,
+            lease_refresher,
+        )
+        .and_then(|(width, height)| {
+            let bytes = finalize_thumbnail_output(config, &temp_path, output_path)?;
+            Ok((width, height, bytes))
+        });
+        results.push(result);
+    }
+    Ok(results)
 }
 
 pub fn run_thumbnail_cleanup_task(
@@ -104,72 +339,368 @@ pub fn run_thumbnail_cleanup_task(
     cleanup: &ThumbnailCleanupRecord,
 ) -> Result<usize> {
     refresh_thumbnail_cleanup_lease(conn, config, cleanup.id)?;
+    // Pinned files may sit on unmounted media; their group keeps its thumbnails.
+    if group_has_pinned_files(conn, &cleanup.group_key)? {
+        return Ok(0);
+    }
     let outputs = list_group_thumbnail_outputs(conn, &cleanup.group_key)?;
+    let mut removed = 0;
 
-    for (index, (_, relpath)) in outputs.into_iter().enumerate() {
-        if index % 128 == 0 {
-            refresh_thumbnail_cleanup_lease(conn, config, cleanup.id)?;
+    // Each chunk's files (sibling sizes included) go first and its rows second, so a
+    // failure never leaves a row deleted while one of its files is still on disk.
+    for chunk in outputs.chunks(config.cleanup_delete_batch_size.max(1)) {
+        refresh_thumbnail_cleanup_lease(conn, config, cleanup.id)?;
+        let mut targets = Vec::with_capacity(chunk.len());
+        for (_, relpath, sizes) in chunk {
+            if let Some(target) = resolve_cleanup_target(config, relpath)? {
+                targets.push(target);
+            }
+            let sizes = if relpath.trim().is_empty() {
+                Vec::new()
+            } else {
+                parse_thumbnail_sizes(sizes.as_deref())?
+            };
+            for size in sizes {
+                if let Some(target) =
+                    resolve_cleanup_target(config, &sibling_output_relpath(relpath, size))?
+                {
+                    targets.push(target);
+                }
+            }
         }
-        if relpath.trim().is_empty() {
-            continue;
+        remove_cleanup_targets(&targets)?;
+
+        let ids: Vec<i64> = chunk.iter().map(|(id, _, _)| *id).collect();
+        removed += delete_thumbnail_rows_by_id(conn, &ids)?;
+    }
+    Ok(removed)
+}
+
+/// Upper bound on threads removing one cleanup chunk's files.
+const CLEANUP_REMOVE_PARALLELISM: usize = 8;
+
+/// Resolves a DB `output_relpath` to its file under `thumbs_root_real`; `None` when the
+/// path is blank or its directory is already gone.
+fn resolve_cleanup_target(config: &WorkerConfig, relpath: &str) -> Result<Option<PathBuf>> {
+    if relpath.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let relative = validate_relative_path(relpath)
+        .with_context(|| format!("invalid thumbnail relative path in DB: {relpath}"))?;
+    let absolute = config.thumbs_root_real.join(relative);
+    let normalized = match normalize_existing_output_target(config, &absolute) {
+        Ok(path) => path,
+        Err(error) => {
+            if !absolute.exists() {
+                return Ok(None);
+            }
+            return Err(error);
         }
+    };
 
-        let relative = validate_relative_path(&relpath)
-            .with_context(|| format!("invalid thumbnail relative path in DB: {relpath}"))?;
-        let absolute = config.thumbs_root_real.join(relative);
-        let normalized = match normalize_existing_output_target(config, &absolute) {
-            Ok(path) => path,
-            Err(error) => {
-                if !absolute.exists() {
-                    continue;
-                }
-                return Err(error);
+    if normalized != config.thumbs_root_real && !normalized.starts_with(&config.thumbs_root_real) {
+        bail!(
+            "thumbnail output path escapes thumbs root: {}",
+            normalized.display()
+        );
+    }
+    Ok(Some(normalized))
+}
+
+fn remove_cleanup_targets(targets: &[PathBuf]) -> Result<()> {
+    if targets.is_empty() {
+        return Ok(());
+    }
+    let per_thread = targets.len().div_ceil(CLEANUP_REMOVE_PARALLELISM);
+    thread::scope(|scope| {
+        let workers: Vec<_> = targets
+            .chunks(per_thread)
+            .map(|slice| {
+                scope.spawn(move || {
+                    slice
+                        .iter()
+                        .try_for_each(|path| remove_cleanup_target(path))
+                })
+            })
+            .collect();
+        workers.into_iter().try_for_each(|worker| {
+            worker
+                .join()
+                .map_err(|_| anyhow!("thumbnail cleanup removal thread panicked"))?
+        })
+    })
+}
+
+fn remove_cleanup_target(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error)
+            .with_context(|| format!("failed to remove thumbnail file: {}", path.display())),
+    }
+}
+
+/// Removes files under `thumbs_root_real` that no `thumbnails.output_relpath` references.
+///
+/// Outputs are written before their row is finalized, so files modified within
+/// `thumbnail_orphan_grace_seconds` are kept. Symlinks are never followed or removed.
+pub fn run_thumbnail_orphan_sweep(
+    conn: &Connection,
+    config: &WorkerConfig,
+    cleanup: &ThumbnailCleanupRecord,
+) -> Result<usize> {
+    refresh_thumbnail_cleanup_lease(conn, config, cleanup.id)?;
+    let grace = Duration::from_secs(config.thumbnail_orphan_grace_seconds);
+    let now = SystemTime::now();
+    let mut pending_dirs = vec![config.thumbs_root_real.clone()];
+    let mut visited = 0usize;
+    let mut removed = 0usize;
+
+    while let Some(dir) = pending_dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("failed to read thumbnail directory: {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            visited += 1;
+            if visited.is_multiple_of(128) {
+                refresh_thumbnail_cleanup_lease(conn, config, cleanup.id)?;
+            }
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending_dirs.push(path);
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
             }
-        };
 
-        if normalized != config.thumbs_root_real
-            && !normalized.starts_with(&config.thumbs_root_real)
-        {
-            bail!(
-                "thumbnail output path escapes thumbs root: {}",
-                normalized.display()
-            );
+            let relpath =
+                to_posix_relative_path(path.strip_prefix(&config.thumbs_root_real)?, false)?;
+            let relative = validate_relative_path(&relpath)
+                .with_context(|| format!("invalid thumbnail relative path on disk: {relpath}"))?;
+            if !is_orphan_thumbnail(conn, &relpath)? {
+                continue;
+            }
+            let modified = entry.metadata()?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() < grace {
+                continue;
+            }
+            if remove_orphan_thumbnail(config, &relative)? {
+                removed += 1;
+            }
         }
+    }
+    Ok(removed)
+}
 
-        match fs::remove_file(&normalized) {
-            Ok(()) => {}
-            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
-            Err(error) => {
-                return Err(error).with_context(|| {
-                    format!("failed to remove thumbnail file: {}", normalized.display())
-                })
+/// Summary of [`run_orphan_thumbnail_scan`]; `orphan_paths` holds at most
+/// `ORPHAN_SCAN_SAMPLE_LIMIT` absolute paths.
+#[derive(Debug, Default)]
+pub struct OrphanScanResult {
+    pub orphan_count: usize,
+    pub orphan_bytes: u64,
+    pub orphan_paths: Vec<PathBuf>,
+    pub deleted_count: usize,
+}
+
+const ORPHAN_SCAN_SAMPLE_LIMIT: usize = 100;
+
+/// Operator report of thumbnail files no `thumbnails` row references. Read-only; files
+/// inside `thumbnail_orphan_grace_seconds` are not counted, as in the sweep.
+pub fn run_orphan_thumbnail_scan(
+    conn: &Connection,
+    config: &WorkerConfig,
+) -> Result<OrphanScanResult> {
+    scan_orphan_thumbnails(conn, config, false)
+}
+
+/// [`run_orphan_thumbnail_scan`] that also removes every orphan it reports.
+pub fn delete_orphan_thumbnails(
+    conn: &Connection,
+    config: &WorkerConfig,
+) -> Result<OrphanScanResult> {
+    scan_orphan_thumbnails(conn, config, true)
+}
+
+fn scan_orphan_thumbnails(
+    conn: &Connection,
+    config: &WorkerConfig,
+    delete: bool,
+) -> Result<OrphanScanResult> {
+    let grace = Duration::from_secs(config.thumbnail_orphan_grace_seconds);
+    let now = SystemTime::now();
+    let mut pending_dirs = vec![config.thumbs_root_real.clone()];
+    let mut result = OrphanScanResult::default();
+
+    while let Some(dir) = pending_dirs.pop() {
+        let entries = fs::read_dir(&dir)
+            .with_context(|| format!("failed to read thumbnail directory: {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                pending_dirs.push(path);
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+
+            let relpath =
+                to_posix_relative_path(path.strip_prefix(&config.thumbs_root_real)?, false)?;
+            let relative = validate_relative_path(&relpath)
+                .with_context(|| format!("invalid thumbnail relative path on disk: {relpath}"))?;
+            if !is_orphan_thumbnail(conn, &relpath)? {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if now.duration_since(metadata.modified()?).unwrap_or_default() < grace {
+                continue;
+            }
+
+            result.orphan_count += 1;
+            result.orphan_bytes += metadata.len();
+            if result.orphan_paths.len() < ORPHAN_SCAN_SAMPLE_LIMIT {
+                result.orphan_paths.push(path);
+            }
+            if delete && remove_orphan_thumbnail(config, &relative)? {
+                result.deleted_count += 1;
             }
         }
     }
+    Ok(result)
+}
 
-    let removed = delete_group_thumbnail_rows(conn, &cleanup.group_key)?;
-    Ok(removed)
+/// True when neither a primary output nor an extra-size sibling row references `relpath`.
+fn is_orphan_thumbnail(conn: &Connection, relpath: &str) -> Result<bool> {
+    if is_thumbnail_output_referenced(conn, relpath)? {
+        return Ok(false);
+    }
+    if let Some((primary, size)) = sibling_primary_relpath(relpath) {
+        if is_thumbnail_sibling_referenced(conn, &primary, size)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
 }
 
-pub fn classify_thumbnail_error(error: &anyhow::Error) -> &'static str {
+/// Removes one orphan after re-checking it stays under `thumbs_root_real`; false when it
+/// was already gone.
+fn remove_orphan_thumbnail(config: &WorkerConfig, relative: &Path) -> Result<bool> {
+    let normalized =
+        normalize_existing_output_target(config, &config.thumbs_root_real.join(relative))?;
+    if !normalized.starts_with(&config.thumbs_root_real) {
+        bail!(
+            "thumbnail output path escapes thumbs root: {}",
+            normalized.display()
+        );
+    }
+    match fs::remove_file(&normalized) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error).with_context(|| {
+            format!(
+                "failed to remove orphan thumbnail: {}",
+                normalized.display()
+            )
+        }),
+    }
+}
+
+/// Decoder messages (from the `image` crate's limit errors and allocator
+/// failures) that mean the source would exhaust memory on every attempt.
+const DECODE_RESOURCE_PATTERNS: &[&str] = &[
+    "memory limit exceeded",
+    "image size exceeds limit",
+    "insufficient memory",
+    "memory allocation",
+    "out of memory",
+];
+
+const SOURCE_TOO_LARGE: &str = "source image too large";
+
+/// Context on `create_dir_all` failures for the output directory; the io error below it
+/// decides between a config problem and a full disk.
+const OUTPUT_DIR_CREATE_FAILED: &str = "failed to create thumbnail output directory";
+
+pub fn classify_thumbnail_error(error: &anyhow::Error) -> ErrorCode {
     let message = error.to_string().to_lowercase();
+    if message.contains("library root is offline") {
+        return ErrorCode::ThumbLibraryOffline;
+    }
+    if message.contains(SOURCE_TOO_LARGE) {
+        return ErrorCode::ThumbSourceTooLarge;
+    }
+    match output_dir_error_kind(error) {
+        Some(io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem) => {
+            return ErrorCode::ThumbDirPermission;
+        }
+        Some(io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded) => {
+            return ErrorCode::ThumbDiskFull;
+        }
+        _ => {}
+    }
+    // Decoder errors sit below the context layers, so match the whole chain.
+    let chain = format!("{error:#}").to_lowercase();
+    if DECODE_RESOURCE_PATTERNS
+        .iter()
+        .any(|pattern| chain.contains(pattern))
+    {
+        return ErrorCode::ThumbDecodeResource;
+    }
+    if message.contains("heif decoder unavailable") {
+        return ErrorCode::ThumbHeifUnsupported;
+    }
+    if message.contains("ffmpeg") && message.contains(FFMPEG_TERMINAL_MARKER) {
+        return ErrorCode::ThumbVideoFfmpegTerminal;
+    }
     if message.contains("ffmpeg") {
-        return "THUMB_VIDEO_FFMPEG_FAILED";
+        return ErrorCode::ThumbVideoFfmpegFailed;
     }
     if message.contains("path") || message.contains("escape") {
-        return "THUMB_PATH_POLICY_REJECTED";
+        return ErrorCode::ThumbPathPolicyRejected;
     }
     if message.contains("format") || message.contains("decode") {
-        return "THUMB_DECODE_FAILED";
+        return ErrorCode::ThumbDecodeFailed;
     }
-    "THUMB_GENERATION_FAILED"
+    ErrorCode::ThumbGenerationFailed
+}
+
+/// Kind of the io error behind a failed output directory creation. Read from the error
+/// chain because the stored message no longer carries it.
+fn output_dir_error_kind(error: &anyhow::Error) -> Option<io::ErrorKind> {
+    if !error
+        .chain()
+        .any(|cause| cause.to_string().starts_with(OUTPUT_DIR_CREATE_FAILED))
+    {
+        return None;
+    }
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<io::Error>())
+        .map(io::Error::kind)
 }
 
 fn resolve_source_path(config: &WorkerConfig, task: &ThumbnailTaskRecord) -> Result<PathBuf> {
-    let root =
-        resolve_root_under_libraries(&config.libraries_root_real, &PathBuf::from(&task.root_path))?;
-    let relative = validate_relative_path(&task.relative_path)?;
-    let candidate = root.join(relative);
+    let root_path = PathBuf::from(&task.root_path);
+    if !root_path.is_dir() {
+        bail!("source library root is offline: {}", root_path.display());
+    }
+    let root = resolve_root_under_libraries(
+        &config.libraries_roots_real,
+        &root_path,
+        config.allow_symlinked_roots,
+    )?;
+    let relative = resolve_stored_relative_path(&root, &task.relative_path)?;
+    let mut candidate = root.join(&relative);
+    if !candidate.exists() && config.scan_normalize_unicode {
+        if let Some(variant) = find_unicode_variant(&root, &relative) {
+            candidate = variant;
+        }
+    }
 
     if candidate.exists() {
         let real_candidate = candidate.canonicalize().with_context(|| {
@@ -203,62 +734,454 @@ fn resolve_output_path(config: &WorkerConfig, task: &ThumbnailTaskRecord) -> Res
     Ok(candidate)
 }
 
+fn validate_task_source(
+    config: &WorkerConfig,
+    task: &ThumbnailTaskRecord,
+) -> Result<(PathBuf, fs::Metadata)> {
+    let source_path = resolve_source_path(config, task)?;
+    let metadata = fs::metadata(&source_path)
+        .with_context(|| format!("failed to read source metadata: {}", source_path.display()))?;
+
+    let source_size =
+        i64::try_from(metadata.len()).context("thumbnail source size over i64 range")?;
+    if source_size != task.source_size_bytes {
+        bail!("source size changed before thumbnail generation");
+    }
+    let source_mtime_ns = metadata_mtime_ns(&metadata)?;
+    if source_mtime_ns != task.source_mtime_ns {
+        bail!("source mtime changed before thumbnail generation");
+    }
+    Ok((source_path, metadata))
+}
+
+fn reuse_fresh_output(
+    conn: &Connection,
+    task: &ThumbnailTaskRecord,
+    output_path: &Path,
+) -> Result<Option<(i64, i64, i64)>> {
+    let Some((width, height)) = find_fresh_thumbnail_dimensions(conn, task)? else {
+        return Ok(None);
+    };
+    match fs::metadata(output_path) {
+        Ok(output_metadata) if output_metadata.is_file() => {
+            let output_bytes = i64::try_from(output_metadata.len())
+                .context("thumbnail output size over i64 range")?;
+            Ok(Some((width, height, output_bytes)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn clamp_max_dimension(config: &WorkerConfig, requested: i64) -> usize {
+    usize::try_from(requested)
+        .ok()
+        .map(|value| value.min(config.thumbnail_max_dimension))
+        .unwrap_or(config.thumbnail_max_dimension)
+        .max(16)
+}
+
+/// Runs `ffmpeg -version` through `run` and returns the reported version string, or why the
+/// binary cannot be used for video thumbnails.
+pub fn probe_ffmpeg<F>(ffmpeg_bin: &str, run: F) -> Result<String>
+where
+    F: FnOnce(&mut Command) -> io::Result<Output>,
+{
+    let mut command = Command::new(ffmpeg_bin);
+    command.arg("-version");
+    let output = run(&mut command)
+        .with_context(|| format!("failed to execute ffmpeg binary '{ffmpeg_bin}'"))?;
+    if !output.status.success() {
+        bail!(
+            "ffmpeg binary '{ffmpeg_bin}' exited with {} on -version",
+            output.status
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("ffmpeg version "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("'{ffmpeg_bin}' did not report an ffmpeg version"))
+}
+
+/// Moves a rendered temp file over the final output and returns its size in bytes.
+///
+/// With `thumbnail_fsync` the data is flushed before the rename and the directory entry
+/// after it, so a crash cannot leave a torn file behind a `ready` row.
+fn finalize_thumbnail_output(
+    config: &WorkerConfig,
+    temp_path: &Path,
+    output_path: &Path,
+) -> Result<i64> {
+    if config.thumbnail_fsync {
+        fs::File::open(temp_path)
+            .and_then(|file| file.sync_all())
+            .with_context(|| {
+                format!(
+                    "failed to fsync thumbnail temp output: {}",
+                    temp_path.display()
+                )
+            })?;
+    }
+    if output_path.exists() {
+        fs::remove_file(output_path).with_context(|| {
+            format!(
+                "failed to replace existing thumbnail output file: {}",
+                output_path.display()
+            )
+        })?;
+    }
+    fs::rename(temp_path, output_path).with_context(|| {
+        format!(
+            "failed to move thumbnail temp output into final path: {}",
+            output_path.display()
+        )
+    })?;
+    if config.thumbnail_fsync {
+        sync_parent_dir(output_path)?;
+    }
+    apply_permission_mode(output_path, config.thumbnail_file_mode)?;
+
+    i64::try_from(
+        fs::metadata(output_path)
+            .with_context(|| format!("failed to stat thumbnail output: {}", output_path.display()))?
+            .len(),
+    )
+    .context("thumbnail output size over i64 range")
+}
+
 fn generate_image_thumbnail(
-    source_path: &PathBuf,
-    output_path: &PathBuf,
+    config: &WorkerConfig,
+    source_path: &Path,
+    output_path: &Path,
     max_dimension: usize,
     output_format: &str,
     lease_refresher: &mut LeaseRefresher<'_>,
 ) -> Result<(u32, u32)> {
+    let image = decode_source_image(config, source_path, output_path, lease_refresher)?;
+    render_thumbnail(
+        config,
+        &image,
+        output_path,
+        max_dimension,
+        output_format,
+        lease_refresher,
+    )
+}
+
+/// `scratch_path` names the HEIF fallback's transcode file, which is placed next to it.
+fn decode_source_image(
+    config: &WorkerConfig,
+    source_path: &Path,
+    scratch_path: &Path,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Result<DynamicImage> {
     lease_refresher.maybe_refresh()?;
-    let image = ImageReader::open(source_path)
+    let reader = ImageReader::open(source_path)
         .with_context(|| format!("failed to open source image: {}", source_path.display()))?
         .with_guessed_format()
-        .context("failed to guess source image format")?
-        .decode()
-        .context("failed to decode source image")?;
+        .context("failed to guess source image format")?;
+    let source_format = reader.format();
+    check_source_pixel_budget(source_path, config.thumbnail_max_source_pixels)?;
+    let decoded = if source_format == Some(ImageFormat::Gif) {
+        decode_gif_frames(source_path, config.thumbnail_gif_max_frames)
+            .map(|mut frames| frames.swap_remove(0))
+    } else {
+        reader.decode()
+    };
+    let mut image = match decoded {
+        Ok(image) => image,
+        Err(ImageError::Unsupported(_)) if is_heif_family(source_path) => {
+            if !config.thumbnail_enable_heif_fallback {
+                bail!("heif decoder unavailable: ffmpeg fallback is disabled");
+            }
+            decode_heif_with_ffmpeg(config, source_path, scratch_path, lease_refresher)?
+        }
+        Err(error) => return Err(error).context("failed to decode source image"),
+    };
+    if config.thumbnail_apply_exif_orientation && source_format == Some(ImageFormat::Jpeg) {
+        if let Some(orientation) = read_exif_orientation(source_path) {
+            image = apply_exif_orientation(image, orientation);
+        }
+    }
+    Ok(image)
+}
+
+/// Rejects sources whose header dimensions exceed `max_pixels` before anything is
+/// allocated for the decode. Formats the reader cannot size are left to the decoder.
+fn check_source_pixel_budget(source_path: &Path, max_pixels: u64) -> Result<()> {
+    let Ok((width, height)) = ImageReader::open(source_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(ImageError::IoError)
+        .and_then(|reader| reader.into_dimensions())
+    else {
+        return Ok(());
+    };
+    ensure_pixel_budget(width, height, max_pixels)
+}
+
+fn ensure_pixel_budget(width: u32, height: u32, max_pixels: u64) -> Result<()> {
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels > max_pixels {
+        bail!(
+            "{SOURCE_TOO_LARGE}: {width}x{height} exceeds thumbnail_max_source_pixels ({max_pixels})"
+        );
+    }
+    Ok(())
+}
 
+fn render_thumbnail(
+    config: &WorkerConfig,
+    image: &DynamicImage,
+    output_path: &Path,
+    max_dimension: usize,
+    output_format: &str,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Result<(u32, u32)> {
     let thumb = image.thumbnail(max_dimension as u32, max_dimension as u32);
     let (width, height) = (thumb.width(), thumb.height());
 
     lease_refresher.maybe_refresh()?;
     let format = parse_output_format(output_format)?;
-    thumb
-        .save_with_format(output_path, format)
-        .with_context(|| format!("failed to write image thumbnail: {}", output_path.display()))?;
+    write_thumbnail_output(&thumb, output_path, format, config)
+        .with_context(|| format!("failed to write thumbnail: {}", output_path.display()))?;
 
     Ok((width, height))
 }
 
-fn generate_video_thumbnail(
+/// Animated GIFs are composited frame by frame; only the first `max_frames` are
+/// decoded. The result is never empty.
+fn decode_gif_frames(path: &Path, max_frames: usize) -> image::ImageResult<Vec<DynamicImage>> {
+    let file = fs::File::open(path).map_err(ImageError::IoError)?;
+    let decoder = GifDecoder::new(BufReader::new(file))?;
+    let frames = decoder
+        .into_frames()
+        .take(max_frames.max(1))
+        .map(|frame| frame.map(|frame| DynamicImage::ImageRgba8(frame.into_buffer())))
+        .collect::<image::ImageResult<Vec<_>>>()?;
+    if frames.is_empty() {
+        return Err(ImageError::Decoding(image::error::DecodingError::new(
+            ImageFormat::Gif.into(),
+            "gif contains no frames",
+        )));
+    }
+    Ok(frames)
+}
+
+/// Missing or unreadable EXIF data is treated as "no orientation".
+fn read_exif_orientation(path: &Path) -> Option<u32> {
+    let file = fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()?;
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+        .value
+        .get_uint(0)
+}
+
+fn apply_exif_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+fn is_heif_family(path: &Path) -> bool {
+    path.extension()
+        .and_then(|value| value.to_str())
+        .map(|value| {
+            matches!(
+                value.to_ascii_lowercase().as_str(),
+                "heic" | "heif" | "avif"
+            )
+        })
+        .unwrap_or(false)
+}
+
+fn decode_heif_with_ffmpeg(
     config: &WorkerConfig,
-    source_path: &PathBuf,
-    output_path: &PathBuf,
-    max_dimension: usize,
-    output_format: &str,
+    source_path: &Path,
+    output_path: &Path,
     lease_refresher: &mut LeaseRefresher<'_>,
-) -> Result<(u32, u32)> {
-    let frame_path = output_path.with_file_name(format!(
-        "{}-frame.jpg",
-        output_path
-            .file_stem()
-            .and_then(|value| value.to_str())
-            .unwrap_or("frame")
-    ));
-    let _frame_guard = TempFileGuard::new(frame_path.clone());
+) -> Result<DynamicImage> {
+    let transcoded_path = unique_scratch_path(
+        output_path,
+        &file_stem_or(output_path, "source"),
+        "-heif.png",
+    );
+    let _transcoded_guard = TempFileGuard::new(transcoded_path.clone());
 
-    let mut ffmpeg_child = Command::new(&config.thumbnail_ffmpeg_bin)
+    let mut command = Command::new(&config.thumbnail_ffmpeg_bin);
+    command
         .arg("-v")
         .arg("error")
         .arg("-y")
-        .arg("-ss")
-        .arg("00:00:01")
         .arg("-i")
         .arg(source_path)
         .arg("-frames:v")
         .arg("1")
-        .arg(&frame_path)
-        .stdout(Stdio::null())
+        .arg(&transcoded_path);
+    if let Err(error) = run_ffmpeg(config, &mut command, "heif transcode", lease_refresher) {
+        let message = error.to_string().to_lowercase();
+        if message.contains("decoder") || message.contains("codec") {
+            bail!("heif decoder unavailable in ffmpeg: {error}");
+        }
+        return Err(error);
+    }
+
+    lease_refresher.maybe_refresh()?;
+    ImageReader::open(&transcoded_path)
+        .with_context(|| {
+            format!(
+                "failed to open transcoded heif frame: {}",
+                transcoded_path.display()
+            )
+        })?
+        .with_guessed_format()
+        .context("failed to detect transcoded heif frame format")?
+        .decode()
+        .context("failed to decode transcoded heif frame")
+}
+
+fn generate_video_thumbnail(
+    config: &WorkerConfig,
+    source_path: &Path,
+    output_path: &Path,
+    max_dimension: usize,
+    output_format: &str,
+    seek_override: Option<f64>,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Result<(u32, u32)> {
+    let image = extract_video_frame(
+        config,
+        source_path,
+        output_path,
+        seek_override,
+        lease_refresher,
+    )?;
+    render_thumbnail(
+        config,
+        &image,
+        output_path,
+        max_dimension,
+        output_format,
+        lease_refresher,
+    )
+}
+
+/// Extracts one frame with ffmpeg into a temp file next to `scratch_path` and decodes it.
+fn extract_video_frame(
+    config: &WorkerConfig,
+    source_path: &Path,
+    scratch_path: &Path,
+    seek_override: Option<f64>,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Result<DynamicImage> {
+    let frame_path = unique_scratch_path(
+        scratch_path,
+        &file_stem_or(scratch_path, "frame"),
+        "-frame.jpg",
+    );
+    let _frame_guard = TempFileGuard::new(frame_path.clone());
+
+    // A task-level seek is an explicit choice and always wins over the filter.
+    let representative = seek_override.is_none()
+        && config.thumbnail_video_frame_strategy == VideoFrameStrategy::Representative;
+    let mut have_frame = false;
+    if representative {
+        let selection = FrameSelection::Representative {
+            window_seconds: config.thumbnail_video_representative_window_seconds,
+        };
+        let mut command = frame_extraction_command(config, source_path, &frame_path, selection);
+        run_ffmpeg(
+            config,
+            &mut command,
+            "representative frame extraction",
+            lease_refresher,
+        )?;
+        have_frame = fs::metadata(&frame_path).is_ok_and(|metadata| metadata.len() > 0);
+    }
+
+    if !have_frame {
+        let duration_seconds = probe_video_duration_seconds(config, source_path, lease_refresher);
+        let seek_seconds = match seek_override {
+            Some(seconds) => compute_video_seek_seconds(seconds, None, duration_seconds),
+            None => compute_video_seek_seconds(
+                config.thumbnail_video_seek_seconds,
+                config.thumbnail_video_seek_percent,
+                duration_seconds,
+            ),
+        };
+        let selection = FrameSelection::Seek(seek_seconds);
+        let mut command = frame_extraction_command(config, source_path, &frame_path, selection);
+        run_ffmpeg(config, &mut command, "frame extraction", lease_refresher)?;
+    }
+
+    lease_refresher.maybe_refresh()?;
+    let image = ImageReader::open(&frame_path)
+        .with_context(|| format!("failed to open extracted frame: {}", frame_path.display()))?
+        .with_guessed_format()
+        .context("failed to detect frame format")?
+        .decode()
+        .context("failed to decode extracted frame")?;
+    Ok(image)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FrameSelection {
+    /// Grab the first frame at this offset.
+    Seek(f64),
+    /// Let the `thumbnail` filter pick the most representative frame of the first
+    /// `window_seconds`.
+    Representative { window_seconds: f64 },
+}
+
+fn frame_extraction_command(
+    config: &WorkerConfig,
+    source_path: &Path,
+    frame_path: &Path,
+    selection: FrameSelection,
+) -> Command {
+    let mut command = Command::new(&config.thumbnail_ffmpeg_bin);
+    command.arg("-v").arg("error").arg("-y");
+    match selection {
+        FrameSelection::Seek(seek_seconds) => {
+            command
+                .arg("-ss")
+                .arg(format!("{seek_seconds:.3}"))
+                .arg("-i")
+                .arg(source_path);
+        }
+        FrameSelection::Representative { window_seconds } => {
+            command
+                .arg("-t")
+                .arg(format!("{window_seconds:.3}"))
+                .arg("-i")
+                .arg(source_path)
+                .arg("-vf")
+                .arg("thumbnail");
+        }
+    }
+    command.arg("-frames:v").arg("1").arg(frame_path);
+    command
+}
+
+fn run_ffmpeg(
+    config: &WorkerConfig,
+    command: &mut Command,
+    action: &str,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Result<String> {
+    let mut ffmpeg_child = command
+        .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .with_context(|| {
@@ -278,62 +1201,193 @@ fn generate_video_thumbnail(
         {
             if !status.success() {
                 let stderr = read_child_stderr(&mut ffmpeg_child);
+                let disposition = if ffmpeg_failure_is_terminal(config, status.code(), &stderr) {
+                    FFMPEG_TERMINAL_MARKER
+                } else {
+                    "failed"
+                };
                 bail!(
-                    "ffmpeg frame extraction failed: {}",
+                    "ffmpeg {action} {disposition}: {}",
                     truncate_error_message(&stderr, 2048)
                 );
             }
-            break;
+            let mut stdout = String::new();
+            if let Some(mut pipe) = ffmpeg_child.stdout.take() {
+                let _ = pipe.read_to_string(&mut stdout);
+            }
+            return Ok(stdout);
         }
         if ffmpeg_started_at.elapsed() >= ffmpeg_timeout {
             let _ = ffmpeg_child.kill();
             let _ = ffmpeg_child.wait();
             bail!(
-                "ffmpeg frame extraction timed out after {} seconds",
+                "ffmpeg {action} timed out after {} seconds",
                 config.thumbnail_ffmpeg_timeout_seconds
             );
         }
         thread::sleep(Duration::from_millis(200));
     }
+}
+
+/// Returns `None` when ffprobe is unavailable or reports no usable duration;
+/// callers then fall back to the fixed seek offset.
+/// Set in the ffmpeg error message when the failure matches the configured terminal
+/// exit codes or stderr patterns; `classify_thumbnail_error` keys off it.
+const FFMPEG_TERMINAL_MARKER: &str = "failed permanently";
+
+/// Whether this ffmpeg build can never succeed on the input. With nothing configured
+/// (the default) every failure stays retryable.
+fn ffmpeg_failure_is_terminal(config: &WorkerConfig, exit_code: Option<i32>, stderr: &str) -> bool {
+    if exit_code.is_some_and(|code| config.thumbnail_ffmpeg_terminal_exit_codes.contains(&code)) {
+        return true;
+    }
+    let stderr = stderr.to_lowercase();
+    config
+        .thumbnail_ffmpeg_terminal_stderr_patterns
+        .iter()
+        .any(|pattern| stderr.contains(pattern.as_str()))
+}
+
+fn probe_video_duration_seconds(
+    config: &WorkerConfig,
+    source_path: &Path,
+    lease_refresher: &mut LeaseRefresher<'_>,
+) -> Option<f64> {
+    let mut command = Command::new(&config.thumbnail_ffprobe_bin);
+    command
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("default=noprint_wrappers=1:nokey=1")
+        .arg(source_path);
+    let stdout = run_ffmpeg(config, &mut command, "duration probe", lease_refresher).ok()?;
+    stdout
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value > 0.0)
+}
 
-    lease_refresher.maybe_refresh()?;
-    let image = ImageReader::open(&frame_path)
-        .with_context(|| format!("failed to open extracted frame: {}", frame_path.display()))?
-        .with_guessed_format()
-        .context("failed to detect frame format")?
-        .decode()
-        .context("failed to decode extracted frame")?;
+fn compute_video_seek_seconds(
+    fixed_seconds: f64,
+    percent: Option<f64>,
+    duration_seconds: Option<f64>,
+) -> f64 {
+    let Some(duration) = duration_seconds else {
+        return fixed_seconds;
+    };
+    let seek = match percent {
+        Some(percent) => duration * percent / 100.0,
+        None => fixed_seconds,
+    };
+    // Seeking to the very end yields no frame, so stay just short of it.
+    seek.min((duration - 0.1).max(0.0))
+}
 
-    let thumb = image.thumbnail(max_dimension as u32, max_dimension as u32);
-    let (width, height) = (thumb.width(), thumb.height());
+/// Drops the two low bits of every color channel, replicating the high bits so 0 and 255
+/// stay put. Alpha is left exact.
+fn quantize_colors(image: &mut DynamicImage) {
+    let quantize = |value: &mut u8| *value = (*value & 0xFC) | (*value >> 6);
+    match image {
+        DynamicImage::ImageRgba8(pixels) => pixels
+            .pixels_mut()
+            .for_each(|pixel| pixel.0[..3].iter_mut().for_each(quantize)),
+        DynamicImage::ImageRgb8(pixels) => pixels
+            .pixels_mut()
+            .for_each(|pixel| pixel.0.iter_mut().for_each(quantize)),
+        _ => {}
+    }
+}
 
-    lease_refresher.maybe_refresh()?;
-    let format = parse_output_format(output_format)?;
-    thumb
-        .save_with_format(output_path, format)
-        .with_context(|| format!("failed to write video thumbnail: {}", output_path.display()))?;
+fn write_thumbnail_output(
+    image: &DynamicImage,
+    path: &Path,
+    format: ImageFormat,
+    config: &WorkerConfig,
+) -> Result<()> {
+    if format == ImageFormat::Jpeg {
+        let mut buffer = Cursor::new(Vec::new());
+        JpegEncoder::new_with_quality(&mut buffer, config.thumbnail_jpeg_quality)
+            .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+            .context("failed to encode jpeg thumbnail")?;
+        fs::write(path, buffer.into_inner())?;
+        return Ok(());
+    }
+    if format == ImageFormat::WebP {
+        // image's pure-Rust WebP encoder only writes lossless VP8L. Without
+        // thumbnail_webp_lossless, colors are quantized first (libwebp's near-lossless
+        // approach), which shrinks the output at the cost of exact pixels.
+        let mut converted = if image.color().has_alpha() {
+            DynamicImage::ImageRgba8(image.to_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(image.to_rgb8())
+        };
+        if !config.thumbnail_webp_lossless {
+            quantize_colors(&mut converted);
+        }
+        let mut buffer = Cursor::new(Vec::new());
+        converted
+            .write_with_encoder(WebPEncoder::new_lossless(&mut buffer))
+            .context("failed to encode webp thumbnail")?;
+        fs::write(path, buffer.into_inner())?;
+        return Ok(());
+    }
+    if format != ImageFormat::Png {
+        bail!("unsupported thumbnail output format: {format:?}");
+    }
 
-    Ok((width, height))
+    // image's PNG encoder does not take a zlib level, so drive png directly.
+    let (color, pixels) = if image.color().has_alpha() {
+        (png::ColorType::Rgba, image.to_rgba8().into_raw())
+    } else {
+        (png::ColorType::Rgb, image.to_rgb8().into_raw())
+    };
+    let mut buffer = Cursor::new(Vec::new());
+    {
+        let mut encoder = png::Encoder::new(&mut buffer, image.width(), image.height());
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_deflate_compression(match config.thumbnail_png_compression {
+            0 => png::DeflateCompression::NoCompression,
+            level => png::DeflateCompression::Level(level as u8),
+        });
+        let mut writer = encoder
+            .write_header()
+            .context("failed to write png header")?;
+        writer
+            .write_image_data(&pixels)
+            .context("failed to encode png thumbnail")?;
+        writer.finish().context("failed to finish png thumbnail")?;
+    }
+    fs::write(path, buffer.into_inner())?;
+    Ok(())
 }
 
 fn parse_output_format(raw_format: &str) -> Result<ImageFormat> {
     match raw_format {
         "jpeg" => Ok(ImageFormat::Jpeg),
         "webp" => Ok(ImageFormat::WebP),
+        "png" => Ok(ImageFormat::Png),
         _ => bail!("unsupported thumbnail output format: {raw_format}"),
     }
 }
 
-fn normalize_output_target(config: &WorkerConfig, path: &PathBuf) -> Result<PathBuf> {
+fn normalize_output_target(config: &WorkerConfig, path: &Path) -> Result<PathBuf> {
     let parent = path
         .parent()
         .ok_or_else(|| anyhow::anyhow!("thumbnail output path has no parent directory"))?;
-    fs::create_dir_all(parent).with_context(|| {
-        format!(
-            "failed to create thumbnail output directory: {}",
-            parent.display()
-        )
-    })?;
+    let created_dirs: Vec<PathBuf> = parent
+        .ancestors()
+        .take_while(|ancestor| !ancestor.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    fs::create_dir_all(parent)
+        .with_context(|| format!("{OUTPUT_DIR_CREATE_FAILED}: {}", parent.display()))?;
+    for created in created_dirs.iter().rev() {
+        apply_permission_mode(created, config.thumbnail_dir_mode)?;
+    }
     let parent_real = parent.canonicalize().with_context(|| {
         format!(
             "failed to resolve thumbnail output directory: {}",
@@ -352,7 +1406,7 @@ fn normalize_output_target(config: &WorkerConfig, path: &PathBuf) -> Result<Path
     Ok(parent_real.join(filename))
 }
 
-fn normalize_existing_output_target(config: &WorkerConfig, path: &PathBuf) -> Result<PathBuf> {
+fn normalize_existing_output_target(config: &WorkerConfig, path: &Path) -> Result<PathBuf> {
     let parent = path
         .parent()
         .ok_or_else(|| anyhow::anyhow!("thumbnail output path has no parent directory"))?;
@@ -374,6 +1428,27 @@ fn normalize_existing_output_target(config: &WorkerConfig, path: &PathBuf) -> Re
     Ok(parent_real.join(filename))
 }
 
+#[cfg(unix)]
+fn apply_permission_mode(path: &Path, mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Some(mode) = mode else {
+        return Ok(());
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).with_context(|| {
+        format!(
+            "failed to set thumbnail permission mode {:o}: {}",
+            mode,
+            path.display()
+        )
+    })
+}
+
+#[cfg(not(unix))]
+fn apply_permission_mode(_path: &Path, _mode: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
 fn read_child_stderr(child: &mut std::process::Child) -> String {
     let mut stderr = String::new();
     if let Some(mut pipe) = child.stderr.take() {
@@ -392,18 +1467,18 @@ fn truncate_error_message(raw: &str, max_chars: usize) -> String {
 struct LeaseRefresher<'a> {
     conn: &'a Connection,
     config: &'a WorkerConfig,
-    task_id: i64,
+    task_ids: Vec<i64>,
     interval: Duration,
     last_refresh_at: Instant,
 }
 
 impl<'a> LeaseRefresher<'a> {
-    fn new(conn: &'a Connection, config: &'a WorkerConfig, task_id: i64) -> Self {
+    fn new(conn: &'a Connection, config: &'a WorkerConfig, task_ids: Vec<i64>) -> Self {
         let interval_seconds = (config.job_lock_ttl_seconds / 3).max(1);
         Self {
             conn,
             config,
-            task_id,
+            task_ids,
             interval: Duration::from_secs(interval_seconds),
             last_refresh_at: Instant::now(),
         }
@@ -411,7 +1486,9 @@ impl<'a> LeaseRefresher<'a> {
 
     fn maybe_refresh(&mut self) -> Result<()> {
         if self.last_refresh_at.elapsed() >= self.interval {
-            refresh_thumbnail_lease(self.conn, self.config, self.task_id)?;
+            for task_id in &self.task_ids {
+                refresh_thumbnail_lease(self.conn, self.config, *task_id)?;
+            }
             self.last_refresh_at = Instant::now();
         }
         Ok(())
@@ -431,6 +1508,40 @@ fn reserve_thumbnail_io_budget(conn: &Connection, config: &WorkerConfig, bytes:
     Ok(())
 }
 
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<()> {
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    fs::File::open(parent)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| {
+            format!(
+                "failed to fsync thumbnail output directory: {}",
+                parent.display()
+            )
+        })
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// A sibling of `path` named `{stem}.{random}{suffix}`. Two workers holding the same
+/// `thumb_key` (a lease reclaimed from a stalled process) never share scratch files.
+fn unique_scratch_path(path: &Path, stem: &str, suffix: &str) -> PathBuf {
+    let random = Alphanumeric.sample_string(&mut rand::thread_rng(), 8);
+    path.with_file_name(format!("{stem}.{random}{suffix}"))
+}
+
+fn file_stem_or(path: &Path, fallback: &str) -> String {
+    path.file_stem()
+        .and_then(|value| value.to_str())
+        .unwrap_or(fallback)
+        .to_string()
+}
+
 struct TempFileGuard {
     path: PathBuf,
 }
@@ -458,7 +1569,7 @@ fn metadata_mtime_ns(metadata: &fs::Metadata) -> Result<i64> {
     Ok(metadata
         .mtime()
         .saturating_mul(1_000_000_000)
-        .saturating_add(i64::from(metadata.mtime_nsec())))
+        .saturating_add(metadata.mtime_nsec()))
 }
 
 #[cfg(not(unix))]
@@ -471,3 +1582,1059 @@ fn metadata_mtime_ns(metadata: &fs::Metadata) -> Result<i64> {
         .context("source modified timestamp before UNIX_EPOCH")?;
     i64::try_from(duration.as_nanos()).context("source mtime_ns over i64 range")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        classify_thumbnail_error, compute_video_seek_seconds, delete_orphan_thumbnails,
+        ensure_pixel_budget, ffmpeg_failure_is_terminal, finalize_thumbnail_output,
+        frame_extraction_command, is_heif_family, metadata_mtime_ns, parse_output_format,
+        probe_ffmpeg, run_orphan_thumbnail_scan, run_thumbnail_cleanup_task,
+        run_thumbnail_orphan_sweep, run_thumbnail_task, run_thumbnail_task_group,
+        sibling_output_relpath, sibling_primary_relpath, unique_scratch_path,
+        write_thumbnail_output, FrameSelection, TempFileGuard, OUTPUT_DIR_CREATE_FAILED,
+    };
+    use crate::config::WorkerConfig;
+    use crate::db::{
+        claim_thumbnail_cleanup_job, claim_thumbnail_task_group, is_terminal_thumbnail_error,
+        thumbnail_failure_exhausts_retries, ThumbnailCleanupKind, ThumbnailCleanupRecord,
+        ThumbnailTaskRecord,
+    };
+    use crate::error_codes::ErrorCode;
+    use crate::test_support::{create_worker_schema, insert_library, test_config, TestDir};
+    use image::codecs::gif::GifEncoder;
+    use image::{DynamicImage, Frame, ImageFormat, ImageReader, Rgb, RgbImage, Rgba, RgbaImage};
+    use rusqlite::{params, Connection};
+    use std::fs;
+    use std::io::{self, Cursor};
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::Path;
+    use std::process::{ExitStatus, Output};
+
+    #[test]
+    fn classify_thumbnail_error_distinguishes_offline_library() {
+        let offline = anyhow::anyhow!("source library root is offline: /libraries/nas");
+        assert_eq!(
+            classify_thumbnail_error(&offline),
+            ErrorCode::ThumbLibraryOffline
+        );
+
+        let missing = anyhow::anyhow!("source media file does not exist: /libraries/nas/a.jpg");
+        assert_eq!(
+            classify_thumbnail_error(&missing),
+            ErrorCode::ThumbGenerationFailed
+        );
+    }
+
+    #[test]
+    fn ffmpeg_probe_reports_version_or_why_the_binary_is_unusable() {
+        let answer = |code: i32, stdout: &str| {
+            let stdout = stdout.as_bytes().to_vec();
+            move |command: &mut std::process::Command| {
+                let args: Vec<_> = command.get_args().collect();
+                assert_eq!(args, ["-version"]);
+                Ok(Output {
+                    status: ExitStatus::from_raw(code << 8),
+                    stdout,
+                    stderr: Vec::new(),
+                })
+            }
+        };
+
+        let version = probe_ffmpeg(
+            "ffmpeg",
+            answer(
+                0,
+                "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023\nbuilt with gcc\n",
+            ),
+        )
+        .expect("ffmpeg answers");
+        assert_eq!(version, "6.1.1-3ubuntu5");
+
+        let error = probe_ffmpeg("ffmpeg", |_: &mut std::process::Command| {
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        })
+        .expect_err("missing binary");
+        assert!(format!("{error:#}").contains("failed to execute ffmpeg binary 'ffmpeg'"));
+
+        let error = probe_ffmpeg("ffmpeg", answer(1, "")).expect_err("failing binary");
+        assert!(error.to_string().contains("exited with"));
+
+        let error =
+            probe_ffmpeg("/bin/true", answer(0, "something else\n")).expect_err("not ffmpeg");
+        assert!(error
+            .to_string()
+            .contains("did not report an ffmpeg version"));
+    }
+
+    #[test]
+    fn scratch_paths_are_unique_per_claim() {
+        let dir = TestDir::new("thumb-scratch-unique");
+        let output_path = dir.path().join("abc.jpg");
+        let first = unique_scratch_path(&output_path, "abc", ".tmp");
+        let second = unique_scratch_path(&output_path, "abc", ".tmp");
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), output_path.parent());
+        assert!(first.to_string_lossy().ends_with(".tmp"));
+
+        fs::write(&first, b"first").expect("write first scratch");
+        fs::write(&second, b"second").expect("write second scratch");
+        drop(TempFileGuard::new(first.clone()));
+        assert!(!first.exists());
+        assert_eq!(fs::read(&second).expect("second untouched"), b"second");
+        drop(TempFileGuard::new(second.clone()));
+        assert!(!second.exists());
+    }
+
+    #[test]
+    fn finalize_moves_synced_output_and_fails_without_temp_file() {
+        let dir = TestDir::new("thumb-fsync");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        for fsync in [true, false] {
+            config.thumbnail_fsync = fsync;
+            let temp_path = dir.path().join("thumb.tmp");
+            let output_path = dir.path().join("thumb.jpg");
+            fs::write(&temp_path, b"rendered").expect("write temp output");
+            let bytes = finalize_thumbnail_output(&config, &temp_path, &output_path)
+                .expect("finalize thumbnail");
+            assert_eq!(bytes, 8);
+            assert!(!temp_path.exists());
+        }
+
+        config.thumbnail_fsync = true;
+        let missing = dir.path().join("missing.tmp");
+        let error = finalize_thumbnail_output(&config, &missing, &dir.path().join("other.jpg"))
+            .expect_err("missing temp output");
+        assert!(format!("{error:#}").contains("failed to fsync thumbnail temp output"));
+        assert!(!dir.path().join("other.jpg").exists());
+    }
+
+    #[test]
+    fn output_directory_failures_are_classified_by_io_kind() {
+        let dir_error = |kind: io::ErrorKind| {
+            anyhow::Error::new(io::Error::from(kind))
+                .context(format!("{OUTPUT_DIR_CREATE_FAILED}: /state/thumbs/ab"))
+        };
+        let denied = dir_error(io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            classify_thumbnail_error(&denied),
+            ErrorCode::ThumbDirPermission
+        );
+        assert!(is_terminal_thumbnail_error(ErrorCode::ThumbDirPermission));
+        let full = dir_error(io::ErrorKind::StorageFull);
+        assert_eq!(classify_thumbnail_error(&full), ErrorCode::ThumbDiskFull);
+        assert!(!is_terminal_thumbnail_error(ErrorCode::ThumbDiskFull));
+
+        let dir = TestDir::new("thumb-disk-full");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        assert!(!thumbnail_failure_exhausts_retries(
+            &config,
+            config.thumbnail_max_error_count + 3,
+            ErrorCode::ThumbDiskFull
+        ));
+
+        // The same io kind outside directory creation keeps the generic classification.
+        let source_denied = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("failed to open source image");
+        assert_eq!(
+            classify_thumbnail_error(&source_denied),
+            ErrorCode::ThumbGenerationFailed
+        );
+    }
+
+    #[test]
+    fn decode_limit_errors_are_classified_as_resource() {
+        let limits = image::ImageError::Limits(image::error::LimitError::from_kind(
+            image::error::LimitErrorKind::InsufficientMemory,
+        ));
+        let error = anyhow::Error::new(limits).context("failed to decode source image");
+        assert_eq!(
+            classify_thumbnail_error(&error),
+            ErrorCode::ThumbDecodeResource
+        );
+
+        let dimensions = image::ImageError::Limits(image::error::LimitError::from_kind(
+            image::error::LimitErrorKind::DimensionError,
+        ));
+        let error = anyhow::Error::new(dimensions).context("failed to decode source image");
+        assert_eq!(
+            classify_thumbnail_error(&error),
+            ErrorCode::ThumbDecodeResource
+        );
+
+        let corrupt =
+            anyhow::anyhow!("unexpected end of file").context("failed to decode source image");
+        assert_eq!(
+            classify_thumbnail_error(&corrupt),
+            ErrorCode::ThumbDecodeFailed
+        );
+    }
+
+    #[test]
+    fn heif_fallback_errors_are_classified_separately() {
+        assert!(is_heif_family(Path::new("/libraries/phone/IMG_0001.HEIC")));
+        assert!(is_heif_family(Path::new("/libraries/phone/cover.avif")));
+        assert!(!is_heif_family(Path::new("/libraries/phone/cover.png")));
+
+        let missing_codec = anyhow::anyhow!(
+            "heif decoder unavailable in ffmpeg: ffmpeg heif transcode failed: Decoder not found"
+        );
+        assert_eq!(
+            classify_thumbnail_error(&missing_codec),
+            ErrorCode::ThumbHeifUnsupported
+        );
+    }
+
+    #[test]
+    fn output_format_accepts_png_and_still_rejects_bmp() {
+        assert_eq!(parse_output_format("png").expect("png"), ImageFormat::Png);
+        assert_eq!(
+            parse_output_format("jpeg").expect("jpeg"),
+            ImageFormat::Jpeg
+        );
+        assert_eq!(
+            parse_output_format("webp").expect("webp"),
+            ImageFormat::WebP
+        );
+        let error = parse_output_format("bmp").expect_err("bmp rejected");
+        assert!(error
+            .to_string()
+            .contains("unsupported thumbnail output format: bmp"));
+    }
+
+    #[test]
+    fn configured_ffmpeg_failures_are_terminal() {
+        let dir = TestDir::new("thumb-ffmpeg-terminal");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let stderr = "[h264 @ 0x1] Decoder not found for codec hevc";
+        assert!(!ffmpeg_failure_is_terminal(&config, Some(69), stderr));
+
+        config.thumbnail_ffmpeg_terminal_exit_codes = vec![69];
+        config.thumbnail_ffmpeg_terminal_stderr_patterns = vec!["decoder not found".to_string()];
+        assert!(ffmpeg_failure_is_terminal(&config, Some(69), "killed"));
+        assert!(ffmpeg_failure_is_terminal(&config, Some(1), stderr));
+        assert!(!ffmpeg_failure_is_terminal(
+            &config,
+            Some(1),
+            "Resource temporarily unavailable"
+        ));
+
+        let terminal = anyhow::anyhow!("ffmpeg frame extraction failed permanently: {stderr}");
+        assert_eq!(
+            classify_thumbnail_error(&terminal),
+            ErrorCode::ThumbVideoFfmpegTerminal
+        );
+        assert!(thumbnail_failure_exhausts_retries(
+            &config,
+            0,
+            ErrorCode::ThumbVideoFfmpegTerminal
+        ));
+        let transient = anyhow::anyhow!("ffmpeg frame extraction failed: out of file handles");
+        assert_eq!(
+            classify_thumbnail_error(&transient),
+            ErrorCode::ThumbVideoFfmpegFailed
+        );
+    }
+
+    #[test]
+    fn video_seek_uses_percent_and_stays_inside_duration() {
+        assert_eq!(compute_video_seek_seconds(1.0, None, None), 1.0);
+        assert_eq!(compute_video_seek_seconds(1.0, Some(50.0), None), 1.0);
+        assert_eq!(
+            compute_video_seek_seconds(1.0, Some(10.0), Some(120.0)),
+            12.0
+        );
+        assert_eq!(compute_video_seek_seconds(5.0, None, Some(60.0)), 5.0);
+        assert_eq!(compute_video_seek_seconds(0.25, None, Some(60.0)), 0.25);
+
+        let short_clip = compute_video_seek_seconds(1.0, None, Some(0.5));
+        assert!(short_clip < 0.5);
+    }
+
+    #[test]
+    fn frame_extraction_passes_millisecond_seek() {
+        let dir = TestDir::new("thumb-seek-args");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let command = frame_extraction_command(
+            &config,
+            Path::new("/libraries/lib/clip.mp4"),
+            Path::new("/state/thumbs/frame.jpg"),
+            FrameSelection::Seek(2.5),
+        );
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let seek_index = args.iter().position(|arg| arg == "-ss").expect("-ss flag");
+        assert_eq!(args[seek_index + 1], "2.500");
+        assert!(seek_index < args.iter().position(|arg| arg == "-i").expect("-i flag"));
+    }
+
+    #[test]
+    fn representative_frame_extraction_limits_the_thumbnail_filter_window() {
+        let dir = TestDir::new("thumb-representative-args");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let command = frame_extraction_command(
+            &config,
+            Path::new("/libraries/lib/clip.mp4"),
+            Path::new("/state/thumbs/frame.jpg"),
+            FrameSelection::Representative {
+                window_seconds: 8.0,
+            },
+        );
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "-v",
+                "error",
+                "-y",
+                "-t",
+                "8.000",
+                "-i",
+                "/libraries/lib/clip.mp4",
+                "-vf",
+                "thumbnail",
+                "-frames:v",
+                "1",
+                "/state/thumbs/frame.jpg",
+            ]
+        );
+    }
+
+    fn insert_running_task(
+        conn: &Connection,
+        config: &WorkerConfig,
+        library_root: &Path,
+        relative_path: &str,
+        format: &str,
+        output_relpath: &str,
+    ) -> ThumbnailTaskRecord {
+        let metadata = fs::metadata(library_root.join(relative_path)).expect("stat source");
+        let source_size_bytes = metadata.len() as i64;
+        let source_mtime_ns = metadata_mtime_ns(&metadata).expect("source mtime");
+
+        let library_id = insert_library(conn, "lib", library_root);
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, ?2, ?3, ?4)",
+            params![library_id, relative_path, source_size_bytes, source_mtime_ns],
+        )
+        .expect("insert file");
+        let file_id = conn.last_insert_rowid();
+        conn.execute(
+            "
+            INSERT INTO thumbnails(thumb_key, file_id, status, media_type, format, source_size_bytes, source_mtime_ns, output_relpath, worker_id, lease_expires_at)
+            VALUES ('new', ?1, 'running', 'image', ?2, ?3, ?4, ?5, ?6, datetime('now', '+300 seconds'))
+            ",
+            params![file_id, format, source_size_bytes, source_mtime_ns, output_relpath, config.worker_id],
+        )
+        .expect("insert running thumbnail");
+
+        ThumbnailTaskRecord {
+            id: conn.last_insert_rowid(),
+            thumb_key: "new".to_string(),
+            file_id,
+            relative_path: relative_path.to_string(),
+            root_path: library_root.to_string_lossy().to_string(),
+            media_type: "image".to_string(),
+            format: format.to_string(),
+            max_dimension: 256,
+            source_size_bytes,
+            source_mtime_ns,
+            output_relpath: output_relpath.to_string(),
+            error_count: 0,
+            seek_seconds: None,
+            sizes: None,
+        }
+    }
+
+    #[test]
+    fn fresh_thumbnail_is_reused_without_decoding() {
+        let dir = TestDir::new("thumb-fresh");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.thumbnail_skip_if_fresh = true;
+
+        // Not a decodable image: any decode attempt would fail the task.
+        fs::write(library_root.join("photo.jpg"), b"not an image").expect("write source");
+
+        let output_relpath = "ab/cd/abcd.jpg";
+        let output_path = config.thumbs_root_real.join(output_relpath);
+        fs::create_dir_all(output_path.parent().expect("output parent"))
+            .expect("create output dir");
+        fs::write(&output_path, b"previous thumbnail").expect("write output");
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let task = insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "photo.jpg",
+            "jpeg",
+            output_relpath,
+        );
+        conn.execute(
+            "
+            INSERT INTO thumbnails(thumb_key, file_id, status, media_type, source_size_bytes, source_mtime_ns, output_relpath, width, height, finished_at)
+            VALUES ('old', ?1, 'ready', 'image', ?2, ?3, ?4, 200, 150, CURRENT_TIMESTAMP)
+            ",
+            params![task.file_id, task.source_size_bytes, task.source_mtime_ns, output_relpath],
+        )
+        .expect("insert ready thumbnail");
+
+        let (width, height, bytes) = run_thumbnail_task(&conn, &config, &task).expect("fresh task");
+        assert_eq!((width, height), (200, 150));
+        assert_eq!(bytes, b"previous thumbnail".len() as i64);
+
+        config.thumbnail_skip_if_fresh = false;
+        assert!(run_thumbnail_task(&conn, &config, &task).is_err());
+    }
+
+    #[test]
+    fn extra_sizes_are_rendered_to_sibling_outputs_from_one_task() {
+        let dir = TestDir::new("thumb-sizes");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+
+        let source =
+            RgbImage::from_fn(640, 320, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, 64]));
+        source
+            .save_with_format(library_root.join("photo.jpg"), ImageFormat::Jpeg)
+            .expect("write jpeg source");
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let output_relpath = "aa/bb/aabb.jpg";
+        let mut task = insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "photo.jpg",
+            "jpeg",
+            output_relpath,
+        );
+        task.sizes = Some("256,64".to_string());
+
+        let (width, height, bytes) = run_thumbnail_task(&conn, &config, &task).expect("sizes task");
+        assert_eq!((width, height), (256, 128));
+        let primary = config.thumbs_root_real.join(output_relpath);
+        assert_eq!(
+            fs::metadata(&primary).expect("primary output").len() as i64,
+            bytes
+        );
+
+        let sibling_relpath = sibling_output_relpath(output_relpath, 64);
+        assert_eq!(sibling_relpath, "aa/bb/aabb_64.jpg");
+        let sibling = image::open(config.thumbs_root_real.join(&sibling_relpath))
+            .expect("decode sibling output");
+        assert_eq!((sibling.width(), sibling.height()), (64, 32));
+        assert_eq!(
+            sibling_primary_relpath(&sibling_relpath),
+            Some((output_relpath.to_string(), 64))
+        );
+        assert_eq!(sibling_primary_relpath(output_relpath), None);
+    }
+
+    #[test]
+    fn png_output_is_written_from_jpeg_source() {
+        let dir = TestDir::new("thumb-png");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.thumbnail_png_compression = 9;
+
+        let source = RgbImage::from_fn(640, 320, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        });
+        source
+            .save_with_format(library_root.join("photo.jpg"), ImageFormat::Jpeg)
+            .expect("write jpeg source");
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let output_relpath = "ef/01/ef01.png";
+        let task = insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "photo.jpg",
+            "png",
+            output_relpath,
+        );
+
+        let (width, height, bytes) = run_thumbnail_task(&conn, &config, &task).expect("png task");
+        assert_eq!((width, height), (256, 128));
+        assert!(bytes > 0);
+
+        let reader = ImageReader::open(config.thumbs_root_real.join(output_relpath))
+            .expect("open output")
+            .with_guessed_format()
+            .expect("guess output format");
+        assert_eq!(reader.format(), Some(ImageFormat::Png));
+        let decoded = reader.decode().expect("decode png output");
+        assert_eq!((decoded.width(), decoded.height()), (256, 128));
+    }
+
+    #[test]
+    fn sources_over_the_pixel_budget_are_rejected_before_decode() {
+        let dir = TestDir::new("thumb-pixel-budget");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        RgbImage::from_pixel(64, 32, Rgb([10, 20, 30]))
+            .save_with_format(library_root.join("small.png"), ImageFormat::Png)
+            .expect("write png source");
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let task = insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "small.png",
+            "jpeg",
+            "aa/01/aa01.jpg",
+        );
+        run_thumbnail_task(&conn, &config, &task).expect("small source within budget");
+
+        config.thumbnail_max_source_pixels = 64 * 32 - 1;
+        let error = run_thumbnail_task(&conn, &config, &task).expect_err("over budget");
+        assert_eq!(
+            classify_thumbnail_error(&error),
+            ErrorCode::ThumbSourceTooLarge
+        );
+        assert!(is_terminal_thumbnail_error(ErrorCode::ThumbSourceTooLarge));
+
+        // A 50000x50000 header would need ~10 GB to decode.
+        let bomb = ensure_pixel_budget(50_000, 50_000, 100_000_000).expect_err("bomb rejected");
+        assert!(bomb.to_string().contains("50000x50000"));
+        ensure_pixel_budget(10_000, 10_000, 100_000_000).expect("exactly at budget");
+    }
+
+    #[test]
+    fn animated_gif_renders_first_frame() {
+        let dir = TestDir::new("thumb-gif");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let config = test_config(&libraries_root, &dir.path().join("state"));
+
+        let frames = [[255, 0, 0, 255], [0, 0, 255, 255]]
+            .map(|color| Frame::new(RgbaImage::from_pixel(320, 160, Rgba(color))));
+        let file = fs::File::create(library_root.join("anim.gif")).expect("create gif");
+        let mut encoder = GifEncoder::new(file);
+        encoder.encode_frames(frames).expect("encode gif");
+        drop(encoder);
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let output_relpath = "a1/b2/a1b2.png";
+        let task = insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "anim.gif",
+            "png",
+            output_relpath,
+        );
+
+        let (width, height, _) = run_thumbnail_task(&conn, &config, &task).expect("gif task");
+        assert_eq!((width, height), (256, 128));
+        let output = ImageReader::open(config.thumbs_root_real.join(output_relpath))
+            .expect("open output")
+            .decode()
+            .expect("decode output")
+            .to_rgb8();
+        assert_eq!(output.get_pixel(128, 64), &Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn jpeg_quality_controls_output_size() {
+        let dir = TestDir::new("thumb-jpeg-quality");
+        let libraries_root = dir.path().join("libraries");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 256, |x, y| {
+            Rgb([
+                (x * 7 % 256) as u8,
+                (y * 13 % 256) as u8,
+                ((x ^ y) % 256) as u8,
+            ])
+        }));
+
+        let low_path = dir.path().join("low.jpg");
+        config.thumbnail_jpeg_quality = 50;
+        write_thumbnail_output(&image, &low_path, ImageFormat::Jpeg, &config).expect("write q50");
+        let high_path = dir.path().join("high.jpg");
+        config.thumbnail_jpeg_quality = 95;
+        write_thumbnail_output(&image, &high_path, ImageFormat::Jpeg, &config).expect("write q95");
+
+        let low = fs::metadata(&low_path).expect("stat q50").len();
+        let high = fs::metadata(&high_path).expect("stat q95").len();
+        assert!(
+            low < high,
+            "q50={low} bytes should be smaller than q95={high} bytes"
+        );
+    }
+
+    #[test]
+    fn webp_output_is_lossless_and_keeps_alpha() {
+        let dir = TestDir::new("thumb-webp");
+        let config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 24, |x, y| {
+            Rgba([
+                (x * 8) as u8,
+                (y * 10) as u8,
+                77,
+                if x < 16 { 255 } else { 40 },
+            ])
+        }));
+
+        let path = dir.path().join("thumb.webp");
+        write_thumbnail_output(&image, &path, ImageFormat::WebP, &config).expect("write webp");
+        let decoded = ImageReader::open(&path)
+            .expect("open webp")
+            .with_guessed_format()
+            .expect("guess format")
+            .decode()
+            .expect("decode webp");
+        assert_eq!(decoded.to_rgba8(), image.to_rgba8());
+    }
+
+    #[test]
+    fn webp_without_lossless_quantizes_colors_for_smaller_output() {
+        let dir = TestDir::new("thumb-webp-lossy");
+        let mut config = test_config(&dir.path().join("libraries"), &dir.path().join("state"));
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(128, 128, |x, y| {
+            Rgb([
+                (x * 7 % 256) as u8,
+                (y * 13 % 256) as u8,
+                ((x * y) % 256) as u8,
+            ])
+        }));
+
+        let exact_path = dir.path().join("exact.webp");
+        write_thumbnail_output(&image, &exact_path, ImageFormat::WebP, &config)
+            .expect("write lossless webp");
+        config.thumbnail_webp_lossless = false;
+        let lossy_path = dir.path().join("lossy.webp");
+        write_thumbnail_output(&image, &lossy_path, ImageFormat::WebP, &config)
+            .expect("write quantized webp");
+
+        let exact = fs::metadata(&exact_path).expect("stat exact").len();
+        let lossy = fs::metadata(&lossy_path).expect("stat lossy").len();
+        assert!(
+            lossy < exact,
+            "quantized={lossy} bytes should be smaller than lossless={exact} bytes"
+        );
+        let decoded = ImageReader::open(&lossy_path)
+            .expect("open webp")
+            .with_guessed_format()
+            .expect("guess format")
+            .decode()
+            .expect("decode webp")
+            .to_rgb8();
+        assert_ne!(decoded, image.to_rgb8());
+        assert!(decoded.pixels().all(|pixel| pixel
+            .0
+            .iter()
+            .all(|value| { (value & 0xFC) | (value >> 6) == *value })));
+    }
+
+    fn jpeg_with_orientation(width: u32, height: u32, orientation: u16) -> Vec<u8> {
+        let mut jpeg = Cursor::new(Vec::new());
+        RgbImage::from_pixel(width, height, Rgb([90, 120, 150]))
+            .write_to(&mut jpeg, ImageFormat::Jpeg)
+            .expect("encode jpeg");
+        let jpeg = jpeg.into_inner();
+
+        // Big-endian TIFF with a single IFD0 entry: Orientation (0x0112), SHORT.
+        let mut app1 = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+        app1.extend_from_slice(&orientation.to_be_bytes());
+        app1.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let segment_len = u16::try_from(app1.len() + 2).expect("segment length");
+
+        let mut output = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        output.extend_from_slice(&segment_len.to_be_bytes());
+        output.extend_from_slice(&app1);
+        output.extend_from_slice(&jpeg[2..]);
+        output
+    }
+
+    #[test]
+    fn exif_orientation_rotates_jpeg_before_resizing() {
+        let dir = TestDir::new("thumb-exif");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        fs::write(
+            library_root.join("portrait.jpg"),
+            jpeg_with_orientation(400, 200, 6),
+        )
+        .expect("write source");
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let task = insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "portrait.jpg",
+            "jpeg",
+            "aa/bb/aabb.jpg",
+        );
+
+        let (width, height, _) = run_thumbnail_task(&conn, &config, &task).expect("rotated task");
+        assert!(
+            width < height,
+            "expected portrait output, got {width}x{height}"
+        );
+        assert_eq!((width, height), (128, 256));
+
+        config.thumbnail_apply_exif_orientation = false;
+        let (width, height, _) = run_thumbnail_task(&conn, &config, &task).expect("raw task");
+        assert_eq!((width, height), (256, 128));
+    }
+
+    /// Inserts one pending task per `(max_dimension, output_relpath)` for the same source file.
+    fn insert_pending_sizes(
+        conn: &Connection,
+        library_root: &Path,
+        relative_path: &str,
+        media_type: &str,
+        sizes: &[(i64, &str)],
+    ) {
+        let metadata = fs::metadata(library_root.join(relative_path)).expect("stat source");
+        let source_size_bytes = metadata.len() as i64;
+        let source_mtime_ns = metadata_mtime_ns(&metadata).expect("source mtime");
+
+        let library_id = insert_library(conn, "lib", library_root);
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, ?2, ?3, ?4)",
+            params![library_id, relative_path, source_size_bytes, source_mtime_ns],
+        )
+        .expect("insert file");
+        let file_id = conn.last_insert_rowid();
+        for (max_dimension, output_relpath) in sizes {
+            conn.execute(
+                "
+                INSERT INTO thumbnails(thumb_key, file_id, status, media_type, format, max_dimension, source_size_bytes, source_mtime_ns, output_relpath)
+                VALUES (?1, ?2, 'pending', ?3, 'jpeg', ?4, ?5, ?6, ?7)
+                ",
+                params![
+                    format!("{relative_path}-{max_dimension}"),
+                    file_id,
+                    media_type,
+                    max_dimension,
+                    source_size_bytes,
+                    source_mtime_ns,
+                    output_relpath
+                ],
+            )
+            .expect("insert pending thumbnail");
+        }
+    }
+
+    fn output_dimensions(config: &WorkerConfig, output_relpath: &str) -> (u32, u32) {
+        let output = ImageReader::open(config.thumbs_root_real.join(output_relpath))
+            .expect("open output")
+            .decode()
+            .expect("decode output");
+        (output.width(), output.height())
+    }
+
+    #[test]
+    fn image_group_renders_every_size_from_one_claim() {
+        let dir = TestDir::new("thumb-multi-image");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.thumbnail_multi_size = true;
+        config.thumbnail_max_dimension = 1024;
+        RgbImage::from_pixel(1024, 512, Rgb([40, 80, 120]))
+            .save_with_format(library_root.join("wide.png"), ImageFormat::Png)
+            .expect("write source");
+
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        insert_pending_sizes(
+            &conn,
+            &library_root,
+            "wide.png",
+            "image",
+            &[(128, "aa/11/grid.jpg"), (512, "aa/11/preview.jpg")],
+        );
+
+        let tasks = claim_thumbnail_task_group(&mut conn, &config).expect("claim group");
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].max_dimension, 512);
+        let group_ids: Vec<Option<i64>> = conn
+            .prepare("SELECT task_group_id FROM thumbnails WHERE status = 'running'")
+            .expect("prepare")
+            .query_map([], |row| row.get(0))
+            .expect("query")
+            .map(|row| row.expect("row"))
+            .collect();
+        assert_eq!(group_ids.len(), 2);
+        assert!(group_ids[0].is_some() && group_ids[0] == group_ids[1]);
+
+        let results = run_thumbnail_task_group(&conn, &config, &tasks).expect("group run");
+        let dimensions: Vec<(i64, i64)> = results
+            .into_iter()
+            .map(|result| {
+                let (width, height, bytes) = result.expect("size rendered");
+                assert!(bytes > 0);
+                (width, height)
+            })
+            .collect();
+        assert_eq!(dimensions, vec![(512, 256), (128, 64)]);
+        assert_eq!(output_dimensions(&config, "aa/11/preview.jpg"), (512, 256));
+        assert_eq!(output_dimensions(&config, "aa/11/grid.jpg"), (128, 64));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn video_group_extracts_one_frame_for_every_size() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestDir::new("thumb-multi-video");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.thumbnail_multi_size = true;
+        config.thumbnail_max_dimension = 1024;
+        fs::write(library_root.join("clip.mp4"), b"not really a video").expect("write source");
+
+        // Stand-in ffmpeg: copies a fixed frame to the output argument and counts its runs.
+        let frame = dir.path().join("frame.jpg");
+        RgbImage::from_pixel(640, 360, Rgb([200, 30, 30]))
+            .save_with_format(&frame, ImageFormat::Jpeg)
+            .expect("write frame");
+        let runs = dir.path().join("runs.log");
+        let ffmpeg = dir.path().join("fake-ffmpeg");
+        fs::write(
+            &ffmpeg,
+            format!(
+                "#!/bin/sh\nfor last in \"$@\"; do :; done\necho run >> '{}'\ncp '{}' \"$last\"\n",
+                runs.display(),
+                frame.display()
+            ),
+        )
+        .expect("write fake ffmpeg");
+        fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).expect("chmod");
+        config.thumbnail_ffmpeg_bin = ffmpeg.to_string_lossy().to_string();
+        config.thumbnail_ffprobe_bin = dir
+            .path()
+            .join("missing-ffprobe")
+            .to_string_lossy()
+            .to_string();
+
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        insert_pending_sizes(
+            &conn,
+            &library_root,
+            "clip.mp4",
+            "video",
+            &[(128, "bb/22/grid.jpg"), (320, "bb/22/preview.jpg")],
+        );
+
+        let tasks = claim_thumbnail_task_group(&mut conn, &config).expect("claim group");
+        assert_eq!(tasks.len(), 2);
+        let results = run_thumbnail_task_group(&conn, &config, &tasks).expect("group run");
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(output_dimensions(&config, "bb/22/preview.jpg"), (320, 180));
+        assert_eq!(output_dimensions(&config, "bb/22/grid.jpg"), (128, 72));
+        assert_eq!(
+            fs::read_to_string(&runs).expect("runs log").lines().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn orphan_sweep_removes_only_unreferenced_files_past_grace() {
+        let dir = TestDir::new("thumb-orphan-sweep");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        fs::write(library_root.join("photo.jpg"), b"source").expect("write source");
+
+        let referenced = config.thumbs_root_real.join("ab/cd/referenced.jpg");
+        let orphan = config.thumbs_root_real.join("ef/gh/orphan.jpg");
+        for path in [&referenced, &orphan] {
+            fs::create_dir_all(path.parent().expect("output parent")).expect("create output dir");
+            fs::write(path, b"thumbnail").expect("write output");
+        }
+
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "photo.jpg",
+            "jpeg",
+            "ab/cd/referenced.jpg",
+        );
+        conn.execute(
+            "INSERT INTO thumbnail_cleanup_jobs(group_key, cleanup_kind) VALUES ('__orphan_sweep__', 'orphan_sweep')",
+            [],
+        )
+        .expect("insert sweep job");
+        let cleanup = claim_thumbnail_cleanup_job(&mut conn, &config)
+            .expect("claim sweep")
+            .expect("sweep claimed");
+        assert_eq!(cleanup.kind, ThumbnailCleanupKind::OrphanSweep);
+
+        // Both files were just written, so the default grace period protects the orphan.
+        assert_eq!(
+            run_thumbnail_orphan_sweep(&conn, &config, &cleanup).expect("sweep within grace"),
+            0
+        );
+        assert!(orphan.exists());
+
+        config.thumbnail_orphan_grace_seconds = 0;
+        assert_eq!(
+            run_thumbnail_orphan_sweep(&conn, &config, &cleanup).expect("sweep"),
+            1
+        );
+        assert!(referenced.exists());
+        assert!(!orphan.exists());
+    }
+
+    #[test]
+    fn orphan_scan_reports_unreferenced_files_and_deletes_only_on_request() {
+        let dir = TestDir::new("thumb-orphan-scan");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.thumbnail_orphan_grace_seconds = 0;
+        fs::write(library_root.join("photo.jpg"), b"source").expect("write source");
+
+        let referenced = config.thumbs_root_real.join("ab/cd/referenced.jpg");
+        let orphan = config.thumbs_root_real.join("ef/gh/orphan.jpg");
+        fs::create_dir_all(referenced.parent().expect("parent")).expect("create output dir");
+        fs::create_dir_all(orphan.parent().expect("parent")).expect("create output dir");
+        fs::write(&referenced, b"thumbnail").expect("write referenced");
+        fs::write(&orphan, b"orphan!").expect("write orphan");
+
+        let conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        insert_running_task(
+            &conn,
+            &config,
+            &library_root,
+            "photo.jpg",
+            "jpeg",
+            "ab/cd/referenced.jpg",
+        );
+
+        let report = run_orphan_thumbnail_scan(&conn, &config).expect("scan");
+        assert_eq!(report.orphan_count, 1);
+        assert_eq!(report.orphan_bytes, 7);
+        assert_eq!(report.orphan_paths, vec![orphan.clone()]);
+        assert_eq!(report.deleted_count, 0);
+        assert!(orphan.exists());
+
+        let report = delete_orphan_thumbnails(&conn, &config).expect("delete");
+        assert_eq!((report.orphan_count, report.deleted_count), (1, 1));
+        assert!(referenced.exists());
+        assert!(!orphan.exists());
+    }
+
+    #[test]
+    fn group_cleanup_removes_every_output_in_chunks() {
+        let dir = TestDir::new("thumb-group-cleanup");
+        let libraries_root = dir.path().join("libraries");
+        let library_root = libraries_root.join("lib");
+        fs::create_dir_all(&library_root).expect("create library");
+        let mut config = test_config(&libraries_root, &dir.path().join("state"));
+        config.cleanup_delete_batch_size = 128;
+
+        let mut conn = Connection::open_in_memory().expect("open db");
+        create_worker_schema(&conn);
+        let library_id = insert_library(&conn, "lib", &library_root);
+        conn.execute(
+            "INSERT INTO library_files(library_id, relative_path, size_bytes, mtime_ns) VALUES (?1, 'a.jpg', 1, 1)",
+            params![library_id],
+        )
+        .expect("insert file");
+        let file_id = conn.last_insert_rowid();
+        for index in 0..300 {
+            let relpath = format!("g/{:02}/{index}.jpg", index % 16);
+            let path = config.thumbs_root_real.join(&relpath);
+            fs::create_dir_all(path.parent().expect("output parent")).expect("create output dir");
+            fs::write(&path, b"thumbnail").expect("write output");
+            conn.execute(
+                "
+                INSERT INTO thumbnails(thumb_key, file_id, group_key, status, media_type, source_size_bytes, source_mtime_ns, output_relpath)
+                VALUES (?1, ?2, 'sha256:g', 'ready', 'image', 1, 1, ?3)
+                ",
+                params![format!("t{index}"), file_id, relpath],
+            )
+            .expect("insert ready thumbnail");
+        }
+        conn.execute(
+            "INSERT INTO thumbnail_cleanup_jobs(group_key) VALUES ('sha256:g')",
+            [],
+        )
+        .expect("insert cleanup job");
+        let cleanup = claim_thumbnail_cleanup_job(&mut conn, &config)
+            .expect("claim cleanup")
+            .expect("cleanup claimed");
+        assert_eq!(cleanup.kind, ThumbnailCleanupKind::Group);
+
+        assert_eq!(
+            run_thumbnail_cleanup_task(&conn, &config, &cleanup).expect("cleanup"),
+            300
+        );
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(1) FROM thumbnails", [], |row| row.get(0))
+            .expect("count rows");
+        assert_eq!(remaining, 0);
+        for index in 0..300 {
+            let relpath = format!("g/{:02}/{index}.jpg", index % 16);
+            assert!(!config.thumbs_root_real.join(relpath).exists());
+        }
+
+        // A pinned member keeps the group's thumbnails in place.
+        let relpath = "g/pinned.jpg";
+        fs::write(config.thumbs_root_real.join(relpath), b"thumbnail").expect("write output");
+        conn.execute(
+            "UPDATE library_files SET pinned = 1, hash_algorithm = 'sha256', content_hash = X'0A' WHERE id = ?1",
+            params![file_id],
+        )
+        .expect("pin file");
+        conn.execute(
+            "
+            INSERT INTO thumbnails(thumb_key, file_id, group_key, status, media_type, source_size_bytes, source_mtime_ns, output_relpath)
+            VALUES ('pinned', ?1, 'sha256:0a', 'ready', 'image', 1, 1, ?2)
+            ",
+            params![file_id, relpath],
+        )
+        .expect("insert pinned thumbnail");
+        let pinned_cleanup = ThumbnailCleanupRecord {
+            group_key: "sha256:0a".to_string(),
+            ..cleanup
+        };
+        assert_eq!(
+            run_thumbnail_cleanup_task(&conn, &config, &pinned_cleanup).expect("cleanup"),
+            0
+        );
+        assert!(config.thumbs_root_real.join(relpath).exists());
+    }
+}
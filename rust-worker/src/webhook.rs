@@ -0,0 +1,262 @@
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+
+use crate::config::WorkerConfig;
+
+/// Events waiting for the delivery thread; once this many are queued, new ones are dropped.
+const DELIVERY_QUEUE_CAPACITY: usize = 256;
+
+/// The delivery queue, started on the first event; the CLI drains it before exiting.
+static QUEUE: Mutex<Option<DeliveryQueue>> = Mutex::new(None);
+
+struct Delivery {
+    url: String,
+    body: String,
+    max_attempts: u32,
+    timeout: Duration,
+}
+
+/// A bounded channel drained by a single sender thread, one event at a time.
+struct DeliveryQueue {
+    sender: SyncSender<Delivery>,
+    worker: JoinHandle<()>,
+}
+
+impl DeliveryQueue {
+    fn start(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Delivery>(capacity);
+        let worker = thread::spawn(move || {
+            for delivery in receiver {
+                if let Err(error) = deliver(
+                    &delivery.url,
+                    &delivery.body,
+                    delivery.max_attempts,
+                    delivery.timeout,
+                ) {
+                    tracing::warn!(error = %format!("{error:#}"), "completion webhook delivery failed");
+                }
+            }
+        });
+        Self { sender, worker }
+    }
+
+    /// Queues without blocking; `false` when the queue is full and the event was dropped.
+    fn offer(&self, delivery: Delivery) -> bool {
+        self.sender.try_send(delivery).is_ok()
+    }
+
+    /// Delivers what is already queued, then stops the sender thread.
+    fn drain(self) {
+        drop(self.sender);
+        let _ = self.worker.join();
+    }
+}
+
+/// Queues a completion event for `completion_webhook_url`.
+///
+/// Called after the row has reached its final state, so delivery can neither block nor
+/// change the job outcome. Failed deliveries are retried with backoff and then logged;
+/// events arriving while the queue is full are logged and dropped.
+pub fn notify_completion(
+    config: &WorkerConfig,
+    id: Value,
+    kind: &str,
+    outcome: &str,
+    counters: Value,
+    error: Option<&str>,
+) {
+    let Some(url) = config.completion_webhook_url.clone() else {
+        return;
+    };
+    let id_label = id.to_string();
+    let body = json!({
+        "id": id,
+        "kind": kind,
+        "outcome": outcome,
+        "worker_id": config.worker_id,
+        "counters": counters,
+        "error": error,
+    })
+    .to_string();
+    let delivery = Delivery {
+        url,
+        body,
+        max_attempts: config.completion_webhook_max_attempts,
+        timeout: Duration::from_secs(config.completion_webhook_timeout_seconds),
+    };
+
+    let Ok(mut queue) = QUEUE.lock() else {
+        return;
+    };
+    let queue = queue.get_or_insert_with(|| DeliveryQueue::start(DELIVERY_QUEUE_CAPACITY));
+    if !queue.offer(delivery) {
+        tracing::warn!(
+            id = %id_label,
+            kind,
+            outcome,
+            "completion webhook queue is full; event dropped"
+        );
+    }
+}
+
+pub fn wait_for_in_flight() {
+    let queue = match QUEUE.lock() {
+        Ok(mut queue) => queue.take(),
+        Err(_) => return,
+    };
+    if let Some(queue) = queue {
+        queue.drain();
+    }
+}
+
+/// Progress counters recorded on a finished `jobs` row; `null` when the row is gone.
+pub fn job_counters(conn: &Connection, job_id: &str) -> Value {
+    conn.query_row(
+        "SELECT processed_items, total_items, progress FROM jobs WHERE id = ?1",
+        params![job_id],
+        |row| {
+            Ok(json!({
+                "processed_items": row.get::<_, i64>(0)?,
+                "total_items": row.get::<_, Option<i64>>(1)?,
+                "progress": row.get::<_, f64>(2)?,
+            }))
+        },
+    )
+    .optional()
+    .ok()
+    .flatten()
+    .unwrap_or(Value::Null)
+}
+
+fn deliver(url: &str, body: &str, max_attempts: u32, timeout: Duration) -> Result<()> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let mut last_error = anyhow!("no delivery attempted");
+    for attempt in 0..max_attempts {
+        if attempt > 0 {
+            thread::sleep(Duration::from_millis(500 << attempt.min(5)));
+        }
+        match agent
+            .post(url)
+            .set("Content-Type", "application/json")
+            .send_string(body)
+        {
+            Ok(_) => return Ok(()),
+            Err(error) => last_error = anyhow!("attempt {}: {error}", attempt + 1),
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deliver, Delivery, DeliveryQueue};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn delivery_posts_json_and_gives_up_after_bounded_attempts() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let url = format!("http://{}/hook", listener.local_addr().expect("local addr"));
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).contains("\"outcome\"") {
+                let read = stream.read(&mut buffer).expect("read request");
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .expect("write response");
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        deliver(
+            &url,
+            r#"{"id":"job-1","outcome":"completed"}"#,
+            1,
+            Duration::from_secs(5),
+        )
+        .expect("delivered");
+        let request = server.join().expect("server thread");
+        assert!(request.starts_with("POST /hook"));
+        assert!(request.contains(r#""id":"job-1""#));
+
+        // Nothing listens on the closed port: every attempt fails and the error surfaces.
+        let closed = TcpListener::bind("127.0.0.1:0").expect("bind closed");
+        let closed_url = format!("http://{}/hook", closed.local_addr().expect("local addr"));
+        drop(closed);
+        assert!(deliver(&closed_url, "{}", 2, Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn queue_uses_one_sender_and_drops_events_when_full() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let url = format!("http://{}/hook", listener.local_addr().expect("local addr"));
+        let (accepted_tx, accepted_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                while !request.ends_with(b"}") {
+                    let read = stream.read(&mut buffer).expect("read request");
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+                if bodies.is_empty() {
+                    accepted_tx.send(()).expect("signal accept");
+                    release_rx.recv().expect("wait for release");
+                }
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                    .expect("write response");
+                let request = String::from_utf8_lossy(&request).to_string();
+                bodies.push(
+                    request
+                        .split("\r\n\r\n")
+                        .nth(1)
+                        .unwrap_or_default()
+                        .to_string(),
+                );
+            }
+            bodies
+        });
+
+        let event = |seq: u32| Delivery {
+            url: url.clone(),
+            body: format!(r#"{{"seq":{seq}}}"#),
+            max_attempts: 1,
+            timeout: Duration::from_secs(5),
+        };
+        let queue = DeliveryQueue::start(1);
+        assert!(queue.offer(event(1)));
+        accepted_rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("first delivery in flight");
+        // The sender thread is busy with the first event: one more fits, the next is dropped.
+        assert!(queue.offer(event(2)));
+        assert!(!queue.offer(event(3)));
+
+        release_tx.send(()).expect("release server");
+        queue.drain();
+        let bodies = server.join().expect("server thread");
+        assert_eq!(bodies, vec![r#"{"seq":1}"#, r#"{"seq":2}"#]);
+    }
+}
@@ -2,7 +2,9 @@ from __future__ import annotations
 
 from pathlib import Path
 
+import pytest
 from sqlalchemy import create_engine, text
+from sqlalchemy.exc import IntegrityError
 
 from dedupfs.db.migrations import MIGRATIONS, apply_migrations
 
@@ -438,6 +440,76 @@ def test_apply_migrations_resolves_duplicate_pending_scan_hash_jobs(tmp_path: Pa
     assert migration_versions == [step.version for step in MIGRATIONS]
 
 
+def test_apply_migrations_extends_scan_hash_mutex_to_every_claimed_kind(tmp_path: Path) -> None:
+    db_path = tmp_path / "claim_kinds_mutex.sqlite3"
+    engine = create_engine(f"sqlite:///{db_path.as_posix()}", future=True)
+
+    with engine.begin() as conn:
+        conn.execute(
+            text(
+                """
+                CREATE TABLE jobs (
+                    id VARCHAR(36) PRIMARY KEY,
+                    kind VARCHAR(16) NOT NULL,
+                    status VARCHAR(16) NOT NULL,
+                    dry_run BOOLEAN NOT NULL DEFAULT 1,
+                    worker_id VARCHAR(128),
+                    worker_heartbeat_at DATETIME,
+                    lease_expires_at DATETIME,
+                    payload JSON NOT NULL,
+                    error_code VARCHAR(64),
+                    error_message TEXT,
+                    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                    started_at DATETIME,
+                    finished_at DATETIME
+                )
+                """
+            )
+        )
+        conn.execute(
+            text(
+                "CREATE UNIQUE INDEX ix_jobs_single_active_scan_hash "
+                "ON jobs((1)) WHERE lower(status) IN ('pending', 'running') AND lower(kind) IN ('scan', 'hash')"
+            )
+        )
+        conn.execute(
+            text(
+                """
+                CREATE TABLE schema_migrations (
+                    version INTEGER PRIMARY KEY,
+                    name VARCHAR(255) NOT NULL,
+                    applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+                )
+                """
+            )
+        )
+        conn.execute(
+            text(
+                "INSERT INTO jobs(id, kind, status, payload, created_at, updated_at) VALUES "
+                "('job-scan', 'scan', 'pending', '{}', '2026-01-01 00:00:00', '2026-01-01 00:00:00'), "
+                "('job-verify', 'verify', 'running', '{}', '2026-01-02 00:00:00', '2026-01-02 00:00:00'), "
+                "('job-migrate', 'migrate', 'pending', '{}', '2026-01-03 00:00:00', '2026-01-03 00:00:00')"
+            )
+        )
+        for step in MIGRATIONS:
+            if step.version < 44:
+                conn.execute(
+                    text("INSERT INTO schema_migrations(version, name) VALUES (:version, :name)"),
+                    {"version": step.version, "name": step.name},
+                )
+
+    apply_migrations(engine)
+
+    with engine.begin() as conn:
+        statuses = dict(conn.execute(text("SELECT id, status FROM jobs")).all())
+
+    assert statuses == {"job-scan": "retryable", "job-verify": "running", "job-migrate": "retryable"}
+    with pytest.raises(IntegrityError):
+        with engine.begin() as conn:
+            conn.execute(text("INSERT INTO jobs(id, kind, status, payload) VALUES ('job-dedup', 'dedup', 'pending', '{}')"))
+
+
 def test_apply_migrations_normalizes_existing_wal_maintenance_rows(tmp_path: Path) -> None:
     db_path = tmp_path / "legacy_wal_maintenance.sqlite3"
     engine = create_engine(f"sqlite:///{db_path.as_posix()}", future=True)
@@ -146,3 +146,34 @@ def test_list_jobs_rejects_unknown_cursor(tmp_path: Path) -> None:
         pass
     else:
         raise AssertionError("expected ValueError for unknown cursor")
+
+
+def test_create_job_persists_priority(tmp_path: Path) -> None:
+    service = make_service(tmp_path, dry_run=True, allow_real_delete=False)
+    job = service.create_job(kind=JobKind.HASH, payload={}, priority=5)
+    assert job.priority == 5
+
+    claimed = service.claim_pending_scan_hash_job("worker-a")
+    assert claimed is not None
+    assert claimed.id == job.id
+    assert claimed.priority == 5
+
+
+def test_failed_job_is_dead_lettered_at_max_retries_and_restorable(tmp_path: Path) -> None:
+    service = make_service(tmp_path, dry_run=True, allow_real_delete=False)
+    job = service.create_job(kind=JobKind.HASH, payload={}, max_retries=1)
+    assert job.max_retries == 1
+
+    claimed = service.claim_pending_scan_hash_job("worker-a")
+    assert claimed is not None
+    finished = service.finish_job(job.id, worker_id="worker-a", success=False, error_message="disk gone")
+    assert finished.status == JobStatus.DEAD_LETTERED
+    assert finished.retry_count == 1
+    assert [item.id for item in service.list_dead_lettered_jobs()] == [job.id]
+
+    restored = service.restore_dead_lettered_job(job.id)
+    assert restored.status == JobStatus.PENDING
+    assert restored.retry_count == 0
+    reclaimed = service.claim_pending_scan_hash_job("worker-a")
+    assert reclaimed is not None
+    assert reclaimed.id == job.id
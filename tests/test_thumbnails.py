@@ -6,6 +6,7 @@ from datetime import datetime, timedelta, timezone
 from pathlib import Path
 
 import dedupfs.db.session as db_session_module
+import pytest
 from fastapi.testclient import TestClient
 
 from dedupfs.api.app import create_app
@@ -20,7 +21,7 @@ from dedupfs.db.models import (
     ThumbnailCleanupStatus,
     ThumbnailStatus,
 )
-from dedupfs.thumbs.service import ThumbnailPolicyError, ThumbnailService
+from dedupfs.thumbs.service import ThumbnailCleanupError, ThumbnailPolicyError, ThumbnailService
 from dedupfs.thumbs.service import ThumbnailQueueFullError
 
 
@@ -128,6 +129,27 @@ def test_thumbnail_failure_backoff_and_retry_requeue(tmp_path: Path) -> None:
     assert retried.error_code is None
 
 
+def test_terminal_thumbnail_failure_is_not_requeued(tmp_path: Path) -> None:
+    service = make_thumbnail_service(tmp_path)
+    file_id = seed_file(service, root_path="/libraries/lib-b", relative_path="media/bomb.png")
+
+    first = service.request_thumbnail(file_id=file_id)
+
+    session_factory = db_session_module.get_session_factory()
+    with session_factory() as session:
+        row = session.query(Thumbnail).filter(Thumbnail.thumb_key == first.thumb_key).one()
+        row.status = ThumbnailStatus.FAILED
+        row.error_code = "THUMB_DECODE_RESOURCE"
+        row.error_message = "Memory limit exceeded"
+        row.error_count = 1
+        row.retry_after = None
+        session.commit()
+
+    blocked = service.request_thumbnail(file_id=file_id)
+    assert blocked.status == ThumbnailStatus.FAILED
+    assert blocked.error_code == "THUMB_DECODE_RESOURCE"
+
+
 def test_group_cleanup_only_removes_thumbnails_and_is_idempotent(tmp_path: Path) -> None:
     service = make_thumbnail_service(tmp_path)
     file_id = seed_file(
@@ -159,6 +181,54 @@ def test_group_cleanup_only_removes_thumbnails_and_is_idempotent(tmp_path: Path)
     assert file_count == 1
 
 
+def test_group_cleanup_skips_groups_with_pinned_files(tmp_path: Path) -> None:
+    service = make_thumbnail_service(tmp_path)
+    file_id = seed_file(
+        service,
+        root_path="/libraries/lib-pinned",
+        relative_path="media/offline.jpg",
+        with_group_key=True,
+    )
+
+    snapshot = service.request_thumbnail(file_id=file_id)
+    output_path = service.resolve_thumbnail_output_path(snapshot)
+    output_path.parent.mkdir(parents=True, exist_ok=True)
+    output_path.write_bytes(b"thumb-bytes")
+
+    with db_session_module.get_session_factory()() as session:
+        row = session.query(Thumbnail).filter(Thumbnail.thumb_key == snapshot.thumb_key).one()
+        row.status = ThumbnailStatus.READY
+        session.query(LibraryFile).filter(LibraryFile.id == file_id).one().pinned = True
+        session.commit()
+
+    assert service.prune_group_thumbnails(group_key=snapshot.group_key or "") == 0
+    assert output_path.exists()
+    with pytest.raises(ThumbnailCleanupError):
+        service.schedule_group_cleanup(group_key=snapshot.group_key or "")
+
+
+def test_thumbnail_request_with_sizes_uses_largest_as_primary_output(tmp_path: Path) -> None:
+    service = make_thumbnail_service(tmp_path)
+    file_id = seed_file(
+        service,
+        root_path="/libraries/lib-sizes",
+        relative_path="media/poster.jpg",
+        with_group_key=True,
+    )
+
+    snapshot = service.request_thumbnail(file_id=file_id, sizes=[128, 256, 128])
+    assert snapshot.max_dimension == 256
+    assert snapshot.sizes == [256, 128]
+    assert snapshot.thumb_key != service.request_thumbnail(file_id=file_id, max_dimension=256).thumb_key
+
+    primary = service.resolve_thumbnail_output_path(snapshot)
+    small = service.resolve_thumbnail_output_path(snapshot, size=128)
+    assert small.parent == primary.parent
+    assert small.name == f"{snapshot.thumb_key}_128.jpg"
+    with pytest.raises(ThumbnailPolicyError):
+        service.resolve_thumbnail_output_path(snapshot, size=64)
+
+
 def test_thumbnail_request_rejects_library_root_outside_libraries(tmp_path: Path) -> None:
     service = make_thumbnail_service(tmp_path)
     file_id = seed_file(service, root_path="/tmp/escape", relative_path="media/picture.jpg")
@@ -10,7 +10,7 @@ from fastapi.testclient import TestClient
 from dedupfs.api.app import create_app
 from dedupfs.core.config import get_settings
 from dedupfs.db.init_db import initialize_database
-from dedupfs.db.models import WalCheckpointMode, WalMaintenanceJob, WalMaintenanceStatus
+from dedupfs.db.models import WalCheckpointMode, WalMaintenanceJob, WalMaintenanceStatus, WorkerControl
 from dedupfs.maintenance.service import (
     WalMaintenanceConflictError,
     WalMaintenancePolicyError,
@@ -171,3 +171,56 @@ def test_wal_maintenance_api_rejects_invalid_mode(tmp_path: Path) -> None:
 
     response = client.post("/api/v1/maintenance/wal/checkpoint", json={"mode": "invalid"})
     assert response.status_code == 422
+
+
+def test_worker_control_api_toggles_per_kind_pause_flags(tmp_path: Path) -> None:
+    make_wal_service(tmp_path)
+    app = create_app()
+    client = TestClient(app)
+
+    initial = client.get("/api/v1/maintenance/worker-control")
+    assert initial.status_code == 200
+    assert initial.json()["paused"] is False
+    assert initial.json()["pause_hash"] is False
+
+    paused = client.patch("/api/v1/maintenance/worker-control", json={"pause_hash": True, "pause_wal": True})
+    assert paused.status_code == 200
+    body = paused.json()
+    assert body["pause_hash"] is True
+    assert body["pause_wal"] is True
+    assert body["pause_scan"] is False
+    assert body["pause_thumbnail"] is False
+
+    resumed = client.patch("/api/v1/maintenance/worker-control", json={"pause_hash": False})
+    assert resumed.status_code == 200
+    assert resumed.json()["pause_hash"] is False
+    assert resumed.json()["pause_wal"] is True
+
+    with db_session_module.get_session_factory()() as session:
+        rows = session.query(WorkerControl).all()
+    assert len(rows) == 1
+
+    rejected = client.patch("/api/v1/maintenance/worker-control", json={"pause_everything": True})
+    assert rejected.status_code == 422
+
+
+def test_db_maintenance_api_queues_operation_and_reuses_active_row(tmp_path: Path) -> None:
+    make_wal_service(tmp_path)
+    app = create_app()
+    client = TestClient(app)
+
+    first = client.post("/api/v1/maintenance/db", json={"operation": "analyze", "reason": "stats"})
+    assert first.status_code == 202
+    assert first.json()["operation"] == "analyze"
+    assert first.json()["status"] == "pending"
+
+    second = client.post("/api/v1/maintenance/db", json={"operation": "ANALYZE"})
+    assert second.status_code == 202
+    assert second.json()["id"] == first.json()["id"]
+
+    latest = client.get("/api/v1/maintenance/db/latest")
+    assert latest.status_code == 200
+    assert latest.json()["id"] == first.json()["id"]
+
+    rejected = client.post("/api/v1/maintenance/db", json={"operation": "reindex"})
+    assert rejected.status_code == 422